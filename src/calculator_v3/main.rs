@@ -1,124 +1,279 @@
+use std::{
+    f64::consts::{E, PI},
+    fmt,
+};
+
 use crate::utils::ui;
 
-fn _try_parse_float(value: &str) -> f64 {
-    match value.parse::<f64>() {
-        Ok(v) => v,
-        Err(_) => 0.0,
+#[derive(Debug, Clone, PartialEq)]
+pub enum CalcError {
+    /// The text found at the position, or `end of input`, where it makes no sense.
+    UnexpectedToken(String, usize),
+    /// A `(` without its `)`, or a `)` without its `(`.
+    UnbalancedParen(usize),
+    DivisionByZero(usize),
+    /// An operator without a real result, such as `(0-4)^0.5`.
+    DomainError(usize),
+}
+
+impl CalcError {
+    /// The character offset in the input the error points at.
+    #[allow(dead_code)]
+    pub fn position(&self) -> usize {
+        match self {
+            CalcError::UnexpectedToken(_, position)
+            | CalcError::UnbalancedParen(position)
+            | CalcError::DivisionByZero(position)
+            | CalcError::DomainError(position) => *position,
+        }
     }
 }
 
+impl fmt::Display for CalcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalcError::UnexpectedToken(token, position) => {
+                write!(f, "unexpected {} at position {}", token, position)
+            }
+            CalcError::UnbalancedParen(position) => {
+                write!(f, "unbalanced parenthesis at position {}", position)
+            }
+            CalcError::DivisionByZero(position) => {
+                write!(f, "division by zero at position {}", position)
+            }
+            CalcError::DomainError(position) => {
+                write!(f, "result is not a real number at position {}", position)
+            }
+        }
+    }
+}
+
+/// Names that can stand in for a number, as in `2pi`.
+const CONSTANTS: [(&str, f64); 3] = [("pi", PI), ("π", PI), ("e", E)];
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum Token {
     Number(f64),
+    Constant(&'static str, f64),
     Op(char),
     Eof,
 }
 
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(number) => write!(f, "'{}'", number),
+            Token::Constant(name, _) => write!(f, "'{}'", name),
+            Token::Op(op) => write!(f, "'{}'", op),
+            Token::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+fn unexpected(token: Token, position: usize) -> CalcError {
+    CalcError::UnexpectedToken(token.to_string(), position)
+}
+
+/// Tokens with the character offset each starts at.
 #[derive(Debug)]
 struct Lexer {
-    tokens: Vec<Token>,
+    tokens: Vec<(Token, usize)>,
+    end: usize,
 }
 
 impl Lexer {
-    fn new(input: &str) -> Lexer {
-        let mut tokens: Vec<Token> = Vec::new();
-        let mut number: String = String::new();
+    fn new(input: &str) -> Result<Lexer, CalcError> {
+        let chars: Vec<char> = input.chars().collect();
+        let mut tokens: Vec<(Token, usize)> = Vec::new();
+        let mut position = 0;
 
-        for c in input.chars().filter(|it| !it.is_ascii_whitespace()) {
-            if c.is_digit(10) || c == '.' {
-                number.push(c);
+        while position < chars.len() {
+            let start = position;
+            let c = chars[position];
+            let run = |position: &mut usize, class: fn(&char) -> bool| {
+                while chars.get(*position).is_some_and(class) {
+                    *position += 1;
+                }
+                chars[start..*position].iter().collect::<String>()
+            };
+            if c.is_ascii_digit() || c == '.' {
+                let number = run(&mut position, |c| c.is_ascii_digit() || *c == '.');
+                let value = number
+                    .parse::<f64>()
+                    .map_err(|_| CalcError::UnexpectedToken(format!("'{}'", number), start))?;
+                tokens.push((Token::Number(value), start));
+            } else if c.is_alphabetic() {
+                let name = run(&mut position, |c| c.is_alphabetic());
+                let &(name, value) = CONSTANTS
+                    .iter()
+                    .find(|(constant, _)| *constant == name)
+                    .ok_or_else(|| CalcError::UnexpectedToken(format!("'{}'", name), start))?;
+                tokens.push((Token::Constant(name, value), start));
             } else {
-                if !number.is_empty() {
-                    tokens.push(Token::Number(_try_parse_float(&number)));
-                    number.clear();
+                position += 1;
+                if !c.is_whitespace() {
+                    tokens.push((Token::Op(c), start));
                 }
-                tokens.push(Token::Op(c));
             }
         }
-        if !number.is_empty() {
-            tokens.push(Token::Number(_try_parse_float(&number)));
-        }
 
         tokens.reverse();
-        Lexer { tokens }
+        Ok(Lexer {
+            tokens,
+            end: chars.len(),
+        })
     }
-    fn next(&mut self) -> Token {
-        self.tokens.pop().unwrap_or(Token::Eof)
+    fn next(&mut self) -> (Token, usize) {
+        self.tokens.pop().unwrap_or((Token::Eof, self.end))
     }
 
-    fn peek(&mut self) -> Token {
-        self.tokens.last().copied().unwrap_or(Token::Eof)
+    fn peek(&mut self) -> (Token, usize) {
+        self.tokens
+            .last()
+            .copied()
+            .unwrap_or((Token::Eof, self.end))
     }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub enum Expression {
     Number(f64),
-    Operation(char, Vec<Expression>),
+    /// An operator, its one or two operands and the position of the operator in the input.
+    Operation(char, Vec<Expression>, usize),
 }
 impl Expression {
-    pub fn from_str(input: &str) -> Expression {
-        let mut lexer = Lexer::new(input);
-        parse_expression(&mut lexer, 0.0)
+    pub fn from_str(input: &str) -> Result<Expression, CalcError> {
+        let mut lexer = Lexer::new(input)?;
+        let expression = parse_expression(&mut lexer, 0.0)?;
+        match lexer.next() {
+            (Token::Eof, _) => Ok(expression),
+            (Token::Op(')'), position) => Err(CalcError::UnbalancedParen(position)),
+            (token, position) => Err(unexpected(token, position)),
+        }
     }
 
     #[allow(unused)]
-    pub fn eval(&self) -> f64 {
+    pub fn eval(&self) -> Result<f64, CalcError> {
         match self {
-            Expression::Number(c) => *c,
-            Expression::Operation(operator, operands) => {
-                let lhs = operands.first().unwrap().eval();
-                let rhs = operands.last().unwrap().eval();
-                match operator {
-                    '+' => return lhs + rhs,
-                    '-' => return lhs - rhs,
-                    '*' => return lhs * rhs,
-                    '/' => return lhs / rhs,
-                    '^' => return lhs.powf(rhs),
-                    '√' => return lhs.powf(1.0 / (rhs)),
-                    op => panic!("Bad operator: {}", op),
+            Expression::Number(c) => Ok(*c),
+            Expression::Operation(operator, operands, position) => {
+                let operands = operands
+                    .iter()
+                    .map(Expression::eval)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let value = match (operator, operands.as_slice()) {
+                    ('+', [value]) => *value,
+                    ('-', [value]) => -value,
+                    ('!', [value]) => factorial(*value).ok_or(CalcError::DomainError(*position))?,
+                    ('%', [value]) => value / 100.0,
+                    ('+', [lhs, rhs]) => lhs + rhs,
+                    ('-', [lhs, rhs]) => lhs - rhs,
+                    ('*', [lhs, rhs]) => lhs * rhs,
+                    ('/', [_, rhs]) if *rhs == 0.0 => {
+                        return Err(CalcError::DivisionByZero(*position))
+                    }
+                    ('/', [lhs, rhs]) => lhs / rhs,
+                    ('^', [lhs, rhs]) => lhs.powf(*rhs),
+                    ('√', [_, rhs]) if *rhs == 0.0 => {
+                        return Err(CalcError::DomainError(*position))
+                    }
+                    ('√', [lhs, rhs]) => lhs.powf(1.0 / (rhs)),
+                    (op, _) => return Err(unexpected(Token::Op(*op), *position)),
+                };
+                if value.is_nan() {
+                    return Err(CalcError::DomainError(*position));
                 }
+                Ok(value)
             }
         }
     }
 }
 
-fn parse_expression(lexer: &mut Lexer, min_bp: f32) -> Expression {
+/// `n!` for a whole number `n`; too large a result is infinite.
+fn factorial(n: f64) -> Option<f64> {
+    if n < 0.0 || n.fract() != 0.0 {
+        return None;
+    }
+    // 171! no longer fits in an f64
+    if n > 170.0 {
+        return Some(f64::INFINITY);
+    }
+    Some((2..=n as u32).map(f64::from).product())
+}
+
+fn parse_expression(lexer: &mut Lexer, min_bp: f32) -> Result<Expression, CalcError> {
     let mut lhs = match lexer.next() {
-        Token::Number(it) => Expression::Number(it),
-        Token::Op('(') => {
-            let lhs = parse_expression(lexer, 0.0);
-            assert_eq!(lexer.next(), Token::Op(')'));
+        (Token::Number(it), _) | (Token::Constant(_, it), _) => Expression::Number(it),
+        (Token::Op('('), position) => {
+            let lhs = parse_expression(lexer, 0.0)?;
+            if lexer.next().0 != Token::Op(')') {
+                return Err(CalcError::UnbalancedParen(position));
+            }
             lhs
         }
-        t => panic!("bad token: {:?}", t),
+        (Token::Op(op), position) => {
+            let r_bp =
+                prefix_binding_power(op).ok_or_else(|| unexpected(Token::Op(op), position))?;
+            let operand = parse_expression(lexer, r_bp)?;
+            Expression::Operation(op, vec![operand], position)
+        }
+        (t, position) => return Err(unexpected(t, position)),
     };
     loop {
-        let op = match lexer.peek() {
-            Token::Eof => break,
-            Token::Op(')') => break,
-            Token::Op(op) => op,
-            t => panic!("bad token: {:?}", t),
+        let (op, position, implicit) = match lexer.peek() {
+            (Token::Eof, _) => break,
+            (Token::Op(')'), _) => break,
+            // `2(3 + 4)` and `3pi` multiply without an operator
+            (Token::Op('(') | Token::Constant(..), position) => ('*', position, true),
+            (Token::Op(op), position) => (op, position, false),
+            (t, position) => return Err(unexpected(t, position)),
         };
-        let (l_bp, r_bp) = infix_binding_power(op);
+
+        if let Some(l_bp) = postfix_binding_power(op) {
+            if l_bp < min_bp {
+                break;
+            }
+            lexer.next();
+            lhs = Expression::Operation(op, vec![lhs], position);
+            continue;
+        }
+
+        let (l_bp, r_bp) =
+            infix_binding_power(op).ok_or_else(|| unexpected(Token::Op(op), position))?;
 
         if l_bp < min_bp {
             break;
         }
-        lexer.next();
-        let rhs = parse_expression(lexer, r_bp);
-        lhs = Expression::Operation(op, vec![lhs, rhs]);
+        if !implicit {
+            lexer.next();
+        }
+        let rhs = parse_expression(lexer, r_bp)?;
+        lhs = Expression::Operation(op, vec![lhs, rhs], position);
+    }
+    Ok(lhs)
+}
+
+/// Below `^`, so `-2^2` is `-(2^2)`, but above `*` and `/`.
+fn prefix_binding_power(op: char) -> Option<f32> {
+    match op {
+        '+' | '-' => Some(2.5),
+        _ => None,
     }
-    lhs
 }
 
-fn infix_binding_power(op: char) -> (f32, f32) {
+fn postfix_binding_power(op: char) -> Option<f32> {
     match op {
-        '+' | '-' => (1.0, 1.1),
-        '*' | '/' => (2.0, 2.1),
-        '^' | '√' => (3.1, 3.0),
-        '.' => (4.0, 4.1),
-        _ => panic!("bad op: {:?}", op),
+        '!' | '%' => Some(4.0),
+        _ => None,
+    }
+}
+
+fn infix_binding_power(op: char) -> Option<(f32, f32)> {
+    match op {
+        '+' | '-' => Some((1.0, 1.1)),
+        '*' | '/' => Some((2.0, 2.1)),
+        '^' | '√' => Some((3.1, 3.0)),
+        _ => None,
     }
 }
 
@@ -131,8 +286,9 @@ pub fn calculator() {
         if input == "exit" {
             break;
         }
-        let expr: Expression = Expression::from_str(&input);
-        let value = expr.eval();
-        println!("{}", value);
+        match Expression::from_str(&input).and_then(|expr| expr.eval()) {
+            Ok(value) => println!("{}", value),
+            Err(err) => println!("Error: {}", err),
+        }
     }
 }
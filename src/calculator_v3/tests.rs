@@ -1,32 +1,103 @@
 #[cfg(test)]
-use crate::calculator_v3::main::Expression;
+use crate::calculator_v3::main::{CalcError, Expression};
+
+#[cfg(test)]
+fn eval(input: &str) -> Result<f64, CalcError> {
+    Expression::from_str(input)?.eval()
+}
 
 #[test]
 fn test_1() {
-    let s = Expression::from_str("1");
-    assert_eq!(s.eval(), 1.0);
+    let s = Expression::from_str("1").unwrap();
+    assert_eq!(s.eval(), Ok(1.0));
 }
 
 #[test]
 fn test_2() {
-    let s = Expression::from_str("1 + 2 * 3");
-    assert_eq!(s.eval(), 7.0);
+    let s = Expression::from_str("1 + 2 * 3").unwrap();
+    assert_eq!(s.eval(), Ok(7.0));
 }
 
 #[test]
 fn test_3() {
-    let s = Expression::from_str("12 * 2 * 5");
-    assert_eq!(s.eval(), 120.0);
+    let s = Expression::from_str("12 * 2 * 5").unwrap();
+    assert_eq!(s.eval(), Ok(120.0));
 }
 
 #[test]
 fn test_4() {
-    let s = Expression::from_str("12 / 3 * (4 - 3 / 2)");
-    assert_eq!(s.eval(), 10.0);
+    let s = Expression::from_str("12 / 3 * (4 - 3 / 2)").unwrap();
+    assert_eq!(s.eval(), Ok(10.0));
 }
 
 #[test]
 fn test_5() {
-    let s = Expression::from_str("123/123*(88^2)");
-    assert_eq!(s.eval(), 7744.0);
+    let s = Expression::from_str("123/123*(88^2)").unwrap();
+    assert_eq!(s.eval(), Ok(7744.0));
+}
+
+#[test]
+fn test_errors() {
+    assert_eq!(
+        eval("1 +"),
+        Err(CalcError::UnexpectedToken("end of input".to_string(), 3))
+    );
+    assert_eq!(eval("(2"), Err(CalcError::UnbalancedParen(0)));
+    assert_eq!(eval("2 * (1 + 3))"), Err(CalcError::UnbalancedParen(11)));
+    assert_eq!(
+        eval("1 2"),
+        Err(CalcError::UnexpectedToken("'2'".to_string(), 2))
+    );
+    assert_eq!(
+        eval("1 $ 2"),
+        Err(CalcError::UnexpectedToken("'$'".to_string(), 2))
+    );
+    assert_eq!(
+        eval("1.2.3"),
+        Err(CalcError::UnexpectedToken("'1.2.3'".to_string(), 0))
+    );
+    assert_eq!(eval("4 / (2 - 2)"), Err(CalcError::DivisionByZero(2)));
+    assert_eq!(eval("(0 - 4) ^ 0.5"), Err(CalcError::DomainError(8)));
+    assert_eq!(eval("8 √ 0"), Err(CalcError::DomainError(2)));
+    assert_eq!(
+        CalcError::UnbalancedParen(0).to_string(),
+        "unbalanced parenthesis at position 0"
+    );
+}
+
+#[test]
+fn test_unary_operators() {
+    assert_eq!(eval("-3 + 2"), Ok(-1.0));
+    assert_eq!(eval("2 * -1"), Ok(-2.0));
+    assert_eq!(eval("+4 - -4"), Ok(8.0));
+    assert_eq!(eval("-2^2"), Ok(-4.0));
+    assert_eq!(eval("2^-1"), Ok(0.5));
+    assert_eq!(eval("3! + 0!"), Ok(7.0));
+    assert_eq!(eval("-3!"), Ok(-6.0));
+    assert_eq!(eval("2^3!"), Ok(64.0));
+    assert_eq!(eval("50% * 8"), Ok(4.0));
+    assert_eq!(eval("200!"), Ok(f64::INFINITY));
+    assert_eq!(eval("(-1)!"), Err(CalcError::DomainError(4)));
+    assert_eq!(eval("2.5!"), Err(CalcError::DomainError(3)));
+    assert_eq!(eval("(-4)^0.5"), Err(CalcError::DomainError(4)));
+    assert_eq!(
+        eval("*2"),
+        Err(CalcError::UnexpectedToken("'*'".to_string(), 0))
+    );
+}
+
+#[test]
+fn test_implicit_multiplication() {
+    assert_eq!(eval("2(3+4)"), Ok(14.0));
+    assert_eq!(eval("(1 + 1)(2 + 3)"), Ok(10.0));
+    assert_eq!(eval("3pi"), Ok(3.0 * std::f64::consts::PI));
+    assert_eq!(eval("2π^2"), Ok(2.0 * std::f64::consts::PI.powi(2)));
+    assert_eq!(eval("1 + 2(3)!"), Ok(13.0));
+    assert_eq!(eval("6 / 2(3)"), Ok(9.0));
+    assert_eq!(eval("e"), Ok(std::f64::consts::E));
+    assert_eq!(
+        eval("2 tau"),
+        Err(CalcError::UnexpectedToken("'tau'".to_string(), 2))
+    );
+    assert_eq!(eval("2(3) / 0"), Err(CalcError::DivisionByZero(5)));
 }
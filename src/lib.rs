@@ -4,6 +4,8 @@ use std::env;
 
 pub mod models;
 pub mod schema;
+pub mod utils;
+pub mod xsdb;
 
 pub fn establish_connection() -> PgConnection {
     dotenv().ok();
@@ -5,9 +5,9 @@ mod calculator_v3;
 mod hello;
 mod name_db;
 mod todo;
-mod utils;
 mod weather;
-mod xsdb;
+
+use learn_rust::{utils, xsdb};
 
 fn main() {
     let functions = [
@@ -0,0 +1,270 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+};
+
+pub const PAGE_SIZE: usize = 4096;
+
+pub type PageId = u64;
+
+/// Where pages live when they are not cached, such as the database file.
+pub trait PageStore {
+    /// Fill `buffer` with the page; pages that were never written read as zeros.
+    fn read_page(&mut self, id: PageId, buffer: &mut [u8]) -> io::Result<()>;
+    fn write_page(&mut self, id: PageId, buffer: &[u8]) -> io::Result<()>;
+
+    /// Drop the pages from `pages` on; stores that cannot shrink keep them.
+    fn truncate(&mut self, _pages: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Wait until every page written so far has reached the disk.
+    fn sync(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<S: PageStore + ?Sized> PageStore for Box<S> {
+    fn read_page(&mut self, id: PageId, buffer: &mut [u8]) -> io::Result<()> {
+        (**self).read_page(id, buffer)
+    }
+
+    fn write_page(&mut self, id: PageId, buffer: &[u8]) -> io::Result<()> {
+        (**self).write_page(id, buffer)
+    }
+
+    fn truncate(&mut self, pages: u64) -> io::Result<()> {
+        (**self).truncate(pages)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        (**self).sync()
+    }
+}
+
+impl PageStore for File {
+    fn read_page(&mut self, id: PageId, buffer: &mut [u8]) -> io::Result<()> {
+        buffer.fill(0);
+        self.seek(SeekFrom::Start(id * PAGE_SIZE as u64))?;
+        // the last page may be short, or past the end of the file
+        let mut read = 0;
+        while read < buffer.len() {
+            match self.read(&mut buffer[read..])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_page(&mut self, id: PageId, buffer: &[u8]) -> io::Result<()> {
+        self.seek(SeekFrom::Start(id * PAGE_SIZE as u64))?;
+        self.write_all(buffer)
+    }
+
+    fn truncate(&mut self, pages: u64) -> io::Result<()> {
+        self.set_len(pages * PAGE_SIZE as u64)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.sync_all()
+    }
+}
+
+#[derive(Debug)]
+pub enum BufferError {
+    Io(io::Error),
+    /// Every frame holds a pinned page, so none can be evicted.
+    AllPinned,
+}
+
+impl fmt::Display for BufferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BufferError::Io(err) => write!(f, "{}", err),
+            BufferError::AllPinned => write!(f, "all buffer pool pages are pinned"),
+        }
+    }
+}
+
+impl From<io::Error> for BufferError {
+    fn from(err: io::Error) -> Self {
+        BufferError::Io(err)
+    }
+}
+
+/// Counters for benchmarking how well the pool fits the working set.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Metrics {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// Dirty pages written back to the store, on eviction or flush.
+    pub writes: u64,
+}
+
+/// A page held in the pool until it is passed back to [`BufferPool::unpin`].
+#[derive(Debug)]
+#[must_use = "a pinned page stays in memory until it is unpinned"]
+pub struct PinnedPage {
+    frame: usize,
+    id: PageId,
+}
+
+impl PinnedPage {
+    pub fn id(&self) -> PageId {
+        self.id
+    }
+}
+
+#[derive(Debug)]
+struct Frame {
+    id: PageId,
+    data: Box<[u8]>,
+    pins: usize,
+    dirty: bool,
+    /// Tick of the last pin, for least-recently-used eviction.
+    used: u64,
+}
+
+/// A fixed number of page frames cached in front of a [`PageStore`].
+///
+/// Pinned pages are never evicted. When the pool is full, the least recently used
+/// unpinned page makes room, and is written back first if it was modified.
+#[derive(Debug)]
+pub struct BufferPool<S: PageStore> {
+    store: S,
+    capacity: usize,
+    frames: Vec<Frame>,
+    pages: HashMap<PageId, usize>,
+    tick: u64,
+    metrics: Metrics,
+}
+
+impl<S: PageStore> BufferPool<S> {
+    /// A pool caching as many pages as fit in `memory_limit` bytes, and at least one.
+    pub fn new(store: S, memory_limit: usize) -> Self {
+        let capacity = (memory_limit / PAGE_SIZE).max(1);
+        Self {
+            store,
+            capacity,
+            frames: Vec::with_capacity(capacity),
+            pages: HashMap::new(),
+            tick: 0,
+            metrics: Metrics::default(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        self.metrics
+    }
+
+    /// Load the page if it is not cached and keep it in memory until unpinned.
+    pub fn pin(&mut self, id: PageId) -> Result<PinnedPage, BufferError> {
+        self.tick += 1;
+        if let Some(&frame) = self.pages.get(&id) {
+            self.metrics.hits += 1;
+            let frame_data = &mut self.frames[frame];
+            frame_data.pins += 1;
+            frame_data.used = self.tick;
+            return Ok(PinnedPage { frame, id });
+        }
+
+        self.metrics.misses += 1;
+        let frame = self.free_frame()?;
+        let frame_data = &mut self.frames[frame];
+        self.store.read_page(id, &mut frame_data.data)?;
+        frame_data.id = id;
+        frame_data.pins = 1;
+        frame_data.dirty = false;
+        frame_data.used = self.tick;
+        self.pages.insert(id, frame);
+        Ok(PinnedPage { frame, id })
+    }
+
+    pub fn unpin(&mut self, page: PinnedPage) {
+        self.frames[page.frame].pins -= 1;
+    }
+
+    pub fn page(&self, page: &PinnedPage) -> &[u8] {
+        &self.frames[page.frame].data
+    }
+
+    /// The page's bytes for writing; the page is written back before it is evicted.
+    pub fn page_mut(&mut self, page: &PinnedPage) -> &mut [u8] {
+        let frame = &mut self.frames[page.frame];
+        frame.dirty = true;
+        &mut frame.data
+    }
+
+    /// Write every modified page back to the store.
+    pub fn flush(&mut self) -> Result<(), BufferError> {
+        for frame in self.frames.iter_mut().filter(|frame| frame.dirty) {
+            self.store.write_page(frame.id, &frame.data)?;
+            frame.dirty = false;
+            self.metrics.writes += 1;
+        }
+        Ok(())
+    }
+
+    /// Wait until the pages written back so far have reached the disk of the store.
+    pub fn sync(&mut self) -> Result<(), BufferError> {
+        Ok(self.store.sync()?)
+    }
+
+    /// Drop the pages from `pages` on, from the pool without writing them back and
+    /// from the store. None of them may be pinned.
+    pub fn truncate(&mut self, pages: u64) -> Result<(), BufferError> {
+        let mut frame = 0;
+        while frame < self.frames.len() {
+            let id = self.frames[frame].id;
+            if id < pages {
+                frame += 1;
+                continue;
+            }
+            debug_assert_eq!(self.frames[frame].pins, 0);
+            self.pages.remove(&id);
+            self.frames.swap_remove(frame);
+            if let Some(moved) = self.frames.get(frame) {
+                self.pages.insert(moved.id, frame);
+            }
+        }
+        Ok(self.store.truncate(pages)?)
+    }
+
+    /// A frame to load a page into: a new one while under capacity, else an evicted one.
+    fn free_frame(&mut self) -> Result<usize, BufferError> {
+        if self.frames.len() < self.capacity {
+            self.frames.push(Frame {
+                id: 0,
+                data: vec![0; PAGE_SIZE].into_boxed_slice(),
+                pins: 0,
+                dirty: false,
+                used: 0,
+            });
+            return Ok(self.frames.len() - 1);
+        }
+
+        let (index, victim) = self
+            .frames
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, frame)| frame.pins == 0)
+            .min_by_key(|(_, frame)| frame.used)
+            .ok_or(BufferError::AllPinned)?;
+        if victim.dirty {
+            self.store.write_page(victim.id, &victim.data)?;
+            victim.dirty = false;
+            self.metrics.writes += 1;
+        }
+        self.pages.remove(&victim.id);
+        self.metrics.evictions += 1;
+        Ok(index)
+    }
+}
@@ -0,0 +1,1488 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use super::{
+    buffer::PageId,
+    database::Row,
+    expression::Expression,
+    fulltext::{Analyzer, InvertedIndex},
+    functions::{FunctionError, Functions},
+    json,
+    migrations::{MIGRATIONS_COLUMNS, MIGRATIONS_TABLE},
+    mvcc::{RowStore, Stamp, TxnId},
+    ordered::OrderedIndex,
+    query::{BodyKind, Query, WhenKind},
+    stats::{ColumnStats, STATS_COLUMNS, STATS_TABLE},
+    storage::{SharedPager, StorageError},
+    tokenizer::{TokenKind, TokenValue, Tokenizer},
+    value::Value,
+};
+
+const DEFAULT_DATABASE: &str = "main";
+
+/// Rows per `INSERT` statement of a dump.
+const INSERT_BATCH: usize = 100;
+
+fn query_value(query: &Query) -> Result<&str, CatalogError> {
+    query.value().ok_or(CatalogError::UnsupportedQuery)
+}
+
+/// `name` as it has to be written in SQL, double-quoted unless it is a plain identifier.
+fn quote_identifier(name: &str) -> String {
+    match Tokenizer::new(name).tokenize().as_deref() {
+        Ok([token, _]) if token.token() == TokenKind::Identifier && token.text() == name => {
+            name.to_string()
+        }
+        _ => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CatalogError {
+    DatabaseExists(String),
+    NoSuchDatabase(String),
+    DatabaseInUse(String),
+    TableExists(String),
+    NoSuchTable(String),
+    ColumnExists(String),
+    NoSuchColumn(String),
+    /// A column whose `DEFAULT` is not a value that can be computed without a row.
+    InvalidDefault(String),
+    /// A `JSON` column given a value that is not valid JSON.
+    InvalidJson(String),
+    /// A column name that more than one `FROM` item provides.
+    AmbiguousColumn(String),
+    /// The number of values or columns supplied, where another number was expected.
+    ColumnCountMismatch(usize, usize),
+    /// The 1-based position of a column that a set operation combines from a number
+    /// on one side and text on the other.
+    ColumnTypeMismatch(usize),
+    ViewExists(String),
+    NoSuchView(String),
+    /// A view that, through other views, selects from itself.
+    RecursiveView(String),
+    /// Triggers that kept firing other triggers, past the given depth.
+    TriggerDepth(usize),
+    /// A recursive common table that still added rows after the given number of steps.
+    RecursionLimit(String, usize),
+    TriggerExists(String),
+    NoSuchTrigger(String),
+    IndexExists(String),
+    NoSuchIndex(String),
+    /// A `FULLTEXT` index language without an analyzer.
+    NoSuchLanguage(String),
+    /// A `CREATE FUNCTION` in a language such as `plpgsql`, which xsdb cannot run.
+    UnsupportedLanguage(String),
+    UnsupportedQuery,
+    /// Rows could not be read from or written to the database file.
+    Storage(String),
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CatalogError::DatabaseExists(name) => write!(f, "database '{}' already exists", name),
+            CatalogError::NoSuchDatabase(name) => write!(f, "no such database '{}'", name),
+            CatalogError::DatabaseInUse(name) => {
+                write!(f, "cannot drop database '{}' while it is in use", name)
+            }
+            CatalogError::TableExists(name) => write!(f, "table '{}' already exists", name),
+            CatalogError::NoSuchTable(name) => write!(f, "no such table '{}'", name),
+            CatalogError::ColumnExists(name) => write!(f, "column '{}' already exists", name),
+            CatalogError::NoSuchColumn(name) => write!(f, "no such column '{}'", name),
+            CatalogError::InvalidDefault(name) => {
+                write!(f, "invalid default for column '{}'", name)
+            }
+            CatalogError::InvalidJson(name) => {
+                write!(f, "column '{}' only holds valid JSON", name)
+            }
+            CatalogError::AmbiguousColumn(name) => write!(f, "ambiguous column name '{}'", name),
+            CatalogError::ColumnCountMismatch(expected, found) => {
+                write!(f, "expected {} columns but found {}", expected, found)
+            }
+            CatalogError::ColumnTypeMismatch(position) => write!(
+                f,
+                "set operation combines column {} of different types",
+                position
+            ),
+            CatalogError::ViewExists(name) => write!(f, "view '{}' already exists", name),
+            CatalogError::NoSuchView(name) => write!(f, "no such view '{}'", name),
+            CatalogError::RecursiveView(name) => write!(f, "view '{}' selects from itself", name),
+            CatalogError::TriggerDepth(depth) => {
+                write!(f, "triggers nested more than {} deep", depth)
+            }
+            CatalogError::RecursionLimit(name, steps) => {
+                write!(
+                    f,
+                    "common table '{}' recursed more than {} times",
+                    name, steps
+                )
+            }
+            CatalogError::TriggerExists(name) => write!(f, "trigger '{}' already exists", name),
+            CatalogError::NoSuchTrigger(name) => write!(f, "no such trigger '{}'", name),
+            CatalogError::IndexExists(name) => write!(f, "index '{}' already exists", name),
+            CatalogError::NoSuchIndex(name) => write!(f, "no such index '{}'", name),
+            CatalogError::NoSuchLanguage(name) => write!(f, "no such language '{}'", name),
+            CatalogError::UnsupportedLanguage(name) => {
+                write!(f, "functions in language '{}' are not supported", name)
+            }
+            CatalogError::UnsupportedQuery => write!(f, "unsupported query"),
+            CatalogError::Storage(err) => write!(f, "database file: {}", err),
+        }
+    }
+}
+
+impl From<StorageError> for CatalogError {
+    fn from(err: StorageError) -> Self {
+        CatalogError::Storage(err.to_string())
+    }
+}
+
+/// Identifies a row of a table for as long as the row exists.
+pub type RowId = u64;
+
+/// The declared type and `DEFAULT` of a column, as written.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnDefinition {
+    type_name: Option<String>,
+    default: Option<(String, Expression)>,
+}
+
+impl ColumnDefinition {
+    /// The definition held by the `Type` and `Default` subqueries of column `name`.
+    fn new(name: &str, subqueries: &[Query]) -> Result<Self, CatalogError> {
+        let mut definition = Self::default();
+        for subquery in subqueries {
+            let text = query_value(subquery)?.to_string();
+            match subquery.body() {
+                BodyKind::Type => definition.type_name = Some(text),
+                BodyKind::Default => {
+                    let expression = Tokenizer::new(&text)
+                        .tokenize()
+                        .and_then(|tokens| Expression::parse_value(&tokens))
+                        .map_err(|_| CatalogError::InvalidDefault(name.to_string()))?;
+                    definition.default = Some((text, expression));
+                }
+                _ => return Err(CatalogError::UnsupportedQuery),
+            }
+        }
+        Ok(definition)
+    }
+
+    pub fn type_name(&self) -> Option<&str> {
+        self.type_name.as_deref()
+    }
+
+    /// Whether the column is declared `JSON` or `JSONB`, and so only holds valid JSON.
+    pub fn is_json(&self) -> bool {
+        self.type_name.as_deref().is_some_and(|name| {
+            name.eq_ignore_ascii_case("JSON") || name.eq_ignore_ascii_case("JSONB")
+        })
+    }
+
+    pub fn default_text(&self) -> Option<&str> {
+        self.default.as_ref().map(|(text, _)| text.as_str())
+    }
+
+    /// The value of the `DEFAULT`, or `NULL` without one.
+    pub(crate) fn default_value(&self, functions: &Functions) -> Result<Value, FunctionError> {
+        match &self.default {
+            Some((_, expression)) => expression.eval(functions),
+            None => Ok(Value::Null),
+        }
+    }
+}
+
+/// Columns of a table, with its rows in versions shared by the snapshots that read them.
+///
+/// Clones share the rows; a clone only reads and writes the versions at the stamp it is
+/// given, so snapshots of a database can be cloned cheaply.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    columns: Vec<String>,
+    /// The definition of each column, in column order.
+    definitions: Vec<ColumnDefinition>,
+    /// Values in column order, keyed by ids handed out in insertion order.
+    rows: Arc<RwLock<RowStore>>,
+}
+
+impl Table {
+    /// Append `INSERT` statements for the rows seen at `at` to `script`, one row per line
+    /// and [`INSERT_BATCH`] rows per statement.
+    fn dump_rows(&self, name: &str, at: Stamp, script: &mut String) -> Result<(), StorageError> {
+        let columns: Vec<_> = self.columns.iter().map(|c| quote_identifier(c)).collect();
+        for batch in self.rows(at)?.chunks(INSERT_BATCH) {
+            let values: Vec<_> = batch
+                .iter()
+                .map(|row| {
+                    let values: Vec<_> = row.iter().map(Value::to_literal).collect();
+                    format!("({})", values.join(", "))
+                })
+                .collect();
+            *script += &format!(
+                "INSERT INTO {} ({}) VALUES\n{};\n",
+                quote_identifier(name),
+                columns.join(", "),
+                values.join(",\n")
+            );
+        }
+        Ok(())
+    }
+
+    /// A table of columns with the given names and types, keeping its rows in `rows`,
+    /// for the tables the database keeps for itself.
+    fn internal(columns: &[(&str, &str)], rows: RowStore) -> Self {
+        Self {
+            columns: columns.iter().map(|(name, _)| name.to_string()).collect(),
+            definitions: columns
+                .iter()
+                .map(|(_, type_name)| ColumnDefinition {
+                    type_name: Some(type_name.to_string()),
+                    default: None,
+                })
+                .collect(),
+            rows: Arc::new(RwLock::new(rows)),
+        }
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn definitions(&self) -> &[ColumnDefinition] {
+        &self.definitions
+    }
+
+    fn store(&self) -> RwLockReadGuard<'_, RowStore> {
+        self.rows.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn store_mut(&self) -> RwLockWriteGuard<'_, RowStore> {
+        self.rows.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The number of row versions kept, including ones only older snapshots see.
+    pub fn version_count(&self) -> usize {
+        self.store().version_count()
+    }
+
+    /// How many rows are seen at `at`.
+    pub(crate) fn count(&self, at: Stamp) -> usize {
+        self.store().count(at)
+    }
+
+    /// Rows seen at `at`, in insertion order.
+    pub(crate) fn rows(&self, at: Stamp) -> Result<Vec<Row>, StorageError> {
+        let entries = self.store().entries(at)?;
+        Ok(entries.into_iter().map(|(_, row)| row).collect())
+    }
+
+    /// Rows seen at `at`, in insertion order, with the ids that update or delete them.
+    pub(crate) fn entries(&self, at: Stamp) -> Result<Vec<(RowId, Row)>, StorageError> {
+        self.store().entries(at)
+    }
+
+    pub(crate) fn row(&self, id: RowId, at: Stamp) -> Result<Option<Row>, StorageError> {
+        self.store().row(id, at)
+    }
+
+    fn insert(&self, row: Row, at: Stamp) -> Result<RowId, StorageError> {
+        self.store_mut().insert(row, at)
+    }
+
+    /// Replace a row, returning `false` if it no longer exists.
+    fn update(&self, id: RowId, row: Row, at: Stamp) -> Result<bool, StorageError> {
+        self.store_mut().update(id, row, at)
+    }
+
+    fn delete(&self, id: RowId, at: Stamp) -> Result<Option<Row>, StorageError> {
+        self.store_mut().delete(id, at)
+    }
+
+    /// Where the heap of the table's rows starts, how long it is, and the id of the
+    /// next row, for saving the table.
+    pub(crate) fn extent(&self) -> (PageId, u64, RowId) {
+        self.store().extent()
+    }
+
+    /// A copy of the table that shares no rows with this one, holding those seen at `at`
+    /// in memory.
+    fn detached(&self, at: Stamp) -> Result<Self, StorageError> {
+        Ok(Self {
+            columns: self.columns.clone(),
+            definitions: self.definitions.clone(),
+            rows: Arc::new(RwLock::new(self.store().copy(at, None, |_| ())?)),
+        })
+    }
+
+    /// Check a row about to be written against the types of the columns.
+    fn check(&self, row: &[Value]) -> Result<(), CatalogError> {
+        for ((column, definition), value) in self.columns.iter().zip(&self.definitions).zip(row) {
+            if definition.is_json() && !json::storable(value) {
+                return Err(CatalogError::InvalidJson(column.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn position(&self, column: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c == column)
+    }
+
+    /// Add a column, to be filled in existing rows with its `DEFAULT` or `NULL`.
+    fn add_column(
+        &mut self,
+        action: &Query,
+        functions: &Functions,
+        changes: &mut Vec<RowChange>,
+    ) -> Result<(), CatalogError> {
+        let name = query_value(action)?;
+        if self.position(name).is_some() {
+            return match action.when() {
+                WhenKind::IfNotExists => Ok(()),
+                _ => Err(CatalogError::ColumnExists(name.to_string())),
+            };
+        }
+        let definition = ColumnDefinition::new(name, action.subqueries())?;
+        let value = definition
+            .default_value(functions)
+            .map_err(|_| CatalogError::InvalidDefault(name.to_string()))?;
+        if definition.is_json() && !json::storable(&value) {
+            return Err(CatalogError::InvalidJson(name.to_string()));
+        }
+        self.columns.push(name.to_string());
+        self.definitions.push(definition);
+        changes.push(RowChange::Add(value));
+        Ok(())
+    }
+
+    fn drop_column(
+        &mut self,
+        action: &Query,
+        changes: &mut Vec<RowChange>,
+    ) -> Result<(), CatalogError> {
+        let name = query_value(action)?;
+        match (self.position(name), action.when()) {
+            (Some(index), _) => {
+                self.columns.remove(index);
+                self.definitions.remove(index);
+                changes.push(RowChange::Drop(index));
+                Ok(())
+            }
+            (None, WhenKind::IfExists) => Ok(()),
+            (None, _) => Err(CatalogError::NoSuchColumn(name.to_string())),
+        }
+    }
+
+    fn rename_column(&mut self, action: &Query) -> Result<(), CatalogError> {
+        let old_name = query_value(action)?;
+        let new_name = action
+            .subqueries()
+            .first()
+            .map(query_value)
+            .ok_or(CatalogError::UnsupportedQuery)??;
+        let index = self
+            .position(old_name)
+            .ok_or_else(|| CatalogError::NoSuchColumn(old_name.to_string()))?;
+        if self.position(new_name).is_some() {
+            return Err(CatalogError::ColumnExists(new_name.to_string()));
+        }
+        self.columns[index] = new_name.to_string();
+        Ok(())
+    }
+}
+
+/// How `ALTER TABLE` changes each row of the table, in order.
+enum RowChange {
+    Add(Value),
+    Drop(usize),
+}
+
+impl RowChange {
+    fn apply(&self, row: &mut Row) {
+        match self {
+            RowChange::Add(value) => row.push(value.clone()),
+            RowChange::Drop(index) => {
+                row.remove(*index);
+            }
+        }
+    }
+}
+
+fn table_in<'a>(
+    tables: &'a BTreeMap<String, Table>,
+    name: &str,
+) -> Result<&'a Table, CatalogError> {
+    tables
+        .get(name)
+        .ok_or_else(|| CatalogError::NoSuchTable(name.to_string()))
+}
+
+/// Tables of one named database.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    tables: BTreeMap<String, Table>,
+    views: BTreeMap<String, View>,
+    triggers: BTreeMap<String, Trigger>,
+    indexes: BTreeMap<String, Index>,
+}
+
+impl Schema {
+    /// Add a row to a table and to the indexes on it.
+    fn insert_row(&mut self, table: &str, row: Row, at: Stamp) -> Result<RowId, CatalogError> {
+        let table_rows = table_in(&self.tables, table)?;
+        table_rows.check(&row)?;
+        let id = table_rows.insert(row, at)?;
+        for index in self.indexes.values_mut().filter(|i| i.table == table) {
+            index.insert(table_rows, id, at)?;
+        }
+        Ok(id)
+    }
+
+    fn update_row(
+        &mut self,
+        table: &str,
+        id: RowId,
+        row: Row,
+        at: Stamp,
+    ) -> Result<bool, CatalogError> {
+        let table_rows = table_in(&self.tables, table)?;
+        table_rows.check(&row)?;
+        if !table_rows.update(id, row, at)? {
+            return Ok(false);
+        }
+        for index in self.indexes.values_mut().filter(|i| i.table == table) {
+            index.insert(table_rows, id, at)?;
+        }
+        Ok(true)
+    }
+
+    fn delete_row(
+        &mut self,
+        table: &str,
+        id: RowId,
+        at: Stamp,
+    ) -> Result<Option<Row>, CatalogError> {
+        let row = table_in(&self.tables, table)?.delete(id, at)?;
+        for index in self.indexes.values_mut().filter(|i| i.table == table) {
+            index.remove(id);
+        }
+        Ok(row)
+    }
+
+    /// Tables and views share one namespace, so either kind of name clashes.
+    fn check_name_free(&self, name: &str) -> Result<(), CatalogError> {
+        if self.tables.contains_key(name) {
+            return Err(CatalogError::TableExists(name.to_string()));
+        }
+        if self.views.contains_key(name) {
+            return Err(CatalogError::ViewExists(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Append the statements that rebuild the schema to `script`: tables, their rows seen at
+    /// `rows` if given, indexes, views, then triggers. Rows come before the triggers, so
+    /// replaying them fires none.
+    fn dump(&self, script: &mut String, rows: Option<Stamp>) -> Result<(), StorageError> {
+        for (name, table) in &self.tables {
+            let columns: Vec<_> = table
+                .columns
+                .iter()
+                .zip(&table.definitions)
+                .map(|(column, definition)| {
+                    let mut column = quote_identifier(column);
+                    if let Some(type_name) = definition.type_name() {
+                        column += &format!(" {}", type_name);
+                    }
+                    if let Some(default) = definition.default_text() {
+                        column += &format!(" DEFAULT {}", default);
+                    }
+                    column
+                })
+                .collect();
+            *script += &format!(
+                "CREATE TABLE {} ({});\n",
+                quote_identifier(name),
+                columns.join(", ")
+            );
+        }
+
+        if let Some(at) = rows {
+            for (name, table) in &self.tables {
+                table.dump_rows(name, at, script)?;
+            }
+        }
+
+        for (name, index) in &self.indexes {
+            let columns: Vec<_> = index.columns.iter().map(|c| quote_identifier(c)).collect();
+            let language = match &index.language {
+                Some(language) => format!(" WITH {}", quote_identifier(language)),
+                None => String::new(),
+            };
+            let kind = if index.is_fulltext() { "FULLTEXT " } else { "" };
+            *script += &format!(
+                "CREATE {}INDEX {} ON {} ({}){};\n",
+                kind,
+                quote_identifier(name),
+                quote_identifier(&index.table),
+                columns.join(", "),
+                language
+            );
+        }
+
+        let mut dumped = BTreeSet::new();
+        for name in self.views.keys() {
+            self.dump_view(name, &mut dumped, script);
+        }
+
+        for (name, trigger) in &self.triggers {
+            *script += &format!(
+                "CREATE TRIGGER {} {} {} ON {} FOR EACH ROW BEGIN {} END;\n",
+                quote_identifier(name),
+                trigger.timing,
+                trigger.event,
+                quote_identifier(&trigger.table),
+                trigger.statements
+            );
+        }
+        Ok(())
+    }
+
+    /// Dump the views `name` selects from before the view itself.
+    fn dump_view(&self, name: &str, dumped: &mut BTreeSet<String>, script: &mut String) {
+        if !dumped.insert(name.to_string()) {
+            return;
+        }
+        let query = &self.views[name].query;
+        for token in Tokenizer::new(query).iter().map_while(Result::ok) {
+            let dependency = match (token.token(), token.value()) {
+                (TokenKind::Identifier, _) => token.text(),
+                (TokenKind::DoubleQuotedString, Some(TokenValue::String(name))) => name,
+                _ => continue,
+            };
+            if self.views.contains_key(dependency) {
+                self.dump_view(dependency, dumped, script);
+            }
+        }
+        *script += &format!("CREATE VIEW {} AS {};\n", quote_identifier(name), query);
+    }
+}
+
+/// A named `SELECT`, stored as written.
+#[derive(Debug, Clone)]
+pub struct View {
+    query: String,
+}
+
+impl View {
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriggerTiming {
+    Before,
+    After,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriggerEvent {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl fmt::Display for TriggerTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TriggerTiming::Before => write!(f, "BEFORE"),
+            TriggerTiming::After => write!(f, "AFTER"),
+        }
+    }
+}
+
+impl fmt::Display for TriggerEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TriggerEvent::Insert => write!(f, "INSERT"),
+            TriggerEvent::Update => write!(f, "UPDATE"),
+            TriggerEvent::Delete => write!(f, "DELETE"),
+        }
+    }
+}
+
+/// A `FOR EACH ROW` trigger; its statements are stored as written.
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    table: String,
+    timing: TriggerTiming,
+    event: TriggerEvent,
+    statements: String,
+}
+
+impl Trigger {
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn timing(&self) -> TriggerTiming {
+        self.timing
+    }
+
+    pub fn event(&self) -> TriggerEvent {
+        self.event
+    }
+
+    pub fn statements(&self) -> &str {
+        &self.statements
+    }
+
+    fn from_query(query: &Query) -> Result<Self, CatalogError> {
+        let [timing, table, block] = query.subqueries() else {
+            return Err(CatalogError::UnsupportedQuery);
+        };
+        let event = match query_value(timing)? {
+            "INSERT" => TriggerEvent::Insert,
+            "UPDATE" => TriggerEvent::Update,
+            "DELETE" => TriggerEvent::Delete,
+            _ => return Err(CatalogError::UnsupportedQuery),
+        };
+        let timing = match timing.body() {
+            BodyKind::Before => TriggerTiming::Before,
+            BodyKind::After => TriggerTiming::After,
+            _ => return Err(CatalogError::UnsupportedQuery),
+        };
+        Ok(Self {
+            table: query_value(table)?.to_string(),
+            timing,
+            event,
+            statements: query_value(block)?.to_string(),
+        })
+    }
+}
+
+/// An index over columns of a table: `FULLTEXT`, or ordered by the values of the
+/// columns.
+#[derive(Debug, Clone)]
+pub struct Index {
+    table: String,
+    columns: Vec<String>,
+    /// The `WITH` language, if the index was created with one.
+    language: Option<String>,
+    keys: IndexKeys,
+}
+
+/// What an index keeps of each row, keyed by row id; copied only when a transaction
+/// changes it.
+#[derive(Debug, Clone)]
+enum IndexKeys {
+    /// The text of the indexed columns.
+    Words(Arc<InvertedIndex>),
+    /// The values of the indexed columns.
+    Ordered(Arc<OrderedIndex>),
+}
+
+impl Index {
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn is_fulltext(&self) -> bool {
+        matches!(self.keys, IndexKeys::Words(_))
+    }
+
+    /// How a `FULLTEXT` index reads text; `None` for an ordered one.
+    pub(crate) fn analyzer(&self) -> Option<&Analyzer> {
+        match &self.keys {
+            IndexKeys::Words(words) => Some(words.analyzer()),
+            IndexKeys::Ordered(_) => None,
+        }
+    }
+
+    /// The rows of an ordered index in order; `None` for a `FULLTEXT` one.
+    pub(crate) fn ordered(&self) -> Option<&OrderedIndex> {
+        match &self.keys {
+            IndexKeys::Words(_) => None,
+            IndexKeys::Ordered(ordered) => Some(ordered),
+        }
+    }
+
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// At most how many rows [`Index::search`] finds for `query`; `None` if it has no
+    /// words or the index is not `FULLTEXT`.
+    pub(crate) fn estimate(&self, query: &str) -> Option<usize> {
+        match &self.keys {
+            IndexKeys::Words(words) => words.estimate(query),
+            IndexKeys::Ordered(_) => None,
+        }
+    }
+
+    /// Rows whose indexed columns contain every word of `query`, best BM25 score first.
+    /// An ordered index finds none.
+    pub(crate) fn search(&self, query: &str) -> Vec<(RowId, f64)> {
+        match &self.keys {
+            IndexKeys::Words(words) => words.search(query),
+            IndexKeys::Ordered(_) => vec![],
+        }
+    }
+
+    /// Index every row of `table` seen at `at`, from scratch.
+    fn rebuild(&mut self, table: &Table, at: Stamp) -> Result<(), StorageError> {
+        self.keys = match &self.keys {
+            IndexKeys::Words(words) => {
+                IndexKeys::Words(Arc::new(InvertedIndex::new(words.analyzer().clone())))
+            }
+            IndexKeys::Ordered(_) => IndexKeys::Ordered(Arc::default()),
+        };
+        for (id, _) in table.entries(at)? {
+            self.insert(table, id, at)?;
+        }
+        Ok(())
+    }
+
+    /// Index row `id` of `table`, the table this index is on, as seen at `at`, replacing
+    /// what was indexed for it before. A `FULLTEXT` index indexes numbers as their text
+    /// and skips `NULL`s and blobs.
+    fn insert(&mut self, table: &Table, id: RowId, at: Stamp) -> Result<(), StorageError> {
+        let Some(row) = table.row(id, at)? else {
+            return Ok(());
+        };
+        let values = self
+            .columns
+            .iter()
+            .filter_map(|column| table.position(column).map(|position| &row[position]));
+        match &mut self.keys {
+            IndexKeys::Words(words) => {
+                let text: Vec<_> = values
+                    .filter(|value| !matches!(value, Value::Null | Value::Blob(_)))
+                    .map(Value::to_string)
+                    .collect();
+                Arc::make_mut(words).insert(id, &text.join(" "));
+            }
+            IndexKeys::Ordered(ordered) => {
+                Arc::make_mut(ordered).insert(id, values.cloned().collect());
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, id: RowId) {
+        match &mut self.keys {
+            IndexKeys::Words(words) => Arc::make_mut(words).remove(id),
+            IndexKeys::Ordered(ordered) => Arc::make_mut(ordered).remove(id),
+        }
+    }
+}
+
+/// Databases created in the current session; table statements apply to the one in use.
+///
+/// Clones share the rows of their tables, see [`Table`].
+#[derive(Debug, Clone)]
+pub struct Catalog {
+    databases: BTreeMap<String, Schema>,
+    current: String,
+    /// The statement rows are read and written at.
+    stamp: Stamp,
+    /// The pages of the database file new tables keep their rows in, if there is one.
+    pager: Option<SharedPager>,
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Self {
+            databases: BTreeMap::from([(DEFAULT_DATABASE.to_string(), Schema::default())]),
+            current: DEFAULT_DATABASE.to_string(),
+            stamp: Stamp::default(),
+            pager: None,
+        }
+    }
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An empty catalog whose tables keep their rows in the pages of `pager`, if given.
+    pub(crate) fn paged(pager: Option<SharedPager>) -> Self {
+        Self {
+            pager,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn pager(&self) -> Option<&SharedPager> {
+        self.pager.as_ref()
+    }
+
+    /// A store for the rows of a new table.
+    fn new_store(&self) -> RowStore {
+        match &self.pager {
+            Some(pager) => RowStore::paged(pager),
+            None => RowStore::default(),
+        }
+    }
+
+    /// The transaction that wrote this version of the catalog, or is writing it.
+    pub fn txn(&self) -> TxnId {
+        self.stamp.txn()
+    }
+
+    pub(crate) fn stamp(&self) -> Stamp {
+        self.stamp
+    }
+
+    /// Read and write rows as transaction `txn`, from before its first statement.
+    pub(crate) fn begin_transaction(&mut self, txn: TxnId) {
+        self.stamp = Stamp::begin(txn);
+    }
+
+    /// Move on to the next statement, so that it can be rolled back on its own.
+    pub(crate) fn begin_statement(&mut self) {
+        self.stamp = self.stamp.next_statement();
+    }
+
+    /// Undo every row written at `from` or later, in the tables of this catalog.
+    pub(crate) fn rollback(&self, from: Stamp) {
+        for schema in self.databases.values() {
+            for table in schema.tables.values() {
+                table.store_mut().rollback(from);
+            }
+        }
+    }
+
+    /// Let every table know that its rows up to now are saved.
+    pub(crate) fn committed(&self) {
+        for (_, _, table) in self.all_tables() {
+            table.store_mut().committed();
+        }
+    }
+
+    /// Forget the row versions no reader sees any more, see [`RowStore::prune`].
+    pub(crate) fn prune_rows(&self, horizon: Stamp) {
+        for (_, _, table) in self.all_tables() {
+            table.store_mut().prune(horizon);
+        }
+    }
+
+    /// Move the rows of every table to new pages, see [`RowStore::rewrite`].
+    pub(crate) fn rewrite_rows(&self, horizon: Stamp) -> Result<(), StorageError> {
+        for (_, _, table) in self.all_tables() {
+            table.store_mut().rewrite(horizon)?;
+        }
+        Ok(())
+    }
+
+    /// A copy that shares no rows with this catalog, and keeps its own in memory.
+    pub(crate) fn detached(&self) -> Result<Self, StorageError> {
+        let mut catalog = self.clone();
+        catalog.pager = None;
+        for schema in catalog.databases.values_mut() {
+            for table in schema.tables.values_mut() {
+                *table = table.detached(self.stamp)?;
+            }
+        }
+        Ok(catalog)
+    }
+
+    /// Run a schema statement, computing column defaults with `functions`.
+    pub fn execute(&mut self, query: &Query, functions: &Functions) -> Result<(), CatalogError> {
+        match query.body() {
+            BodyKind::CreateTable => self.create_table(query),
+            BodyKind::AlterTable => self.alter_table(query, functions),
+            BodyKind::DropTable => self.drop_table(query),
+            BodyKind::CreateDatabase => self.create_database(query),
+            BodyKind::DropDatabase => self.drop_database(query),
+            BodyKind::UseDatabase => self.use_database(query),
+            BodyKind::Vacuum => self.vacuum(query),
+            BodyKind::Analyze => self.analyze(query),
+            BodyKind::CreateView => self.create_view(query),
+            BodyKind::DropView => self.drop_view(query),
+            BodyKind::CreateTrigger => self.create_trigger(query),
+            BodyKind::DropTrigger => self.drop_trigger(query),
+            BodyKind::CreateFulltextIndex | BodyKind::CreateIndex => self.create_index(query),
+            BodyKind::DropIndex => self.drop_index(query),
+            BodyKind::CreateFunction => {
+                let language = query.subqueries().first().and_then(Query::value);
+                Err(CatalogError::UnsupportedLanguage(
+                    language.unwrap_or_default().to_string(),
+                ))
+            }
+            _ => Err(CatalogError::UnsupportedQuery),
+        }
+    }
+
+    pub fn current_database(&self) -> &str {
+        &self.current
+    }
+
+    pub fn database_names(&self) -> impl Iterator<Item = &str> {
+        self.databases.keys().map(|name| name.as_str())
+    }
+
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables().get(name)
+    }
+
+    /// Add a row to a table, keeping the indexes on it up to date.
+    pub(crate) fn insert_row(&mut self, table: &str, row: Row) -> Result<RowId, CatalogError> {
+        let at = self.stamp;
+        self.schema_mut().insert_row(table, row, at)
+    }
+
+    /// Replace a row, returning `false` if it no longer exists.
+    pub(crate) fn update_row(
+        &mut self,
+        table: &str,
+        id: RowId,
+        row: Row,
+    ) -> Result<bool, CatalogError> {
+        let at = self.stamp;
+        self.schema_mut().update_row(table, id, row, at)
+    }
+
+    pub(crate) fn delete_row(
+        &mut self,
+        table: &str,
+        id: RowId,
+    ) -> Result<Option<Row>, CatalogError> {
+        let at = self.stamp;
+        self.schema_mut().delete_row(table, id, at)
+    }
+
+    pub fn table_names(&self) -> impl Iterator<Item = &str> {
+        self.tables().keys().map(|name| name.as_str())
+    }
+
+    pub fn view(&self, name: &str) -> Option<&View> {
+        self.schema().views.get(name)
+    }
+
+    pub fn view_names(&self) -> impl Iterator<Item = &str> {
+        self.schema().views.keys().map(|name| name.as_str())
+    }
+
+    pub fn trigger(&self, name: &str) -> Option<&Trigger> {
+        self.schema().triggers.get(name)
+    }
+
+    pub fn trigger_names(&self) -> impl Iterator<Item = &str> {
+        self.schema().triggers.keys().map(|name| name.as_str())
+    }
+
+    pub fn index(&self, name: &str) -> Option<&Index> {
+        self.schema().indexes.get(name)
+    }
+
+    pub fn index_names(&self) -> impl Iterator<Item = &str> {
+        self.schema().indexes.keys().map(|name| name.as_str())
+    }
+
+    /// The indexes on `table` with their names, in name order.
+    pub(crate) fn indexes_on<'a>(
+        &'a self,
+        table: &'a str,
+    ) -> impl Iterator<Item = (&'a str, &'a Index)> {
+        self.schema()
+            .indexes
+            .iter()
+            .filter(move |(_, index)| index.table == table)
+            .map(|(name, index)| (name.as_str(), index))
+    }
+
+    /// Every table of every database, with the name of the database it belongs to.
+    pub(crate) fn all_tables(&self) -> impl Iterator<Item = (&str, &str, &Table)> {
+        self.databases.iter().flat_map(|(database, schema)| {
+            schema
+                .tables
+                .iter()
+                .map(move |(name, table)| (database.as_str(), name.as_str(), table))
+        })
+    }
+
+    /// Give a table of `database` the rows read back from a database file, and index them.
+    pub(crate) fn load_rows(
+        &mut self,
+        database: &str,
+        table: &str,
+        rows: RowStore,
+    ) -> Result<(), CatalogError> {
+        let at = self.stamp;
+        let schema = self
+            .databases
+            .get_mut(database)
+            .ok_or_else(|| CatalogError::NoSuchDatabase(database.to_string()))?;
+        let loaded = schema
+            .tables
+            .get_mut(table)
+            .ok_or_else(|| CatalogError::NoSuchTable(table.to_string()))?;
+        loaded.rows = Arc::new(RwLock::new(rows));
+        for index in schema.indexes.values_mut().filter(|i| i.table == table) {
+            index.rebuild(loaded, at)?;
+        }
+        Ok(())
+    }
+
+    /// Versions of the migrations applied to the database in use, oldest first, as
+    /// recorded in its [`MIGRATIONS_TABLE`].
+    pub fn applied_migrations(&self) -> Result<Vec<String>, CatalogError> {
+        let Some(table) = self.table(MIGRATIONS_TABLE) else {
+            return Ok(vec![]);
+        };
+        let mut versions: Vec<_> = table
+            .rows(self.stamp)?
+            .into_iter()
+            .filter_map(|row| match row.into_iter().next() {
+                Some(Value::Text(version)) => Some(version),
+                _ => None,
+            })
+            .collect();
+        versions.sort();
+        Ok(versions)
+    }
+
+    /// Record `version` as applied, creating the [`MIGRATIONS_TABLE`] first if needed.
+    pub(crate) fn record_migration(&mut self, version: &str) -> Result<(), CatalogError> {
+        if !self.tables().contains_key(MIGRATIONS_TABLE) {
+            self.schema_mut().check_name_free(MIGRATIONS_TABLE)?;
+            let table = Table::internal(&MIGRATIONS_COLUMNS, self.new_store());
+            self.tables_mut()
+                .insert(MIGRATIONS_TABLE.to_string(), table);
+        }
+        self.insert_row(MIGRATIONS_TABLE, vec![Value::Text(version.to_string())])?;
+        Ok(())
+    }
+
+    pub(crate) fn forget_migration(&mut self, version: &str) -> Result<(), CatalogError> {
+        let Some(table) = self.table(MIGRATIONS_TABLE) else {
+            return Ok(());
+        };
+        let recorded: Vec<_> = table
+            .entries(self.stamp)?
+            .into_iter()
+            .filter(|(_, row)| matches!(&row[0], Value::Text(applied) if applied == version))
+            .map(|(id, _)| id)
+            .collect();
+        for id in recorded {
+            self.delete_row(MIGRATIONS_TABLE, id)?;
+        }
+        Ok(())
+    }
+
+    /// Triggers to fire, in name order, when `event` happens on each row of `table`.
+    pub fn triggers_for<'a>(
+        &'a self,
+        table: &'a str,
+        timing: TriggerTiming,
+        event: TriggerEvent,
+    ) -> impl Iterator<Item = &'a Trigger> {
+        self.schema()
+            .triggers
+            .values()
+            .filter(move |t| t.table == table && t.timing == timing && t.event == event)
+    }
+
+    /// A script that rebuilds every database and its rows when replayed, ending in the
+    /// one in use.
+    pub fn dump(&self) -> Result<String, StorageError> {
+        self.script(Some(self.stamp))
+    }
+
+    /// The script [`Catalog::dump`] writes, without the rows.
+    pub fn dump_schema(&self) -> Result<String, StorageError> {
+        self.script(None)
+    }
+
+    /// The dump, with the rows seen at `rows` if given.
+    fn script(&self, rows: Option<Stamp>) -> Result<String, StorageError> {
+        let mut script = String::new();
+        for (name, schema) in &self.databases {
+            if name != DEFAULT_DATABASE {
+                script += &format!("CREATE DATABASE {};\n", quote_identifier(name));
+            }
+            script += &format!("USE {};\n", quote_identifier(name));
+            schema.dump(&mut script, rows)?;
+        }
+        script += &format!("USE {};\n", quote_identifier(&self.current));
+        Ok(script)
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.databases[&self.current]
+    }
+
+    fn schema_mut(&mut self) -> &mut Schema {
+        self.databases.get_mut(&self.current).unwrap()
+    }
+
+    fn tables(&self) -> &BTreeMap<String, Table> {
+        &self.schema().tables
+    }
+
+    fn tables_mut(&mut self) -> &mut BTreeMap<String, Table> {
+        &mut self.schema_mut().tables
+    }
+
+    fn create_database(&mut self, query: &Query) -> Result<(), CatalogError> {
+        let name = query_value(query)?;
+        if self.databases.contains_key(name) {
+            return match query.when() {
+                WhenKind::IfNotExists => Ok(()),
+                _ => Err(CatalogError::DatabaseExists(name.to_string())),
+            };
+        }
+        self.databases.insert(name.to_string(), Schema::default());
+        Ok(())
+    }
+
+    fn drop_database(&mut self, query: &Query) -> Result<(), CatalogError> {
+        let name = query_value(query)?;
+        if !self.databases.contains_key(name) {
+            return match query.when() {
+                WhenKind::IfExists => Ok(()),
+                _ => Err(CatalogError::NoSuchDatabase(name.to_string())),
+            };
+        }
+        if name == self.current {
+            return Err(CatalogError::DatabaseInUse(name.to_string()));
+        }
+        self.databases.remove(name);
+        Ok(())
+    }
+
+    fn use_database(&mut self, query: &Query) -> Result<(), CatalogError> {
+        let name = query_value(query)?;
+        if !self.databases.contains_key(name) {
+            return Err(CatalogError::NoSuchDatabase(name.to_string()));
+        }
+        self.current = name.to_string();
+        Ok(())
+    }
+
+    /// Every database is kept in one file, which is compacted as a whole once the
+    /// statement succeeds; this checks that the database exists.
+    fn vacuum(&mut self, query: &Query) -> Result<(), CatalogError> {
+        let name = query.value().unwrap_or(&self.current);
+        if !self.databases.contains_key(name) {
+            return Err(CatalogError::NoSuchDatabase(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Replace the statistics of the table named, or of every table, in [`STATS_TABLE`],
+    /// creating it first if needed.
+    fn analyze(&mut self, query: &Query) -> Result<(), CatalogError> {
+        let names: Vec<_> = match query.value() {
+            Some(name) if !self.tables().contains_key(name) => {
+                return Err(CatalogError::NoSuchTable(name.to_string()))
+            }
+            Some(name) => vec![name.to_string()],
+            None => self
+                .table_names()
+                .filter(|name| *name != STATS_TABLE)
+                .map(str::to_string)
+                .collect(),
+        };
+        if !self.tables().contains_key(STATS_TABLE) {
+            self.schema_mut().check_name_free(STATS_TABLE)?;
+            let table = Table::internal(&STATS_COLUMNS, self.new_store());
+            self.tables_mut().insert(STATS_TABLE.to_string(), table);
+        }
+
+        for name in names {
+            let table = &self.tables()[&name];
+            let rows = table.rows(self.stamp)?;
+            let stats: Vec<_> = table
+                .columns()
+                .iter()
+                .enumerate()
+                .map(|(position, column)| {
+                    ColumnStats::collect(rows.iter().map(|row| &row[position]))
+                        .to_row(&name, column)
+                })
+                .collect();
+            self.forget_stats(&name)?;
+            for row in stats {
+                self.insert_row(STATS_TABLE, row)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete the statistics `ANALYZE` kept for `table`, if any.
+    fn forget_stats(&mut self, table: &str) -> Result<(), CatalogError> {
+        let Some(stats) = self.tables().get(STATS_TABLE) else {
+            return Ok(());
+        };
+        let stale: Vec<_> = stats
+            .entries(self.stamp)?
+            .into_iter()
+            .filter(|(_, row)| matches!(&row[0], Value::Text(name) if name == table))
+            .map(|(id, _)| id)
+            .collect();
+        for id in stale {
+            self.delete_row(STATS_TABLE, id)?;
+        }
+        Ok(())
+    }
+
+    /// What the last `ANALYZE` of `table` found in each of its columns, by column name.
+    pub(crate) fn column_stats(
+        &self,
+        table: &str,
+    ) -> Result<HashMap<String, ColumnStats>, StorageError> {
+        let Some(stats) = self.table(STATS_TABLE) else {
+            return Ok(HashMap::new());
+        };
+        Ok(stats
+            .rows(self.stamp)?
+            .iter()
+            .filter_map(|row| ColumnStats::from_row(row))
+            .filter(|(name, _, _)| name == table)
+            .map(|(_, column, stats)| (column, stats))
+            .collect())
+    }
+
+    fn drop_table(&mut self, query: &Query) -> Result<(), CatalogError> {
+        let name = query_value(query)?;
+        match (self.tables_mut().remove(name), query.when()) {
+            (Some(_), _) => {
+                self.schema_mut().triggers.retain(|_, t| t.table != name);
+                self.schema_mut().indexes.retain(|_, i| i.table != name);
+                self.forget_stats(name)
+            }
+            (None, WhenKind::IfExists) => Ok(()),
+            (None, _) => Err(CatalogError::NoSuchTable(name.to_string())),
+        }
+    }
+
+    fn create_view(&mut self, query: &Query) -> Result<(), CatalogError> {
+        let name = query_value(query)?;
+        if let Err(err) = self.schema().check_name_free(name) {
+            return match query.when() {
+                WhenKind::IfNotExists => Ok(()),
+                _ => Err(err),
+            };
+        }
+
+        let select = query
+            .subqueries()
+            .first()
+            .filter(|select| matches!(select.body(), BodyKind::Select | BodyKind::SelectDistinct))
+            .map(query_value)
+            .ok_or(CatalogError::UnsupportedQuery)??;
+        let view = View {
+            query: select.to_string(),
+        };
+        self.schema_mut().views.insert(name.to_string(), view);
+        Ok(())
+    }
+
+    fn create_trigger(&mut self, query: &Query) -> Result<(), CatalogError> {
+        let name = query_value(query)?;
+        if self.schema().triggers.contains_key(name) {
+            return match query.when() {
+                WhenKind::IfNotExists => Ok(()),
+                _ => Err(CatalogError::TriggerExists(name.to_string())),
+            };
+        }
+
+        let trigger = Trigger::from_query(query)?;
+        if !self.tables().contains_key(&trigger.table) {
+            return Err(CatalogError::NoSuchTable(trigger.table));
+        }
+        self.schema_mut().triggers.insert(name.to_string(), trigger);
+        Ok(())
+    }
+
+    fn drop_trigger(&mut self, query: &Query) -> Result<(), CatalogError> {
+        let name = query_value(query)?;
+        match (self.schema_mut().triggers.remove(name), query.when()) {
+            (Some(_), _) | (None, WhenKind::IfExists) => Ok(()),
+            (None, _) => Err(CatalogError::NoSuchTrigger(name.to_string())),
+        }
+    }
+
+    fn create_index(&mut self, query: &Query) -> Result<(), CatalogError> {
+        let name = query_value(query)?;
+        if self.schema().indexes.contains_key(name) {
+            return match query.when() {
+                WhenKind::IfNotExists => Ok(()),
+                _ => Err(CatalogError::IndexExists(name.to_string())),
+            };
+        }
+
+        let (table_name, fields) = query
+            .subqueries()
+            .split_first()
+            .ok_or(CatalogError::UnsupportedQuery)?;
+        let table_name = query_value(table_name)?;
+        let table = self
+            .table(table_name)
+            .ok_or_else(|| CatalogError::NoSuchTable(table_name.to_string()))?;
+        let mut columns = vec![];
+        let mut language = None;
+        for field in fields {
+            let column = query_value(field)?;
+            if field.body() == BodyKind::Language {
+                language = Some(column.to_string());
+                continue;
+            }
+            if table.position(column).is_none() {
+                return Err(CatalogError::NoSuchColumn(column.to_string()));
+            }
+            columns.push(column.to_string());
+        }
+        let keys = match (query.body(), &language) {
+            (BodyKind::CreateIndex, _) => IndexKeys::Ordered(Arc::default()),
+            (_, Some(language)) => {
+                let analyzer = Analyzer::for_language(language)
+                    .ok_or_else(|| CatalogError::NoSuchLanguage(language.to_string()))?;
+                IndexKeys::Words(Arc::new(InvertedIndex::new(analyzer)))
+            }
+            (_, None) => IndexKeys::Words(Arc::default()),
+        };
+
+        let mut index = Index {
+            table: table_name.to_string(),
+            columns,
+            language,
+            keys,
+        };
+        index.rebuild(table, self.stamp)?;
+        self.schema_mut().indexes.insert(name.to_string(), index);
+        Ok(())
+    }
+
+    fn drop_index(&mut self, query: &Query) -> Result<(), CatalogError> {
+        let name = query_value(query)?;
+        match (self.schema_mut().indexes.remove(name), query.when()) {
+            (Some(_), _) | (None, WhenKind::IfExists) => Ok(()),
+            (None, _) => Err(CatalogError::NoSuchIndex(name.to_string())),
+        }
+    }
+
+    fn drop_view(&mut self, query: &Query) -> Result<(), CatalogError> {
+        let name = query_value(query)?;
+        match (self.schema_mut().views.remove(name), query.when()) {
+            (Some(_), _) | (None, WhenKind::IfExists) => Ok(()),
+            (None, _) => Err(CatalogError::NoSuchView(name.to_string())),
+        }
+    }
+
+    fn create_table(&mut self, query: &Query) -> Result<(), CatalogError> {
+        let name = query_value(query)?;
+        if let Err(err) = self.schema().check_name_free(name) {
+            return match query.when() {
+                WhenKind::IfNotExists => Ok(()),
+                _ => Err(err),
+            };
+        }
+
+        let mut columns = vec![];
+        let mut definitions = vec![];
+        for field in query.subqueries() {
+            let column = query_value(field)?;
+            columns.push(column.to_string());
+            definitions.push(ColumnDefinition::new(column, field.subqueries())?);
+        }
+        let table = Table {
+            columns,
+            definitions,
+            rows: Arc::new(RwLock::new(self.new_store())),
+        };
+        self.tables_mut().insert(name.to_string(), table);
+        Ok(())
+    }
+
+    /// Apply every action to a copy of the table and only replace it once all succeeded,
+    /// then rebuild the indexes on it. Adding or dropping columns copies the rows, so
+    /// snapshots taken before keep reading the old ones.
+    fn alter_table(&mut self, query: &Query, functions: &Functions) -> Result<(), CatalogError> {
+        let name = query_value(query)?;
+        let mut table = self
+            .tables()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| CatalogError::NoSuchTable(name.to_string()))?;
+        let mut new_name = name;
+        // each dropped column, or renamed column with its new name, in order
+        let mut changed_columns = vec![];
+        let mut changes = vec![];
+
+        for action in query.subqueries() {
+            match action.body() {
+                BodyKind::AddColumn => table.add_column(action, functions, &mut changes)?,
+                BodyKind::DropColumn => {
+                    table.drop_column(action, &mut changes)?;
+                    changed_columns.push((query_value(action)?, None));
+                }
+                BodyKind::RenameColumn => {
+                    table.rename_column(action)?;
+                    let renamed = action.subqueries().first().map(query_value).transpose()?;
+                    changed_columns.push((query_value(action)?, renamed));
+                }
+                BodyKind::RenameTable => new_name = query_value(action)?,
+                _ => return Err(CatalogError::UnsupportedQuery),
+            }
+        }
+
+        if new_name != name {
+            self.schema().check_name_free(new_name)?;
+        }
+        if !changes.is_empty() {
+            let rows = table.store().copy(self.stamp, self.pager.as_ref(), |row| {
+                for change in &changes {
+                    change.apply(row);
+                }
+            })?;
+            table.rows = Arc::new(RwLock::new(rows));
+        }
+        self.tables_mut().remove(name);
+        self.tables_mut().insert(new_name.to_string(), table);
+        for trigger in self.schema_mut().triggers.values_mut() {
+            if trigger.table == name {
+                trigger.table = new_name.to_string();
+            }
+        }
+
+        // like dropping the table, dropping an indexed column drops the index
+        self.schema_mut().indexes.retain(|_, index| {
+            if index.table != name {
+                return true;
+            }
+            index.table = new_name.to_string();
+            for (old, new) in &changed_columns {
+                for column in index.columns.iter_mut().filter(|c| c == old) {
+                    match new {
+                        Some(new) => *column = new.to_string(),
+                        None => return false,
+                    }
+                }
+            }
+            true
+        });
+        let at = self.stamp;
+        let schema = self.schema_mut();
+        if let Some(table) = schema.tables.get(new_name) {
+            for index in schema.indexes.values_mut() {
+                if index.table == new_name {
+                    index.rebuild(table, at)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
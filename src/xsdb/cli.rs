@@ -1,11 +1,275 @@
-use crate::utils::ui::get_text;
+use std::fs;
 
-use crate::xsdb::{query::Query, tokenizer::Tokenizer};
+use tokio::net::TcpListener;
+
+use crate::xsdb::{
+    catalog::Catalog,
+    completion::SqlCompleter,
+    database::{Database, Variables},
+    editor::read_line,
+    migrations::Migrations,
+    server,
+    shared::SharedDatabase,
+};
+
+const DEFAULT_PORT: u16 = 5433;
+const MIGRATIONS_DIR: &str = "migrations";
 
 pub fn main() {
-    let input = get_text("Enter your query: ");
-    let mut tokenizer = Tokenizer::new(input.as_str());
-    let tokens = tokenizer.tokenize().unwrap();
-    let query = Query::new(tokens);
-    println!("{:?}", query);
+    println!("XSDb");
+    println!("enter '.exit' to quit, '.schema' to list the schema, '.dbinfo' for statistics");
+    println!("'.open file' opens a database file, saving every change to it from then on");
+    println!("'.dump [file]' writes schema and rows as SQL, '.restore file' replays such a script");
+    println!("'.migrate [run|list|revert|redo] [dir]' applies migrations/<version>_<name>/up.sql");
+    println!("'SET @name := value' or 'name := (SELECT ...)' sets a variable, '.vars' lists them");
+    println!("'.serve [port]' accepts PostgreSQL clients until Ctrl+C");
+    println!("############");
+    let mut db = Database::new();
+    loop {
+        let prompt = format!("xsdb ({})>", db.catalog().current_database());
+        let input = match read_line(&prompt, SqlCompleter::new(db.catalog())) {
+            Ok(Some(input)) => input,
+            Ok(None) => break,
+            Err(err) => {
+                println!("Error: {}", err);
+                break;
+            }
+        };
+        match input.trim() {
+            ".exit" => break,
+            ".schema" => {
+                print_schema(db.catalog());
+                continue;
+            }
+            ".dbinfo" => {
+                print_dbinfo(&db);
+                continue;
+            }
+            ".vars" => {
+                print_variables(db.variables());
+                continue;
+            }
+            command if command.starts_with(".open") => {
+                match command[".open".len()..].trim() {
+                    "" => println!("Error: usage: .open file"),
+                    path => match Database::open(path) {
+                        Ok(opened) => db = opened,
+                        Err(err) => println!("Error: {}", err),
+                    },
+                }
+                continue;
+            }
+            command if command.starts_with(".dump") => {
+                dump(&db, command[".dump".len()..].trim());
+                continue;
+            }
+            command if command.starts_with(".restore") => {
+                match command[".restore".len()..].trim() {
+                    "" => println!("Error: usage: .restore file"),
+                    path => restore(&mut db, path),
+                }
+                continue;
+            }
+            command if command.starts_with(".migrate") => {
+                migrate(&mut db, command[".migrate".len()..].trim());
+                continue;
+            }
+            command if command.starts_with(".serve") => {
+                match command[".serve".len()..].trim() {
+                    "" => db = serve(db, DEFAULT_PORT),
+                    port => match port.parse() {
+                        Ok(port) => db = serve(db, port),
+                        Err(_) => println!("Error: invalid port '{}'", port),
+                    },
+                }
+                continue;
+            }
+            _ => (),
+        }
+
+        match db.execute(&input, &[]) {
+            Ok(result) if result.columns().is_empty() => println!("OK"),
+            Ok(result) => {
+                println!("{}", result.columns().join(" | "));
+                for row in result.rows() {
+                    let row = row.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                    println!("{}", row.join(" | "));
+                }
+            }
+            Err(err) => println!("Error: {}", err),
+        }
+    }
+}
+
+/// Print the dump, or write it to `path` if one is given.
+fn dump(db: &Database, path: &str) {
+    let script = match db.catalog().dump() {
+        Ok(script) => script,
+        Err(err) => return println!("Error: {}", err),
+    };
+    if path.is_empty() {
+        return print!("{}", script);
+    }
+    match fs::write(path, script) {
+        Ok(()) => println!("dumped to {}", path),
+        Err(err) => println!("Error: {}", err),
+    }
+}
+
+fn restore(db: &mut Database, path: &str) {
+    let script = match fs::read_to_string(path) {
+        Ok(script) => script,
+        Err(err) => return println!("Error: {}", err),
+    };
+    match db.restore(&script) {
+        Ok(count) => println!("restored {} statements from {}", count, path),
+        Err(err) => println!("Error: {}, nothing restored", err),
+    }
+}
+
+/// `.migrate [run|list|revert|redo] [dir]`, reading `migrations/` by default.
+fn migrate(db: &mut Database, args: &str) {
+    let mut args = args.split_whitespace();
+    let action = args.next().unwrap_or("run");
+    let dir = args.next().unwrap_or(MIGRATIONS_DIR);
+    let migrations = match Migrations::load(dir) {
+        Ok(migrations) => migrations,
+        Err(err) => return println!("Error: {}: {}", dir, err),
+    };
+
+    let result = match action {
+        "run" => migrations.run_pending(db).map(|applied| {
+            if applied.is_empty() {
+                println!("no pending migrations");
+            }
+            for version in applied {
+                println!("applied {}", version);
+            }
+        }),
+        "revert" => migrations
+            .revert(db)
+            .map(|version| println!("reverted {}", version)),
+        "redo" => migrations
+            .redo(db)
+            .map(|version| println!("redid {}", version)),
+        "list" => migrations.pending(db).map(|pending| {
+            let pending: Vec<_> = pending.map(|m| m.version()).collect();
+            for migration in migrations.iter() {
+                let mark = if pending.contains(&migration.version()) {
+                    " "
+                } else {
+                    "X"
+                };
+                println!("[{}] {}_{}", mark, migration.version(), migration.name());
+            }
+        }),
+        _ => return println!("Error: usage: .migrate [run|list|revert|redo] [dir]"),
+    };
+    if let Err(err) = result {
+        println!("Error: {}", err);
+    }
+}
+
+/// Share `db` with PostgreSQL clients on localhost until Ctrl+C, then hand it back.
+fn serve(db: Database, port: u16) -> Database {
+    let file = db.storage().is_some();
+    let db = SharedDatabase::new(db);
+    let runtime = tokio::runtime::Runtime::new().expect("failed to start the tokio runtime");
+    runtime.block_on(async {
+        let listener = match TcpListener::bind(("127.0.0.1", port)).await {
+            Ok(listener) => listener,
+            Err(err) => return println!("Error: {}", err),
+        };
+        println!(
+            "listening on 127.0.0.1:{}, e.g. psql -h 127.0.0.1 -p {}",
+            port, port
+        );
+        tokio::select! {
+            result = server::serve(listener, db.clone()) => {
+                if let Err(err) = result {
+                    println!("Error: {}", err);
+                }
+            }
+            _ = tokio::signal::ctrl_c() => println!("server stopped"),
+        }
+    });
+    // dropping the runtime ends the sessions, rolling back any open transaction
+    drop(runtime);
+    let fallback = db.snapshot().database().fork();
+    // the last handle hands its file over without copying, so this only fails in theory
+    futures::executor::block_on(db.into_database()).unwrap_or_else(|err| {
+        println!("Error: {}", err);
+        if file {
+            println!("changes are no longer saved to the database file");
+        }
+        fallback
+    })
+}
+
+fn print_dbinfo(db: &Database) {
+    let catalog = db.catalog();
+    println!("database:  {}", catalog.current_database());
+    println!("databases: {}", catalog.database_names().count());
+    println!("tables:    {}", catalog.table_names().count());
+    println!("views:     {}", catalog.view_names().count());
+    println!("triggers:  {}", catalog.trigger_names().count());
+    println!("indexes:   {}", catalog.index_names().count());
+    match db.storage() {
+        Some(storage) => {
+            let metrics = storage.metrics();
+            let free = storage.free_pages();
+            println!(
+                "pages:     {} ({} free, {:.0}% fragmentation)",
+                storage.pages(),
+                free,
+                100.0 * free as f64 / storage.pages() as f64
+            );
+            println!(
+                "cache:     {} hits, {} misses, {} evictions",
+                metrics.hits, metrics.misses, metrics.evictions
+            );
+        }
+        None => println!("storage:   in memory"),
+    }
+}
+
+fn print_variables(variables: &Variables) {
+    for (name, value) in variables {
+        println!("@{} = {}", name, value);
+    }
+}
+
+fn print_schema(catalog: &Catalog) {
+    for name in catalog.table_names() {
+        let columns = catalog.table(name).unwrap().columns();
+        println!("TABLE {} ({})", name, columns.join(", "));
+    }
+    for name in catalog.index_names() {
+        let index = catalog.index(name).unwrap();
+        println!(
+            "{} {} ON {} ({})",
+            if index.is_fulltext() {
+                "FULLTEXT INDEX"
+            } else {
+                "INDEX"
+            },
+            name,
+            index.table(),
+            index.columns().join(", ")
+        );
+    }
+    for name in catalog.view_names() {
+        println!("VIEW {} AS {}", name, catalog.view(name).unwrap().query());
+    }
+    for name in catalog.trigger_names() {
+        let trigger = catalog.trigger(name).unwrap();
+        println!(
+            "TRIGGER {} {} {} ON {} BEGIN {} END",
+            name,
+            trigger.timing(),
+            trigger.event(),
+            trigger.table(),
+            trigger.statements()
+        );
+    }
 }
@@ -0,0 +1,116 @@
+use inquire::{autocompletion::Replacement, Autocomplete, CustomUserError};
+
+use super::{
+    catalog::Catalog,
+    tokenizer::{TokenKind, Tokenizer, KEYWORDS},
+};
+
+/// Completes the word under the cursor with keywords, table, view, column and database names.
+#[derive(Debug, Clone)]
+pub struct SqlCompleter {
+    tables: Vec<(String, Vec<String>)>,
+    databases: Vec<String>,
+}
+
+impl SqlCompleter {
+    pub fn new(catalog: &Catalog) -> Self {
+        let tables = catalog
+            .table_names()
+            .map(|name| {
+                let columns = catalog.table(name).unwrap().columns().to_vec();
+                (name.to_string(), columns)
+            })
+            .chain(catalog.view_names().map(|name| (name.to_string(), vec![])))
+            .collect();
+        let databases = catalog
+            .database_names()
+            .map(|name| name.to_string())
+            .collect();
+        Self { tables, databases }
+    }
+
+    fn candidates(&self, input: &str) -> Vec<String> {
+        let word = current_word(input);
+        if word.is_empty() {
+            return vec![];
+        }
+
+        // prefer the columns of the tables already mentioned in the input
+        let mentioned = Tokenizer::new(input)
+            .iter()
+            .map_while(Result::ok)
+            .filter(|t| t.token() == TokenKind::Identifier)
+            .map(|t| t.text().to_string())
+            .collect::<Vec<_>>();
+        let mut tables = self
+            .tables
+            .iter()
+            .filter(|(name, _)| mentioned.contains(name))
+            .peekable();
+        let columns = if tables.peek().is_some() {
+            tables.flat_map(|(_, columns)| columns).collect::<Vec<_>>()
+        } else {
+            self.tables
+                .iter()
+                .flat_map(|(_, columns)| columns)
+                .collect()
+        };
+
+        let words = columns
+            .into_iter()
+            .cloned()
+            .chain(self.tables.iter().map(|(name, _)| name.clone()))
+            .chain(self.databases.iter().cloned())
+            .chain(KEYWORDS.iter().map(|(keyword, _)| keyword.to_string()));
+
+        let prefix = word.to_lowercase();
+        let mut candidates: Vec<String> = vec![];
+        for candidate in words {
+            if candidate.to_lowercase().starts_with(&prefix)
+                && candidate != word
+                && !candidates.contains(&candidate)
+            {
+                candidates.push(candidate);
+            }
+        }
+        candidates
+    }
+}
+
+impl Autocomplete for SqlCompleter {
+    fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, CustomUserError> {
+        Ok(self.candidates(input))
+    }
+
+    fn get_completion(
+        &mut self,
+        input: &str,
+        highlighted_suggestion: Option<String>,
+    ) -> Result<Replacement, CustomUserError> {
+        let completion = match highlighted_suggestion {
+            Some(suggestion) => Some(suggestion),
+            None => {
+                let candidates = self.candidates(input);
+                match candidates.as_slice() {
+                    [only] => Some(only.clone()),
+                    _ => None,
+                }
+            }
+        };
+
+        Ok(completion.map(|word| {
+            let start = input.len() - current_word(input).len();
+            format!("{}{}", &input[..start], word)
+        }))
+    }
+}
+
+fn current_word(input: &str) -> &str {
+    let start = input
+        .char_indices()
+        .rev()
+        .take_while(|(_, ch)| ch.is_alphanumeric() || *ch == '_')
+        .last()
+        .map_or(input.len(), |(i, _)| i);
+    &input[start..]
+}
@@ -0,0 +1,743 @@
+use std::{collections::BTreeMap, fmt, fs::OpenOptions, path::Path};
+
+use serde::de::DeserializeOwned;
+
+use super::{
+    buffer::PageStore,
+    catalog::{Catalog, CatalogError, TriggerEvent, TriggerTiming},
+    de,
+    executor::Executor,
+    expression::{truth, ColumnName, Expression},
+    functions::{Arity, FunctionError, Functions},
+    query::{BodyKind, Query},
+    storage::{Storage, StorageError},
+    tokenizer::{ParseError, TokenKind, Tokenizer},
+    value::Value,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum DatabaseError {
+    Parse(ParseError),
+    Catalog(CatalogError),
+    Function(FunctionError),
+    /// A row could not be turned into the requested type.
+    Deserialize(String),
+    /// A statement that writes was run on a snapshot.
+    ReadOnly,
+    /// Another transaction holds the write lock.
+    Busy,
+    /// The database file could not be read or written.
+    Storage(String),
+}
+
+impl fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatabaseError::Parse(err) => write!(f, "parse error: {}", err),
+            DatabaseError::Catalog(err) => write!(f, "{}", err),
+            DatabaseError::Function(err) => write!(f, "{}", err),
+            DatabaseError::Deserialize(err) => write!(f, "cannot read row: {}", err),
+            DatabaseError::ReadOnly => write!(f, "cannot write to a read-only snapshot"),
+            DatabaseError::Busy => write!(f, "another transaction is writing"),
+            DatabaseError::Storage(err) => write!(f, "database file: {}", err),
+        }
+    }
+}
+
+impl From<ParseError> for DatabaseError {
+    fn from(err: ParseError) -> Self {
+        DatabaseError::Parse(err)
+    }
+}
+
+impl From<CatalogError> for DatabaseError {
+    fn from(err: CatalogError) -> Self {
+        match err {
+            CatalogError::Storage(err) => DatabaseError::Storage(err),
+            err => DatabaseError::Catalog(err),
+        }
+    }
+}
+
+impl From<FunctionError> for DatabaseError {
+    fn from(err: FunctionError) -> Self {
+        DatabaseError::Function(err)
+    }
+}
+
+impl From<StorageError> for DatabaseError {
+    fn from(err: StorageError) -> Self {
+        DatabaseError::Storage(err.to_string())
+    }
+}
+
+pub type Row = Vec<Value>;
+
+/// Values of `@name` session variables, keyed by lowercase name.
+pub type Variables = BTreeMap<String, Value>;
+
+/// Named columns and the rows a statement produced.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResultSet {
+    pub(crate) columns: Vec<String>,
+    pub(crate) rows: Vec<Row>,
+}
+
+impl ResultSet {
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+}
+
+/// Bytes of a database file kept in memory by [`Database::open`].
+pub const POOL_MEMORY: usize = 1 << 20;
+
+/// The schema together with the functions and variables its queries can use.
+#[derive(Debug)]
+pub struct Database {
+    catalog: Catalog,
+    functions: Functions,
+    variables: Variables,
+    /// The file every change is saved to, if the database was opened from one.
+    storage: Option<Storage>,
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self {
+            catalog: Catalog::new(),
+            functions: Functions::new(),
+            variables: Variables::new(),
+            storage: None,
+        }
+    }
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the database file at `path`, creating it if it does not exist, and save
+    /// every statement that changes the database to it. At most [`POOL_MEMORY`] bytes
+    /// of the file are kept in memory.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DatabaseError> {
+        Self::open_with_memory_limit(path, POOL_MEMORY)
+    }
+
+    /// [`Database::open`], keeping at most `memory_limit` bytes of the file in memory.
+    pub fn open_with_memory_limit(
+        path: impl AsRef<Path>,
+        memory_limit: usize,
+    ) -> Result<Self, DatabaseError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(StorageError::from)?;
+        Self::open_store(file, memory_limit)
+    }
+
+    /// Open the database file held in `store`.
+    pub(crate) fn open_store(
+        store: impl PageStore + Send + 'static,
+        memory_limit: usize,
+    ) -> Result<Self, DatabaseError> {
+        let mut storage = Storage::open(store, memory_limit)?;
+        let (script, tables) = storage.load()?;
+        let mut db = Self {
+            catalog: Catalog::paged(Some(storage.pager().clone())),
+            ..Self::new()
+        };
+        if !script.is_empty() {
+            db.restore(&script)?;
+            for (database, table, rows) in tables {
+                db.catalog.load_rows(&database, &table, rows)?;
+            }
+        }
+        db.storage = Some(storage);
+        Ok(db)
+    }
+
+    /// The file this database is saved to.
+    pub fn storage(&self) -> Option<&Storage> {
+        self.storage.as_ref()
+    }
+
+    /// Move the file this database is saved to out of it, leaving it in memory only.
+    pub(crate) fn take_storage(&mut self) -> Option<Storage> {
+        self.storage.take()
+    }
+
+    /// Save to `storage` from now on; it must hold this database as it is now.
+    pub(crate) fn set_storage(&mut self, storage: Storage) {
+        self.storage = Some(storage);
+    }
+
+    /// A copy with rows of its own, which can be written without affecting this
+    /// database. The copy is kept in memory only, so every row is read into it.
+    pub fn try_clone(&self) -> Result<Self, DatabaseError> {
+        Ok(Self {
+            catalog: self.catalog.detached()?,
+            ..self.fork()
+        })
+    }
+
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+
+    /// A copy that shares the row versions of this database, for a transaction to
+    /// write to while snapshots keep reading this one.
+    pub(crate) fn fork(&self) -> Self {
+        Self {
+            catalog: self.catalog.clone(),
+            functions: self.functions.clone(),
+            variables: self.variables.clone(),
+            storage: None,
+        }
+    }
+
+    /// The variables set so far with `SET @name := ...` or `name := ...`.
+    pub fn variables(&self) -> &Variables {
+        &self.variables
+    }
+
+    pub(crate) fn catalog_mut(&mut self) -> &mut Catalog {
+        &mut self.catalog
+    }
+
+    /// Make a Rust closure callable from SQL as `name(...)`.
+    pub fn register_function<F>(&mut self, name: &str, arity: impl Into<Arity>, function: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, FunctionError> + Send + Sync + 'static,
+    {
+        self.functions.register(name, arity, function);
+    }
+
+    /// Run a single statement with `$1`, `$2`, ... bound to `params`.
+    pub fn execute(&mut self, sql: &str, params: &[Value]) -> Result<ResultSet, DatabaseError> {
+        let query = Query::new(Tokenizer::new(sql).tokenize()?)?;
+        match query.body() {
+            BodyKind::Select | BodyKind::SelectDistinct | BodyKind::Explain => {
+                self.select(&query, params, &[])
+            }
+            BodyKind::SetVariable => self.set_variable(&query, params),
+            _ => {
+                // the statement, every trigger it fires and saving the result to the
+                // database file take effect together or not at all
+                self.catalog.begin_statement();
+                let saved = self.catalog.clone();
+                let result = match query.body() {
+                    BodyKind::Insert | BodyKind::Update | BodyKind::Delete => {
+                        self.write(&query, params, &[], 0)
+                    }
+                    _ => match self.catalog.execute(&query, &self.functions) {
+                        Ok(()) => Ok(ResultSet::default()),
+                        Err(err) => Err(err.into()),
+                    },
+                };
+                let result = result.and_then(|result| {
+                    self.save()?;
+                    Ok(result)
+                });
+                if result.is_err() {
+                    saved.rollback(saved.stamp());
+                    self.catalog = saved;
+                } else if query.body() == BodyKind::Vacuum {
+                    // the statement is saved by now, whether or not the file is compacted
+                    self.vacuum()?;
+                }
+                result
+            }
+        }
+    }
+
+    /// Run `change` as if it were one statement: the statements it runs are saved
+    /// together once it returns, and are all undone if it fails. The outer error is a
+    /// failure to save, after which nothing is applied either.
+    pub(crate) fn atomically<E>(
+        &mut self,
+        change: impl FnOnce(&mut Self) -> Result<(), E>,
+    ) -> Result<Result<(), E>, DatabaseError> {
+        self.catalog.begin_statement();
+        let saved = self.catalog.clone();
+        let storage = self.storage.take();
+        let changed = change(self);
+        self.storage = storage;
+        let result = match changed {
+            Ok(()) => self.save().map(Ok),
+            Err(err) => Ok(Err(err)),
+        };
+        if !matches!(result, Ok(Ok(()))) {
+            saved.rollback(saved.stamp());
+            self.catalog = saved;
+        }
+        result
+    }
+
+    /// Write the database as it is now to its file, if it has one.
+    pub(crate) fn save(&mut self) -> Result<(), DatabaseError> {
+        if let Some(storage) = &mut self.storage {
+            storage.write(&self.catalog)?;
+        }
+        Ok(())
+    }
+
+    /// Compact the database file, if it has one, dropping the row versions deleted by
+    /// the statements so far.
+    fn vacuum(&mut self) -> Result<(), DatabaseError> {
+        if let Some(storage) = &mut self.storage {
+            storage.vacuum(&self.catalog, self.catalog.stamp())?;
+        }
+        Ok(())
+    }
+
+    /// Run a statement that does not change the database.
+    pub fn read(&self, sql: &str, params: &[Value]) -> Result<ResultSet, DatabaseError> {
+        let query = Query::new(Tokenizer::new(sql).tokenize()?)?;
+        match query.body() {
+            BodyKind::Select | BodyKind::SelectDistinct | BodyKind::Explain => {
+                self.select(&query, params, &[])
+            }
+            _ => Err(DatabaseError::ReadOnly),
+        }
+    }
+
+    /// Replace the databases and their rows with the ones a script such as
+    /// [`Catalog::dump`] builds, returning the number of statements run.
+    ///
+    /// The script runs against an empty catalog and only replaces this one if every
+    /// statement succeeds. Registered functions and session variables are kept, and
+    /// the result is saved to the database file.
+    pub fn restore(&mut self, script: &str) -> Result<usize, DatabaseError> {
+        let mut restored = Self {
+            catalog: Catalog::paged(self.catalog.pager().cloned()),
+            functions: self.functions.clone(),
+            variables: self.variables.clone(),
+            storage: None,
+        };
+        restored.catalog.begin_transaction(self.catalog.txn());
+        let statements = Tokenizer::new(script).statements()?;
+        for statement in &statements {
+            restored.execute(statement, &[])?;
+        }
+        restored.storage = self.storage.take();
+        *self = restored;
+        self.save()?;
+        Ok(statements.len())
+    }
+
+    /// Run a query and deserialize each row into `T`, matching columns to fields by name.
+    pub fn query<T: DeserializeOwned>(
+        &mut self,
+        sql: &str,
+        params: &[Value],
+    ) -> Result<Vec<T>, DatabaseError> {
+        let result = self.execute(sql, params)?;
+        result
+            .rows
+            .iter()
+            .map(|row| {
+                de::from_row(&result.columns, row)
+                    .map_err(|err| DatabaseError::Deserialize(err.to_string()))
+            })
+            .collect()
+    }
+
+    /// Store the value of an assignment: the first column of the first row for a
+    /// parenthesized `SELECT`, `NULL` if it has no rows, else the expression's value.
+    fn set_variable(
+        &mut self,
+        query: &Query,
+        params: &[Value],
+    ) -> Result<ResultSet, DatabaseError> {
+        let name = query.value().ok_or(CatalogError::UnsupportedQuery)?;
+        let source = query
+            .subqueries()
+            .first()
+            .and_then(Query::value)
+            .ok_or(CatalogError::UnsupportedQuery)?;
+        let value = match scalar_subquery(source)? {
+            Some(select) => self
+                .read(select, params)?
+                .rows
+                .first()
+                .and_then(|row| row.first())
+                .cloned()
+                .unwrap_or(Value::Null),
+            None => {
+                let expression = Expression::parse_value(&Tokenizer::new(source).tokenize()?)?;
+                Executor::new(&self.catalog, &self.functions, &self.variables, params)
+                    .eval(&expression)?
+            }
+        };
+        self.variables.insert(name.to_string(), value);
+        Ok(ResultSet::default())
+    }
+
+    fn select(
+        &self,
+        query: &Query,
+        params: &[Value],
+        bindings: &[(ColumnName, Value)],
+    ) -> Result<ResultSet, DatabaseError> {
+        Executor::new(&self.catalog, &self.functions, &self.variables, params)
+            .with_bindings(bindings)
+            .query(query)
+    }
+
+    /// Run an `INSERT`, `UPDATE` or `DELETE` and the triggers it fires, `depth` triggers deep.
+    fn write(
+        &mut self,
+        query: &Query,
+        params: &[Value],
+        bindings: &[(ColumnName, Value)],
+        depth: usize,
+    ) -> Result<ResultSet, DatabaseError> {
+        match query.body() {
+            BodyKind::Insert => self.insert(query, params, bindings, depth),
+            BodyKind::Update => self.update(query, params, bindings, depth),
+            BodyKind::Delete => self.delete(query, params, bindings, depth),
+            _ => Err(CatalogError::UnsupportedQuery.into()),
+        }
+    }
+
+    /// Add the rows of `INSERT INTO ... VALUES` or `INSERT INTO ... SELECT`, filling
+    /// columns that are not named with their `DEFAULT` or `NULL`.
+    fn insert(
+        &mut self,
+        query: &Query,
+        params: &[Value],
+        bindings: &[(ColumnName, Value)],
+        depth: usize,
+    ) -> Result<ResultSet, DatabaseError> {
+        let name = query.value().ok_or(CatalogError::UnsupportedQuery)?;
+        let table = self
+            .catalog
+            .table(name)
+            .ok_or_else(|| CatalogError::NoSuchTable(name.to_string()))?;
+        let (fields, source): (Vec<_>, Vec<_>) = query
+            .subqueries()
+            .iter()
+            .partition(|subquery| subquery.body() == BodyKind::Field);
+        let positions = if fields.is_empty() {
+            (0..table.columns().len()).collect()
+        } else {
+            fields
+                .iter()
+                .map(|field| {
+                    let column = field.value().ok_or(CatalogError::UnsupportedQuery)?;
+                    table
+                        .position(column)
+                        .ok_or_else(|| CatalogError::NoSuchColumn(column.to_string()))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let mut executor = Executor::new(&self.catalog, &self.functions, &self.variables, params)
+            .with_bindings(bindings);
+        let values = match source.as_slice() {
+            [select] if select.body() != BodyKind::Values => executor.query(select)?.rows,
+            rows => {
+                let mut values = vec![];
+                for row in rows {
+                    let mut row_values = vec![];
+                    for value in row.subqueries() {
+                        let expression = parse_expression(value)?;
+                        row_values.push(executor.eval(&expression)?);
+                    }
+                    values.push(row_values);
+                }
+                values
+            }
+        };
+
+        let mut rows = vec![];
+        for values in values {
+            if values.len() != positions.len() {
+                return Err(
+                    CatalogError::ColumnCountMismatch(positions.len(), values.len()).into(),
+                );
+            }
+            let mut row = table
+                .definitions()
+                .iter()
+                .map(|definition| definition.default_value(&self.functions))
+                .collect::<Result<Row, _>>()?;
+            for (&position, value) in positions.iter().zip(values) {
+                row[position] = value;
+            }
+            rows.push(row);
+        }
+        for row in rows {
+            self.fire(
+                name,
+                TriggerTiming::Before,
+                TriggerEvent::Insert,
+                None,
+                Some(&row),
+                depth,
+            )?;
+            self.catalog.insert_row(name, row.clone())?;
+            self.fire(
+                name,
+                TriggerTiming::After,
+                TriggerEvent::Insert,
+                None,
+                Some(&row),
+                depth,
+            )?;
+        }
+        Ok(ResultSet::default())
+    }
+
+    /// Assign new values to the rows that match the `WHERE` condition, computing every
+    /// new row from the table as it was before the statement.
+    fn update(
+        &mut self,
+        query: &Query,
+        params: &[Value],
+        bindings: &[(ColumnName, Value)],
+        depth: usize,
+    ) -> Result<ResultSet, DatabaseError> {
+        let name = query.value().ok_or(CatalogError::UnsupportedQuery)?;
+        let table = self
+            .catalog
+            .table(name)
+            .ok_or_else(|| CatalogError::NoSuchTable(name.to_string()))?;
+        let columns = table_columns(name, table.columns());
+        let executor = Executor::new(&self.catalog, &self.functions, &self.variables, params)
+            .with_bindings(bindings);
+
+        let mut assignments = vec![];
+        let mut filter = None;
+        for subquery in query.subqueries() {
+            match subquery.body() {
+                BodyKind::Field => {
+                    let column = subquery.value().ok_or(CatalogError::UnsupportedQuery)?;
+                    let position = table
+                        .position(column)
+                        .ok_or_else(|| CatalogError::NoSuchColumn(column.to_string()))?;
+                    let value = subquery
+                        .subqueries()
+                        .first()
+                        .ok_or(CatalogError::UnsupportedQuery)?;
+                    let expression = executor.prepare(&parse_expression(value)?, &columns)?;
+                    assignments.push((position, expression));
+                }
+                BodyKind::Where => {
+                    filter = Some(executor.prepare(&parse_expression(subquery)?, &columns)?)
+                }
+                _ => return Err(CatalogError::UnsupportedQuery.into()),
+            }
+        }
+
+        let mut changes = vec![];
+        for (id, row) in table.entries(self.catalog.stamp())? {
+            if !matches_filter(&filter, &self.functions, &row)? {
+                continue;
+            }
+            let mut new = row.clone();
+            for (position, expression) in &assignments {
+                new[*position] = expression.eval_row(&self.functions, &row)?;
+            }
+            changes.push((id, row, new));
+        }
+        for (id, old, new) in changes {
+            self.fire(
+                name,
+                TriggerTiming::Before,
+                TriggerEvent::Update,
+                Some(&old),
+                Some(&new),
+                depth,
+            )?;
+            // a trigger may have deleted the row already
+            if self.catalog.update_row(name, id, new.clone())? {
+                self.fire(
+                    name,
+                    TriggerTiming::After,
+                    TriggerEvent::Update,
+                    Some(&old),
+                    Some(&new),
+                    depth,
+                )?;
+            }
+        }
+        Ok(ResultSet::default())
+    }
+
+    /// Remove the rows that match the `WHERE` condition, or every row without one.
+    fn delete(
+        &mut self,
+        query: &Query,
+        params: &[Value],
+        bindings: &[(ColumnName, Value)],
+        depth: usize,
+    ) -> Result<ResultSet, DatabaseError> {
+        let name = query.value().ok_or(CatalogError::UnsupportedQuery)?;
+        let table = self
+            .catalog
+            .table(name)
+            .ok_or_else(|| CatalogError::NoSuchTable(name.to_string()))?;
+        let filter = match query.subqueries().first() {
+            Some(condition) => Some(
+                Executor::new(&self.catalog, &self.functions, &self.variables, params)
+                    .with_bindings(bindings)
+                    .prepare(
+                        &parse_expression(condition)?,
+                        &table_columns(name, table.columns()),
+                    )?,
+            ),
+            None => None,
+        };
+
+        let mut deleted = vec![];
+        for (id, row) in table.entries(self.catalog.stamp())? {
+            if matches_filter(&filter, &self.functions, &row)? {
+                deleted.push((id, row));
+            }
+        }
+        for (id, old) in deleted {
+            self.fire(
+                name,
+                TriggerTiming::Before,
+                TriggerEvent::Delete,
+                Some(&old),
+                None,
+                depth,
+            )?;
+            if self.catalog.delete_row(name, id)?.is_some() {
+                self.fire(
+                    name,
+                    TriggerTiming::After,
+                    TriggerEvent::Delete,
+                    Some(&old),
+                    None,
+                    depth,
+                )?;
+            }
+        }
+        Ok(ResultSet::default())
+    }
+
+    /// Run the statements of every trigger on `table` for `timing` and `event`, with
+    /// `OLD` and `NEW` bound to the row before and after the change.
+    fn fire(
+        &mut self,
+        table: &str,
+        timing: TriggerTiming,
+        event: TriggerEvent,
+        old: Option<&Row>,
+        new: Option<&Row>,
+        depth: usize,
+    ) -> Result<(), DatabaseError> {
+        let triggers: Vec<_> = self
+            .catalog
+            .triggers_for(table, timing, event)
+            .map(|trigger| trigger.statements().to_string())
+            .collect();
+        if triggers.is_empty() {
+            return Ok(());
+        }
+        if depth >= MAX_TRIGGER_DEPTH {
+            return Err(CatalogError::TriggerDepth(MAX_TRIGGER_DEPTH).into());
+        }
+
+        let columns = self
+            .catalog
+            .table(table)
+            .ok_or_else(|| CatalogError::NoSuchTable(table.to_string()))?
+            .columns()
+            .to_vec();
+        let mut bindings = vec![];
+        for (qualifier, row) in [("old", old), ("new", new)] {
+            if let Some(row) = row {
+                bindings.extend(columns.iter().zip(row).map(|(column, value)| {
+                    (ColumnName::new(Some(qualifier), column), value.clone())
+                }));
+            }
+        }
+        for statements in &triggers {
+            for statement in Tokenizer::new(statements).statements()? {
+                let query = Query::new(Tokenizer::new(statement).tokenize()?)?;
+                match query.body() {
+                    BodyKind::Select | BodyKind::SelectDistinct => {
+                        self.select(&query, &[], &bindings)?;
+                    }
+                    _ => {
+                        self.write(&query, &[], &bindings, depth + 1)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How many triggers deep a statement may go, so that triggers firing each other stop.
+const MAX_TRIGGER_DEPTH: usize = 32;
+
+/// The columns of a table, qualified by its name.
+fn table_columns(table: &str, columns: &[String]) -> Vec<ColumnName> {
+    columns
+        .iter()
+        .map(|column| ColumnName::new(Some(table), column))
+        .collect()
+}
+
+/// The expression an `Expression` or `Where` query holds as source.
+fn parse_expression(query: &Query) -> Result<Expression, DatabaseError> {
+    let sql = query.value().ok_or(CatalogError::UnsupportedQuery)?;
+    Ok(Expression::parse_value(&Tokenizer::new(sql).tokenize()?)?)
+}
+
+/// `true` if there is no filter or it holds for `row`.
+fn matches_filter(
+    filter: &Option<Expression>,
+    functions: &Functions,
+    row: &[Value],
+) -> Result<bool, DatabaseError> {
+    match filter {
+        Some(filter) => Ok(truth(&filter.eval_row(functions, row)?) == Some(true)),
+        None => Ok(true),
+    }
+}
+
+/// The inner statement of `(SELECT ...)` or `(WITH ...)` when the parentheses enclose all of `sql`.
+fn scalar_subquery(sql: &str) -> Result<Option<&str>, ParseError> {
+    let tokens = Tokenizer::new(sql).tokenize()?;
+    let tokens: Vec<_> = tokens
+        .iter()
+        .filter(|t| !matches!(t.token(), TokenKind::EOF) && !t.token().is_comment())
+        .collect();
+    let [open, first, .., close] = tokens.as_slice() else {
+        return Ok(None);
+    };
+    if !matches!(open.token(), TokenKind::LeftParen)
+        || !matches!(first.token(), TokenKind::SELECT | TokenKind::WITH)
+        || !matches!(close.token(), TokenKind::RightParen)
+    {
+        return Ok(None);
+    }
+    // `(SELECT 1) + (SELECT 2)` is an expression, not one subquery
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.token() {
+            TokenKind::LeftParen => depth += 1,
+            TokenKind::RightParen => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 && i + 1 < tokens.len() {
+            return Ok(None);
+        }
+    }
+    Ok(Some(&sql[first.span().start..close.span().start]))
+}
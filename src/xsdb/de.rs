@@ -0,0 +1,84 @@
+use serde::{
+    de::{
+        value::{Error, MapDeserializer, SeqDeserializer},
+        DeserializeOwned, Deserializer, IntoDeserializer, Visitor,
+    },
+    forward_to_deserialize_any,
+};
+
+use super::value::Value;
+
+/// Read a row as `T`, matching columns to fields by name.
+pub(crate) fn from_row<T: DeserializeOwned>(columns: &[String], row: &[Value]) -> Result<T, Error> {
+    let entries = columns
+        .iter()
+        .map(String::as_str)
+        .zip(row.iter().map(ValueDeserializer));
+    T::deserialize(MapDeserializer::new(entries))
+}
+
+struct ValueDeserializer<'a>(&'a Value);
+
+impl<'de> IntoDeserializer<'de, Error> for ValueDeserializer<'_> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Null => visitor.visit_unit(),
+            Value::Integer(integer) => visitor.visit_i64(*integer),
+            Value::Real(real) => visitor.visit_f64(*real),
+            Value::Text(text) => visitor.visit_str(text),
+            Value::Blob(blob) => visitor.visit_bytes(blob),
+        }
+    }
+
+    /// Predicates produce `0` and `1`.
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Integer(integer) => visitor.visit_bool(*integer != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    /// Blobs can be read into `Vec<u8>`.
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Blob(blob) => visitor.visit_seq(SeqDeserializer::new(blob.iter().copied())),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    /// Text names a unit variant.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Text(text) => visitor.visit_enum(text.as_str().into_deserializer()),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct tuple tuple_struct
+        map struct identifier ignored_any
+    }
+}
@@ -0,0 +1,229 @@
+use std::io::{self, IsTerminal, Write};
+
+use crossterm::{
+    cursor::MoveToColumn,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::Print,
+    terminal::{self, Clear, ClearType},
+};
+use inquire::Autocomplete;
+
+use super::{
+    completion::SqlCompleter,
+    highlight::highlight_tokens,
+    tokenizer::{Edit, Token, TokenKind, Tokenizer},
+};
+
+/// What a key press asks of the prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Redraw,
+    Submit,
+    Cancel,
+    /// Tab found several candidates (or none); list them and keep editing.
+    Suggest(Vec<String>),
+}
+
+/// A single-line SQL editor: the line is highlighted on every key press and Tab
+/// completes the word before the cursor. Each edit re-lexes only the tokens around it.
+pub struct LineEditor {
+    line: String,
+    cursor: usize,
+    /// Tokens of the line, detached from it, so only their kinds and spans are read;
+    /// they stop early after a lexing error.
+    tokens: Vec<Token<'static>>,
+    completer: SqlCompleter,
+}
+
+impl LineEditor {
+    pub fn new(completer: SqlCompleter) -> Self {
+        Self {
+            line: String::new(),
+            cursor: 0,
+            tokens: vec![Token::eof("")],
+            completer,
+        }
+    }
+
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// Byte offset of the cursor in the line.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// The line with ANSI colours, as it is drawn after the prompt.
+    pub fn render(&self) -> String {
+        highlight_tokens(&self.line, &self.tokens)
+    }
+
+    pub fn handle(&mut self, key: KeyEvent) -> Action {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c' | 'd') if control => return Action::Cancel,
+            KeyCode::Char('a') if control => self.cursor = 0,
+            KeyCode::Char('e') if control => self.cursor = self.line.len(),
+            KeyCode::Char(ch) if !control => {
+                self.replace(self.cursor, self.cursor, ch.encode_utf8(&mut [0; 4]))
+            }
+            KeyCode::Enter => return Action::Submit,
+            KeyCode::Tab => return self.complete(),
+            KeyCode::Backspace => {
+                if let Some(start) = self.char_before() {
+                    self.replace(start, self.cursor, "");
+                }
+            }
+            KeyCode::Delete => {
+                if let Some(end) = self.char_after() {
+                    self.replace(self.cursor, end, "");
+                }
+            }
+            KeyCode::Left => self.cursor = self.char_before().unwrap_or(self.cursor),
+            KeyCode::Right => self.cursor = self.char_after().unwrap_or(self.cursor),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.line.len(),
+            _ => (),
+        }
+        Action::Redraw
+    }
+
+    fn complete(&mut self) -> Action {
+        let before = self.line[..self.cursor].to_string();
+        match self.completer.get_completion(&before, None) {
+            Ok(Some(completed)) => {
+                self.replace(0, self.cursor, &completed);
+                Action::Redraw
+            }
+            _ => Action::Suggest(self.completer.get_suggestions(&before).unwrap_or_default()),
+        }
+    }
+
+    /// Replace `start..end` of the line with `text` and put the cursor after it.
+    fn replace(&mut self, start: usize, end: usize, text: &str) {
+        self.line.replace_range(start..end, text);
+        self.cursor = start + text.len();
+        self.relex(Edit {
+            start,
+            old_end: end,
+            new_end: self.cursor,
+        });
+    }
+
+    /// Tokens that stopped at a lexing error can't be reused, so the line is lexed in full.
+    fn relex(&mut self, edit: Edit) {
+        let tokenizer = Tokenizer::new(&self.line);
+        let complete = self
+            .tokens
+            .last()
+            .is_some_and(|t| t.token() == TokenKind::EOF);
+        let relexed = if complete {
+            tokenizer.relex(&self.tokens, &edit)
+        } else {
+            tokenizer.iter().collect()
+        };
+        self.tokens = match relexed {
+            Ok(tokens) => tokens.iter().map(Token::detach).collect(),
+            Err(_) => tokenizer
+                .iter()
+                .map_while(Result::ok)
+                .map(|t| t.detach())
+                .collect(),
+        };
+    }
+
+    fn char_before(&self) -> Option<usize> {
+        self.line[..self.cursor]
+            .char_indices()
+            .next_back()
+            .map(|(i, _)| i)
+    }
+
+    fn char_after(&self) -> Option<usize> {
+        self.line[self.cursor..]
+            .chars()
+            .next()
+            .map(|ch| self.cursor + ch.len_utf8())
+    }
+}
+
+/// Disables raw mode again however the prompt is left.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Read a line at `prompt`, highlighting it as it is typed. `None` on Ctrl+C, Ctrl+D or
+/// end of input. Without a terminal (e.g. a piped script) lines are read as they come.
+/// Lines wider than the terminal are not redrawn correctly.
+pub fn read_line(prompt: &str, completer: SqlCompleter) -> io::Result<Option<String>> {
+    if !io::stdin().is_terminal() {
+        let mut line = String::new();
+        return match io::stdin().read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line.trim_end_matches(['\r', '\n']).to_string())),
+        };
+    }
+
+    let mut editor = LineEditor::new(completer);
+    let mut out = io::stdout();
+    let _raw = RawMode::enable()?;
+    draw(&mut out, prompt, &editor)?;
+    loop {
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        match editor.handle(key) {
+            Action::Redraw => (),
+            Action::Submit => {
+                queue!(out, Print("\r\n"))?;
+                out.flush()?;
+                return Ok(Some(editor.line));
+            }
+            Action::Cancel => {
+                queue!(out, Print("\r\n"))?;
+                out.flush()?;
+                return Ok(None);
+            }
+            Action::Suggest(candidates) if candidates.is_empty() => (),
+            Action::Suggest(candidates) => {
+                queue!(
+                    out,
+                    Print("\r\n"),
+                    Print(candidates.join("  ")),
+                    Print("\r\n")
+                )?;
+            }
+        }
+        draw(&mut out, prompt, &editor)?;
+    }
+}
+
+fn draw(out: &mut impl Write, prompt: &str, editor: &LineEditor) -> io::Result<()> {
+    let column = prompt.chars().count() + 1 + editor.line()[..editor.cursor()].chars().count();
+    queue!(
+        out,
+        MoveToColumn(0),
+        Clear(ClearType::CurrentLine),
+        Print(prompt),
+        Print(' '),
+        Print(editor.render()),
+        MoveToColumn(column as u16)
+    )?;
+    out.flush()
+}
@@ -0,0 +1,1102 @@
+use std::{cmp::Ordering, collections::HashSet, iter, mem};
+
+use super::{
+    catalog::{Catalog, CatalogError, RowId, Table},
+    database::{DatabaseError, ResultSet, Row, Variables},
+    expression::{compare, truth, BinaryOperator, ColumnName, Expression, PatternKind},
+    functions::{FunctionError, Functions},
+    json,
+    plan::{like_prefix, Condition, IndexScan, Input, Plan},
+    query::{BodyKind, Query},
+    select::{JoinKind, ResultColumn, Select, Source, SourceKind},
+    stats::ColumnStats,
+    tokenizer::Tokenizer,
+    value::{DistinctKey, Value},
+    window::{self, WindowRow},
+};
+
+/// Rows together with the qualified names of their columns.
+struct Relation {
+    columns: Vec<ColumnName>,
+    rows: Vec<Row>,
+}
+
+impl Relation {
+    /// The rows of a query, with every column qualified by `table`.
+    fn qualified(result: ResultSet, table: Option<&str>) -> Self {
+        Self {
+            columns: result
+                .columns
+                .iter()
+                .map(|name| ColumnName::new(table, name))
+                .collect(),
+            rows: result.rows,
+        }
+    }
+}
+
+/// A sort key: a result column, or an expression over the `FROM` row.
+enum SortKey {
+    Output(usize),
+    Source(Expression),
+}
+
+/// Runs `SELECT`s against a catalog, expanding each view into the query it stands for.
+pub(crate) struct Executor<'a> {
+    catalog: &'a Catalog,
+    functions: &'a Functions,
+    variables: &'a Variables,
+    params: &'a [Value],
+    /// Values of `NEW.column` and `OLD.column` while a trigger runs.
+    bindings: &'a [(ColumnName, Value)],
+    /// Views being expanded, innermost last.
+    views: Vec<String>,
+    /// Rows of the `WITH` tables in scope, innermost last.
+    common_tables: Vec<(String, ResultSet)>,
+}
+
+/// How many times a recursive common table may run its recursive step.
+const MAX_RECURSION_DEPTH: usize = 1000;
+
+impl<'a> Executor<'a> {
+    pub(crate) fn new(
+        catalog: &'a Catalog,
+        functions: &'a Functions,
+        variables: &'a Variables,
+        params: &'a [Value],
+    ) -> Self {
+        Self {
+            catalog,
+            functions,
+            variables,
+            params,
+            bindings: &[],
+            views: vec![],
+            common_tables: vec![],
+        }
+    }
+
+    /// Read `NEW.column` and `OLD.column` from `bindings`, for the statements of a trigger.
+    pub(crate) fn with_bindings(mut self, bindings: &'a [(ColumnName, Value)]) -> Self {
+        self.bindings = bindings;
+        self
+    }
+
+    /// Rows of a `Select` or `SelectDistinct` query, with its `WITH` tables in scope, or
+    /// the plan of one under `EXPLAIN`.
+    pub(crate) fn query(&mut self, query: &Query) -> Result<ResultSet, DatabaseError> {
+        let scope = self.common_tables.len();
+        let result = match query.body() {
+            BodyKind::Select | BodyKind::SelectDistinct => self.query_in_scope(query),
+            BodyKind::Explain => self.explain(query),
+            _ => return Err(CatalogError::UnsupportedQuery.into()),
+        };
+        self.common_tables.truncate(scope);
+        result
+    }
+
+    fn query_in_scope(&mut self, query: &Query) -> Result<ResultSet, DatabaseError> {
+        if let Some(compound) = self.common_tables_of(query)? {
+            check_compound(compound)?;
+            return self.compound_query(compound);
+        }
+        let sql = query.value().ok_or(CatalogError::UnsupportedQuery)?;
+        self.select(&Select::parse(&Tokenizer::new(sql).tokenize()?)?)
+    }
+
+    /// Bring the `WITH` tables of `query` into scope, returning its set operation if it
+    /// has one.
+    fn common_tables_of<'q>(
+        &mut self,
+        query: &'q Query,
+    ) -> Result<Option<&'q Query>, DatabaseError> {
+        let mut compound = None;
+        for subquery in query.subqueries() {
+            match subquery.body() {
+                BodyKind::CommonTable | BodyKind::RecursiveCommonTable => {
+                    let result = self.common_table(subquery)?;
+                    let name = subquery.value().ok_or(CatalogError::UnsupportedQuery)?;
+                    self.common_tables.push((name.to_string(), result));
+                }
+                _ => compound = Some(subquery),
+            }
+        }
+        Ok(compound)
+    }
+
+    /// The plan of the query under `EXPLAIN`: for each `SELECT` in it, how each `FROM`
+    /// item is read, in order, with the rows expected after it.
+    fn explain(&mut self, explain: &Query) -> Result<ResultSet, DatabaseError> {
+        let [query] = explain.subqueries() else {
+            return Err(CatalogError::UnsupportedQuery.into());
+        };
+        let mut rows = vec![];
+        match self.common_tables_of(query)? {
+            Some(compound) => self.explain_compound(compound, &mut rows)?,
+            None => self.explain_select(query, &mut rows)?,
+        }
+        Ok(ResultSet {
+            columns: vec!["step".to_string(), "estimated_rows".to_string()],
+            rows,
+        })
+    }
+
+    fn explain_compound(
+        &mut self,
+        query: &Query,
+        rows: &mut Vec<Row>,
+    ) -> Result<(), DatabaseError> {
+        match query.subqueries() {
+            [left, right] => {
+                self.explain_compound(left, rows)?;
+                self.explain_compound(right, rows)
+            }
+            _ => self.explain_select(query, rows),
+        }
+    }
+
+    fn explain_select(&mut self, query: &Query, rows: &mut Vec<Row>) -> Result<(), DatabaseError> {
+        let sql = query.value().ok_or(CatalogError::UnsupportedQuery)?;
+        let select = Select::parse(&Tokenizer::new(sql).tokenize()?)?;
+        if let Some((relation, _)) = self.fulltext_scan(&select)? {
+            let read = format!("SEARCH {} USING FULLTEXT INDEX", label(&select.from[0]));
+            rows.push(vec![
+                Value::Text(read),
+                Value::Integer(relation.rows.len() as i64),
+            ]);
+            return Ok(());
+        }
+        rows.extend(self.plan(&select.from, select.filter.as_ref())?.explain());
+        Ok(())
+    }
+
+    /// Rows of one `WITH` table, with the columns it names.
+    fn common_table(&mut self, common_table: &Query) -> Result<ResultSet, DatabaseError> {
+        let name = common_table.value().ok_or(CatalogError::UnsupportedQuery)?;
+        let (fields, select) = match common_table.subqueries() {
+            [fields @ .., select] => (fields, select),
+            [] => return Err(CatalogError::UnsupportedQuery.into()),
+        };
+        if common_table.body() == BodyKind::RecursiveCommonTable {
+            if let [union] = select.subqueries() {
+                if matches!(union.body(), BodyKind::Union | BodyKind::UnionAll) {
+                    return self.recursive(name, fields, union);
+                }
+            }
+        }
+        let result = self.query(select)?;
+        Ok(ResultSet {
+            columns: rename(result.columns, fields)?,
+            rows: result.rows,
+        })
+    }
+
+    /// Rows of `anchor UNION [ALL] step`, where `step` reads the rows the run before it
+    /// added under the table's own name, until a run adds none.
+    fn recursive(
+        &mut self,
+        name: &str,
+        fields: &[Query],
+        union: &Query,
+    ) -> Result<ResultSet, DatabaseError> {
+        let [anchor, step] = union.subqueries() else {
+            return Err(CatalogError::UnsupportedQuery.into());
+        };
+        let distinct = union.body() == BodyKind::Union;
+        let mut seen = HashSet::new();
+        let mut new_rows = |rows: Vec<Row>| -> Vec<Row> {
+            rows.into_iter()
+                .filter(|row| !distinct || seen.insert(distinct_key(row)))
+                .collect()
+        };
+
+        let anchor = self.compound(anchor, None)?;
+        let columns = rename(anchor.columns, fields)?;
+        let mut rows = vec![];
+        let mut added = new_rows(anchor.rows);
+        let mut depth = 0;
+        while !added.is_empty() {
+            rows.extend(added.iter().cloned());
+            if depth == MAX_RECURSION_DEPTH {
+                return Err(
+                    CatalogError::RecursionLimit(name.to_string(), MAX_RECURSION_DEPTH).into(),
+                );
+            }
+            depth += 1;
+
+            let working = ResultSet {
+                columns: columns.clone(),
+                rows: added,
+            };
+            self.common_tables.push((name.to_string(), working));
+            let step = self.compound(step, None);
+            self.common_tables.pop();
+            let step = step?;
+            if step.columns.len() != columns.len() {
+                return Err(
+                    CatalogError::ColumnCountMismatch(columns.len(), step.columns.len()).into(),
+                );
+            }
+            added = new_rows(step.rows);
+        }
+        Ok(ResultSet { columns, rows })
+    }
+
+    /// A copy of `expression` with parameters, variables and trigger rows bound,
+    /// ready to evaluate on rows with `columns`.
+    pub(crate) fn prepare(
+        &self,
+        expression: &Expression,
+        columns: &[ColumnName],
+    ) -> Result<Expression, DatabaseError> {
+        let mut expression = expression.clone();
+        expression.bind(self.params, self.variables)?;
+        expression.substitute(self.bindings);
+        expression.resolve(columns)?;
+        Ok(expression)
+    }
+
+    /// The value of an expression that reads no columns.
+    pub(crate) fn eval(&self, expression: &Expression) -> Result<Value, DatabaseError> {
+        Ok(self.prepare(expression, &[])?.eval(self.functions)?)
+    }
+
+    fn select(&mut self, select: &Select) -> Result<ResultSet, DatabaseError> {
+        let (source, filter) = match self.fulltext_scan(select)? {
+            Some(scan) => scan,
+            None => self.join(&select.from, select.filter.as_ref())?,
+        };
+
+        let filter = match &filter {
+            Some(filter) => Some(self.prepare(filter, &source.columns)?),
+            None => None,
+        };
+        let mut rows = vec![];
+        for row in source.rows {
+            if let Some(filter) = &filter {
+                if truth(&filter.eval_row(self.functions, &row)?) != Some(true) {
+                    continue;
+                }
+            }
+            rows.push(row);
+        }
+
+        let mut columns = vec![];
+        let mut outputs = vec![];
+        for column in &select.columns {
+            match column {
+                ResultColumn::All(table) => {
+                    let before = outputs.len();
+                    for (index, column) in source.columns.iter().enumerate() {
+                        if table.is_none() || column.table == *table {
+                            columns.push(column.name.clone());
+                            outputs.push(Expression::Field(index));
+                        }
+                    }
+                    match table {
+                        Some(table) if outputs.len() == before => {
+                            return Err(CatalogError::NoSuchTable(table.clone()).into())
+                        }
+                        None if select.from.is_empty() => {
+                            return Err(CatalogError::UnsupportedQuery.into())
+                        }
+                        _ => (),
+                    }
+                }
+                ResultColumn::Expression(name, expression) => {
+                    columns.push(name.clone());
+                    outputs.push(self.prepare(expression, &source.columns)?);
+                }
+            }
+        }
+
+        let mut keys = vec![];
+        for (expression, descending) in &select.order_by {
+            keys.push((
+                self.sort_key(expression, &columns, &source.columns)?,
+                *descending,
+            ));
+        }
+
+        let mut windows = vec![];
+        let width = source.columns.len();
+        for output in &mut outputs {
+            output.extract_windows(&mut windows, width);
+        }
+        for (key, _) in &mut keys {
+            if let SortKey::Source(expression) = key {
+                expression.extract_windows(&mut windows, width);
+            }
+        }
+        self.evaluate_windows(&windows, &mut rows)?;
+
+        // DISTINCT keeps the first of each set of equal rows, in scan order
+        let mut seen = HashSet::new();
+        let mut results = vec![];
+        for row in &rows {
+            let output = outputs
+                .iter()
+                .map(|output| output.eval_row(self.functions, row))
+                .collect::<Result<Row, _>>()?;
+            if select.distinct && !seen.insert(distinct_key(&output)) {
+                continue;
+            }
+            let sort = self.sort_values(&keys, &output, row)?;
+            results.push((output, sort));
+        }
+        Ok(ResultSet {
+            columns,
+            rows: self.order_and_limit(results, &keys, select)?,
+        })
+    }
+
+    /// Append the value of each window function to every row, computed over all of them.
+    fn evaluate_windows(
+        &self,
+        windows: &[Expression],
+        rows: &mut [Row],
+    ) -> Result<(), DatabaseError> {
+        for expression in windows {
+            let Expression::Window {
+                function,
+                args,
+                window,
+            } = expression
+            else {
+                unreachable!("{:?} is not a window function", expression);
+            };
+            let eval_all = |expressions: &mut dyn Iterator<Item = &Expression>, row: &Row| {
+                expressions
+                    .map(|expression| expression.eval_row(self.functions, row))
+                    .collect::<Result<Vec<_>, _>>()
+            };
+            let mut inputs = vec![];
+            for row in rows.iter() {
+                inputs.push(WindowRow {
+                    partition: eval_all(&mut window.partition_by.iter(), row)?,
+                    order: eval_all(&mut window.order_by.iter().map(|(order, _)| order), row)?,
+                    args: eval_all(&mut args.iter(), row)?,
+                });
+            }
+            let values = window::evaluate(function, window, &inputs)?;
+            for (row, value) in rows.iter_mut().zip(values) {
+                row.push(value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Rows of a set operation. `last` stands in for its rightmost `SELECT`, once its
+    /// `ORDER BY`, `LIMIT` and `OFFSET` are taken to apply to the whole result.
+    fn compound(
+        &mut self,
+        query: &Query,
+        last: Option<&Select>,
+    ) -> Result<ResultSet, DatabaseError> {
+        let [left, right] = query.subqueries() else {
+            return match last {
+                Some(select) => self.select(select),
+                None => self.query(query),
+            };
+        };
+        let left = self.compound(left, None)?;
+        let right = self.compound(right, last)?;
+        if left.columns.len() != right.columns.len() {
+            return Err(
+                CatalogError::ColumnCountMismatch(left.columns.len(), right.columns.len()).into(),
+            );
+        }
+
+        let mut seen = HashSet::new();
+        let rows = match query.body() {
+            BodyKind::UnionAll => left.rows.into_iter().chain(right.rows).collect(),
+            BodyKind::Union => left
+                .rows
+                .into_iter()
+                .chain(right.rows)
+                .filter(|row| seen.insert(distinct_key(row)))
+                .collect(),
+            BodyKind::Intersect | BodyKind::Except => {
+                let keep = query.body() == BodyKind::Intersect;
+                let right: HashSet<_> = right.rows.iter().map(|row| distinct_key(row)).collect();
+                left.rows
+                    .into_iter()
+                    .filter(|row| {
+                        let key = distinct_key(row);
+                        right.contains(&key) == keep && seen.insert(key)
+                    })
+                    .collect()
+            }
+            _ => return Err(CatalogError::UnsupportedQuery.into()),
+        };
+        Ok(ResultSet {
+            columns: left.columns,
+            rows,
+        })
+    }
+
+    /// The compound query's rows, sorted and limited by the clauses at the end of its
+    /// rightmost `SELECT`. They may only name result columns.
+    fn compound_query(&mut self, compound: &Query) -> Result<ResultSet, DatabaseError> {
+        let mut rightmost = compound;
+        while let [_, right] = rightmost.subqueries() {
+            rightmost = right;
+        }
+        let sql = rightmost.value().ok_or(CatalogError::UnsupportedQuery)?;
+        let mut last = Select::parse(&Tokenizer::new(sql).tokenize()?)?;
+        let clauses = Select {
+            order_by: mem::take(&mut last.order_by),
+            limit: last.limit.take(),
+            offset: last.offset.take(),
+            ..last.clone()
+        };
+
+        let result = self.compound(compound, Some(&last))?;
+        let columns: Vec<_> = result
+            .columns
+            .iter()
+            .map(|column| ColumnName::new(None, column))
+            .collect();
+        let mut keys = vec![];
+        for (expression, descending) in &clauses.order_by {
+            keys.push((
+                self.sort_key(expression, &result.columns, &columns)?,
+                *descending,
+            ));
+        }
+        let mut results = vec![];
+        for row in result.rows {
+            let sort = self.sort_values(&keys, &row, &row)?;
+            results.push((row, sort));
+        }
+        Ok(ResultSet {
+            columns: result.columns,
+            rows: self.order_and_limit(results, &keys, &clauses)?,
+        })
+    }
+
+    /// The values `row` sorts by, given its `output`.
+    fn sort_values(
+        &self,
+        keys: &[(SortKey, bool)],
+        output: &[Value],
+        row: &[Value],
+    ) -> Result<Row, DatabaseError> {
+        let mut values = vec![];
+        for (key, _) in keys {
+            values.push(match key {
+                SortKey::Output(index) => output[*index].clone(),
+                SortKey::Source(expression) => expression.eval_row(self.functions, row)?,
+            });
+        }
+        Ok(values)
+    }
+
+    /// Sort `(row, sort values)` pairs by `keys` and apply the `LIMIT` and `OFFSET` of `select`.
+    fn order_and_limit(
+        &self,
+        mut results: Vec<(Row, Row)>,
+        keys: &[(SortKey, bool)],
+        select: &Select,
+    ) -> Result<Vec<Row>, DatabaseError> {
+        // a stable sort keeps the rows in scan order among equal keys
+        results.sort_by(|(_, left), (_, right)| {
+            iter::zip(left, right)
+                .zip(keys)
+                .map(|((left, right), (_, descending))| {
+                    let ordering = sort_order(left, right);
+                    if *descending {
+                        ordering.reverse()
+                    } else {
+                        ordering
+                    }
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let offset = self.count("OFFSET", &select.offset)?.unwrap_or(0);
+        let limit = self.count("LIMIT", &select.limit)?.unwrap_or(usize::MAX);
+        Ok(results
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(output, _)| output)
+            .collect())
+    }
+
+    /// `ORDER BY` a result column's position or name, else an expression over the source.
+    fn sort_key(
+        &self,
+        expression: &Expression,
+        outputs: &[String],
+        columns: &[ColumnName],
+    ) -> Result<SortKey, DatabaseError> {
+        match expression {
+            Expression::Literal(Value::Integer(position)) => usize::try_from(*position)
+                .ok()
+                .filter(|position| (1..=outputs.len()).contains(position))
+                .map(|position| SortKey::Output(position - 1))
+                .ok_or_else(|| CatalogError::NoSuchColumn(position.to_string()).into()),
+            Expression::Column(ColumnName { table: None, name }) if outputs.contains(name) => Ok(
+                SortKey::Output(outputs.iter().position(|output| output == name).unwrap()),
+            ),
+            expression => Ok(SortKey::Source(self.prepare(expression, columns)?)),
+        }
+    }
+
+    /// The value of `LIMIT` or `OFFSET`; `None` when it is missing or negative.
+    fn count(
+        &self,
+        name: &str,
+        expression: &Option<Expression>,
+    ) -> Result<Option<usize>, DatabaseError> {
+        let Some(expression) = expression else {
+            return Ok(None);
+        };
+        match self.eval(expression)? {
+            Value::Integer(count) => Ok(usize::try_from(count).ok()),
+            value => Err(FunctionError::InvalidArgument(name.to_string(), value).into()),
+        }
+    }
+
+    /// The rows of a `SELECT` from one table whose `WHERE` has a `column MATCH query`
+    /// condition on a column with a `FULLTEXT` index, best BM25 score first, and the rest
+    /// of the condition. `None` if no index applies.
+    ///
+    /// The condition is checked with the index's analyzer, so `MATCH` follows the language
+    /// of the index.
+    fn fulltext_scan(
+        &self,
+        select: &Select,
+    ) -> Result<Option<(Relation, Option<Expression>)>, DatabaseError> {
+        let ([source], Some(filter)) = (select.from.as_slice(), &select.filter) else {
+            return Ok(None);
+        };
+        let Some((name, table)) = self.table(source) else {
+            return Ok(None);
+        };
+        let qualifier = source.alias.as_deref().unwrap_or(name);
+        let columns = qualified_columns(table.columns(), qualifier);
+
+        let mut conditions = vec![];
+        conjuncts(filter, &mut conditions);
+        for (position, condition) in conditions.iter().enumerate() {
+            let Expression::Match {
+                expression,
+                query,
+                negated: false,
+            } = condition
+            else {
+                continue;
+            };
+            let Expression::Column(column) = expression.as_ref() else {
+                continue;
+            };
+            let Some(field) = columns.iter().position(|c| column.refers_to(c)) else {
+                continue;
+            };
+            let Some((index, analyzer)) = self
+                .catalog
+                .indexes_on(name)
+                .filter(|(_, index)| index.columns().contains(&column.name))
+                .find_map(|(_, index)| Some((index, index.analyzer()?)))
+            else {
+                continue;
+            };
+            // a query that reads the row has to be evaluated row by row
+            let query = match self.eval(query) {
+                Ok(Value::Blob(_)) | Err(_) => continue,
+                Ok(query) => query,
+            };
+
+            let mut rows = vec![];
+            if !query.is_null() {
+                let query = query.to_string();
+                for (id, _) in index.search(&query) {
+                    let Some(row) = table.row(id, self.catalog.stamp())? else {
+                        continue;
+                    };
+                    let text = match &row[field] {
+                        Value::Null | Value::Blob(_) => continue,
+                        value => value.to_string(),
+                    };
+                    if analyzer.matches(&text, &query) {
+                        rows.push(row);
+                    }
+                }
+            }
+            let rest = conditions
+                .iter()
+                .enumerate()
+                .filter(|(other, _)| *other != position)
+                .map(|(_, condition)| (*condition).clone())
+                .reduce(|left, right| Expression::Binary {
+                    operator: BinaryOperator::And,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                });
+            return Ok(Some((Relation { columns, rows }, rest)));
+        }
+        Ok(None)
+    }
+
+    /// The rows of the `FROM` clause, a single row without columns if there is none,
+    /// and the part of `filter` still to check on them.
+    fn join(
+        &mut self,
+        from: &[Source],
+        filter: Option<&Expression>,
+    ) -> Result<(Relation, Option<Expression>), DatabaseError> {
+        let plan = self.plan(from, filter)?;
+        let rows = plan.run(self.functions)?;
+        let filter = filter.filter(|_| !plan.checks_where()).cloned();
+        Ok((
+            Relation {
+                columns: plan.columns,
+                rows,
+            },
+            filter,
+        ))
+    }
+
+    /// Read every `FROM` item and plan how to join them and check `filter`, from the
+    /// statistics `ANALYZE` collected. Tables are read last, through an index where one
+    /// finds their rows.
+    fn plan(
+        &mut self,
+        from: &[Source],
+        filter: Option<&Expression>,
+    ) -> Result<Plan, DatabaseError> {
+        let at = self.catalog.stamp();
+        let mut columns = vec![];
+        let mut inputs = vec![];
+        let mut tables = vec![];
+        let mut conditions = vec![];
+        for (position, source) in from.iter().enumerate() {
+            let table = self.table(source);
+            let (relation, count) = match table {
+                Some((name, table)) => {
+                    let qualifier = source.alias.as_deref().unwrap_or(name);
+                    let relation = Relation {
+                        columns: qualified_columns(table.columns(), qualifier),
+                        rows: vec![],
+                    };
+                    (relation, table.count(at))
+                }
+                None => {
+                    let relation = self.source(source)?;
+                    let count = relation.rows.len();
+                    (relation, count)
+                }
+            };
+            let offset = columns.len();
+            columns.extend(relation.columns);
+            if let Some(on) = &source.on {
+                conditions.push((Some(position), self.prepare(on, &columns)?));
+            }
+            let stats: Vec<_> = match table {
+                Some((name, _)) => {
+                    let mut stats = self.catalog.column_stats(name)?;
+                    columns[offset..]
+                        .iter()
+                        .map(|column| stats.remove(&column.name))
+                        .collect()
+                }
+                None => vec![],
+            };
+            let estimate = stats
+                .iter()
+                .flatten()
+                .next()
+                .map_or(count as f64, |stats: &ColumnStats| stats.rows as f64);
+            inputs.push(Input {
+                label: label(source),
+                rows: relation.rows.into_iter().enumerate().collect(),
+                offset,
+                stats,
+                estimate,
+                left: source.join == JoinKind::Left,
+                index: None,
+            });
+            tables.push(table);
+        }
+        if let Some(filter) = filter {
+            let mut filters = vec![];
+            conjuncts(filter, &mut filters);
+            for filter in filters {
+                conditions.push((None, self.prepare(filter, &columns)?));
+            }
+        }
+
+        let mut conditions: Vec<_> = conditions
+            .into_iter()
+            .map(|(on, expression)| Condition::new(expression, on, &inputs))
+            .collect();
+        if !inputs.iter().any(|input| input.left) {
+            for condition in conditions.iter_mut().filter(|c| c.on.is_none()) {
+                self.search_index(&tables, &mut inputs, condition);
+            }
+        }
+        for (input, table) in inputs.iter_mut().zip(&tables) {
+            let Some((_, table)) = table else {
+                continue;
+            };
+            let rows = match &input.index {
+                Some(index) => {
+                    let mut rows = vec![];
+                    for &id in &index.ids {
+                        rows.extend(table.row(id, at)?);
+                    }
+                    rows
+                }
+                None => table.rows(at)?,
+            };
+            input.rows = rows.into_iter().enumerate().collect();
+        }
+        Ok(Plan::new(columns, inputs, conditions))
+    }
+
+    /// Read an item through an index if `condition` is on one of its indexed columns and
+    /// reading the rows the index finds costs less than scanning the item:
+    /// `column MATCH query` through a `FULLTEXT` index, and `column BETWEEN low AND high`
+    /// or `column LIKE 'prefix%'` through an ordered index on the column first.
+    /// `condition` is still checked, so the index only has to find every row it keeps.
+    fn search_index(
+        &self,
+        tables: &[Option<(&str, &Table)>],
+        inputs: &mut [Input],
+        condition: &mut Condition,
+    ) {
+        let (Some(expression), [position]) = (indexed_expression(condition), &condition.inputs[..])
+        else {
+            return;
+        };
+        let Expression::Field(field) = expression else {
+            return;
+        };
+        let input = &mut inputs[*position];
+        let (Some((name, table)), None) = (tables[*position], &input.index) else {
+            return;
+        };
+        let Some(column) = table.columns().get(field - input.offset) else {
+            return;
+        };
+        // the statistics of the column, where collected, tell how many rows are in range
+        let kept = input.estimate * condition.selectivity;
+        let Some(index) = self.lookup(name, column, &condition.expression, kept, input.estimate)
+        else {
+            return;
+        };
+        input.estimate = index.estimate;
+        input.index = Some(index.scan);
+        condition.selectivity = 1.0;
+    }
+
+    /// The rows an index on `column` of table `name` finds for `condition`, which
+    /// [`indexed_expression`] reads the column in, if reading them costs less than
+    /// scanning `scan` rows. An ordered index is expected to find `kept` rows.
+    fn lookup(
+        &self,
+        name: &str,
+        column: &str,
+        condition: &Expression,
+        kept: f64,
+        scan: f64,
+    ) -> Option<Lookup> {
+        // the other side has to be known before any row is read
+        let constant = |expression: &Expression| match expression.eval(self.functions) {
+            Ok(Value::Null | Value::Blob(_)) | Err(_) => None,
+            Ok(value) => Some(value),
+        };
+        let mut indexes = self.catalog.indexes_on(name);
+        if let Expression::Match { query, .. } = condition {
+            // only an index that reads text the way `MATCH` does finds every row it keeps
+            let (_, index) = indexes.find(|(_, index)| {
+                index.is_fulltext()
+                    && index.language().is_none()
+                    && index.columns().iter().any(|c| c == column)
+            })?;
+            let query = constant(query)?.to_string();
+            let estimate = index.estimate(&query)?;
+            if !cheaper(estimate as f64, scan) {
+                return None;
+            }
+            let ids = index.search(&query).into_iter().map(|(id, _)| id);
+            return Some(Lookup::new("FULLTEXT INDEX".to_string(), estimate, ids));
+        }
+
+        let (name, ordered) = indexes.find_map(|(name, index)| {
+            let first = index.columns().first().is_some_and(|first| first == column);
+            Some((name, index.ordered().filter(|_| first)?))
+        })?;
+        if !cheaper(kept, scan) {
+            return None;
+        }
+        let ids = match condition {
+            Expression::Between { low, high, .. } => {
+                ordered.between(&constant(low)?, &constant(high)?)
+            }
+            Expression::Like { pattern, .. } => {
+                let Value::Text(pattern) = constant(pattern)? else {
+                    return None;
+                };
+                ordered.prefixed(&like_prefix(&pattern)?)
+            }
+            _ => return None,
+        };
+        Some(Lookup::new(format!("INDEX {name}"), ids.len(), ids))
+    }
+
+    /// The table a `FROM` item names, with its name, unless a `WITH` table hides it.
+    fn table<'s>(&self, source: &'s Source) -> Option<(&'s str, &'a Table)> {
+        let SourceKind::Named(name) = &source.kind else {
+            return None;
+        };
+        if self.common_tables.iter().any(|(table, _)| table == name) {
+            return None;
+        }
+        Some((name, self.catalog.table(name)?))
+    }
+
+    /// The rows of a `FROM` item other than a table, with columns qualified by its alias
+    /// or name.
+    fn source(&mut self, source: &Source) -> Result<Relation, DatabaseError> {
+        let (name, result) = match &source.kind {
+            SourceKind::Named(name) => {
+                let common_table = self
+                    .common_tables
+                    .iter()
+                    .rev()
+                    .find(|(common_table, _)| common_table == name);
+                if let Some((_, result)) = common_table {
+                    let qualifier = source.alias.as_deref().unwrap_or(name);
+                    return Ok(Relation::qualified(result.clone(), Some(qualifier)));
+                }
+                (Some(name), self.view(name)?)
+            }
+            SourceKind::Function(name, args) => (Some(name), self.table_function(name, args)?),
+            SourceKind::Subquery(sql) => {
+                let query = Query::new(Tokenizer::new(sql).tokenize()?)?;
+                (None, self.query(&query)?)
+            }
+        };
+        let qualifier = source.alias.as_deref().or(name.map(String::as_str));
+        Ok(Relation::qualified(result, qualifier))
+    }
+
+    /// Expand a view into the rows of its query.
+    fn view(&mut self, name: &str) -> Result<ResultSet, DatabaseError> {
+        let view = self
+            .catalog
+            .view(name)
+            .ok_or_else(|| CatalogError::NoSuchTable(name.to_string()))?;
+        if self.views.iter().any(|expanding| expanding == name) {
+            return Err(CatalogError::RecursiveView(name.to_string()).into());
+        }
+        let query = Query::new(Tokenizer::new(view.query()).tokenize()?)?;
+        // a view sees the catalog, not the `WITH` tables of the query that reads it
+        let common_tables = mem::take(&mut self.common_tables);
+        self.views.push(name.to_string());
+        let result = self.query(&query);
+        self.views.pop();
+        self.common_tables = common_tables;
+        result
+    }
+
+    /// Rows of `json_each(...)`, the one table-valued function.
+    fn table_function(
+        &self,
+        function: &str,
+        args: &[Expression],
+    ) -> Result<ResultSet, DatabaseError> {
+        if !function.eq_ignore_ascii_case("json_each") {
+            return Err(CatalogError::NoSuchTable(function.to_string()).into());
+        }
+        let args = args
+            .iter()
+            .map(|arg| self.eval(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ResultSet {
+            columns: json::EACH_COLUMNS.map(str::to_string).to_vec(),
+            rows: json::each(&args)?,
+        })
+    }
+}
+
+/// Kind of a result column, as far as it can be told before running the query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnKind {
+    Number,
+    String,
+    Unknown,
+}
+
+/// Check, before running it, that both sides of each set operation select as many columns
+/// of matching kinds, where literals tell. Returns `None` when a side selects `*`, whose
+/// columns are only known once it runs.
+fn check_compound(query: &Query) -> Result<Option<Vec<ColumnKind>>, DatabaseError> {
+    let [left, right] = query.subqueries() else {
+        let sql = query.value().ok_or(CatalogError::UnsupportedQuery)?;
+        let select = Select::parse(&Tokenizer::new(sql).tokenize()?)?;
+        return Ok(select
+            .columns
+            .iter()
+            .map(|column| match column {
+                ResultColumn::All(_) => None,
+                ResultColumn::Expression(
+                    _,
+                    Expression::Literal(Value::Integer(_) | Value::Real(_)),
+                ) => Some(ColumnKind::Number),
+                ResultColumn::Expression(_, Expression::Literal(Value::Text(_))) => {
+                    Some(ColumnKind::String)
+                }
+                ResultColumn::Expression(..) => Some(ColumnKind::Unknown),
+            })
+            .collect());
+    };
+    match (check_compound(left)?, check_compound(right)?) {
+        (Some(left), Some(right)) => {
+            if left.len() != right.len() {
+                return Err(CatalogError::ColumnCountMismatch(left.len(), right.len()).into());
+            }
+            let mut columns = vec![];
+            for (position, (left, right)) in iter::zip(left, right).enumerate() {
+                columns.push(match (left, right) {
+                    (ColumnKind::Unknown, kind) | (kind, ColumnKind::Unknown) => kind,
+                    (left, right) if left == right => left,
+                    _ => return Err(CatalogError::ColumnTypeMismatch(position + 1).into()),
+                });
+            }
+            Ok(Some(columns))
+        }
+        (left, right) => Ok(left.or(right)),
+    }
+}
+
+/// How many rows of a scan reading one row through an index is taken to cost.
+const INDEX_ROW_COST: f64 = 2.0;
+
+/// Whether reading `rows` rows through an index costs less than scanning `scan` rows.
+fn cheaper(rows: f64, scan: f64) -> bool {
+    rows * INDEX_ROW_COST < scan
+}
+
+/// The rows an index finds for a condition, and how many it is expected to find.
+struct Lookup {
+    estimate: f64,
+    scan: IndexScan,
+}
+
+impl Lookup {
+    fn new(name: String, estimate: usize, ids: impl IntoIterator<Item = RowId>) -> Self {
+        let mut ids: Vec<_> = ids.into_iter().collect();
+        ids.sort_unstable();
+        Self {
+            estimate: estimate as f64,
+            scan: IndexScan { name, ids },
+        }
+    }
+}
+
+/// What an index would have to read for `condition` to find the rows it keeps:
+/// the text of `MATCH`, or the value of `BETWEEN` or a case-sensitive `LIKE`.
+fn indexed_expression(condition: &Condition) -> Option<&Expression> {
+    match &condition.expression {
+        Expression::Match {
+            expression,
+            negated: false,
+            ..
+        }
+        | Expression::Between {
+            expression,
+            negated: false,
+            ..
+        }
+        | Expression::Like {
+            expression,
+            kind: PatternKind::Like,
+            negated: false,
+            ..
+        } => Some(expression),
+        _ => None,
+    }
+}
+
+/// The conditions that `AND` together into `expression`, left to right.
+fn conjuncts<'e>(expression: &'e Expression, conditions: &mut Vec<&'e Expression>) {
+    match expression {
+        Expression::Binary {
+            operator: BinaryOperator::And,
+            left,
+            right,
+        } => {
+            conjuncts(left, conditions);
+            conjuncts(right, conditions);
+        }
+        expression => conditions.push(expression),
+    }
+}
+
+/// The column names a `WITH name (column, ...)` list gives a query's result, if it has one.
+fn rename(columns: Vec<String>, fields: &[Query]) -> Result<Vec<String>, CatalogError> {
+    if fields.is_empty() {
+        return Ok(columns);
+    }
+    if fields.len() != columns.len() {
+        return Err(CatalogError::ColumnCountMismatch(
+            fields.len(),
+            columns.len(),
+        ));
+    }
+    fields
+        .iter()
+        .map(|field| {
+            field
+                .value()
+                .map(str::to_string)
+                .ok_or(CatalogError::UnsupportedQuery)
+        })
+        .collect()
+}
+
+/// How `EXPLAIN` names a `FROM` item.
+/// `columns` of a table, qualified by its alias or name.
+fn qualified_columns(columns: &[String], qualifier: &str) -> Vec<ColumnName> {
+    columns
+        .iter()
+        .map(|column| ColumnName::new(Some(qualifier), column))
+        .collect()
+}
+
+fn label(source: &Source) -> String {
+    let name = match &source.kind {
+        SourceKind::Named(name) | SourceKind::Function(name, _) => name.as_str(),
+        SourceKind::Subquery(_) => "(subquery)",
+    };
+    match &source.alias {
+        Some(alias) => format!("{name} AS {alias}"),
+        None => name.to_string(),
+    }
+}
+
+fn distinct_key(row: &[Value]) -> Vec<DistinctKey> {
+    row.iter().map(Value::distinct_key).collect()
+}
+
+/// A total order for sorting: `NULL` first, then numbers, text and blobs.
+pub(crate) fn sort_order(left: &Value, right: &Value) -> Ordering {
+    let rank = |value: &Value| match value {
+        Value::Null => 0,
+        Value::Integer(_) | Value::Real(_) => 1,
+        Value::Text(_) => 2,
+        Value::Blob(_) => 3,
+    };
+    match rank(left).cmp(&rank(right)) {
+        Ordering::Equal => compare("ORDER BY", left, right)
+            .ok()
+            .flatten()
+            .unwrap_or(Ordering::Equal),
+        ordering => ordering,
+    }
+}
@@ -0,0 +1,1259 @@
+use std::{cmp::Ordering, fmt, iter::Peekable, mem};
+
+use super::{
+    catalog::CatalogError,
+    database::Variables,
+    fulltext::Analyzer,
+    functions::{FunctionError, Functions},
+    json,
+    query::identifier_name,
+    tokenizer::{ParseError, Token, TokenKind, TokenValue},
+    value::Value,
+    window::{FrameBound, Window},
+};
+
+/// An expression evaluated on one row at a time, or on none for a `SELECT` without `FROM`.
+///
+/// Predicates follow SQL's three-valued logic: they evaluate to `1`, `0` or `NULL`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Literal(Value),
+    /// `$1`, replaced by [`Expression::bind`] before evaluation.
+    Parameter(usize),
+    /// `@name`, replaced by [`Expression::bind`]; `NULL` if it was never set.
+    Variable(String),
+    /// `name` or `table.name`, replaced by [`Expression::resolve`] before evaluation.
+    Column(ColumnName),
+    /// The value at this position of the row being evaluated.
+    Field(usize),
+    Function(String, Vec<Expression>),
+    /// `name(args) OVER (...)`, computed across all result rows by the executor, which
+    /// moves it out with [`Expression::extract_windows`] first.
+    Window {
+        function: String,
+        args: Vec<Expression>,
+        window: Window,
+    },
+    /// `json -> path` gives JSON text, `json ->> path` the SQL value.
+    JsonExtract {
+        json: Box<Expression>,
+        path: Box<Expression>,
+        as_value: bool,
+    },
+    Not(Box<Expression>),
+    /// Unary minus.
+    Negative(Box<Expression>),
+    Binary {
+        operator: BinaryOperator,
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
+    Like {
+        expression: Box<Expression>,
+        pattern: Box<Expression>,
+        kind: PatternKind,
+        negated: bool,
+    },
+    /// `text MATCH query`: whether the text contains every word of the query.
+    Match {
+        expression: Box<Expression>,
+        query: Box<Expression>,
+        negated: bool,
+    },
+    Between {
+        expression: Box<Expression>,
+        low: Box<Expression>,
+        high: Box<Expression>,
+        negated: bool,
+    },
+    InList {
+        expression: Box<Expression>,
+        list: Vec<Expression>,
+        negated: bool,
+    },
+    IsNull {
+        expression: Box<Expression>,
+        negated: bool,
+    },
+}
+
+/// A column as written in a query, or as provided by a `FROM` item.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnName {
+    /// The table or alias qualifying the column, if any.
+    pub table: Option<String>,
+    pub name: String,
+}
+
+impl ColumnName {
+    pub fn new(table: Option<&str>, name: &str) -> Self {
+        Self {
+            table: table.map(str::to_string),
+            name: name.to_string(),
+        }
+    }
+
+    /// Whether a reference written as `self` can mean `column`.
+    pub(crate) fn refers_to(&self, column: &ColumnName) -> bool {
+        self.name == column.name
+            && self
+                .table
+                .as_ref()
+                .is_none_or(|table| column.table.as_ref() == Some(table))
+    }
+}
+
+impl fmt::Display for ColumnName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.table {
+            Some(table) => write!(f, "{}.{}", table, self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PatternKind {
+    /// `%` and `_` wildcards, case-sensitive.
+    Like,
+    /// `LIKE` ignoring case.
+    ILike,
+    /// `*`, `?` and `[...]` wildcards, case-sensitive.
+    Glob,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Or,
+    And,
+    Eq,
+    NotEq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Concat,
+}
+
+impl BinaryOperator {
+    fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOperator::Or => "OR",
+            BinaryOperator::And => "AND",
+            BinaryOperator::Eq => "=",
+            BinaryOperator::NotEq => "<>",
+            BinaryOperator::Lt => "<",
+            BinaryOperator::Lte => "<=",
+            BinaryOperator::Gt => ">",
+            BinaryOperator::Gte => ">=",
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Concat => "||",
+        }
+    }
+}
+
+impl PatternKind {
+    fn name(&self) -> &'static str {
+        match self {
+            PatternKind::Like => "LIKE",
+            PatternKind::ILike => "ILIKE",
+            PatternKind::Glob => "GLOB",
+        }
+    }
+}
+
+impl Expression {
+    /// Parse tokens holding exactly one expression, such as the value of `SET @x := 1 + 2`.
+    pub fn parse_value(tokens: &[Token]) -> Result<Self, ParseError> {
+        let mut token_iter = tokens
+            .iter()
+            .filter(|t| !matches!(t.token(), TokenKind::EOF) && !t.token().is_comment())
+            .peekable();
+        let expression = Self::parse(&mut token_iter)?;
+        match token_iter.next() {
+            None => Ok(expression),
+            Some(_) => Err(ParseError::UnexpectedToken),
+        }
+    }
+
+    /// A full expression. From loosest to tightest binding: `OR`, `AND`, `NOT`, predicates
+    /// and `=`/`<>`, `<`/`<=`/`>`/`>=`, `+`/`-`, `*`/`/`/`%`, `||`, then unary minus.
+    pub(crate) fn parse<'a, 's: 'a, I>(token_iter: &mut Peekable<I>) -> Result<Self, ParseError>
+    where
+        I: Iterator<Item = &'a Token<'s>>,
+    {
+        let mut expression = Self::parse_and(token_iter)?;
+        while token_iter.next_if(|t| t.token() == TokenKind::OR).is_some() {
+            expression = binary(BinaryOperator::Or, expression, Self::parse_and(token_iter)?);
+        }
+        Ok(expression)
+    }
+
+    fn parse_and<'a, 's: 'a, I>(token_iter: &mut Peekable<I>) -> Result<Self, ParseError>
+    where
+        I: Iterator<Item = &'a Token<'s>>,
+    {
+        let mut expression = Self::parse_not(token_iter)?;
+        while token_iter
+            .next_if(|t| t.token() == TokenKind::AND)
+            .is_some()
+        {
+            expression = binary(
+                BinaryOperator::And,
+                expression,
+                Self::parse_not(token_iter)?,
+            );
+        }
+        Ok(expression)
+    }
+
+    fn parse_not<'a, 's: 'a, I>(token_iter: &mut Peekable<I>) -> Result<Self, ParseError>
+    where
+        I: Iterator<Item = &'a Token<'s>>,
+    {
+        if token_iter
+            .next_if(|t| t.token() == TokenKind::NOT)
+            .is_some()
+        {
+            return Ok(Expression::Not(Box::new(Self::parse_not(token_iter)?)));
+        }
+        Self::parse_predicate(token_iter)
+    }
+
+    /// A comparison optionally followed by a predicate, or `=`/`<>` and another comparison.
+    fn parse_predicate<'a, 's: 'a, I>(token_iter: &mut Peekable<I>) -> Result<Self, ParseError>
+    where
+        I: Iterator<Item = &'a Token<'s>>,
+    {
+        let mut expression = Self::parse_comparison(token_iter)?;
+        while let Some(operator) =
+            token_iter.next_if(|t| matches!(t.token(), TokenKind::Eq | TokenKind::NotEq))
+        {
+            let operator = match operator.token() {
+                TokenKind::Eq => BinaryOperator::Eq,
+                _ => BinaryOperator::NotEq,
+            };
+            expression = binary(operator, expression, Self::parse_comparison(token_iter)?);
+        }
+
+        let expression = Box::new(expression);
+        if token_iter.next_if(|t| t.token() == TokenKind::IS).is_some() {
+            let negated = token_iter
+                .next_if(|t| t.token() == TokenKind::NOT)
+                .is_some();
+            expect(token_iter, TokenKind::NULL)?;
+            return Ok(Expression::IsNull {
+                expression,
+                negated,
+            });
+        }
+
+        let negated = token_iter
+            .next_if(|t| t.token() == TokenKind::NOT)
+            .is_some();
+        let kind = match token_iter.peek().map(|t| t.token()) {
+            Some(TokenKind::LIKE) => Some(PatternKind::Like),
+            Some(TokenKind::ILIKE) => Some(PatternKind::ILike),
+            Some(TokenKind::GLOB) => Some(PatternKind::Glob),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            token_iter.next();
+            return Ok(Expression::Like {
+                expression,
+                pattern: Box::new(Self::parse_comparison(token_iter)?),
+                kind,
+                negated,
+            });
+        }
+
+        if token_iter
+            .next_if(|t| t.token() == TokenKind::MATCH)
+            .is_some()
+        {
+            return Ok(Expression::Match {
+                expression,
+                query: Box::new(Self::parse_comparison(token_iter)?),
+                negated,
+            });
+        }
+
+        match token_iter.next_if(|t| matches!(t.token(), TokenKind::BETWEEN | TokenKind::IN)) {
+            Some(t) if t.token() == TokenKind::BETWEEN => {
+                let low = Box::new(Self::parse_comparison(token_iter)?);
+                expect(token_iter, TokenKind::AND)?;
+                let high = Box::new(Self::parse_comparison(token_iter)?);
+                Ok(Expression::Between {
+                    expression,
+                    low,
+                    high,
+                    negated,
+                })
+            }
+            Some(_) => {
+                expect(token_iter, TokenKind::LeftParen)?;
+                let mut list = vec![Self::parse(token_iter)?];
+                while token_iter
+                    .next_if(|t| t.token() == TokenKind::Comma)
+                    .is_some()
+                {
+                    list.push(Self::parse(token_iter)?);
+                }
+                expect(token_iter, TokenKind::RightParen)?;
+                Ok(Expression::InList {
+                    expression,
+                    list,
+                    negated,
+                })
+            }
+            // a bare NOT after an operand must start a predicate
+            None if negated => Err(ParseError::UnexpectedToken),
+            None => Ok(*expression),
+        }
+    }
+
+    fn parse_comparison<'a, 's: 'a, I>(token_iter: &mut Peekable<I>) -> Result<Self, ParseError>
+    where
+        I: Iterator<Item = &'a Token<'s>>,
+    {
+        let mut expression = Self::parse_sum(token_iter)?;
+        while let Some(operator) = token_iter.next_if(|t| {
+            matches!(
+                t.token(),
+                TokenKind::Lt | TokenKind::Lte | TokenKind::Gt | TokenKind::Gte
+            )
+        }) {
+            let operator = match operator.token() {
+                TokenKind::Lt => BinaryOperator::Lt,
+                TokenKind::Lte => BinaryOperator::Lte,
+                TokenKind::Gt => BinaryOperator::Gt,
+                _ => BinaryOperator::Gte,
+            };
+            expression = binary(operator, expression, Self::parse_sum(token_iter)?);
+        }
+        Ok(expression)
+    }
+
+    fn parse_sum<'a, 's: 'a, I>(token_iter: &mut Peekable<I>) -> Result<Self, ParseError>
+    where
+        I: Iterator<Item = &'a Token<'s>>,
+    {
+        let mut expression = Self::parse_product(token_iter)?;
+        while let Some(operator) =
+            token_iter.next_if(|t| matches!(t.token(), TokenKind::Plus | TokenKind::Minus))
+        {
+            let operator = match operator.token() {
+                TokenKind::Plus => BinaryOperator::Add,
+                _ => BinaryOperator::Subtract,
+            };
+            expression = binary(operator, expression, Self::parse_product(token_iter)?);
+        }
+        Ok(expression)
+    }
+
+    fn parse_product<'a, 's: 'a, I>(token_iter: &mut Peekable<I>) -> Result<Self, ParseError>
+    where
+        I: Iterator<Item = &'a Token<'s>>,
+    {
+        let mut expression = Self::parse_concat(token_iter)?;
+        while let Some(operator) = token_iter.next_if(|t| {
+            matches!(
+                t.token(),
+                TokenKind::Multiply | TokenKind::Divide | TokenKind::Modulo
+            )
+        }) {
+            let operator = match operator.token() {
+                TokenKind::Multiply => BinaryOperator::Multiply,
+                TokenKind::Divide => BinaryOperator::Divide,
+                _ => BinaryOperator::Modulo,
+            };
+            expression = binary(operator, expression, Self::parse_concat(token_iter)?);
+        }
+        Ok(expression)
+    }
+
+    fn parse_concat<'a, 's: 'a, I>(token_iter: &mut Peekable<I>) -> Result<Self, ParseError>
+    where
+        I: Iterator<Item = &'a Token<'s>>,
+    {
+        let mut expression = Self::parse_unary(token_iter)?;
+        while token_iter
+            .next_if(|t| t.token() == TokenKind::Concat)
+            .is_some()
+        {
+            expression = binary(
+                BinaryOperator::Concat,
+                expression,
+                Self::parse_unary(token_iter)?,
+            );
+        }
+        Ok(expression)
+    }
+
+    /// An operand with any number of leading `-` and `+` signs.
+    fn parse_unary<'a, 's: 'a, I>(token_iter: &mut Peekable<I>) -> Result<Self, ParseError>
+    where
+        I: Iterator<Item = &'a Token<'s>>,
+    {
+        match token_iter.next_if(|t| matches!(t.token(), TokenKind::Minus | TokenKind::Plus)) {
+            Some(sign) if sign.token() == TokenKind::Plus => Self::parse_unary(token_iter),
+            // negative literals stay literals, so they can be compared with `==` in plans
+            Some(_) => match Self::parse_unary(token_iter)? {
+                Expression::Literal(Value::Integer(integer)) if integer != i64::MIN => {
+                    Ok(Expression::Literal(Value::Integer(-integer)))
+                }
+                Expression::Literal(Value::Real(real)) => {
+                    Ok(Expression::Literal(Value::Real(-real)))
+                }
+                expression => Ok(Expression::Negative(Box::new(expression))),
+            },
+            None => Self::parse_operand(token_iter),
+        }
+    }
+
+    /// A primary expression followed by any number of `->` and `->>` extractions.
+    fn parse_operand<'a, 's: 'a, I>(token_iter: &mut Peekable<I>) -> Result<Self, ParseError>
+    where
+        I: Iterator<Item = &'a Token<'s>>,
+    {
+        let mut expression = Self::parse_primary(token_iter)?;
+        while let Some(arrow) = token_iter
+            .next_if(|t| matches!(t.token(), TokenKind::JsonArrow | TokenKind::JsonLongArrow))
+        {
+            expression = Expression::JsonExtract {
+                json: Box::new(expression),
+                path: Box::new(Self::parse_primary(token_iter)?),
+                as_value: arrow.token() == TokenKind::JsonLongArrow,
+            };
+        }
+        Ok(expression)
+    }
+
+    fn parse_primary<'a, 's: 'a, I>(token_iter: &mut Peekable<I>) -> Result<Self, ParseError>
+    where
+        I: Iterator<Item = &'a Token<'s>>,
+    {
+        let token = token_iter.next().ok_or(ParseError::UnexpectedToken)?;
+        if let Some(value) = Value::from_literal(token)? {
+            return Ok(Expression::Literal(value));
+        }
+
+        match token.token() {
+            TokenKind::Placeholder => match token.value() {
+                Some(TokenValue::Integer(index)) if *index > 0 => {
+                    Ok(Expression::Parameter(*index as usize))
+                }
+                _ => Err(ParseError::InvalidLiteral),
+            },
+            TokenKind::Variable => match token.value() {
+                Some(TokenValue::String(name)) => Ok(Expression::Variable(name.to_lowercase())),
+                _ => Err(ParseError::UnexpectedToken),
+            },
+            TokenKind::LeftParen => {
+                let expression = Self::parse(token_iter)?;
+                match token_iter.next() {
+                    Some(t) if t.token() == TokenKind::RightParen => Ok(expression),
+                    _ => Err(ParseError::UnexpectedToken),
+                }
+            }
+            TokenKind::Identifier
+                if token_iter
+                    .next_if(|t| t.token() == TokenKind::LeftParen)
+                    .is_some() =>
+            {
+                let mut args = vec![];
+                let no_args = token_iter
+                    .next_if(|t| t.token() == TokenKind::RightParen)
+                    .is_some()
+                    // `COUNT(*)` counts rows rather than values
+                    || (token_iter
+                        .next_if(|t| t.token() == TokenKind::Multiply)
+                        .is_some()
+                        && expect(token_iter, TokenKind::RightParen).is_ok());
+                if !no_args {
+                    loop {
+                        args.push(Self::parse(token_iter)?);
+                        match token_iter.next().map(|t| t.token()) {
+                            Some(TokenKind::Comma) => (),
+                            Some(TokenKind::RightParen) => break,
+                            _ => return Err(ParseError::UnexpectedToken),
+                        }
+                    }
+                }
+
+                let function = token.text().to_string();
+                if token_iter
+                    .next_if(|t| t.token() == TokenKind::OVER)
+                    .is_some()
+                {
+                    let window = Self::parse_window(token_iter)?;
+                    return Ok(Expression::Window {
+                        function,
+                        args,
+                        window,
+                    });
+                }
+                Ok(Expression::Function(function, args))
+            }
+            // `key` of json_each is a keyword
+            TokenKind::Identifier | TokenKind::DoubleQuotedString | TokenKind::KEY => {
+                let name = match token.token() {
+                    TokenKind::KEY => token.text().to_lowercase(),
+                    _ => identifier_name(token)?,
+                };
+                if token_iter
+                    .next_if(|t| t.token() == TokenKind::Period)
+                    .is_none()
+                {
+                    return Ok(Expression::Column(ColumnName { table: None, name }));
+                }
+                let column = token_iter.next().ok_or(ParseError::UnexpectedToken)?;
+                Ok(Expression::Column(ColumnName {
+                    table: Some(name),
+                    name: identifier_name(column)?,
+                }))
+            }
+            _ => Err(ParseError::UnexpectedToken),
+        }
+    }
+
+    /// `(PARTITION BY ... ORDER BY ... ROWS ...)` after `OVER`.
+    fn parse_window<'a, 's: 'a, I>(token_iter: &mut Peekable<I>) -> Result<Window, ParseError>
+    where
+        I: Iterator<Item = &'a Token<'s>>,
+    {
+        expect(token_iter, TokenKind::LeftParen)?;
+        let mut window = Window {
+            partition_by: vec![],
+            order_by: vec![],
+            frame: None,
+        };
+
+        if token_iter
+            .next_if(|t| t.token() == TokenKind::PARTITION)
+            .is_some()
+        {
+            expect(token_iter, TokenKind::BY)?;
+            loop {
+                window.partition_by.push(Self::parse(token_iter)?);
+                if token_iter
+                    .next_if(|t| t.token() == TokenKind::Comma)
+                    .is_none()
+                {
+                    break;
+                }
+            }
+        }
+
+        if token_iter
+            .next_if(|t| t.token() == TokenKind::ORDER)
+            .is_some()
+        {
+            expect(token_iter, TokenKind::BY)?;
+            loop {
+                let expression = Self::parse(token_iter)?;
+                let descending = token_iter
+                    .next_if(|t| matches!(t.token(), TokenKind::ASC | TokenKind::DESC))
+                    .is_some_and(|t| t.token() == TokenKind::DESC);
+                window.order_by.push((expression, descending));
+                if token_iter
+                    .next_if(|t| t.token() == TokenKind::Comma)
+                    .is_none()
+                {
+                    break;
+                }
+            }
+        }
+
+        if token_iter
+            .next_if(|t| t.token() == TokenKind::ROWS)
+            .is_some()
+        {
+            let frame = if token_iter
+                .next_if(|t| t.token() == TokenKind::BETWEEN)
+                .is_some()
+            {
+                let start = parse_frame_bound(token_iter)?;
+                expect(token_iter, TokenKind::AND)?;
+                (start, parse_frame_bound(token_iter)?)
+            } else {
+                (parse_frame_bound(token_iter)?, FrameBound::CurrentRow)
+            };
+            if matches!(frame, (FrameBound::UnboundedFollowing, _))
+                || matches!(frame, (_, FrameBound::UnboundedPreceding))
+            {
+                return Err(ParseError::UnexpectedToken);
+            }
+            window.frame = Some(frame);
+        }
+
+        expect(token_iter, TokenKind::RightParen)?;
+        Ok(window)
+    }
+
+    /// Replace each `$n` with the `n`th of `params` and each `@name` with its value.
+    pub fn bind(&mut self, params: &[Value], variables: &Variables) -> Result<(), FunctionError> {
+        match self {
+            Expression::Parameter(index) => {
+                let value = params
+                    .get(*index - 1)
+                    .ok_or(FunctionError::UnboundParameter(*index))?;
+                *self = Expression::Literal(value.clone());
+                Ok(())
+            }
+            Expression::Variable(name) => {
+                let value = variables.get(name).cloned().unwrap_or(Value::Null);
+                *self = Expression::Literal(value);
+                Ok(())
+            }
+            _ => {
+                for child in self.children_mut() {
+                    child.bind(params, variables)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Replace each column reference with the position of the column it names in `columns`.
+    pub fn resolve(&mut self, columns: &[ColumnName]) -> Result<(), CatalogError> {
+        if let Expression::Column(column) = self {
+            let mut matches = columns
+                .iter()
+                .enumerate()
+                .filter(|(_, candidate)| column.refers_to(candidate));
+            let index = match (matches.next(), matches.next()) {
+                (Some((index, _)), None) => index,
+                (Some(_), Some(_)) => {
+                    return Err(CatalogError::AmbiguousColumn(column.to_string()))
+                }
+                (None, _) => return Err(CatalogError::NoSuchColumn(column.to_string())),
+            };
+            *self = Expression::Field(index);
+            return Ok(());
+        }
+        for child in self.children_mut() {
+            child.resolve(columns)?;
+        }
+        Ok(())
+    }
+
+    /// Replace each qualified reference to a bound column with its value, such as
+    /// `NEW.id` in a trigger. Qualifiers match case-insensitively.
+    pub(crate) fn substitute(&mut self, bindings: &[(ColumnName, Value)]) {
+        if let Expression::Column(ColumnName {
+            table: Some(table),
+            name,
+        }) = self
+        {
+            let bound = bindings.iter().find(|(column, _)| {
+                column.name == *name
+                    && column
+                        .table
+                        .as_ref()
+                        .is_some_and(|bound| bound.eq_ignore_ascii_case(table))
+            });
+            if let Some((_, value)) = bound {
+                *self = Expression::Literal(value.clone());
+            }
+            return;
+        }
+        for child in self.children_mut() {
+            child.substitute(bindings);
+        }
+    }
+
+    /// Move each window function out into `windows`, leaving the field its value is
+    /// appended at, after the `width` columns of the row.
+    pub(crate) fn extract_windows(&mut self, windows: &mut Vec<Expression>, width: usize) {
+        if let Expression::Window { .. } = self {
+            let field = Expression::Field(width + windows.len());
+            windows.push(mem::replace(self, field));
+            return;
+        }
+        for child in self.children_mut() {
+            child.extract_windows(windows, width);
+        }
+    }
+
+    /// Append the position of every field the expression reads to `fields`.
+    pub(crate) fn fields(&self, fields: &mut Vec<usize>) {
+        if let Expression::Field(field) = self {
+            fields.push(*field);
+            return;
+        }
+        for child in self.children() {
+            child.fields(fields);
+        }
+    }
+
+    fn children(&self) -> Vec<&Expression> {
+        match self {
+            Expression::Literal(_)
+            | Expression::Parameter(_)
+            | Expression::Variable(_)
+            | Expression::Column(_)
+            | Expression::Field(_) => vec![],
+            Expression::Function(_, args) => args.iter().collect(),
+            Expression::Window { args, window, .. } => args
+                .iter()
+                .chain(window.partition_by.iter())
+                .chain(window.order_by.iter().map(|(order, _)| order))
+                .collect(),
+            Expression::JsonExtract { json, path, .. } => vec![json.as_ref(), path.as_ref()],
+            Expression::Not(expression)
+            | Expression::Negative(expression)
+            | Expression::IsNull { expression, .. } => vec![expression.as_ref()],
+            Expression::Binary { left, right, .. } => vec![left.as_ref(), right.as_ref()],
+            Expression::Like {
+                expression,
+                pattern: query,
+                ..
+            }
+            | Expression::Match {
+                expression, query, ..
+            } => vec![expression.as_ref(), query.as_ref()],
+            Expression::Between {
+                expression,
+                low,
+                high,
+                ..
+            } => vec![expression.as_ref(), low.as_ref(), high.as_ref()],
+            Expression::InList {
+                expression, list, ..
+            } => std::iter::once(expression.as_ref())
+                .chain(list.iter())
+                .collect(),
+        }
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            Expression::Literal(_)
+            | Expression::Parameter(_)
+            | Expression::Variable(_)
+            | Expression::Column(_)
+            | Expression::Field(_) => vec![],
+            Expression::Function(_, args) => args.iter_mut().collect(),
+            Expression::Window { args, window, .. } => args
+                .iter_mut()
+                .chain(window.partition_by.iter_mut())
+                .chain(window.order_by.iter_mut().map(|(order, _)| order))
+                .collect(),
+            Expression::JsonExtract { json, path, .. } => vec![json.as_mut(), path.as_mut()],
+            Expression::Not(expression)
+            | Expression::Negative(expression)
+            | Expression::IsNull { expression, .. } => vec![expression.as_mut()],
+            Expression::Binary { left, right, .. } => vec![left.as_mut(), right.as_mut()],
+            Expression::Like {
+                expression,
+                pattern: query,
+                ..
+            }
+            | Expression::Match {
+                expression, query, ..
+            } => vec![expression.as_mut(), query.as_mut()],
+            Expression::Between {
+                expression,
+                low,
+                high,
+                ..
+            } => vec![expression.as_mut(), low.as_mut(), high.as_mut()],
+            Expression::InList {
+                expression, list, ..
+            } => std::iter::once(expression.as_mut())
+                .chain(list.iter_mut())
+                .collect(),
+        }
+    }
+
+    /// The value of an expression that reads no columns.
+    pub fn eval(&self, functions: &Functions) -> Result<Value, FunctionError> {
+        self.eval_row(functions, &[])
+    }
+
+    /// The value for one row, whose columns [`Expression::resolve`] numbered.
+    pub fn eval_row(&self, functions: &Functions, row: &[Value]) -> Result<Value, FunctionError> {
+        match self {
+            Expression::Literal(value) => Ok(value.clone()),
+            Expression::Field(index) => Ok(row[*index].clone()),
+            Expression::Column(column) => {
+                unreachable!("column {} was not resolved", column)
+            }
+            Expression::Parameter(index) => Err(FunctionError::UnboundParameter(*index)),
+            Expression::Variable(name) => Err(FunctionError::UnboundVariable(name.clone())),
+            Expression::Function(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.eval_row(functions, row))
+                    .collect::<Result<Vec<_>, _>>()?;
+                functions.call(name, &args)
+            }
+            // the executor computes windows over all result rows before evaluating them
+            Expression::Window { function, .. } => {
+                Err(FunctionError::MisusedWindow(function.clone()))
+            }
+            Expression::JsonExtract {
+                json,
+                path,
+                as_value,
+            } => json::arrow(
+                &json.eval_row(functions, row)?,
+                &path.eval_row(functions, row)?,
+                *as_value,
+            ),
+            Expression::Not(expression) => Ok(not(truth(&expression.eval_row(functions, row)?))),
+            Expression::Negative(expression) => match expression.eval_row(functions, row)? {
+                Value::Null => Ok(Value::Null),
+                Value::Integer(integer) => integer
+                    .checked_neg()
+                    .map(Value::Integer)
+                    .ok_or_else(|| FunctionError::Overflow("-".to_string())),
+                Value::Real(real) => Ok(Value::Real(-real)),
+                value => Err(FunctionError::InvalidArgument("-".to_string(), value)),
+            },
+            Expression::Binary {
+                operator: BinaryOperator::And,
+                left,
+                right,
+            } => {
+                // FALSE wins over NULL, so the right side only matters if the left is not false
+                match truth(&left.eval_row(functions, row)?) {
+                    Some(false) => Ok(boolean(Some(false))),
+                    left => Ok(boolean(
+                        match (left, truth(&right.eval_row(functions, row)?)) {
+                            (_, Some(false)) => Some(false),
+                            (Some(true), Some(true)) => Some(true),
+                            _ => None,
+                        },
+                    )),
+                }
+            }
+            Expression::Binary {
+                operator: BinaryOperator::Or,
+                left,
+                right,
+            } => match truth(&left.eval_row(functions, row)?) {
+                Some(true) => Ok(boolean(Some(true))),
+                left => Ok(boolean(
+                    match (left, truth(&right.eval_row(functions, row)?)) {
+                        (_, Some(true)) => Some(true),
+                        (Some(false), Some(false)) => Some(false),
+                        _ => None,
+                    },
+                )),
+            },
+            Expression::Binary {
+                operator,
+                left,
+                right,
+            } => apply(
+                *operator,
+                &left.eval_row(functions, row)?,
+                &right.eval_row(functions, row)?,
+            ),
+            Expression::Like {
+                expression,
+                pattern,
+                kind,
+                negated,
+            } => {
+                let (value, pattern) = (
+                    expression.eval_row(functions, row)?,
+                    pattern.eval_row(functions, row)?,
+                );
+                if value.is_null() || pattern.is_null() {
+                    return Ok(Value::Null);
+                }
+                let (text, pattern) = (
+                    as_text(kind.name(), &value)?,
+                    as_text(kind.name(), &pattern)?,
+                );
+                let text = text.chars().collect::<Vec<_>>();
+                let pattern = pattern.chars().collect::<Vec<_>>();
+                let matched = match kind {
+                    PatternKind::Like => like(&text, &pattern, false),
+                    PatternKind::ILike => like(&text, &pattern, true),
+                    PatternKind::Glob => glob(&text, &pattern),
+                };
+                Ok(negate(Some(matched), *negated))
+            }
+            Expression::Match {
+                expression,
+                query,
+                negated,
+            } => {
+                let (value, query) = (
+                    expression.eval_row(functions, row)?,
+                    query.eval_row(functions, row)?,
+                );
+                if value.is_null() || query.is_null() {
+                    return Ok(Value::Null);
+                }
+                let matched =
+                    Analyzer::new().matches(&as_text("MATCH", &value)?, &as_text("MATCH", &query)?);
+                Ok(negate(Some(matched), *negated))
+            }
+            Expression::Between {
+                expression,
+                low,
+                high,
+                negated,
+            } => {
+                let value = expression.eval_row(functions, row)?;
+                let above = compare("BETWEEN", &value, &low.eval_row(functions, row)?)?
+                    .map(|ordering| ordering != Ordering::Less);
+                let below = compare("BETWEEN", &value, &high.eval_row(functions, row)?)?
+                    .map(|ordering| ordering != Ordering::Greater);
+                let between = match (above, below) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                };
+                Ok(negate(between, *negated))
+            }
+            Expression::InList {
+                expression,
+                list,
+                negated,
+            } => {
+                let value = expression.eval_row(functions, row)?;
+                if value.is_null() {
+                    return Ok(Value::Null);
+                }
+                // a NULL in the list makes a miss unknown rather than false
+                let mut found = Some(false);
+                for item in list {
+                    match compare("IN", &value, &item.eval_row(functions, row)?)? {
+                        Some(Ordering::Equal) => {
+                            found = Some(true);
+                            break;
+                        }
+                        Some(_) => (),
+                        None => found = None,
+                    }
+                }
+                Ok(negate(found, *negated))
+            }
+            Expression::IsNull {
+                expression,
+                negated,
+            } => Ok(negate(
+                Some(expression.eval_row(functions, row)?.is_null()),
+                *negated,
+            )),
+        }
+    }
+}
+
+/// `UNBOUNDED PRECEDING`, `n PRECEDING`, `CURRENT ROW`, `n FOLLOWING` or `UNBOUNDED FOLLOWING`.
+fn parse_frame_bound<'a, 's: 'a, I>(token_iter: &mut Peekable<I>) -> Result<FrameBound, ParseError>
+where
+    I: Iterator<Item = &'a Token<'s>>,
+{
+    let token = token_iter.next().ok_or(ParseError::UnexpectedToken)?;
+    let offset = match token.token() {
+        TokenKind::CURRENT => {
+            expect(token_iter, TokenKind::ROW)?;
+            return Ok(FrameBound::CurrentRow);
+        }
+        TokenKind::UNBOUNDED => None,
+        TokenKind::Number => Some(
+            token
+                .text()
+                .parse::<usize>()
+                .map_err(|_| ParseError::InvalidLiteral)?,
+        ),
+        _ => return Err(ParseError::UnexpectedToken),
+    };
+    match (token_iter.next().map(|t| t.token()), offset) {
+        (Some(TokenKind::PRECEDING), None) => Ok(FrameBound::UnboundedPreceding),
+        (Some(TokenKind::PRECEDING), Some(n)) => Ok(FrameBound::Preceding(n)),
+        (Some(TokenKind::FOLLOWING), None) => Ok(FrameBound::UnboundedFollowing),
+        (Some(TokenKind::FOLLOWING), Some(n)) => Ok(FrameBound::Following(n)),
+        _ => Err(ParseError::UnexpectedToken),
+    }
+}
+
+pub(crate) fn expect<'a, 's: 'a, I>(
+    token_iter: &mut Peekable<I>,
+    kind: TokenKind,
+) -> Result<(), ParseError>
+where
+    I: Iterator<Item = &'a Token<'s>>,
+{
+    match token_iter.next() {
+        Some(t) if t.token() == kind => Ok(()),
+        _ => Err(ParseError::UnexpectedToken),
+    }
+}
+
+fn binary(operator: BinaryOperator, left: Expression, right: Expression) -> Expression {
+    Expression::Binary {
+        operator,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+/// A comparison, arithmetic or `||` on two values; `NULL` if either is `NULL`.
+///
+/// Integer arithmetic stays integral and fails on overflow, mixing in a real gives a real,
+/// and dividing by zero gives `NULL`.
+pub(crate) fn apply(
+    operator: BinaryOperator,
+    left: &Value,
+    right: &Value,
+) -> Result<Value, FunctionError> {
+    let name = operator.symbol();
+    if left.is_null() || right.is_null() {
+        return Ok(Value::Null);
+    }
+    let ordering = || compare(name, left, right).map(Option::unwrap);
+    let value = match operator {
+        BinaryOperator::Or | BinaryOperator::And => {
+            unreachable!("logical operators short-circuit in eval")
+        }
+        BinaryOperator::Eq => boolean(Some(ordering()? == Ordering::Equal)),
+        BinaryOperator::NotEq => boolean(Some(ordering()? != Ordering::Equal)),
+        BinaryOperator::Lt => boolean(Some(ordering()? == Ordering::Less)),
+        BinaryOperator::Lte => boolean(Some(ordering()? != Ordering::Greater)),
+        BinaryOperator::Gt => boolean(Some(ordering()? == Ordering::Greater)),
+        BinaryOperator::Gte => boolean(Some(ordering()? != Ordering::Less)),
+        BinaryOperator::Concat => Value::Text(as_text(name, left)? + &as_text(name, right)?),
+        _ => match (left, right) {
+            (Value::Integer(l), Value::Integer(r)) => {
+                let result = match operator {
+                    BinaryOperator::Add => l.checked_add(*r),
+                    BinaryOperator::Subtract => l.checked_sub(*r),
+                    BinaryOperator::Multiply => l.checked_mul(*r),
+                    BinaryOperator::Divide | BinaryOperator::Modulo if *r == 0 => {
+                        return Ok(Value::Null)
+                    }
+                    BinaryOperator::Divide => l.checked_div(*r),
+                    _ => l.checked_rem(*r),
+                };
+                Value::Integer(result.ok_or_else(|| FunctionError::Overflow(name.to_string()))?)
+            }
+            (Value::Integer(_) | Value::Real(_), Value::Integer(_) | Value::Real(_)) => {
+                let (l, r) = (real(left), real(right));
+                match operator {
+                    BinaryOperator::Add => Value::Real(l + r),
+                    BinaryOperator::Subtract => Value::Real(l - r),
+                    BinaryOperator::Multiply => Value::Real(l * r),
+                    _ if r == 0.0 => Value::Null,
+                    BinaryOperator::Divide => Value::Real(l / r),
+                    _ => Value::Real(l % r),
+                }
+            }
+            (Value::Integer(_) | Value::Real(_), value) | (value, _) => {
+                return Err(FunctionError::InvalidArgument(
+                    name.to_string(),
+                    value.clone(),
+                ))
+            }
+        },
+    };
+    Ok(value)
+}
+
+fn real(value: &Value) -> f64 {
+    match value {
+        Value::Integer(integer) => *integer as f64,
+        Value::Real(real) => *real,
+        _ => unreachable!("only numbers are converted"),
+    }
+}
+
+/// `NULL` is unknown, zero is false and anything else is true.
+pub(crate) fn truth(value: &Value) -> Option<bool> {
+    match value {
+        Value::Null => None,
+        Value::Integer(integer) => Some(*integer != 0),
+        Value::Real(real) => Some(*real != 0.0),
+        Value::Text(text) => Some(!text.is_empty()),
+        Value::Blob(blob) => Some(!blob.is_empty()),
+    }
+}
+
+fn boolean(truth: Option<bool>) -> Value {
+    truth.map_or(Value::Null, |truth| Value::Integer(truth as i64))
+}
+
+fn not(truth: Option<bool>) -> Value {
+    boolean(truth.map(|truth| !truth))
+}
+
+fn negate(truth: Option<bool>, negated: bool) -> Value {
+    if negated {
+        not(truth)
+    } else {
+        boolean(truth)
+    }
+}
+
+/// Order two values of the same kind; `None` when either is `NULL`.
+pub(crate) fn compare(
+    name: &str,
+    left: &Value,
+    right: &Value,
+) -> Result<Option<Ordering>, FunctionError> {
+    let ordering = match (left, right) {
+        (Value::Null, _) | (_, Value::Null) => return Ok(None),
+        (Value::Integer(left), Value::Integer(right)) => left.cmp(right),
+        (Value::Integer(left), Value::Real(right)) => (*left as f64).total_cmp(right),
+        (Value::Real(left), Value::Integer(right)) => left.total_cmp(&(*right as f64)),
+        (Value::Real(left), Value::Real(right)) => left.total_cmp(right),
+        (Value::Text(left), Value::Text(right)) => left.cmp(right),
+        (Value::Blob(left), Value::Blob(right)) => left.cmp(right),
+        (_, value) => {
+            return Err(FunctionError::InvalidArgument(
+                name.to_string(),
+                value.clone(),
+            ))
+        }
+    };
+    Ok(Some(ordering))
+}
+
+/// Numbers are matched and concatenated as their text; blobs have no text.
+fn as_text(name: &str, value: &Value) -> Result<String, FunctionError> {
+    match value {
+        Value::Blob(_) => Err(FunctionError::InvalidArgument(
+            name.to_string(),
+            value.clone(),
+        )),
+        value => Ok(value.to_string()),
+    }
+}
+
+/// One element of a compiled `LIKE` or `GLOB` pattern.
+#[derive(Debug)]
+enum Wildcard {
+    /// `%` or `*`: any run of characters, including none.
+    Any,
+    /// `_` or `?`: exactly one character.
+    One,
+    Char(char),
+    /// A `GLOB` `[...]` class as inclusive ranges.
+    Class {
+        inverted: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+fn like(text: &[char], pattern: &[char], ignore_case: bool) -> bool {
+    let pattern: Vec<_> = pattern
+        .iter()
+        .map(|ch| match ch {
+            '%' => Wildcard::Any,
+            '_' => Wildcard::One,
+            ch => Wildcard::Char(*ch),
+        })
+        .collect();
+    let same = |a: char, b: char| a == b || (ignore_case && fold(a).eq(fold(b)));
+    wildcard_match(text, &pattern, same)
+}
+
+/// Lowercase a character for `ILIKE`, treating `İ` as a plain `i` like `LOWER` does.
+fn fold(ch: char) -> impl Iterator<Item = char> {
+    let ch = if ch == 'İ' { 'i' } else { ch };
+    ch.to_lowercase()
+}
+
+fn glob(text: &[char], mut pattern: &[char]) -> bool {
+    let mut compiled = vec![];
+    while let Some((ch, rest)) = pattern.split_first() {
+        pattern = rest;
+        compiled.push(match ch {
+            '*' => Wildcard::Any,
+            '?' => Wildcard::One,
+            '[' => match char_class(rest) {
+                Some((class, rest)) => {
+                    pattern = rest;
+                    class
+                }
+                // an unclosed `[` is matched literally
+                None => Wildcard::Char('['),
+            },
+            ch => Wildcard::Char(*ch),
+        });
+    }
+    wildcard_match(text, &compiled, |a, b| a == b)
+}
+
+/// Whether `pattern` matches all of `text`, in O(text × pattern) time at worst.
+///
+/// Only the most recent `Any` can need retrying: whatever an earlier one would skip,
+/// the later one can skip as well.
+fn wildcard_match(text: &[char], pattern: &[Wildcard], same: impl Fn(char, char) -> bool) -> bool {
+    let (mut t, mut p) = (0, 0);
+    // pattern position after the last `Any`, and the text position it resumes from
+    let mut retry = None;
+    while t < text.len() {
+        let matched = match pattern.get(p) {
+            Some(Wildcard::Any) => {
+                p += 1;
+                retry = Some((p, t));
+                continue;
+            }
+            Some(Wildcard::One) => true,
+            Some(Wildcard::Char(ch)) => same(text[t], *ch),
+            Some(Wildcard::Class { inverted, ranges }) => {
+                ranges
+                    .iter()
+                    .any(|(low, high)| (*low..=*high).contains(&text[t]))
+                    != *inverted
+            }
+            None => false,
+        };
+        if matched {
+            t += 1;
+            p += 1;
+        } else if let Some((after_any, skipped)) = retry {
+            // let the last `Any` swallow one more character and try again from there
+            retry = Some((after_any, skipped + 1));
+            p = after_any;
+            t = skipped + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|w| matches!(w, Wildcard::Any))
+}
+
+/// Parse the class after a `[`, such as `a-z]` or `^0-9]`.
+/// Returns the class and the pattern after the closing `]`.
+fn char_class(pattern: &[char]) -> Option<(Wildcard, &[char])> {
+    let (inverted, mut rest) = match pattern.split_first() {
+        Some(('^', rest)) => (true, rest),
+        _ => (false, pattern),
+    };
+    let mut ranges = vec![];
+    loop {
+        match rest {
+            // `]` right after the opening bracket is a member, not the end
+            [']', tail @ ..] if !ranges.is_empty() => {
+                return Some((Wildcard::Class { inverted, ranges }, tail))
+            }
+            [low, '-', high, tail @ ..] if *high != ']' => {
+                ranges.push((*low, *high));
+                rest = tail;
+            }
+            [member, tail @ ..] => {
+                ranges.push((*member, *member));
+                rest = tail;
+            }
+            [] => return None,
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::Arc,
+};
+
+/// BM25 term frequency saturation.
+const K1: f64 = 1.2;
+/// BM25 document length normalisation.
+const B: f64 = 0.75;
+
+type Stemmer = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// Splits text into lowercase terms at anything that is not a Unicode letter or digit.
+#[derive(Clone, Default)]
+pub struct Analyzer {
+    /// Lowercase `I` to `ı` rather than `i`.
+    turkish: bool,
+    stemmer: Option<Stemmer>,
+}
+
+impl fmt::Debug for Analyzer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Analyzer")
+            .field("turkish", &self.turkish)
+            .field("stemmer", &self.stemmer.is_some())
+            .finish()
+    }
+}
+
+impl Analyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The analyzer a `FULLTEXT` index `WITH language` uses: `simple`, `turkish`, or
+    /// `english`, which also folds plurals like `"lists"` and `"groceries"` to their singular.
+    pub fn for_language(language: &str) -> Option<Self> {
+        match language.to_lowercase().as_str() {
+            "simple" => Some(Self::new()),
+            "turkish" => Some(Self::turkish()),
+            "english" => Some(Self::new().with_stemmer(english_singular)),
+            _ => None,
+        }
+    }
+
+    /// An analyzer for Turkish text, where `I`/`ı` and `İ`/`i` are separate letters.
+    pub fn turkish() -> Self {
+        Self {
+            turkish: true,
+            stemmer: None,
+        }
+    }
+
+    /// Reduce each lowercased term with `stemmer`, e.g. `"lists"` to `"list"`.
+    /// Terms stemmed to nothing are dropped.
+    pub fn with_stemmer<F>(mut self, stemmer: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.stemmer = Some(Arc::new(stemmer));
+        self
+    }
+
+    pub fn terms(&self, text: &str) -> Vec<String> {
+        text.split(|ch: char| !ch.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| {
+                let lower = self.lowercase(word);
+                match &self.stemmer {
+                    Some(stemmer) => stemmer(&lower),
+                    None => lower,
+                }
+            })
+            .filter(|term| !term.is_empty())
+            .collect()
+    }
+
+    /// Whether `text` contains every term of `query`.
+    pub fn matches(&self, text: &str, query: &str) -> bool {
+        let terms = self.terms(text);
+        self.terms(query).iter().all(|term| terms.contains(term))
+    }
+
+    /// Like `LOWER`, `İ` becomes a plain `i` rather than `i` with a combining dot.
+    fn lowercase(&self, word: &str) -> String {
+        word.chars()
+            .map(|ch| match ch {
+                'İ' => "i".to_string(),
+                'I' if self.turkish => "ı".to_string(),
+                ch => ch.to_lowercase().to_string(),
+            })
+            .collect()
+    }
+}
+
+/// Term postings of a set of documents, searched with BM25 ranking.
+#[derive(Debug, Clone, Default)]
+pub struct InvertedIndex {
+    analyzer: Analyzer,
+    /// Term to the documents containing it and how often.
+    postings: HashMap<String, BTreeMap<u64, usize>>,
+    /// Number of terms in each document.
+    lengths: BTreeMap<u64, usize>,
+    total_length: usize,
+}
+
+impl InvertedIndex {
+    pub fn new(analyzer: Analyzer) -> Self {
+        Self {
+            analyzer,
+            ..Self::default()
+        }
+    }
+
+    pub fn analyzer(&self) -> &Analyzer {
+        &self.analyzer
+    }
+
+    pub fn len(&self) -> usize {
+        self.lengths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lengths.is_empty()
+    }
+
+    /// Index `text` as document `id`, replacing what was indexed for it before.
+    pub fn insert(&mut self, id: u64, text: &str) {
+        self.remove(id);
+        let terms = self.analyzer.terms(text);
+        for term in &terms {
+            *self
+                .postings
+                .entry(term.clone())
+                .or_default()
+                .entry(id)
+                .or_default() += 1;
+        }
+        self.total_length += terms.len();
+        self.lengths.insert(id, terms.len());
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        let Some(length) = self.lengths.remove(&id) else {
+            return;
+        };
+        self.total_length -= length;
+        self.postings.retain(|_, documents| {
+            documents.remove(&id);
+            !documents.is_empty()
+        });
+    }
+
+    /// At most how many documents contain every term of `query`: the fewest containing
+    /// any one of them. `None` if the query has no terms.
+    pub fn estimate(&self, query: &str) -> Option<usize> {
+        self.analyzer
+            .terms(query)
+            .iter()
+            .map(|term| self.postings.get(term).map_or(0, BTreeMap::len))
+            .min()
+    }
+
+    /// Documents containing every term of `query`, best BM25 score first.
+    pub fn search(&self, query: &str) -> Vec<(u64, f64)> {
+        if self.is_empty() {
+            return vec![];
+        }
+        let mut terms = self.analyzer.terms(query);
+        terms.sort();
+        terms.dedup();
+        let Some(postings) = terms
+            .iter()
+            .map(|term| self.postings.get(term))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return vec![];
+        };
+        let Some((first, rest)) = postings.split_first() else {
+            return vec![];
+        };
+
+        let count = self.len() as f64;
+        let average_length = self.total_length as f64 / count;
+        let mut results: Vec<(u64, f64)> = first
+            .keys()
+            .filter(|id| rest.iter().all(|documents| documents.contains_key(id)))
+            .map(|&id| {
+                let length = self.lengths[&id] as f64;
+                let score = postings
+                    .iter()
+                    .map(|documents| {
+                        let matching = documents.len() as f64;
+                        let idf = (1.0 + (count - matching + 0.5) / (matching + 0.5)).ln();
+                        let frequency = documents[&id] as f64;
+                        idf * frequency * (K1 + 1.0)
+                            / (frequency + K1 * (1.0 - B + B * length / average_length))
+                    })
+                    .sum();
+                (id, score)
+            })
+            .collect();
+        results.sort_by(|(a, a_score), (b, b_score)| b_score.total_cmp(a_score).then(a.cmp(b)));
+        results
+    }
+}
+
+/// `"lists"` to `"list"` and `"groceries"` to `"grocery"`, leaving `"glass"` and `"is"` alone.
+fn english_singular(term: &str) -> String {
+    if let Some(stem) = term.strip_suffix("ies").filter(|stem| stem.len() > 1) {
+        return format!("{}y", stem);
+    }
+    match term.strip_suffix('s') {
+        Some(stem) if stem.len() > 1 && !stem.ends_with('s') => stem.to_string(),
+        _ => term.to_string(),
+    }
+}
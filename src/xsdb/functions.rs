@@ -0,0 +1,337 @@
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+
+use super::{json, value::Value};
+
+#[derive(Debug, PartialEq)]
+pub enum FunctionError {
+    NoSuchFunction(String),
+    WrongArgumentCount(String, usize),
+    InvalidArgument(String, Value),
+    Overflow(String),
+    UnboundParameter(usize),
+    UnboundVariable(String),
+    /// A window function where there are no result rows to compute it over.
+    MisusedWindow(String),
+}
+
+impl fmt::Display for FunctionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FunctionError::NoSuchFunction(name) => write!(f, "no such function '{}'", name),
+            FunctionError::WrongArgumentCount(name, count) => {
+                write!(f, "wrong number of arguments to {}: {}", name, count)
+            }
+            FunctionError::InvalidArgument(name, value) => write!(
+                f,
+                "invalid argument to {}: {} ({})",
+                name,
+                value,
+                value.type_name()
+            ),
+            FunctionError::Overflow(name) => write!(f, "integer overflow in {}", name),
+            FunctionError::UnboundParameter(index) => {
+                write!(f, "no value for parameter ${}", index)
+            }
+            FunctionError::UnboundVariable(name) => {
+                write!(f, "variable @{} was not bound", name)
+            }
+            FunctionError::MisusedWindow(name) => {
+                write!(f, "misuse of window function {}()", name)
+            }
+        }
+    }
+}
+
+/// Number of arguments a function accepts, inclusive.
+#[derive(Debug, Clone, Copy)]
+pub struct Arity {
+    min: usize,
+    max: Option<usize>,
+}
+
+impl Arity {
+    pub fn between(min: usize, max: usize) -> Self {
+        Self {
+            min,
+            max: Some(max),
+        }
+    }
+
+    pub fn at_least(min: usize) -> Self {
+        Self { min, max: None }
+    }
+
+    pub(crate) fn accepts(&self, count: usize) -> bool {
+        count >= self.min && self.max.is_none_or(|max| count <= max)
+    }
+}
+
+impl From<usize> for Arity {
+    fn from(count: usize) -> Self {
+        Self::between(count, count)
+    }
+}
+
+type Callback = Arc<dyn Fn(&[Value]) -> Result<Value, FunctionError> + Send + Sync>;
+
+#[derive(Clone)]
+struct Function {
+    arity: Arity,
+    callback: Callback,
+}
+
+/// Scalar functions callable from SQL, looked up case-insensitively.
+#[derive(Clone)]
+pub struct Functions {
+    functions: HashMap<String, Function>,
+}
+
+impl Default for Functions {
+    fn default() -> Self {
+        let mut functions = Self {
+            functions: HashMap::new(),
+        };
+        functions.register("LENGTH", 1, length);
+        functions.register("UPPER", Arity::between(1, 2), upper);
+        functions.register("LOWER", Arity::between(1, 2), lower);
+        functions.register("SUBSTR", Arity::between(2, 3), substr);
+        functions.register("COALESCE", Arity::at_least(1), coalesce);
+        functions.register("ROUND", Arity::between(1, 2), round);
+        functions.register("ABS", 1, abs);
+        functions.register("NOW", 0, now);
+        functions.register("DATE", Arity::between(0, 1), date);
+        functions.register("JSON", 1, json::json);
+        functions.register("JSON_VALID", 1, json::json_valid);
+        functions.register("JSON_EXTRACT", Arity::at_least(2), json::json_extract);
+        functions.register("JSON_TYPE", Arity::between(1, 2), json::json_type);
+        functions
+    }
+}
+
+impl fmt::Debug for Functions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.functions.keys()).finish()
+    }
+}
+
+impl Functions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a function, replacing any built-in or earlier one with the same name.
+    pub fn register<F>(&mut self, name: &str, arity: impl Into<Arity>, callback: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, FunctionError> + Send + Sync + 'static,
+    {
+        let function = Function {
+            arity: arity.into(),
+            callback: Arc::new(callback),
+        };
+        self.functions.insert(name.to_uppercase(), function);
+    }
+
+    pub fn call(&self, name: &str, args: &[Value]) -> Result<Value, FunctionError> {
+        let name = name.to_uppercase();
+        let function = self
+            .functions
+            .get(&name)
+            .ok_or_else(|| FunctionError::NoSuchFunction(name.clone()))?;
+        if !function.arity.accepts(args.len()) {
+            return Err(FunctionError::WrongArgumentCount(name, args.len()));
+        }
+        (function.callback)(args)
+    }
+}
+
+pub(crate) fn invalid(name: &str, value: &Value) -> FunctionError {
+    FunctionError::InvalidArgument(name.to_string(), value.clone())
+}
+
+fn text_arg<'a>(name: &str, value: &'a Value) -> Result<&'a str, FunctionError> {
+    match value {
+        Value::Text(text) => Ok(text),
+        _ => Err(invalid(name, value)),
+    }
+}
+
+fn integer_arg(name: &str, value: &Value) -> Result<i64, FunctionError> {
+    match value {
+        Value::Integer(integer) => Ok(*integer),
+        _ => Err(invalid(name, value)),
+    }
+}
+
+/// `true` when the optional locale argument asks for Turkish (or Azerbaijani) casing,
+/// where `i`/`İ` and `ı`/`I` are separate letters.
+fn turkish_locale(name: &str, args: &[Value]) -> Result<bool, FunctionError> {
+    match args.get(1) {
+        None => Ok(false),
+        Some(locale) => {
+            let locale = text_arg(name, locale)?.to_lowercase();
+            Ok(["tr", "az"]
+                .iter()
+                .any(|tr| locale == *tr || locale.starts_with(&format!("{}_", tr))))
+        }
+    }
+}
+
+fn length(args: &[Value]) -> Result<Value, FunctionError> {
+    match &args[0] {
+        Value::Null => Ok(Value::Null),
+        Value::Text(text) => Ok(Value::Integer(text.chars().count() as i64)),
+        Value::Blob(blob) => Ok(Value::Integer(blob.len() as i64)),
+        number => Ok(Value::Integer(number.to_string().chars().count() as i64)),
+    }
+}
+
+fn upper(args: &[Value]) -> Result<Value, FunctionError> {
+    if args[0].is_null() {
+        return Ok(Value::Null);
+    }
+    let text = text_arg("UPPER", &args[0])?;
+    let upper = if turkish_locale("UPPER", args)? {
+        text.chars()
+            .map(|ch| match ch {
+                'i' => "İ".to_string(),
+                'ı' => "I".to_string(),
+                ch => ch.to_uppercase().to_string(),
+            })
+            .collect()
+    } else {
+        text.to_uppercase()
+    };
+    Ok(Value::Text(upper))
+}
+
+fn lower(args: &[Value]) -> Result<Value, FunctionError> {
+    if args[0].is_null() {
+        return Ok(Value::Null);
+    }
+    let text = text_arg("LOWER", &args[0])?;
+    let turkish = turkish_locale("LOWER", args)?;
+    let lower = text
+        .chars()
+        .map(|ch| match ch {
+            // without the locale `İ` still lowers to a plain `i`, not `i` + combining dot
+            'İ' => "i".to_string(),
+            'I' if turkish => "ı".to_string(),
+            ch => ch.to_lowercase().to_string(),
+        })
+        .collect();
+    Ok(Value::Text(lower))
+}
+
+/// `SUBSTR(text, start [, length])` with a 1-based `start` counted in characters.
+fn substr(args: &[Value]) -> Result<Value, FunctionError> {
+    if args.iter().any(Value::is_null) {
+        return Ok(Value::Null);
+    }
+    let text = text_arg("SUBSTR", &args[0])?;
+    let start = integer_arg("SUBSTR", &args[1])?;
+    let end = match args.get(2) {
+        Some(length) => {
+            let length = integer_arg("SUBSTR", length)?;
+            if length < 0 {
+                return Err(invalid("SUBSTR", &args[2]));
+            }
+            Some(start.saturating_add(length))
+        }
+        None => None,
+    };
+
+    // positions before the first character still count towards the length
+    let offset = |position: i64| usize::try_from(position.saturating_sub(1)).unwrap_or(0);
+    let skip = offset(start);
+    let take = end.map_or(usize::MAX, |end| offset(end).saturating_sub(skip));
+    Ok(Value::Text(text.chars().skip(skip).take(take).collect()))
+}
+
+fn coalesce(args: &[Value]) -> Result<Value, FunctionError> {
+    Ok(args
+        .iter()
+        .find(|value| !value.is_null())
+        .cloned()
+        .unwrap_or(Value::Null))
+}
+
+/// Largest power of ten an `f64` holds; rounding past it on either side is a no-op or zero.
+const MAX_ROUND_DIGITS: i64 = 308;
+
+/// `ROUND(x [, digits])`, rounding halves away from zero. Integers stay integers.
+fn round(args: &[Value]) -> Result<Value, FunctionError> {
+    if args.iter().any(Value::is_null) {
+        return Ok(Value::Null);
+    }
+    let digits = match args.get(1) {
+        Some(digits) => integer_arg("ROUND", digits)?,
+        None => 0,
+    };
+    let digits = digits.clamp(-MAX_ROUND_DIGITS, MAX_ROUND_DIGITS) as i32;
+    match &args[0] {
+        Value::Integer(integer) if digits >= 0 => Ok(Value::Integer(*integer)),
+        Value::Integer(integer) => {
+            let factor = 10f64.powi(-digits);
+            let rounded = (*integer as f64 / factor).round() * factor;
+            // `i64::MAX as f64` rounds up to 2^63, which is already out of range
+            if rounded >= i64::MAX as f64 || rounded < i64::MIN as f64 {
+                return Err(FunctionError::Overflow("ROUND".to_string()));
+            }
+            Ok(Value::Integer(rounded as i64))
+        }
+        Value::Real(real) if digits >= 0 => {
+            let scaled = real * 10f64.powi(digits);
+            // past 2^53 every f64 is already a whole number, so there is nothing to round
+            if !scaled.is_finite() || scaled.abs() >= 2f64.powi(53) {
+                return Ok(Value::Real(*real));
+            }
+            Ok(Value::Real(scaled.round() / 10f64.powi(digits)))
+        }
+        Value::Real(real) => {
+            let factor = 10f64.powi(-digits);
+            Ok(Value::Real((real / factor).round() * factor))
+        }
+        value => Err(invalid("ROUND", value)),
+    }
+}
+
+fn abs(args: &[Value]) -> Result<Value, FunctionError> {
+    match &args[0] {
+        Value::Null => Ok(Value::Null),
+        Value::Integer(integer) => integer
+            .checked_abs()
+            .map(Value::Integer)
+            .ok_or_else(|| FunctionError::Overflow("ABS".to_string())),
+        Value::Real(real) => Ok(Value::Real(real.abs())),
+        value => Err(invalid("ABS", value)),
+    }
+}
+
+/// Current UTC time as `YYYY-MM-DD HH:MM:SS` text.
+fn now(_args: &[Value]) -> Result<Value, FunctionError> {
+    Ok(Value::Text(
+        Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    ))
+}
+
+/// `DATE([text])`: the date part of a date or timestamp, today when called without one.
+fn date(args: &[Value]) -> Result<Value, FunctionError> {
+    let date = match args.first() {
+        None => Utc::now().date_naive(),
+        Some(Value::Null) => return Ok(Value::Null),
+        Some(value) => {
+            let text = text_arg("DATE", value)?.trim();
+            NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                .or_else(|_| {
+                    NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").map(|t| t.date())
+                })
+                .or_else(|_| {
+                    NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S").map(|t| t.date())
+                })
+                .map_err(|_| invalid("DATE", value))?
+        }
+    };
+    Ok(Value::Text(date.format("%Y-%m-%d").to_string()))
+}
@@ -0,0 +1,53 @@
+use super::tokenizer::{Token, TokenKind, Tokenizer};
+
+const RESET: &str = "\x1b[0m";
+
+fn colour(kind: TokenKind) -> Option<&'static str> {
+    if kind.is_keyword() {
+        Some("\x1b[1;34m")
+    } else if kind.is_string() {
+        Some("\x1b[32m")
+    } else if kind.is_number() {
+        Some("\x1b[33m")
+    } else if kind.is_comment() {
+        Some("\x1b[90m")
+    } else if kind.is_operator() {
+        Some("\x1b[35m")
+    } else {
+        None
+    }
+}
+
+/// Wrap keywords, literals, comments and operators of `source` in ANSI colours.
+/// Anything after a lexing error is left as is.
+pub fn highlight(source: &str) -> String {
+    let tokens: Vec<_> = Tokenizer::new(source)
+        .iter()
+        .map_while(Result::ok)
+        .collect();
+    highlight_tokens(source, &tokens)
+}
+
+/// Highlight `source` with tokens already lexed from it; text after the last one is left as is.
+pub(crate) fn highlight_tokens(source: &str, tokens: &[Token<'_>]) -> String {
+    let mut highlighted = String::new();
+    let mut last = 0;
+
+    for token in tokens {
+        let span = token.span();
+        let text = &source[span.start..span.end];
+        highlighted.push_str(&source[last..span.start]);
+        match colour(token.token()) {
+            Some(colour) => {
+                highlighted.push_str(colour);
+                highlighted.push_str(text);
+                highlighted.push_str(RESET);
+            }
+            None => highlighted.push_str(text),
+        }
+        last = span.end;
+    }
+
+    highlighted.push_str(&source[last..]);
+    highlighted
+}
@@ -0,0 +1,239 @@
+use serde_json::Value as Json;
+
+use super::{
+    database::Row,
+    functions::{invalid, FunctionError},
+    value::Value,
+};
+
+/// Columns of the rows [`each`] produces, as named in `SELECT ... FROM json_each(...)`.
+pub const EACH_COLUMNS: [&str; 4] = ["key", "value", "type", "fullkey"];
+
+/// One step of a path such as `$.items[0]."a key"[#-1]`.
+#[derive(Debug, PartialEq)]
+enum Step {
+    Key(String),
+    Index(usize),
+    /// `[#-n]`, counted from the end of the array.
+    FromEnd(usize),
+}
+
+/// Parse JSON stored as text, or a number; `None` for `NULL`.
+fn parse(name: &str, value: &Value) -> Result<Option<Json>, FunctionError> {
+    match value {
+        Value::Null => Ok(None),
+        Value::Text(text) => serde_json::from_str(text)
+            .map(Some)
+            .map_err(|_| invalid(name, value)),
+        Value::Integer(_) | Value::Real(_) => serde_json::from_str(&value.to_string())
+            .map(Some)
+            .map_err(|_| invalid(name, value)),
+        value => Err(invalid(name, value)),
+    }
+}
+
+fn parse_path(name: &str, value: &Value) -> Result<Vec<Step>, FunctionError> {
+    let error = || invalid(name, value);
+    let Value::Text(path) = value else {
+        return Err(error());
+    };
+    let mut rest = path.strip_prefix('$').ok_or_else(error)?;
+
+    let mut steps = vec![];
+    while !rest.is_empty() {
+        if let Some(tail) = rest.strip_prefix(".\"") {
+            let end = tail.find('"').ok_or_else(error)?;
+            steps.push(Step::Key(tail[..end].to_string()));
+            rest = &tail[end + 1..];
+        } else if let Some(tail) = rest.strip_prefix('.') {
+            let end = tail.find(['.', '[']).unwrap_or(tail.len());
+            if end == 0 {
+                return Err(error());
+            }
+            steps.push(Step::Key(tail[..end].to_string()));
+            rest = &tail[end..];
+        } else if let Some(tail) = rest.strip_prefix('[') {
+            let end = tail.find(']').ok_or_else(error)?;
+            let index = &tail[..end];
+            let step = match index.strip_prefix("#-") {
+                Some(offset) => Step::FromEnd(offset.parse().map_err(|_| error())?),
+                None => Step::Index(index.parse().map_err(|_| error())?),
+            };
+            steps.push(step);
+            rest = &tail[end + 1..];
+        } else {
+            return Err(error());
+        }
+    }
+    Ok(steps)
+}
+
+/// The right operand of `->` and `->>`: a path, or an object key or array index on its own.
+fn operator_path(name: &str, value: &Value) -> Result<Vec<Step>, FunctionError> {
+    match value {
+        Value::Text(path) if path.starts_with('$') => parse_path(name, value),
+        Value::Text(key) => Ok(vec![Step::Key(key.clone())]),
+        Value::Integer(index) if *index >= 0 => Ok(vec![Step::Index(*index as usize)]),
+        value => Err(invalid(name, value)),
+    }
+}
+
+fn lookup<'a>(json: &'a Json, steps: &[Step]) -> Option<&'a Json> {
+    steps
+        .iter()
+        .try_fold(json, |json, step| match (step, json) {
+            (Step::Key(key), Json::Object(object)) => object.get(key),
+            (Step::Index(index), Json::Array(array)) => array.get(*index),
+            (Step::FromEnd(offset), Json::Array(array)) => {
+                array.len().checked_sub(*offset).and_then(|i| array.get(i))
+            }
+            _ => None,
+        })
+}
+
+/// The SQL value of a JSON value: booleans become `1`/`0`, arrays and objects stay JSON text.
+pub(crate) fn to_value(json: &Json) -> Value {
+    match json {
+        Json::Null => Value::Null,
+        Json::Bool(boolean) => Value::Integer(*boolean as i64),
+        Json::Number(number) => match number.as_i64() {
+            Some(integer) => Value::Integer(integer),
+            None => Value::Real(number.as_f64().unwrap_or(f64::NAN)),
+        },
+        Json::String(text) => Value::Text(text.clone()),
+        json => Value::Text(json.to_string()),
+    }
+}
+
+fn type_name(json: &Json) -> &'static str {
+    match json {
+        Json::Null => "null",
+        Json::Bool(true) => "true",
+        Json::Bool(false) => "false",
+        Json::Number(number) if number.is_i64() => "integer",
+        Json::Number(_) => "real",
+        Json::String(_) => "text",
+        Json::Array(_) => "array",
+        Json::Object(_) => "object",
+    }
+}
+
+/// `json -> path` as JSON text, or `json ->> path` as an SQL value. `NULL` if nothing is there.
+pub(crate) fn arrow(json: &Value, path: &Value, as_value: bool) -> Result<Value, FunctionError> {
+    let name = if as_value { "->>" } else { "->" };
+    let Some(json) = parse(name, json)? else {
+        return Ok(Value::Null);
+    };
+    if path.is_null() {
+        return Ok(Value::Null);
+    }
+    let found = lookup(&json, &operator_path(name, path)?);
+    Ok(match found {
+        None => Value::Null,
+        Some(found) if as_value => to_value(found),
+        Some(found) => Value::Text(found.to_string()),
+    })
+}
+
+/// `JSON(text)`: the text minified, after checking that it is valid JSON.
+pub(crate) fn json(args: &[Value]) -> Result<Value, FunctionError> {
+    Ok(match parse("JSON", &args[0])? {
+        Some(json) => Value::Text(json.to_string()),
+        None => Value::Null,
+    })
+}
+
+/// Whether a value can be stored in a `JSON` column: `NULL`, a number, or text that
+/// parses as JSON.
+pub(crate) fn storable(value: &Value) -> bool {
+    parse("JSON", value).is_ok()
+}
+
+pub(crate) fn json_valid(args: &[Value]) -> Result<Value, FunctionError> {
+    Ok(match &args[0] {
+        Value::Null => Value::Null,
+        Value::Text(text) => Value::Integer(serde_json::from_str::<Json>(text).is_ok() as i64),
+        _ => Value::Integer(0),
+    })
+}
+
+/// `JSON_EXTRACT(json, path, ...)`: the SQL value at one path, or a JSON array for several.
+pub(crate) fn json_extract(args: &[Value]) -> Result<Value, FunctionError> {
+    let Some(json) = parse("JSON_EXTRACT", &args[0])? else {
+        return Ok(Value::Null);
+    };
+    let paths = &args[1..];
+    if paths.iter().any(Value::is_null) {
+        return Ok(Value::Null);
+    }
+    let mut found = vec![];
+    for path in paths {
+        found.push(lookup(&json, &parse_path("JSON_EXTRACT", path)?));
+    }
+    Ok(match found.as_slice() {
+        [None] => Value::Null,
+        [Some(json)] => to_value(json),
+        found => {
+            let array = found.iter().map(|json| json.cloned().unwrap_or(Json::Null));
+            Value::Text(Json::Array(array.collect()).to_string())
+        }
+    })
+}
+
+/// `JSON_TYPE(json [, path])`: `'object'`, `'integer'`, `'true'` and so on.
+pub(crate) fn json_type(args: &[Value]) -> Result<Value, FunctionError> {
+    let Some(json) = parse("JSON_TYPE", &args[0])? else {
+        return Ok(Value::Null);
+    };
+    let steps = match args.get(1) {
+        Some(Value::Null) => return Ok(Value::Null),
+        Some(path) => parse_path("JSON_TYPE", path)?,
+        None => vec![],
+    };
+    Ok(lookup(&json, &steps).map_or(Value::Null, |json| Value::Text(type_name(json).into())))
+}
+
+/// The rows of `json_each(json [, path])`, one per array element or object member
+/// of the value at `path`, with the columns in [`EACH_COLUMNS`].
+/// A scalar gives a single row without a key.
+pub(crate) fn each(args: &[Value]) -> Result<Vec<Row>, FunctionError> {
+    if !(1..=2).contains(&args.len()) {
+        return Err(FunctionError::WrongArgumentCount(
+            "JSON_EACH".to_string(),
+            args.len(),
+        ));
+    }
+    let Some(json) = parse("JSON_EACH", &args[0])? else {
+        return Ok(vec![]);
+    };
+    let (root, steps) = match args.get(1) {
+        Some(Value::Null) => return Ok(vec![]),
+        Some(path @ Value::Text(text)) => (text.as_str(), parse_path("JSON_EACH", path)?),
+        Some(path) => return Err(invalid("JSON_EACH", path)),
+        None => ("$", vec![]),
+    };
+    let Some(json) = lookup(&json, &steps) else {
+        return Ok(vec![]);
+    };
+
+    let row = |key: Value, fullkey: String, json: &Json| {
+        vec![
+            key,
+            to_value(json),
+            Value::Text(type_name(json).to_string()),
+            Value::Text(fullkey),
+        ]
+    };
+    Ok(match json {
+        Json::Array(array) => array
+            .iter()
+            .enumerate()
+            .map(|(i, json)| row(Value::Integer(i as i64), format!("{}[{}]", root, i), json))
+            .collect(),
+        Json::Object(object) => object
+            .iter()
+            .map(|(key, json)| row(Value::Text(key.clone()), format!("{}.{}", root, key), json))
+            .collect(),
+        json => vec![row(Value::Null, root.to_string(), json)],
+    })
+}
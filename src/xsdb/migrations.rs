@@ -0,0 +1,206 @@
+use std::{fmt, fs, io, path::Path};
+
+use super::{
+    database::{Database, DatabaseError},
+    tokenizer::Tokenizer,
+};
+
+/// The table each database records the versions of its applied migrations in.
+pub const MIGRATIONS_TABLE: &str = "xsdb_migrations";
+
+/// Columns of [`MIGRATIONS_TABLE`] and their types.
+pub const MIGRATIONS_COLUMNS: [(&str, &str); 1] = [("version", "TEXT")];
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Io(io::Error),
+    /// A statement of the migration with this version failed.
+    Failed(String, DatabaseError),
+    /// The migration with this version has no `down.sql`.
+    Irreversible(String),
+    NothingToRevert,
+    /// The applied migrations could not be read from the database.
+    Database(DatabaseError),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Io(err) => write!(f, "{}", err),
+            MigrationError::Failed(version, err) => {
+                write!(f, "migration {} failed: {}", version, err)
+            }
+            MigrationError::Irreversible(version) => {
+                write!(f, "migration {} has no down.sql", version)
+            }
+            MigrationError::NothingToRevert => write!(f, "no migration has been applied"),
+            MigrationError::Database(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<io::Error> for MigrationError {
+    fn from(err: io::Error) -> Self {
+        MigrationError::Io(err)
+    }
+}
+
+/// One `<version>_<name>` directory with its `up.sql` and optional `down.sql`.
+#[derive(Debug, Clone)]
+pub struct Migration {
+    version: String,
+    name: String,
+    up: String,
+    down: Option<String>,
+}
+
+impl Migration {
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(&self, db: &mut Database) -> Result<(), MigrationError> {
+        run_script(db, &self.version, &self.up)?;
+        db.catalog_mut()
+            .record_migration(&self.version)
+            .map_err(|err| self.failed(err.into()))
+    }
+
+    fn revert(&self, db: &mut Database) -> Result<(), MigrationError> {
+        let down = self
+            .down
+            .as_ref()
+            .ok_or_else(|| MigrationError::Irreversible(self.version.clone()))?;
+        run_script(db, &self.version, down)?;
+        db.catalog_mut()
+            .forget_migration(&self.version)
+            .map_err(|err| self.failed(err.into()))
+    }
+
+    fn failed(&self, err: DatabaseError) -> MigrationError {
+        MigrationError::Failed(self.version.clone(), err)
+    }
+}
+
+fn run_script(db: &mut Database, version: &str, script: &str) -> Result<(), MigrationError> {
+    let failed = |err| MigrationError::Failed(version.to_string(), err);
+    let statements = Tokenizer::new(script)
+        .statements()
+        .map_err(|err| failed(err.into()))?;
+    for statement in statements {
+        db.execute(statement, &[]).map_err(failed)?;
+    }
+    Ok(())
+}
+
+/// Run `change` on `db`, keeping what it did only if it succeeds, and then saving it
+/// to the database file.
+fn transaction<F>(db: &mut Database, migration: &Migration, change: F) -> Result<(), MigrationError>
+where
+    F: FnOnce(&mut Database) -> Result<(), MigrationError>,
+{
+    db.atomically(change).map_err(|err| migration.failed(err))?
+}
+
+/// The migrations of a Diesel-style `migrations/` directory, ordered by version.
+///
+/// Applied versions are recorded in the database's [`MIGRATIONS_TABLE`], which is
+/// dumped and restored with the rest, so the same directory can be run again later to
+/// apply only what is new.
+#[derive(Debug, Clone)]
+pub struct Migrations {
+    migrations: Vec<Migration>,
+}
+
+impl Migrations {
+    /// Read every `<version>_<name>/up.sql` (and `down.sql`, if present) under `dir`.
+    pub fn load(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let mut migrations = vec![];
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let Some(directory) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let (version, name) = directory.split_once('_').unwrap_or((directory, ""));
+            let down = match fs::read_to_string(path.join("down.sql")) {
+                Ok(down) => Some(down),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+                Err(err) => return Err(err),
+            };
+            migrations.push(Migration {
+                version: version.to_string(),
+                name: name.to_string(),
+                up: fs::read_to_string(path.join("up.sql"))?,
+                down,
+            });
+        }
+        migrations.sort_by(|a, b| a.version.cmp(&b.version));
+        Ok(Self { migrations })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Migration> {
+        self.migrations.iter()
+    }
+
+    pub fn pending<'a>(
+        &'a self,
+        db: &'a Database,
+    ) -> Result<impl Iterator<Item = &'a Migration>, MigrationError> {
+        let applied = applied(db)?;
+        Ok(self
+            .migrations
+            .iter()
+            .filter(move |m| !applied.contains(&m.version)))
+    }
+
+    /// Apply the pending migrations oldest first, each in its own transaction,
+    /// stopping at the first that fails. Returns the versions applied.
+    pub fn run_pending(&self, db: &mut Database) -> Result<Vec<String>, MigrationError> {
+        let pending: Vec<_> = self.pending(db)?.cloned().collect();
+        let mut applied = vec![];
+        for migration in pending {
+            transaction(db, &migration, |db| migration.apply(db))?;
+            applied.push(migration.version);
+        }
+        Ok(applied)
+    }
+
+    /// Run the `down.sql` of the latest applied migration, returning its version.
+    pub fn revert(&self, db: &mut Database) -> Result<String, MigrationError> {
+        let migration = self.latest_applied(db)?;
+        transaction(db, migration, |db| migration.revert(db))?;
+        Ok(migration.version.clone())
+    }
+
+    /// Revert the latest applied migration and apply it again, in one transaction.
+    pub fn redo(&self, db: &mut Database) -> Result<String, MigrationError> {
+        let migration = self.latest_applied(db)?;
+        transaction(db, migration, |db| {
+            migration.revert(db)?;
+            migration.apply(db)
+        })?;
+        Ok(migration.version.clone())
+    }
+
+    fn latest_applied(&self, db: &Database) -> Result<&Migration, MigrationError> {
+        let applied = applied(db)?;
+        self.migrations
+            .iter()
+            .rev()
+            .find(|m| applied.contains(&m.version))
+            .ok_or(MigrationError::NothingToRevert)
+    }
+}
+
+fn applied(db: &Database) -> Result<Vec<String>, MigrationError> {
+    db.catalog()
+        .applied_migrations()
+        .map_err(|err| MigrationError::Database(err.into()))
+}
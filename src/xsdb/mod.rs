@@ -1,3 +1,28 @@
+pub mod buffer;
+pub mod catalog;
 pub mod cli;
+pub mod completion;
+pub mod database;
+mod de;
+pub mod editor;
+mod executor;
+pub mod expression;
+pub mod fulltext;
+pub mod functions;
+pub mod highlight;
+pub mod json;
+pub mod migrations;
+pub mod mvcc;
+pub mod ordered;
+mod plan;
 pub mod query;
+pub mod select;
+pub mod server;
+pub mod shared;
+pub mod stats;
+pub mod storage;
+#[cfg(test)]
+mod tests;
 pub mod tokenizer;
+pub mod value;
+pub mod window;
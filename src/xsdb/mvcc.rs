@@ -0,0 +1,309 @@
+use std::collections::BTreeMap;
+
+use super::{
+    buffer::PageId,
+    catalog::RowId,
+    database::Row,
+    storage::{Heap, SharedPager, StorageError},
+};
+
+/// Identifies a write transaction; each one commits with a larger id than the last.
+pub type TxnId = u64;
+
+/// When a row version was written: by which transaction, and which of its statements.
+///
+/// Stamps order by transaction, then statement, so a reader at a stamp sees exactly
+/// what was written at or before it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Stamp {
+    txn: TxnId,
+    statement: u64,
+}
+
+impl Stamp {
+    /// The stamp a transaction starts at, before its first statement.
+    pub fn begin(txn: TxnId) -> Self {
+        Self { txn, statement: 0 }
+    }
+
+    pub fn txn(&self) -> TxnId {
+        self.txn
+    }
+
+    /// The stamp of the statement after this one, in the same transaction.
+    pub fn next_statement(self) -> Self {
+        Self {
+            statement: self.statement + 1,
+            ..self
+        }
+    }
+}
+
+/// Where the values of a version are kept.
+#[derive(Debug, Clone)]
+enum Payload {
+    /// In memory, for a store without a heap.
+    Row(Row),
+    /// In the heap, in the insert record starting at this offset.
+    Paged(u64),
+}
+
+/// One value of a row, alive from the statement that wrote it until the one that
+/// replaced or deleted it.
+#[derive(Debug, Clone)]
+struct Version {
+    created: Stamp,
+    deleted: Option<Stamp>,
+    payload: Payload,
+}
+
+impl Version {
+    fn visible(&self, at: Stamp) -> bool {
+        self.created <= at && self.deleted.is_none_or(|deleted| deleted > at)
+    }
+}
+
+/// Every version of the rows of a table, oldest first for each row.
+///
+/// Writes add versions rather than changing them, so snapshots taken earlier keep
+/// reading the rows as they were. The versions are tracked in memory; with a [`Heap`]
+/// their values stay in the pages of the database file and are read back through its
+/// buffer pool.
+#[derive(Debug, Default)]
+pub struct RowStore {
+    versions: BTreeMap<RowId, Vec<Version>>,
+    next_row_id: RowId,
+    heap: Option<Heap>,
+    /// The length of the heap before each statement that wrote to it since the last
+    /// commit, for rolling back.
+    marks: Vec<(Stamp, u64)>,
+}
+
+impl RowStore {
+    /// An empty store keeping its rows in a new heap of `pager`.
+    pub(crate) fn paged(pager: &SharedPager) -> Self {
+        Self {
+            heap: Some(Heap::new(pager)),
+            ..Self::default()
+        }
+    }
+
+    /// The rows saved in `heap`, as versions every statement sees.
+    pub(crate) fn open(heap: Heap, next_row_id: RowId) -> Result<Self, StorageError> {
+        let mut versions = BTreeMap::new();
+        for (id, at) in heap.records()? {
+            match at {
+                Some(at) => {
+                    let version = Version {
+                        created: Stamp::default(),
+                        deleted: None,
+                        payload: Payload::Paged(at),
+                    };
+                    versions.insert(id, vec![version]);
+                }
+                None => {
+                    versions.remove(&id);
+                }
+            }
+        }
+        Ok(Self {
+            versions,
+            next_row_id,
+            heap: Some(heap),
+            marks: vec![],
+        })
+    }
+
+    /// Where the heap starts, how long it is, and the id the next row gets.
+    pub(crate) fn extent(&self) -> (PageId, u64, RowId) {
+        match &self.heap {
+            Some(heap) => (heap.first(), heap.len(), self.next_row_id),
+            None => (0, 0, self.next_row_id),
+        }
+    }
+
+    fn visible(&self, id: RowId, at: Stamp) -> Option<usize> {
+        let versions = self.versions.get(&id)?;
+        versions.iter().rposition(|version| version.visible(at))
+    }
+
+    fn load(&self, version: &Version) -> Result<Row, StorageError> {
+        match (&version.payload, &self.heap) {
+            (Payload::Row(row), _) => Ok(row.clone()),
+            (Payload::Paged(at), Some(heap)) => heap.row(*at),
+            (Payload::Paged(_), None) => Err(StorageError::Corrupt),
+        }
+    }
+
+    /// Keep `row` as a version of row `id` written at `at`.
+    fn store(&mut self, id: RowId, row: Row, at: Stamp) -> Result<Payload, StorageError> {
+        let Some(heap) = &mut self.heap else {
+            return Ok(Payload::Row(row));
+        };
+        if self.marks.last().is_none_or(|(stamp, _)| *stamp < at) {
+            self.marks.push((at, heap.len()));
+        }
+        Ok(Payload::Paged(heap.insert(id, &row)?))
+    }
+
+    /// The rows seen at `at`, in insertion order, with their ids.
+    pub fn entries(&self, at: Stamp) -> Result<Vec<(RowId, Row)>, StorageError> {
+        let mut entries = vec![];
+        for (&id, versions) in &self.versions {
+            if let Some(version) = versions.iter().rev().find(|version| version.visible(at)) {
+                entries.push((id, self.load(version)?));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// How many rows are seen at `at`, without reading them.
+    pub fn count(&self, at: Stamp) -> usize {
+        let rows = self.versions.values();
+        rows.filter(|versions| versions.iter().any(|version| version.visible(at)))
+            .count()
+    }
+
+    pub fn row(&self, id: RowId, at: Stamp) -> Result<Option<Row>, StorageError> {
+        match self.visible(id, at) {
+            Some(position) => Ok(Some(self.load(&self.versions[&id][position])?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn insert(&mut self, row: Row, at: Stamp) -> Result<RowId, StorageError> {
+        let id = self.next_row_id;
+        let version = Version {
+            created: at,
+            deleted: None,
+            payload: self.store(id, row, at)?,
+        };
+        self.next_row_id += 1;
+        self.versions.insert(id, vec![version]);
+        Ok(id)
+    }
+
+    /// Replace the row seen at `at` with a new version, returning `false` if there is none.
+    pub fn update(&mut self, id: RowId, row: Row, at: Stamp) -> Result<bool, StorageError> {
+        let Some(position) = self.visible(id, at) else {
+            return Ok(false);
+        };
+        let version = Version {
+            created: at,
+            deleted: None,
+            payload: self.store(id, row, at)?,
+        };
+        let versions = self.versions.entry(id).or_default();
+        versions[position].deleted = Some(at);
+        versions.push(version);
+        Ok(true)
+    }
+
+    pub fn delete(&mut self, id: RowId, at: Stamp) -> Result<Option<Row>, StorageError> {
+        let Some(position) = self.visible(id, at) else {
+            return Ok(None);
+        };
+        let row = self.load(&self.versions[&id][position])?;
+        if let Some(heap) = &mut self.heap {
+            if self.marks.last().is_none_or(|(stamp, _)| *stamp < at) {
+                self.marks.push((at, heap.len()));
+            }
+            heap.delete(id)?;
+        }
+        self.versions.entry(id).or_default()[position].deleted = Some(at);
+        Ok(Some(row))
+    }
+
+    /// Undo every write made at `from` or later.
+    pub fn rollback(&mut self, from: Stamp) {
+        self.versions.retain(|_, versions| {
+            versions.retain(|version| version.created < from);
+            for version in versions.iter_mut() {
+                if version.deleted.is_some_and(|deleted| deleted >= from) {
+                    version.deleted = None;
+                }
+            }
+            !versions.is_empty()
+        });
+        let undone = self.marks.iter().position(|(stamp, _)| *stamp >= from);
+        if let (Some(undone), Some(heap)) = (undone, &mut self.heap) {
+            heap.truncate(self.marks[undone].1);
+            self.marks.truncate(undone);
+        }
+    }
+
+    /// The number of versions kept, of every row.
+    pub fn version_count(&self) -> usize {
+        self.versions.values().map(Vec::len).sum()
+    }
+
+    /// Forget the versions deleted at or before `horizon`, which no reader sees any
+    /// more. Their records stay in the heap until it is rewritten.
+    pub(crate) fn prune(&mut self, horizon: Stamp) {
+        self.versions.retain(|_, versions| {
+            versions.retain(|version| version.deleted.is_none_or(|deleted| deleted > horizon));
+            !versions.is_empty()
+        });
+    }
+
+    /// Forget how to roll back the heap, once everything in it is saved.
+    pub(crate) fn committed(&mut self) {
+        self.marks.clear();
+    }
+
+    /// A store holding only the rows seen at `at`, each passed through `change`, as
+    /// versions written then under the same ids. It keeps them in a heap of `pager`
+    /// if given, else in memory.
+    pub(crate) fn copy(
+        &self,
+        at: Stamp,
+        pager: Option<&SharedPager>,
+        mut change: impl FnMut(&mut Row),
+    ) -> Result<Self, StorageError> {
+        let mut copy = Self {
+            next_row_id: self.next_row_id,
+            heap: pager.map(Heap::new),
+            ..Self::default()
+        };
+        for (id, mut row) in self.entries(at)? {
+            change(&mut row);
+            let version = Version {
+                created: at,
+                deleted: None,
+                payload: copy.store(id, row, at)?,
+            };
+            copy.versions.insert(id, vec![version]);
+        }
+        Ok(copy)
+    }
+
+    /// Move the versions to a new heap of their own, freeing the old heap's pages once
+    /// that is committed. Versions [pruned](Self::prune) at `horizon` are left out.
+    pub(crate) fn rewrite(&mut self, horizon: Stamp) -> Result<(), StorageError> {
+        self.prune(horizon);
+        let Some(old) = &self.heap else {
+            return Ok(());
+        };
+        let mut heap = Heap::new(old.pager());
+        let mut moved = BTreeMap::new();
+        for (&id, versions) in &self.versions {
+            let mut versions = versions.clone();
+            for version in &mut versions {
+                if let Payload::Paged(at) = version.payload {
+                    version.payload = Payload::Paged(heap.insert(id, &old.row(at)?)?);
+                }
+            }
+            if versions
+                .last()
+                .is_some_and(|version| version.deleted.is_some())
+            {
+                heap.delete(id)?;
+            }
+            moved.insert(id, versions);
+        }
+        self.versions = moved;
+        self.heap = Some(heap);
+        self.marks.clear();
+        Ok(())
+    }
+}
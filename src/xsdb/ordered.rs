@@ -0,0 +1,136 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap},
+};
+
+use super::{executor::sort_order, value::Value};
+
+/// The values of the indexed columns of a row, ordered like `ORDER BY` orders them.
+#[derive(Debug, Clone)]
+struct Key(Vec<Value>);
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let orderings = self.0.iter().zip(&other.0);
+        orderings
+            .map(|(left, right)| sort_order(left, right))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| self.0.len().cmp(&other.0.len()))
+    }
+}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Key {}
+
+/// Which of the ranges of [`OrderedIndex`] a first value falls in; values of different
+/// kinds do not compare.
+fn kind(value: &Value) -> usize {
+    match value {
+        Value::Null => 0,
+        Value::Integer(_) | Value::Real(_) => 1,
+        Value::Text(_) => 2,
+        Value::Blob(_) => 3,
+    }
+}
+
+/// Rows in the order of the values of their indexed columns, for range scans over the
+/// first of them.
+#[derive(Debug, Clone, Default)]
+pub struct OrderedIndex {
+    /// Row ids by key, one map for each kind of first value.
+    ranges: [BTreeMap<Key, BTreeSet<u64>>; 4],
+    /// The key each row is indexed under.
+    keys: HashMap<u64, Key>,
+}
+
+impl OrderedIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Index row `id` under `values`, replacing what was indexed for it before.
+    pub fn insert(&mut self, id: u64, values: Vec<Value>) {
+        self.remove(id);
+        let key = Key(values);
+        let range = key.0.first().map_or(0, kind);
+        self.ranges[range]
+            .entry(key.clone())
+            .or_default()
+            .insert(id);
+        self.keys.insert(id, key);
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        let Some(key) = self.keys.remove(&id) else {
+            return;
+        };
+        let range = &mut self.ranges[key.0.first().map_or(0, kind)];
+        if let Some(ids) = range.get_mut(&key) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                range.remove(&key);
+            }
+        }
+    }
+
+    /// Rows whose first value may be `BETWEEN low AND high`: the ones from `low` to
+    /// `high`, and the ones the bounds cannot be compared with, which the condition has
+    /// to reject itself. Nothing if the bounds are of different kinds or `NULL`.
+    pub fn between(&self, low: &Value, high: &Value) -> Vec<u64> {
+        let range = kind(low);
+        if range == 0 || range != kind(high) {
+            return vec![];
+        }
+        let start = Key(vec![low.clone()]);
+        let found = self.ranges[range]
+            .range(start..)
+            .take_while(|(key, _)| sort_order(&key.0[0], high).is_le());
+        self.with_others(range, found)
+    }
+
+    /// Rows whose first value may be `LIKE 'prefix%'`: text starting with `prefix`, and
+    /// numbers and blobs, which `LIKE` reads as their text or rejects.
+    pub fn prefixed(&self, prefix: &str) -> Vec<u64> {
+        let start = Key(vec![Value::Text(prefix.to_string())]);
+        let found = self.ranges[2].range(start..).take_while(
+            |(key, _)| matches!(&key.0[0], Value::Text(text) if text.starts_with(prefix)),
+        );
+        self.with_others(2, found)
+    }
+
+    /// The rows of `found` and of every range but `range` and the `NULL`s, in id order.
+    fn with_others<'a>(
+        &'a self,
+        range: usize,
+        found: impl Iterator<Item = (&'a Key, &'a BTreeSet<u64>)>,
+    ) -> Vec<u64> {
+        let others = (1..self.ranges.len())
+            .filter(|&other| other != range)
+            .flat_map(|other| self.ranges[other].iter());
+        let mut ids: Vec<_> = found
+            .chain(others)
+            .flat_map(|(_, ids)| ids.iter().copied())
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+}
@@ -0,0 +1,426 @@
+use super::{
+    catalog::RowId,
+    database::{DatabaseError, Row},
+    expression::{truth, BinaryOperator, ColumnName, Expression, PatternKind},
+    functions::Functions,
+    stats::{ColumnStats, DEFAULT_EQ_SELECTIVITY, DEFAULT_SELECTIVITY},
+    value::Value,
+};
+
+/// One `FROM` item, read in full before the join.
+pub(crate) struct Input {
+    /// How `EXPLAIN` names it.
+    pub(crate) label: String,
+    /// Its rows, each with its position in the order the item gives them. A table's are
+    /// only read once the plan knows whether an index finds them.
+    pub(crate) rows: Vec<(usize, Row)>,
+    /// Where its columns start in a joined row.
+    pub(crate) offset: usize,
+    /// Statistics of each of its columns, where `ANALYZE` has collected them.
+    pub(crate) stats: Vec<Option<ColumnStats>>,
+    /// How many rows it is expected to give.
+    pub(crate) estimate: f64,
+    pub(crate) left: bool,
+    /// The index its rows are found through, if any.
+    pub(crate) index: Option<IndexScan>,
+}
+
+/// Reading a table through an index rather than in full.
+pub(crate) struct IndexScan {
+    /// How `EXPLAIN` names the index.
+    pub(crate) name: String,
+    /// The rows the index finds, in id order, which is the order of the table.
+    pub(crate) ids: Vec<RowId>,
+}
+
+/// A condition of `ON` or `WHERE`, checked as soon as the items it reads are joined.
+pub(crate) struct Condition {
+    pub(crate) expression: Expression,
+    /// The items whose columns it reads.
+    pub(crate) inputs: Vec<usize>,
+    /// The fraction of rows it is expected to keep.
+    pub(crate) selectivity: f64,
+    /// The item whose `ON` clause it comes from; `None` for `WHERE`.
+    pub(crate) on: Option<usize>,
+}
+
+impl Condition {
+    pub(crate) fn new(expression: Expression, on: Option<usize>, inputs: &[Input]) -> Self {
+        let mut fields = vec![];
+        expression.fields(&mut fields);
+        let mut read: Vec<_> = fields.iter().map(|&field| owner(inputs, field)).collect();
+        read.sort_unstable();
+        read.dedup();
+        Self {
+            selectivity: selectivity(inputs, &expression),
+            expression,
+            inputs: read,
+            on,
+        }
+    }
+}
+
+/// Reading one item into the join.
+struct Step {
+    input: usize,
+    /// Rows expected once the item is joined and `conditions` checked.
+    rows: f64,
+    conditions: Vec<usize>,
+}
+
+/// The order to join the `FROM` items in.
+///
+/// Without `LEFT JOIN` the items are joined smallest estimate first, preferring one that
+/// a condition connects to those already joined, and the `WHERE` conditions are checked
+/// along the way. A `LEFT JOIN` keeps the order written and leaves `WHERE` to the caller.
+/// Either way the rows come out in the order nested loops over the items as written
+/// would give them.
+pub(crate) struct Plan {
+    pub(crate) columns: Vec<ColumnName>,
+    inputs: Vec<Input>,
+    conditions: Vec<Condition>,
+    steps: Vec<Step>,
+}
+
+impl Plan {
+    pub(crate) fn new(
+        columns: Vec<ColumnName>,
+        inputs: Vec<Input>,
+        conditions: Vec<Condition>,
+    ) -> Self {
+        let mut plan = Self {
+            columns,
+            inputs,
+            conditions,
+            steps: vec![],
+        };
+        plan.steps = if plan.checks_where() {
+            plan.cheapest_order()
+        } else {
+            plan.written_order()
+        };
+        plan
+    }
+
+    /// Whether the rows the plan gives have been checked against `WHERE`.
+    pub(crate) fn checks_where(&self) -> bool {
+        !self.inputs.is_empty() && !self.inputs.iter().any(|input| input.left)
+    }
+
+    fn written_order(&self) -> Vec<Step> {
+        let mut rows = 1.0;
+        let mut steps = vec![];
+        for (position, input) in self.inputs.iter().enumerate() {
+            let conditions: Vec<_> = (0..self.conditions.len())
+                .filter(|&condition| self.conditions[condition].on == Some(position))
+                .collect();
+            let joined = conditions
+                .iter()
+                .fold(rows * input.estimate, |joined, &condition| {
+                    joined * self.conditions[condition].selectivity
+                });
+            rows = if input.left { joined.max(rows) } else { joined };
+            steps.push(Step {
+                input: position,
+                rows,
+                conditions,
+            });
+        }
+        steps
+    }
+
+    fn cheapest_order(&self) -> Vec<Step> {
+        let mut joined = vec![false; self.inputs.len()];
+        let mut checked = vec![false; self.conditions.len()];
+        let mut rows = 1.0;
+        let mut steps: Vec<Step> = vec![];
+        while steps.len() < self.inputs.len() {
+            let mut best: Option<(bool, Step)> = None;
+            for candidate in (0..self.inputs.len()).filter(|&input| !joined[input]) {
+                let conditions: Vec<_> = (0..self.conditions.len())
+                    .filter(|&condition| {
+                        !checked[condition]
+                            && self.conditions[condition]
+                                .inputs
+                                .iter()
+                                .all(|&input| joined[input] || input == candidate)
+                    })
+                    .collect();
+                let connected = conditions.iter().any(|&condition| {
+                    self.conditions[condition]
+                        .inputs
+                        .iter()
+                        .any(|&input| joined[input])
+                });
+                let estimate = conditions.iter().fold(
+                    rows * self.inputs[candidate].estimate,
+                    |estimate, &condition| estimate * self.conditions[condition].selectivity,
+                );
+                // a cross product only comes first when nothing connects
+                let cross = !steps.is_empty() && !connected;
+                let better = match &best {
+                    Some((best_cross, best)) => (cross, estimate) < (*best_cross, best.rows),
+                    None => true,
+                };
+                if better {
+                    let step = Step {
+                        input: candidate,
+                        rows: estimate,
+                        conditions,
+                    };
+                    best = Some((cross, step));
+                }
+            }
+            let Some((_, step)) = best else {
+                break;
+            };
+            joined[step.input] = true;
+            for &condition in &step.conditions {
+                checked[condition] = true;
+            }
+            rows = step.rows;
+            steps.push(step);
+        }
+        steps
+    }
+
+    /// The joined rows; a single row without columns if there are no items.
+    pub(crate) fn run(&self, functions: &Functions) -> Result<Vec<Row>, DatabaseError> {
+        let width = self.columns.len();
+        let mut joined = vec![(vec![0; self.inputs.len()], vec![Value::Null; width])];
+        for step in &self.steps {
+            let input = &self.inputs[step.input];
+            let mut rows = vec![];
+            for (positions, left) in &joined {
+                let mut matched = false;
+                for (position, right) in &input.rows {
+                    let mut row = left.clone();
+                    row[input.offset..input.offset + right.len()].clone_from_slice(right);
+                    if !self.check(step, functions, &row)? {
+                        continue;
+                    }
+                    matched = true;
+                    let mut positions = positions.clone();
+                    positions[step.input] = *position;
+                    rows.push((positions, row));
+                }
+                // a left join keeps unmatched rows, with NULL for every right column
+                if !matched && input.left {
+                    rows.push((positions.clone(), left.clone()));
+                }
+            }
+            joined = rows;
+        }
+        joined.sort_by(|(left, _), (right, _)| left.cmp(right));
+        Ok(joined.into_iter().map(|(_, row)| row).collect())
+    }
+
+    fn check(
+        &self,
+        step: &Step,
+        functions: &Functions,
+        row: &[Value],
+    ) -> Result<bool, DatabaseError> {
+        for &condition in &step.conditions {
+            let value = self.conditions[condition]
+                .expression
+                .eval_row(functions, row)?;
+            if truth(&value) != Some(true) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// One row per step of the plan: how the item is read and the rows expected after it,
+    /// then the rows expected to pass `WHERE` if it is checked afterwards.
+    pub(crate) fn explain(&self) -> Vec<Row> {
+        let mut rows = vec![];
+        for (position, step) in self.steps.iter().enumerate() {
+            let input = &self.inputs[step.input];
+            let read = match &input.index {
+                Some(index) => format!("SEARCH {} USING {}", input.label, index.name),
+                None => format!("SCAN {}", input.label),
+            };
+            let read = match (position, input.left) {
+                (0, _) => read,
+                (_, true) => format!("LEFT JOIN {read}"),
+                (_, false) => format!("JOIN {read}"),
+            };
+            rows.push(vec![Value::Text(read), estimate(step.rows)]);
+        }
+        let filter: Vec<_> = self
+            .conditions
+            .iter()
+            .filter(|condition| condition.on.is_none())
+            .collect();
+        if !self.checks_where() && !filter.is_empty() {
+            let joined = self.steps.last().map_or(1.0, |step| step.rows);
+            let rows_left = filter
+                .iter()
+                .fold(joined, |rows, condition| rows * condition.selectivity);
+            rows.push(vec![Value::Text("FILTER".to_string()), estimate(rows_left)]);
+        }
+        rows
+    }
+}
+
+fn estimate(rows: f64) -> Value {
+    Value::Integer(rows.round() as i64)
+}
+
+/// The item a field of the joined row belongs to.
+fn owner(inputs: &[Input], field: usize) -> usize {
+    inputs
+        .iter()
+        .rposition(|input| input.offset <= field)
+        .unwrap_or(0)
+}
+
+/// The item a field belongs to and the statistics of its column, if collected.
+fn column(inputs: &[Input], field: usize) -> (usize, Option<&ColumnStats>) {
+    let input = owner(inputs, field);
+    let stats = inputs
+        .get(input)
+        .and_then(|owner| owner.stats.get(field - owner.offset))
+        .and_then(Option::as_ref);
+    (input, stats)
+}
+
+/// The fraction of rows `expression` is expected to keep, from the statistics of the
+/// columns it compares where there are some.
+fn selectivity(inputs: &[Input], expression: &Expression) -> f64 {
+    match expression {
+        Expression::Binary {
+            operator: BinaryOperator::And,
+            left,
+            right,
+        } => selectivity(inputs, left) * selectivity(inputs, right),
+        Expression::Binary {
+            operator: BinaryOperator::Or,
+            left,
+            right,
+        } => {
+            let (left, right) = (selectivity(inputs, left), selectivity(inputs, right));
+            left + right - left * right
+        }
+        Expression::Binary {
+            operator,
+            left,
+            right,
+        } => match (left.as_ref(), right.as_ref()) {
+            (Expression::Field(left), Expression::Field(right))
+                if *operator == BinaryOperator::Eq =>
+            {
+                let (left, left_stats) = column(inputs, *left);
+                let (right, right_stats) = column(inputs, *right);
+                if left == right {
+                    return DEFAULT_EQ_SELECTIVITY;
+                }
+                // every value of the side with fewer is taken to be among the other's
+                let distinct = |input: usize, stats: Option<&ColumnStats>| {
+                    stats.map_or(inputs[input].estimate as u64, |stats| stats.distinct)
+                };
+                let distinct = distinct(left, left_stats).max(distinct(right, right_stats));
+                1.0 / distinct.max(1) as f64
+            }
+            (Expression::Field(field), Expression::Literal(value)) => {
+                compared(column(inputs, *field).1, *operator, value)
+            }
+            (Expression::Literal(value), Expression::Field(field)) => match mirror(*operator) {
+                Some(operator) => compared(column(inputs, *field).1, operator, value),
+                None => DEFAULT_SELECTIVITY,
+            },
+            _ => default_selectivity(*operator),
+        },
+        Expression::Not(expression) => 1.0 - selectivity(inputs, expression),
+        Expression::IsNull {
+            expression,
+            negated,
+        } => {
+            let nulls = match expression.as_ref() {
+                Expression::Field(field) => match column(inputs, *field).1 {
+                    Some(stats) if stats.rows > 0 => stats.nulls as f64 / stats.rows as f64,
+                    _ => DEFAULT_EQ_SELECTIVITY,
+                },
+                _ => DEFAULT_EQ_SELECTIVITY,
+            };
+            if *negated {
+                1.0 - nulls
+            } else {
+                nulls
+            }
+        }
+        Expression::Between {
+            expression,
+            low,
+            high,
+            negated,
+        } => {
+            let kept = match (expression.as_ref(), low.as_ref(), high.as_ref()) {
+                (Expression::Field(field), Expression::Literal(low), Expression::Literal(high)) => {
+                    column(inputs, *field)
+                        .1
+                        .map_or(DEFAULT_SELECTIVITY, |stats| stats.between(low, high))
+                }
+                _ => DEFAULT_SELECTIVITY,
+            };
+            if *negated {
+                1.0 - kept
+            } else {
+                kept
+            }
+        }
+        Expression::Like {
+            expression,
+            pattern,
+            kind: PatternKind::Like,
+            negated: false,
+        } => match (expression.as_ref(), pattern.as_ref()) {
+            (Expression::Field(field), Expression::Literal(Value::Text(pattern))) => {
+                match (column(inputs, *field).1, like_prefix(pattern)) {
+                    (Some(stats), Some(prefix)) => stats.prefixed(&prefix),
+                    _ => DEFAULT_SELECTIVITY,
+                }
+            }
+            _ => DEFAULT_SELECTIVITY,
+        },
+        _ => DEFAULT_SELECTIVITY,
+    }
+}
+
+/// The text before the first wildcard of a `LIKE` pattern, unless it starts with one.
+pub(crate) fn like_prefix(pattern: &str) -> Option<String> {
+    let prefix: String = pattern
+        .chars()
+        .take_while(|ch| !matches!(ch, '%' | '_'))
+        .collect();
+    (!prefix.is_empty()).then_some(prefix)
+}
+
+/// `column operator value`, from the column's statistics if there are some.
+fn compared(stats: Option<&ColumnStats>, operator: BinaryOperator, value: &Value) -> f64 {
+    match stats {
+        Some(stats) => stats.selectivity(operator, value),
+        None => default_selectivity(operator),
+    }
+}
+
+fn default_selectivity(operator: BinaryOperator) -> f64 {
+    match operator {
+        BinaryOperator::Eq => DEFAULT_EQ_SELECTIVITY,
+        _ => DEFAULT_SELECTIVITY,
+    }
+}
+
+/// The operator that compares the same way with its sides swapped.
+fn mirror(operator: BinaryOperator) -> Option<BinaryOperator> {
+    match operator {
+        BinaryOperator::Eq | BinaryOperator::NotEq => Some(operator),
+        BinaryOperator::Lt => Some(BinaryOperator::Gt),
+        BinaryOperator::Lte => Some(BinaryOperator::Gte),
+        BinaryOperator::Gt => Some(BinaryOperator::Lt),
+        BinaryOperator::Gte => Some(BinaryOperator::Lte),
+        _ => None,
+    }
+}
@@ -1,15 +1,57 @@
 use std::{iter::Peekable, slice::Iter};
 
-use super::tokenizer::{Token, TokenKind};
+use super::tokenizer::{ParseError, Token, TokenKind, TokenValue};
 
-#[derive(Debug)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BodyKind {
     CreateTable,
+    AlterTable,
+    DropTable,
+    CreateDatabase,
+    DropDatabase,
+    UseDatabase,
+    Vacuum,
+    Analyze,
+    Explain,
+    SetVariable,
+    CreateView,
+    DropView,
+    CreateTrigger,
+    DropTrigger,
+    CreateFulltextIndex,
+    CreateIndex,
+    CreateFunction,
+    DropIndex,
+    Insert,
+    Update,
+    Delete,
+    Select,
+    SelectDistinct,
+    Union,
+    UnionAll,
+    Intersect,
+    Except,
+    CommonTable,
+    RecursiveCommonTable,
+    Before,
+    After,
+    Table,
+    Block,
     Field,
+    Values,
+    Where,
+    Language,
+    Type,
+    Default,
+    AddColumn,
+    DropColumn,
+    RenameColumn,
+    RenameTable,
+    Expression,
+    Unknown,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum WhenKind {
     None,
     IfExists,
@@ -17,81 +59,1051 @@ pub enum WhenKind {
 }
 
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct Query {
     body: BodyKind,
     when: WhenKind,
     value: Option<String>,
-    subqueries: Vec<Box<Query>>,
+    subqueries: Vec<Query>,
 }
 
 impl Query {
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new(tokens: Vec<Token>) -> Result<Self, ParseError> {
+        let tokens = tokens
+            .into_iter()
+            .filter(|t| !t.token().is_comment())
+            .collect::<Vec<_>>();
         let mut token_iter = tokens.iter().peekable();
-        let base_token = token_iter.next().unwrap();
-
-        match base_token.token() {
-            TokenKind::CREATE => {
-                let created_keyword = token_iter.next().unwrap();
-
-                match created_keyword.token() {
-                    TokenKind::TABLE => {
-                        let when_kind = peek_when_kind(&mut token_iter);
-                        let value = token_iter.next().unwrap().text();
-                        let remaining_tokens = token_iter.cloned().collect::<Vec<_>>();
-                        let subquery = Query::new(remaining_tokens);
-                        return Self {
-                            body: BodyKind::CreateTable,
-                            when: when_kind,
-                            value: Some(value.to_string()),
-                            subqueries: vec![Box::new(subquery)],
-                        };
-                    }
-                    _ => (),
+        let base_token = next_token(&mut token_iter)?;
+
+        if base_token.token() == TokenKind::CREATE {
+            let created_keyword = next_token(&mut token_iter)?;
+
+            if created_keyword.token() == TokenKind::DATABASE {
+                return Self::named(BodyKind::CreateDatabase, &mut token_iter);
+            }
+
+            if created_keyword.token() == TokenKind::TRIGGER {
+                return Self::trigger(&mut token_iter);
+            }
+
+            if created_keyword.token() == TokenKind::VIEW {
+                let when_kind = peek_when_kind(&mut token_iter);
+                let value = identifier_name(next_token(&mut token_iter)?)?;
+                if next_token(&mut token_iter)?.token() != TokenKind::AS {
+                    return Err(ParseError::UnexpectedToken);
                 }
+                let select = Self::select(&mut token_iter)?;
+                return Ok(Self {
+                    body: BodyKind::CreateView,
+                    when: when_kind,
+                    value: Some(value),
+                    subqueries: vec![select],
+                });
             }
-            _ => (),
+
+            // `CREATE [OR REPLACE] FUNCTION name ... LANGUAGE language`, kept only so that
+            // the catalog can name the language it does not run
+            let replace = created_keyword.token() == TokenKind::OR
+                && token_iter
+                    .next_if(|t| t.text().eq_ignore_ascii_case("REPLACE"))
+                    .is_some();
+            let function = if replace {
+                next_token(&mut token_iter)?
+            } else {
+                created_keyword
+            };
+            if function.text().eq_ignore_ascii_case("FUNCTION") {
+                return Self::function(&mut token_iter);
+            }
+
+            if created_keyword.token() == TokenKind::FULLTEXT {
+                if next_token(&mut token_iter)?.token() != TokenKind::INDEX {
+                    return Err(ParseError::UnexpectedToken);
+                }
+                return Self::index(BodyKind::CreateFulltextIndex, &mut token_iter);
+            }
+
+            if created_keyword.token() == TokenKind::INDEX {
+                return Self::index(BodyKind::CreateIndex, &mut token_iter);
+            }
+
+            if created_keyword.token() == TokenKind::TABLE {
+                let when_kind = peek_when_kind(&mut token_iter);
+                let value = identifier_name(next_token(&mut token_iter)?)?;
+                let fields = parse_fields(&mut token_iter)?;
+                expect_end(&mut token_iter)?;
+                return Ok(Self {
+                    body: BodyKind::CreateTable,
+                    when: when_kind,
+                    value: Some(value),
+                    subqueries: fields,
+                });
+            }
+        }
+
+        if base_token.token() == TokenKind::DROP {
+            match next_token(&mut token_iter)?.token() {
+                TokenKind::TABLE => return Self::named(BodyKind::DropTable, &mut token_iter),
+                TokenKind::DATABASE => return Self::named(BodyKind::DropDatabase, &mut token_iter),
+                TokenKind::VIEW => return Self::named(BodyKind::DropView, &mut token_iter),
+                TokenKind::TRIGGER => return Self::named(BodyKind::DropTrigger, &mut token_iter),
+                TokenKind::INDEX => return Self::named(BodyKind::DropIndex, &mut token_iter),
+                _ => return Err(ParseError::UnexpectedToken),
+            }
+        }
+
+        if matches!(base_token.token(), TokenKind::SELECT | TokenKind::WITH) {
+            let mut select_iter = tokens.iter().peekable();
+            return Self::select(&mut select_iter);
+        }
+
+        if base_token.token() == TokenKind::EXPLAIN {
+            let select = Self::select(&mut token_iter)?;
+            return Ok(Self {
+                body: BodyKind::Explain,
+                when: WhenKind::None,
+                value: None,
+                subqueries: vec![select],
+            });
+        }
+
+        if base_token.token() == TokenKind::INSERT {
+            return Self::insert(&mut token_iter);
+        }
+
+        if base_token.token() == TokenKind::UPDATE {
+            return Self::update(&mut token_iter);
+        }
+
+        if base_token.token() == TokenKind::DELETE {
+            return Self::delete(&mut token_iter);
         }
 
-        return Self {
-            body: BodyKind::CreateTable,
+        if base_token.token() == TokenKind::USE {
+            return Self::named(BodyKind::UseDatabase, &mut token_iter);
+        }
+
+        // `VACUUM [database]` and `ANALYZE [table]`
+        if matches!(base_token.token(), TokenKind::VACUUM | TokenKind::ANALYZE) {
+            let value = match token_iter.peek().map(|t| t.token()) {
+                Some(TokenKind::SemiColon | TokenKind::EOF) | None => None,
+                _ => Some(identifier_name(next_token(&mut token_iter)?)?),
+            };
+            expect_end(&mut token_iter)?;
+            let body = if base_token.token() == TokenKind::VACUUM {
+                BodyKind::Vacuum
+            } else {
+                BodyKind::Analyze
+            };
+            return Ok(Self {
+                body,
+                when: WhenKind::None,
+                value,
+                subqueries: vec![],
+            });
+        }
+
+        // `SET @name {:= | =} value`, `@name := value` or `name := value`
+        if base_token.token() == TokenKind::SET {
+            let variable = next_token(&mut token_iter)?;
+            if variable.token() != TokenKind::Variable {
+                return Err(ParseError::UnexpectedToken);
+            }
+            return match next_token(&mut token_iter)?.token() {
+                TokenKind::Eq | TokenKind::DuckAssignment => {
+                    Self::assignment(variable, &mut token_iter)
+                }
+                _ => Err(ParseError::UnexpectedToken),
+            };
+        }
+        if matches!(
+            base_token.token(),
+            TokenKind::Variable | TokenKind::Identifier
+        ) && token_iter
+            .next_if(|t| t.token() == TokenKind::DuckAssignment)
+            .is_some()
+        {
+            return Self::assignment(base_token, &mut token_iter);
+        }
+
+        if base_token.token() == TokenKind::ALTER {
+            if next_token(&mut token_iter)?.token() != TokenKind::TABLE {
+                return Err(ParseError::UnexpectedToken);
+            }
+            let value = identifier_name(next_token(&mut token_iter)?)?;
+            let mut actions = vec![];
+            loop {
+                actions.push(parse_alter_action(&mut token_iter)?);
+                if token_iter
+                    .next_if(|t| t.token() == TokenKind::Comma)
+                    .is_none()
+                {
+                    break;
+                }
+            }
+            expect_end(&mut token_iter)?;
+            return Ok(Self {
+                body: BodyKind::AlterTable,
+                when: WhenKind::None,
+                value: Some(value),
+                subqueries: actions,
+            });
+        }
+
+        Ok(Self {
+            body: BodyKind::Unknown,
             when: WhenKind::None,
             value: None,
             subqueries: vec![],
+        })
+    }
+
+    /// The value assigned to `variable`, kept as an `Expression` subquery holding its source.
+    fn assignment(
+        variable: &Token,
+        token_iter: &mut Peekable<Iter<Token>>,
+    ) -> Result<Self, ParseError> {
+        let name = match (variable.token(), variable.value()) {
+            (TokenKind::Variable, Some(TokenValue::String(name))) => name.clone(),
+            _ => variable.text().to_string(),
+        };
+        let mut tokens = vec![];
+        while let Some(token) =
+            token_iter.next_if(|t| !matches!(t.token(), TokenKind::SemiColon | TokenKind::EOF))
+        {
+            tokens.push(token);
+        }
+        expect_end(token_iter)?;
+        let (Some(first), Some(last)) = (tokens.first(), tokens.last()) else {
+            return Err(ParseError::UnexpectedToken);
+        };
+        let value = first.source()[first.span().start..last.span().end].to_string();
+
+        Ok(Self {
+            body: BodyKind::SetVariable,
+            when: WhenKind::None,
+            value: Some(name.to_lowercase()),
+            subqueries: vec![Query::leaf(BodyKind::Expression, value)],
+        })
+    }
+
+    /// The name and `LANGUAGE` of a `CREATE FUNCTION`, skipping its signature and body.
+    fn function(token_iter: &mut Peekable<Iter<Token>>) -> Result<Self, ParseError> {
+        let value = identifier_name(next_token(token_iter)?)?;
+        let mut language = None;
+        while let Some(token) =
+            token_iter.next_if(|t| !matches!(t.token(), TokenKind::SemiColon | TokenKind::EOF))
+        {
+            if token.text().eq_ignore_ascii_case("LANGUAGE") {
+                language = Some(identifier_name(next_token(token_iter)?)?);
+            }
+        }
+        expect_end(token_iter)?;
+        let language = language.ok_or(ParseError::UnexpectedToken)?;
+        Ok(Self {
+            body: BodyKind::CreateFunction,
+            when: WhenKind::None,
+            value: Some(value),
+            subqueries: vec![Query::leaf(BodyKind::Language, language)],
+        })
+    }
+
+    /// `[IF [NOT] EXISTS] name` statements such as `DROP TABLE` or `USE`.
+    fn named(body: BodyKind, token_iter: &mut Peekable<Iter<Token>>) -> Result<Self, ParseError> {
+        let when_kind = peek_when_kind(token_iter);
+        let value = identifier_name(next_token(token_iter)?)?;
+        expect_end(token_iter)?;
+        Ok(Self {
+            body,
+            when: when_kind,
+            value: Some(value),
+            subqueries: vec![],
+        })
+    }
+
+    /// `[IF NOT EXISTS] name ON table (column, ...)`, after `CREATE INDEX`, or the same
+    /// followed by `[WITH language]` after `CREATE FULLTEXT INDEX`.
+    ///
+    /// The `Table` subquery comes first, followed by one `Field` per indexed column and
+    /// the `Language`, if any.
+    fn index(body: BodyKind, token_iter: &mut Peekable<Iter<Token>>) -> Result<Self, ParseError> {
+        let when_kind = peek_when_kind(token_iter);
+        let value = identifier_name(next_token(token_iter)?)?;
+        if next_token(token_iter)?.token() != TokenKind::ON {
+            return Err(ParseError::UnexpectedToken);
+        }
+        let table = identifier_name(next_token(token_iter)?)?;
+        let fields = parse_fields(token_iter)?;
+        if fields.is_empty() {
+            return Err(ParseError::UnexpectedToken);
+        }
+        let language = match token_iter.next_if(|t| t.token() == TokenKind::WITH) {
+            Some(_) if body == BodyKind::CreateFulltextIndex => {
+                Some(identifier_name(next_token(token_iter)?)?)
+            }
+            Some(_) => return Err(ParseError::UnexpectedToken),
+            None => None,
+        };
+        expect_end(token_iter)?;
+
+        let mut subqueries = vec![Query::leaf(BodyKind::Table, table)];
+        subqueries.extend(fields);
+        subqueries.extend(language.map(|language| Query::leaf(BodyKind::Language, language)));
+        Ok(Self {
+            body,
+            when: when_kind,
+            value: Some(value),
+            subqueries,
+        })
+    }
+
+    /// `[IF NOT EXISTS] name {BEFORE|AFTER} {INSERT|UPDATE|DELETE} ON table FOR EACH ROW
+    /// BEGIN ... END`, after `CREATE TRIGGER`.
+    ///
+    /// The timing is a `Before`/`After` subquery holding the event, followed by the `Table`
+    /// and the `Block` with the statements between `BEGIN` and `END` as written.
+    fn trigger(token_iter: &mut Peekable<Iter<Token>>) -> Result<Self, ParseError> {
+        let when_kind = peek_when_kind(token_iter);
+        let value = identifier_name(next_token(token_iter)?)?;
+
+        let timing = match next_token(token_iter)?.token() {
+            TokenKind::BEFORE => BodyKind::Before,
+            TokenKind::AFTER => BodyKind::After,
+            _ => return Err(ParseError::UnexpectedToken),
+        };
+        let event = next_token(token_iter)?;
+        if !matches!(
+            event.token(),
+            TokenKind::INSERT | TokenKind::UPDATE | TokenKind::DELETE
+        ) {
+            return Err(ParseError::UnexpectedToken);
+        }
+        if next_token(token_iter)?.token() != TokenKind::ON {
+            return Err(ParseError::UnexpectedToken);
+        }
+        let table = identifier_name(next_token(token_iter)?)?;
+        for keyword in [
+            TokenKind::FOR,
+            TokenKind::EACH,
+            TokenKind::ROW,
+            TokenKind::BEGIN,
+        ] {
+            if next_token(token_iter)?.token() != keyword {
+                return Err(ParseError::UnexpectedToken);
+            }
+        }
+
+        let mut statements: Option<(&str, usize, usize)> = None;
+        loop {
+            let token = next_token(token_iter)?;
+            if token.token() == TokenKind::END {
+                break;
+            }
+            let start = statements.map_or(token.span().start, |(_, start, _)| start);
+            statements = Some((token.source(), start, token.span().end));
+        }
+        let statements = statements.map_or(String::new(), |(source, start, end)| {
+            source[start..end].to_string()
+        });
+        expect_end(token_iter)?;
+
+        Ok(Self {
+            body: BodyKind::CreateTrigger,
+            when: when_kind,
+            value: Some(value),
+            subqueries: vec![
+                Self::leaf(timing, event.text().to_ascii_uppercase()),
+                Self::leaf(BodyKind::Table, table),
+                Self::leaf(BodyKind::Block, statements),
+            ],
+        })
+    }
+
+    /// `INTO name [(column, ...)] {VALUES (value, ...), ... | SELECT ...}`, after `INSERT`.
+    ///
+    /// The named columns come first as `Field`s, followed by either one `Values` subquery
+    /// per row, holding an `Expression` per value, or the `Select` whose rows are inserted.
+    fn insert(token_iter: &mut Peekable<Iter<Token>>) -> Result<Self, ParseError> {
+        if next_token(token_iter)?.token() != TokenKind::INTO {
+            return Err(ParseError::UnexpectedToken);
+        }
+        let value = identifier_name(next_token(token_iter)?)?;
+        let mut subqueries = vec![];
+        if token_iter.peek().map(|t| t.token()) == Some(TokenKind::LeftParen) {
+            subqueries = parse_fields(token_iter)?;
+        }
+
+        match token_iter.peek().map(|t| t.token()) {
+            Some(TokenKind::VALUES) => {
+                token_iter.next();
+                loop {
+                    subqueries.push(parse_values(token_iter)?);
+                    if token_iter
+                        .next_if(|t| t.token() == TokenKind::Comma)
+                        .is_none()
+                    {
+                        break;
+                    }
+                }
+                expect_end(token_iter)?;
+            }
+            Some(TokenKind::SELECT | TokenKind::WITH) => subqueries.push(Self::select(token_iter)?),
+            _ => return Err(ParseError::UnexpectedToken),
+        }
+
+        Ok(Self {
+            body: BodyKind::Insert,
+            when: WhenKind::None,
+            value: Some(value),
+            subqueries,
+        })
+    }
+
+    /// `name SET column = value, ... [WHERE condition]`, after `UPDATE`.
+    ///
+    /// Each assignment is a `Field` holding its value as an `Expression` subquery,
+    /// followed by a `Where` holding the condition's source if there is one.
+    fn update(token_iter: &mut Peekable<Iter<Token>>) -> Result<Self, ParseError> {
+        let value = identifier_name(next_token(token_iter)?)?;
+        if next_token(token_iter)?.token() != TokenKind::SET {
+            return Err(ParseError::UnexpectedToken);
+        }
+        let mut subqueries = vec![];
+        loop {
+            let column = identifier_name(next_token(token_iter)?)?;
+            if next_token(token_iter)?.token() != TokenKind::Eq {
+                return Err(ParseError::UnexpectedToken);
+            }
+            subqueries.push(Self {
+                body: BodyKind::Field,
+                when: WhenKind::None,
+                value: Some(column),
+                subqueries: vec![Query::leaf(
+                    BodyKind::Expression,
+                    expression_text(token_iter)?,
+                )],
+            });
+            if token_iter
+                .next_if(|t| t.token() == TokenKind::Comma)
+                .is_none()
+            {
+                break;
+            }
+        }
+        subqueries.extend(parse_where(token_iter)?);
+        expect_end(token_iter)?;
+
+        Ok(Self {
+            body: BodyKind::Update,
+            when: WhenKind::None,
+            value: Some(value),
+            subqueries,
+        })
+    }
+
+    /// `FROM name [WHERE condition]`, after `DELETE`, with the condition as a `Where` subquery.
+    fn delete(token_iter: &mut Peekable<Iter<Token>>) -> Result<Self, ParseError> {
+        if next_token(token_iter)?.token() != TokenKind::FROM {
+            return Err(ParseError::UnexpectedToken);
+        }
+        let value = identifier_name(next_token(token_iter)?)?;
+        let subqueries = parse_where(token_iter)?.into_iter().collect();
+        expect_end(token_iter)?;
+
+        Ok(Self {
+            body: BodyKind::Delete,
+            when: WhenKind::None,
+            value: Some(value),
+            subqueries,
+        })
+    }
+
+    fn leaf(body: BodyKind, value: String) -> Self {
+        Self {
+            body,
+            when: WhenKind::None,
+            value: Some(value),
+            subqueries: vec![],
+        }
+    }
+
+    /// A `SELECT` statement, kept as its source text for the executor to parse.
+    ///
+    /// A leading `WITH [RECURSIVE]` clause becomes one `CommonTable` (or
+    /// `RecursiveCommonTable`) subquery per name, holding its column `Field`s and its `Select`.
+    /// A compound query adds a last `Union`, `UnionAll`, `Intersect` or `Except` subquery
+    /// whose two operands are `Select`/`SelectDistinct` cores or further set operations.
+    fn select(token_iter: &mut Peekable<Iter<Token>>) -> Result<Self, ParseError> {
+        let first = next_token(token_iter)?;
+        let mut common_tables = vec![];
+        let mut select = first;
+        if first.token() == TokenKind::WITH {
+            let body = match token_iter.next_if(|t| t.token() == TokenKind::RECURSIVE) {
+                Some(_) => BodyKind::RecursiveCommonTable,
+                None => BodyKind::CommonTable,
+            };
+            loop {
+                common_tables.push(parse_common_table(body, token_iter)?);
+                if token_iter
+                    .next_if(|t| t.token() == TokenKind::Comma)
+                    .is_none()
+                {
+                    break;
+                }
+            }
+            select = next_token(token_iter)?;
+        }
+        let mut tokens = vec![select];
+        while let Some(token) =
+            token_iter.next_if(|t| !matches!(t.token(), TokenKind::SemiColon | TokenKind::EOF))
+        {
+            tokens.push(token);
+        }
+        expect_end(token_iter)?;
+
+        let compound = parse_compound(&tokens)?.query;
+        let value = Some(first.source()[first.span().start..compound_end(&tokens)].to_string());
+        if matches!(compound.body, BodyKind::Select | BodyKind::SelectDistinct) {
+            return Ok(Self {
+                body: compound.body,
+                when: WhenKind::None,
+                value,
+                subqueries: common_tables,
+            });
+        }
+        common_tables.push(compound);
+        Ok(Self {
+            body: BodyKind::Select,
+            when: WhenKind::None,
+            value,
+            subqueries: common_tables,
+        })
+    }
+
+    pub(crate) fn body(&self) -> BodyKind {
+        self.body
+    }
+
+    pub(crate) fn when(&self) -> WhenKind {
+        self.when
+    }
+
+    pub(crate) fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+
+    pub(crate) fn subqueries(&self) -> &[Query] {
+        &self.subqueries
+    }
+}
+
+fn next_token<'a, 's>(
+    token_iter: &mut Peekable<Iter<'a, Token<'s>>>,
+) -> Result<&'a Token<'s>, ParseError> {
+    match token_iter.next() {
+        Some(token) if token.token() != TokenKind::EOF => Ok(token),
+        _ => Err(ParseError::UnexpectedToken),
+    }
+}
+
+/// Only an optional `;` may follow the statement.
+fn expect_end(token_iter: &mut Peekable<Iter<Token>>) -> Result<(), ParseError> {
+    token_iter.next_if(|t| t.token() == TokenKind::SemiColon);
+    match token_iter.next() {
+        None => Ok(()),
+        Some(token) if token.token() == TokenKind::EOF => Ok(()),
+        Some(_) => Err(ParseError::UnexpectedToken),
+    }
+}
+
+/// Parse one `ADD`, `DROP` or `RENAME` action of an `ALTER TABLE` statement.
+///
+/// Added columns keep their `Type` and `Default` as subqueries. Renames keep the old
+/// column name in `value` and the new name as a `Field` subquery.
+fn parse_alter_action(token_iter: &mut Peekable<Iter<Token>>) -> Result<Query, ParseError> {
+    let action = next_token(token_iter)?.token();
+    token_iter.next_if(|t| t.token() == TokenKind::COLUMN);
+
+    match action {
+        TokenKind::ADD => {
+            let when_kind = peek_when_kind(token_iter);
+            let name = identifier_name(next_token(token_iter)?)?;
+            Ok(Query {
+                body: BodyKind::AddColumn,
+                when: when_kind,
+                value: Some(name),
+                subqueries: parse_column_definition(token_iter)?,
+            })
+        }
+        TokenKind::DROP => {
+            let when_kind = peek_when_kind(token_iter);
+            let name = identifier_name(next_token(token_iter)?)?;
+            Ok(Query {
+                body: BodyKind::DropColumn,
+                when: when_kind,
+                value: Some(name),
+                subqueries: vec![],
+            })
+        }
+        TokenKind::RENAME => {
+            let first = next_token(token_iter)?;
+            if first.token() == TokenKind::TO {
+                let name = identifier_name(next_token(token_iter)?)?;
+                return Ok(Query {
+                    body: BodyKind::RenameTable,
+                    when: WhenKind::None,
+                    value: Some(name),
+                    subqueries: vec![],
+                });
+            }
+            let old_name = identifier_name(first)?;
+            if next_token(token_iter)?.token() != TokenKind::TO {
+                return Err(ParseError::UnexpectedToken);
+            }
+            let new_name = identifier_name(next_token(token_iter)?)?;
+            Ok(Query {
+                body: BodyKind::RenameColumn,
+                when: WhenKind::None,
+                value: Some(old_name),
+                subqueries: vec![Query::leaf(BodyKind::Field, new_name)],
+            })
+        }
+        _ => Err(ParseError::UnexpectedToken),
+    }
+}
+
+/// Operand of a set operation, with the span of its source.
+struct Operand<'s> {
+    query: Query,
+    source: &'s str,
+    start: usize,
+    end: usize,
+}
+
+fn compound_end(tokens: &[&Token]) -> usize {
+    tokens.last().map_or(0, |t| t.span().end)
+}
+
+/// Split a SELECT at its top-level set operators. `INTERSECT` binds tighter than
+/// `UNION` and `EXCEPT`, which associate to the left.
+fn parse_compound<'s>(tokens: &[&Token<'s>]) -> Result<Operand<'s>, ParseError> {
+    let mut operands = vec![];
+    let mut operators = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    let mut i = 0;
+    while i < tokens.len() {
+        let operator = match tokens[i].token() {
+            TokenKind::LeftParen => {
+                depth += 1;
+                None
+            }
+            TokenKind::RightParen => {
+                depth -= 1;
+                None
+            }
+            TokenKind::UNION if depth == 0 => match tokens.get(i + 1).map(|t| t.token()) {
+                Some(TokenKind::ALL) => Some((BodyKind::UnionAll, 2)),
+                _ => Some((BodyKind::Union, 1)),
+            },
+            TokenKind::INTERSECT if depth == 0 => Some((BodyKind::Intersect, 1)),
+            TokenKind::EXCEPT if depth == 0 => Some((BodyKind::Except, 1)),
+            _ => None,
         };
+        match operator {
+            Some((operator, len)) => {
+                operands.push(parse_select_core(&tokens[start..i])?);
+                operators.push(operator);
+                i += len;
+                start = i;
+            }
+            None => i += 1,
+        }
+    }
+    operands.push(parse_select_core(&tokens[start..])?);
+
+    let mut operands = operands.into_iter();
+    let mut terms = vec![operands.next().unwrap()];
+    let mut term_operators = vec![];
+    for (operator, operand) in operators.into_iter().zip(operands) {
+        if operator == BodyKind::Intersect {
+            let left = terms.pop().unwrap();
+            terms.push(combine(operator, left, operand));
+        } else {
+            term_operators.push(operator);
+            terms.push(operand);
+        }
+    }
+
+    let mut terms = terms.into_iter();
+    let mut compound = terms.next().unwrap();
+    for (operator, operand) in term_operators.into_iter().zip(terms) {
+        compound = combine(operator, compound, operand);
     }
+    Ok(compound)
 }
 
-fn peek_when_kind(token_iter: &mut Peekable<Iter<Token>>) -> WhenKind {
-    let mut is_when = false;
-    let mut is_not = false;
+/// Join two operands. Whether they select compatible columns is only known once the
+/// catalog is consulted, so the executor checks it.
+fn combine<'s>(operator: BodyKind, left: Operand<'s>, right: Operand<'s>) -> Operand<'s> {
+    Operand {
+        query: Query {
+            body: operator,
+            when: WhenKind::None,
+            value: Some(left.source[left.start..right.end].to_string()),
+            subqueries: vec![left.query, right.query],
+        },
+        source: left.source,
+        start: left.start,
+        end: right.end,
+    }
+}
+
+/// `SELECT [DISTINCT | ALL] columns ...` between two set operators.
+fn parse_select_core<'s>(tokens: &[&Token<'s>]) -> Result<Operand<'s>, ParseError> {
+    let (first, last) = match (tokens.first(), tokens.last()) {
+        (Some(first), Some(last)) if first.token() == TokenKind::SELECT => (first, last),
+        _ => return Err(ParseError::UnexpectedToken),
+    };
+
+    let mut rest = &tokens[1..];
+    let body = match rest.first().map(|t| t.token()) {
+        Some(TokenKind::DISTINCT) => {
+            rest = &rest[1..];
+            BodyKind::SelectDistinct
+        }
+        Some(TokenKind::ALL) => {
+            rest = &rest[1..];
+            BodyKind::Select
+        }
+        _ => BodyKind::Select,
+    };
+
+    // result columns run up to the first top-level clause, and none may be empty
+    let mut column: Vec<&Token> = vec![];
+    let mut depth = 0;
+    for token in rest {
+        match token.token() {
+            TokenKind::LeftParen => depth += 1,
+            TokenKind::RightParen => depth -= 1,
+            TokenKind::FROM
+            | TokenKind::WHERE
+            | TokenKind::ORDER
+            | TokenKind::LIMIT
+            | TokenKind::OFFSET
+                if depth == 0 =>
+            {
+                break
+            }
+            TokenKind::Comma if depth == 0 => {
+                if column.is_empty() {
+                    return Err(ParseError::UnexpectedToken);
+                }
+                column.clear();
+                continue;
+            }
+            _ => (),
+        }
+        column.push(token);
+    }
+    if column.is_empty() {
+        return Err(ParseError::UnexpectedToken);
+    }
+
+    let (start, end) = (first.span().start, last.span().end);
+    Ok(Operand {
+        query: Query {
+            body,
+            when: WhenKind::None,
+            value: Some(first.source()[start..end].to_string()),
+            subqueries: vec![],
+        },
+        source: first.source(),
+        start,
+        end,
+    })
+}
+
+/// `name [(columns)] AS (SELECT ...)` of a `WITH` clause.
+fn parse_common_table(
+    body: BodyKind,
+    token_iter: &mut Peekable<Iter<Token>>,
+) -> Result<Query, ParseError> {
+    let name = identifier_name(next_token(token_iter)?)?;
+
+    let mut subqueries = vec![];
+    if token_iter
+        .next_if(|t| t.token() == TokenKind::LeftParen)
+        .is_some()
+    {
+        loop {
+            let column = identifier_name(next_token(token_iter)?)?;
+            subqueries.push(Query::leaf(BodyKind::Field, column));
+            match next_token(token_iter)?.token() {
+                TokenKind::Comma => (),
+                TokenKind::RightParen => break,
+                _ => return Err(ParseError::UnexpectedToken),
+            }
+        }
+    }
+
+    if next_token(token_iter)?.token() != TokenKind::AS
+        || next_token(token_iter)?.token() != TokenKind::LeftParen
+    {
+        return Err(ParseError::UnexpectedToken);
+    }
+    let mut inner = vec![];
+    let mut depth = 0;
+    loop {
+        let token = next_token(token_iter)?;
+        match token.token() {
+            TokenKind::LeftParen => depth += 1,
+            TokenKind::RightParen if depth == 0 => break,
+            TokenKind::RightParen => depth -= 1,
+            _ => (),
+        }
+        inner.push(token.clone());
+    }
+    subqueries.push(Query::select(&mut inner.iter().peekable())?);
+
+    Ok(Query {
+        body,
+        when: WhenKind::None,
+        value: Some(name),
+        subqueries,
+    })
+}
+
+/// Name of a table or column, either a bare identifier or a double quoted one.
+pub(crate) fn identifier_name(token: &Token) -> Result<String, ParseError> {
+    match (token.token(), token.value()) {
+        (TokenKind::Identifier, _) => Ok(token.text().to_string()),
+        (TokenKind::DoubleQuotedString, Some(TokenValue::String(name))) => Ok(name.clone()),
+        _ => Err(ParseError::UnexpectedToken),
+    }
+}
+
+/// Parse a parenthesized column list into `Field` queries, each with the `Type` and
+/// `Default` of its definition, skipping table constraints such as `PRIMARY KEY (a, b)`.
+fn parse_fields(token_iter: &mut Peekable<Iter<Token>>) -> Result<Vec<Query>, ParseError> {
+    if next_token(token_iter)?.token() != TokenKind::LeftParen {
+        return Err(ParseError::UnexpectedToken);
+    }
+
+    let mut fields = vec![];
+    loop {
+        let first = next_token(token_iter)?;
+        if first.token().is_keyword() {
+            skip_definition(token_iter)?;
+        } else {
+            fields.push(Query {
+                body: BodyKind::Field,
+                when: WhenKind::None,
+                value: Some(identifier_name(first)?),
+                subqueries: parse_column_definition(token_iter)?,
+            });
+        }
+        match next_token(token_iter)?.token() {
+            TokenKind::RightParen => return Ok(fields),
+            TokenKind::Comma => (),
+            _ => return Err(ParseError::UnexpectedToken),
+        }
+    }
+}
+
+/// Constraints that can follow a column type without a keyword token of their own.
+const CONSTRAINT_WORDS: [&str; 6] = [
+    "CONSTRAINT",
+    "REFERENCES",
+    "CHECK",
+    "UNIQUE",
+    "COLLATE",
+    "GENERATED",
+];
+
+/// The `Type` and `Default` of a column definition, after its name, as written.
+///
+/// The type is the words before the first constraint, such as `VARCHAR(50)` or
+/// `DOUBLE PRECISION`. Other constraints are skipped, up to the next top-level `,`
+/// or `)` or the end of the statement.
+fn parse_column_definition(
+    token_iter: &mut Peekable<Iter<Token>>,
+) -> Result<Vec<Query>, ParseError> {
+    let mut definition = vec![];
+    let mut type_name: Option<(&Token, &Token)> = None;
+    while let Some(token) = token_iter.next_if(|t| match t.token() {
+        TokenKind::Identifier => !CONSTRAINT_WORDS.contains(&t.text().to_uppercase().as_str()),
+        TokenKind::INTEGER | TokenKind::TEXT => true,
+        _ => false,
+    }) {
+        let mut last = token;
+        if token_iter.peek().map(|t| t.token()) == Some(TokenKind::LeftParen) {
+            last = skip_group(token_iter)?;
+        }
+        type_name = Some((type_name.map_or(token, |(first, _)| first), last));
+    }
+    if let Some((first, last)) = type_name {
+        let text = &first.source()[first.span().start..last.span().end];
+        definition.push(Query::leaf(BodyKind::Type, text.to_string()));
+    }
+
     while let Some(token) = token_iter.peek() {
         match token.token() {
-            TokenKind::IF => {
-                token_iter.next();
-                is_when = true;
+            TokenKind::Comma | TokenKind::RightParen | TokenKind::SemiColon | TokenKind::EOF => {
+                break
             }
-            TokenKind::NOT => {
+            TokenKind::DEFAULT => {
                 token_iter.next();
-                is_not = true;
+                definition.push(Query::leaf(BodyKind::Default, parse_default(token_iter)?));
             }
-            TokenKind::EXISTS => {
+            TokenKind::LeftParen => {
+                skip_group(token_iter)?;
+            }
+            _ => {
                 token_iter.next();
-                break;
+            }
+        }
+    }
+    Ok(definition)
+}
+
+/// The source of a `DEFAULT` value: a literal, a signed number, a function call such as
+/// `NOW()` or a parenthesized expression.
+fn parse_default(token_iter: &mut Peekable<Iter<Token>>) -> Result<String, ParseError> {
+    let first = next_token(token_iter)?;
+    let mut last = first;
+    if matches!(first.token(), TokenKind::Minus | TokenKind::Plus) {
+        last = next_token(token_iter)?;
+    }
+    if last.token() == TokenKind::LeftParen {
+        last = skip_group_from(token_iter, 1)?;
+    } else if token_iter.peek().map(|t| t.token()) == Some(TokenKind::LeftParen) {
+        last = skip_group(token_iter)?;
+    }
+    Ok(first.source()[first.span().start..last.span().end].to_string())
+}
+
+/// Skip a table constraint, up to the next top-level `,` or `)`.
+fn skip_definition(token_iter: &mut Peekable<Iter<Token>>) -> Result<(), ParseError> {
+    while let Some(token) = token_iter.peek() {
+        match token.token() {
+            TokenKind::Comma | TokenKind::RightParen => return Ok(()),
+            TokenKind::LeftParen => {
+                skip_group(token_iter)?;
             }
             _ => {
-                if is_when {
+                next_token(token_iter)?;
+            }
+        }
+    }
+    Err(ParseError::UnexpectedToken)
+}
+
+/// Skip a parenthesized group, returning its closing `)`.
+fn skip_group<'a, 's>(
+    token_iter: &mut Peekable<Iter<'a, Token<'s>>>,
+) -> Result<&'a Token<'s>, ParseError> {
+    if next_token(token_iter)?.token() != TokenKind::LeftParen {
+        return Err(ParseError::UnexpectedToken);
+    }
+    skip_group_from(token_iter, 1)
+}
+
+/// Skip to the `)` that closes `depth` open parentheses, returning it.
+fn skip_group_from<'a, 's>(
+    token_iter: &mut Peekable<Iter<'a, Token<'s>>>,
+    mut depth: usize,
+) -> Result<&'a Token<'s>, ParseError> {
+    loop {
+        let token = next_token(token_iter)?;
+        match token.token() {
+            TokenKind::LeftParen => depth += 1,
+            TokenKind::RightParen if depth == 1 => return Ok(token),
+            TokenKind::RightParen => depth -= 1,
+            _ => (),
+        }
+    }
+}
+
+/// Source of the expression up to the next top-level `,`, `WHERE` or the end of the statement.
+fn expression_text(token_iter: &mut Peekable<Iter<Token>>) -> Result<String, ParseError> {
+    let mut span: Option<(&Token, &Token)> = None;
+    let mut depth = 0;
+    while let Some(token) = token_iter.next_if(|t| {
+        depth > 0
+            || !matches!(
+                t.token(),
+                TokenKind::Comma | TokenKind::WHERE | TokenKind::SemiColon | TokenKind::EOF
+            )
+    }) {
+        match token.token() {
+            TokenKind::LeftParen => depth += 1,
+            TokenKind::RightParen => depth -= 1,
+            _ => (),
+        }
+        span = Some((span.map_or(token, |(first, _)| first), token));
+    }
+    let (first, last) = span.ok_or(ParseError::UnexpectedToken)?;
+    Ok(first.source()[first.span().start..last.span().end].to_string())
+}
+
+/// An optional `WHERE condition`, as a `Where` query holding the condition's source.
+fn parse_where(token_iter: &mut Peekable<Iter<Token>>) -> Result<Option<Query>, ParseError> {
+    match token_iter.next_if(|t| t.token() == TokenKind::WHERE) {
+        Some(_) => Ok(Some(Query::leaf(
+            BodyKind::Where,
+            expression_text(token_iter)?,
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// `(value, ...)` of `VALUES`, one `Expression` subquery per value as written.
+fn parse_values(token_iter: &mut Peekable<Iter<Token>>) -> Result<Query, ParseError> {
+    if next_token(token_iter)?.token() != TokenKind::LeftParen {
+        return Err(ParseError::UnexpectedToken);
+    }
+
+    let mut values = vec![];
+    let mut value: Option<(&Token, &Token)> = None;
+    let mut depth = 0;
+    loop {
+        let token = next_token(token_iter)?;
+        match token.token() {
+            TokenKind::Comma | TokenKind::RightParen if depth == 0 => {
+                let (first, last) = value.take().ok_or(ParseError::UnexpectedToken)?;
+                let text = &first.source()[first.span().start..last.span().end];
+                values.push(Query::leaf(BodyKind::Expression, text.to_string()));
+                if token.token() == TokenKind::RightParen {
                     break;
                 }
-                token_iter.next();
+                continue;
             }
+            TokenKind::LeftParen => depth += 1,
+            TokenKind::RightParen => depth -= 1,
+            _ => (),
         }
+        value = Some((value.map_or(token, |(first, _)| first), token));
     }
-    if !is_when {
+
+    Ok(Query {
+        body: BodyKind::Values,
+        when: WhenKind::None,
+        value: None,
+        subqueries: values,
+    })
+}
+
+fn peek_when_kind(token_iter: &mut Peekable<Iter<Token>>) -> WhenKind {
+    if token_iter.next_if(|t| t.token() == TokenKind::IF).is_none() {
         return WhenKind::None;
     }
+    let is_not = token_iter
+        .next_if(|t| t.token() == TokenKind::NOT)
+        .is_some();
+    token_iter.next_if(|t| t.token() == TokenKind::EXISTS);
     if is_not {
         return WhenKind::IfNotExists;
     }
-    return WhenKind::IfExists;
+    WhenKind::IfExists
 }
@@ -0,0 +1,308 @@
+use std::{iter::Peekable, vec::IntoIter};
+
+use super::{
+    expression::{expect, Expression},
+    query::identifier_name,
+    tokenizer::{ParseError, Token, TokenKind},
+};
+
+type TokenIter<'a, 's> = Peekable<IntoIter<&'a Token<'s>>>;
+
+/// An item of the `SELECT` list.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResultColumn {
+    /// `*`, or `table.*` for the columns of one `FROM` item.
+    All(Option<String>),
+    /// An expression, named by its alias or its own text.
+    Expression(String, Expression),
+}
+
+/// What a `FROM` item reads.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceKind {
+    /// A table or a view.
+    Named(String),
+    /// A table-valued function such as `json_each(...)`.
+    Function(String, Vec<Expression>),
+    /// A parenthesized `SELECT`, kept as its source text.
+    Subquery(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Cross,
+}
+
+/// One `FROM` item and how it joins the items before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Source {
+    pub kind: SourceKind,
+    /// The name its columns are qualified by, if not the table's own.
+    pub alias: Option<String>,
+    /// The first item is a `Cross` join without `ON`.
+    pub join: JoinKind,
+    pub on: Option<Expression>,
+}
+
+/// A `SELECT` between set operators, without the `WITH` clause that [`Query`] keeps apart.
+///
+/// [`Query`]: super::query::Query
+#[derive(Debug, Clone, PartialEq)]
+pub struct Select {
+    pub distinct: bool,
+    pub columns: Vec<ResultColumn>,
+    pub from: Vec<Source>,
+    pub filter: Option<Expression>,
+    /// Expressions with `true` for descending order.
+    pub order_by: Vec<(Expression, bool)>,
+    pub limit: Option<Expression>,
+    pub offset: Option<Expression>,
+}
+
+impl Select {
+    /// Parse `SELECT [DISTINCT | ALL] columns [FROM ...] [WHERE ...] [ORDER BY ...]
+    /// [LIMIT ... [OFFSET ...]]`, skipping a leading `WITH` clause.
+    pub fn parse(tokens: &[Token]) -> Result<Self, ParseError> {
+        let tokens = tokens
+            .iter()
+            .filter(|t| !t.token().is_comment() && t.token() != TokenKind::EOF)
+            .collect::<Vec<_>>();
+        let token_iter = &mut tokens.into_iter().peekable();
+        if token_iter
+            .next_if(|t| t.token() == TokenKind::WITH)
+            .is_some()
+        {
+            let mut depth = 0;
+            while let Some(token) =
+                token_iter.next_if(|t| depth > 0 || t.token() != TokenKind::SELECT)
+            {
+                match token.token() {
+                    TokenKind::LeftParen => depth += 1,
+                    TokenKind::RightParen => depth -= 1,
+                    _ => (),
+                }
+            }
+        }
+        expect(token_iter, TokenKind::SELECT)?;
+        let distinct = token_iter
+            .next_if(|t| matches!(t.token(), TokenKind::ALL | TokenKind::DISTINCT))
+            .is_some_and(|t| t.token() == TokenKind::DISTINCT);
+
+        let mut columns = vec![];
+        loop {
+            columns.push(parse_result_column(token_iter)?);
+            if token_iter
+                .next_if(|t| t.token() == TokenKind::Comma)
+                .is_none()
+            {
+                break;
+            }
+        }
+
+        let mut from = vec![];
+        if token_iter
+            .next_if(|t| t.token() == TokenKind::FROM)
+            .is_some()
+        {
+            from.push(parse_source(JoinKind::Cross, token_iter)?);
+            while let Some(join) = parse_join(token_iter)? {
+                from.push(parse_source(join, token_iter)?);
+            }
+        }
+
+        let filter = match token_iter.next_if(|t| t.token() == TokenKind::WHERE) {
+            Some(_) => Some(Expression::parse(token_iter)?),
+            None => None,
+        };
+
+        let mut order_by = vec![];
+        if token_iter
+            .next_if(|t| t.token() == TokenKind::ORDER)
+            .is_some()
+        {
+            expect(token_iter, TokenKind::BY)?;
+            loop {
+                let expression = Expression::parse(token_iter)?;
+                let descending = token_iter
+                    .next_if(|t| matches!(t.token(), TokenKind::ASC | TokenKind::DESC))
+                    .is_some_and(|t| t.token() == TokenKind::DESC);
+                order_by.push((expression, descending));
+                if token_iter
+                    .next_if(|t| t.token() == TokenKind::Comma)
+                    .is_none()
+                {
+                    break;
+                }
+            }
+        }
+
+        let limit = match token_iter.next_if(|t| t.token() == TokenKind::LIMIT) {
+            Some(_) => Some(Expression::parse(token_iter)?),
+            None => None,
+        };
+        let offset = match token_iter.next_if(|t| t.token() == TokenKind::OFFSET) {
+            Some(_) => Some(Expression::parse(token_iter)?),
+            None => None,
+        };
+
+        token_iter.next_if(|t| t.token() == TokenKind::SemiColon);
+        if token_iter.next().is_some() {
+            return Err(ParseError::UnexpectedToken);
+        }
+        Ok(Self {
+            distinct,
+            columns,
+            from,
+            filter,
+            order_by,
+            limit,
+            offset,
+        })
+    }
+}
+
+/// `*`, `table.*` or an expression with an optional `[AS] alias`.
+fn parse_result_column(token_iter: &mut TokenIter) -> Result<ResultColumn, ParseError> {
+    if token_iter
+        .next_if(|t| t.token() == TokenKind::Multiply)
+        .is_some()
+    {
+        return Ok(ResultColumn::All(None));
+    }
+    let first = *token_iter.peek().ok_or(ParseError::UnexpectedToken)?;
+    if first.token() == TokenKind::Identifier {
+        let mut lookahead = token_iter.clone();
+        lookahead.next();
+        if lookahead.next().map(|t| t.token()) == Some(TokenKind::Period)
+            && lookahead.next().map(|t| t.token()) == Some(TokenKind::Multiply)
+        {
+            *token_iter = lookahead;
+            return Ok(ResultColumn::All(Some(identifier_name(first)?)));
+        }
+    }
+
+    let expression = Expression::parse(token_iter)?;
+    let name = match parse_alias(token_iter)? {
+        Some(alias) => alias,
+        None => {
+            let end = token_iter
+                .peek()
+                .map_or(first.source().len(), |t| t.span().start);
+            first.source()[first.span().start..end]
+                .trim_end()
+                .to_string()
+        }
+    };
+    Ok(ResultColumn::Expression(name, expression))
+}
+
+/// `AS name`, or a bare name right after the aliased item.
+fn parse_alias(token_iter: &mut TokenIter) -> Result<Option<String>, ParseError> {
+    if token_iter.next_if(|t| t.token() == TokenKind::AS).is_some() {
+        let name = token_iter.next().ok_or(ParseError::UnexpectedToken)?;
+        return identifier_name(name).map(Some);
+    }
+    match token_iter.next_if(|t| {
+        matches!(
+            t.token(),
+            TokenKind::Identifier | TokenKind::DoubleQuotedString
+        )
+    }) {
+        Some(name) => identifier_name(name).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// The join before the next `FROM` item, or `None` at the end of the `FROM` clause.
+fn parse_join(token_iter: &mut TokenIter) -> Result<Option<JoinKind>, ParseError> {
+    let Some(token) = token_iter.next_if(|t| {
+        matches!(
+            t.token(),
+            TokenKind::Comma
+                | TokenKind::JOIN
+                | TokenKind::INNER
+                | TokenKind::LEFT
+                | TokenKind::CROSS
+        )
+    }) else {
+        return Ok(None);
+    };
+    let join = match token.token() {
+        TokenKind::Comma => return Ok(Some(JoinKind::Cross)),
+        TokenKind::JOIN => return Ok(Some(JoinKind::Inner)),
+        TokenKind::INNER => JoinKind::Inner,
+        TokenKind::LEFT => {
+            token_iter.next_if(|t| t.token() == TokenKind::OUTER);
+            JoinKind::Left
+        }
+        _ => JoinKind::Cross,
+    };
+    expect(token_iter, TokenKind::JOIN)?;
+    Ok(Some(join))
+}
+
+/// A table, view, `name(args)` or `(SELECT ...)` with an optional alias, and `ON` for joins.
+fn parse_source(join: JoinKind, token_iter: &mut TokenIter) -> Result<Source, ParseError> {
+    let token = token_iter.next().ok_or(ParseError::UnexpectedToken)?;
+    let kind = if token.token() == TokenKind::LeftParen {
+        let mut depth = 0;
+        let mut last = token;
+        loop {
+            let inner = token_iter.next().ok_or(ParseError::UnexpectedToken)?;
+            match inner.token() {
+                TokenKind::LeftParen => depth += 1,
+                TokenKind::RightParen if depth == 0 => break,
+                TokenKind::RightParen => depth -= 1,
+                _ => (),
+            }
+            last = inner;
+        }
+        if last.span().start == token.span().start {
+            return Err(ParseError::UnexpectedToken);
+        }
+        SourceKind::Subquery(
+            token.source()[token.span().end..last.span().end]
+                .trim()
+                .to_string(),
+        )
+    } else {
+        let name = identifier_name(token)?;
+        if token_iter
+            .next_if(|t| t.token() == TokenKind::LeftParen)
+            .is_some()
+        {
+            let mut args = vec![];
+            if token_iter
+                .next_if(|t| t.token() == TokenKind::RightParen)
+                .is_none()
+            {
+                loop {
+                    args.push(Expression::parse(token_iter)?);
+                    match token_iter.next().map(|t| t.token()) {
+                        Some(TokenKind::Comma) => (),
+                        Some(TokenKind::RightParen) => break,
+                        _ => return Err(ParseError::UnexpectedToken),
+                    }
+                }
+            }
+            SourceKind::Function(name, args)
+        } else {
+            SourceKind::Named(name)
+        }
+    };
+    let alias = parse_alias(token_iter)?;
+
+    let on = match (join, token_iter.next_if(|t| t.token() == TokenKind::ON)) {
+        (JoinKind::Cross, Some(_)) => return Err(ParseError::UnexpectedToken),
+        (_, Some(_)) => Some(Expression::parse(token_iter)?),
+        (_, None) => None,
+    };
+    Ok(Source {
+        kind,
+        alias,
+        join,
+        on,
+    })
+}
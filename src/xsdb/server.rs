@@ -0,0 +1,327 @@
+use std::io;
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use super::{
+    catalog::CatalogError,
+    database::{DatabaseError, ResultSet},
+    shared::SharedDatabase,
+    tokenizer::Tokenizer,
+    value::Value,
+};
+
+const PROTOCOL_VERSION: i32 = 196608; // 3.0
+const CANCEL_REQUEST: i32 = 80877102;
+const SSL_REQUEST: i32 = 80877103;
+const GSSENC_REQUEST: i32 = 80877104;
+
+/// Largest message accepted from a client, to bound memory per connection.
+const MAX_MESSAGE_LEN: usize = 1 << 24;
+
+// type OIDs from pg_type
+const INT8_OID: i32 = 20;
+const TEXT_OID: i32 = 25;
+const FLOAT8_OID: i32 = 701;
+const BYTEA_OID: i32 = 17;
+
+/// Serve the simple-query subset of the PostgreSQL v3 protocol, one task per connection.
+/// Every session shares `db`, each query running in its own transaction;
+/// there is no authentication.
+pub async fn serve(listener: TcpListener, db: SharedDatabase) -> io::Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let db = db.clone();
+        tokio::spawn(async move {
+            // a client going away mid-message only ends its own session
+            let _ = Session::new(stream, db).run().await;
+        });
+    }
+}
+
+struct Session {
+    stream: TcpStream,
+    db: SharedDatabase,
+    out: Vec<u8>,
+    /// After an error in the extended protocol, messages are ignored until the next Sync.
+    skipping: bool,
+}
+
+impl Session {
+    fn new(stream: TcpStream, db: SharedDatabase) -> Self {
+        Self {
+            stream,
+            db,
+            out: vec![],
+            skipping: false,
+        }
+    }
+
+    async fn run(mut self) -> io::Result<()> {
+        if !self.startup().await? {
+            return self.flush().await;
+        }
+
+        loop {
+            let tag = match self.stream.read_u8().await {
+                Ok(tag) => tag,
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(err) => return Err(err),
+            };
+            let body = self.read_body().await?;
+            match tag {
+                // Sync ends an extended-protocol batch
+                b'S' => {
+                    self.skipping = false;
+                    self.ready_for_query();
+                }
+                // Terminate
+                b'X' => return self.flush().await,
+                _ if self.skipping => (),
+                b'Q' => {
+                    let sql = String::from_utf8_lossy(body.strip_suffix(&[0]).unwrap_or(&body));
+                    self.simple_query(&sql).await;
+                    self.ready_for_query();
+                }
+                _ => {
+                    self.error("0A000", "only the simple query protocol is supported");
+                    self.skipping = true;
+                }
+            }
+            self.flush().await?;
+        }
+    }
+
+    /// Handle the startup packet, declining encryption. `false` if the session should end.
+    async fn startup(&mut self) -> io::Result<bool> {
+        loop {
+            let body = self.read_body().await?;
+            let code = match body.get(..4) {
+                Some(code) => i32::from_be_bytes(code.try_into().unwrap()),
+                None => return Err(io::ErrorKind::InvalidData.into()),
+            };
+            match code {
+                // the client may go on in plain text
+                SSL_REQUEST | GSSENC_REQUEST => self.stream.write_all(b"N").await?,
+                PROTOCOL_VERSION => break,
+                CANCEL_REQUEST => return Ok(false),
+                _ => {
+                    self.error("08P01", "unsupported frontend protocol");
+                    return Ok(false);
+                }
+            }
+        }
+
+        self.message(b'R', &0i32.to_be_bytes()); // AuthenticationOk
+        for (name, value) in [
+            ("server_version", "14.0"),
+            ("server_encoding", "UTF8"),
+            ("client_encoding", "UTF8"),
+            ("DateStyle", "ISO, YMD"),
+            ("integer_datetimes", "on"),
+            ("standard_conforming_strings", "on"),
+        ] {
+            let mut body = vec![];
+            put_str(&mut body, name);
+            put_str(&mut body, value);
+            self.message(b'S', &body); // ParameterStatus
+        }
+        self.ready_for_query();
+        self.flush().await?;
+        Ok(true)
+    }
+
+    /// Length-prefixed message body, without the length itself.
+    async fn read_body(&mut self) -> io::Result<Vec<u8>> {
+        let len = self.stream.read_i32().await?;
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|len| (4..=MAX_MESSAGE_LEN).contains(len))
+            .ok_or(io::ErrorKind::InvalidData)?;
+        let mut body = vec![0; len - 4];
+        self.stream.read_exact(&mut body).await?;
+        Ok(body)
+    }
+
+    /// Run the `;`-separated statements as one transaction, stopping at the first error,
+    /// which rolls back the statements before it.
+    async fn simple_query(&mut self, sql: &str) {
+        let statements = match Tokenizer::new(sql).statements() {
+            Ok(statements) => statements,
+            Err(err) => return self.error("42601", &format!("parse error: {}", err)),
+        };
+        match statements[..] {
+            [] => return self.message(b'I', &[]), // EmptyQueryResponse
+            // a single read runs on a snapshot, without waiting for writers
+            [statement] => {
+                return match self.db.execute(statement, &[]).await {
+                    Ok(result) => self.complete(statement, &result),
+                    Err(err) => self.error(sqlstate(&err), &err.to_string()),
+                }
+            }
+            _ => (),
+        }
+
+        let db = self.db.clone();
+        let mut transaction = db.begin().await;
+        for statement in statements {
+            match transaction.execute(statement, &[]) {
+                Ok(result) => self.complete(statement, &result),
+                Err(err) => return self.error(sqlstate(&err), &err.to_string()),
+            }
+        }
+        if let Err(err) = transaction.commit() {
+            self.error(sqlstate(&err), &err.to_string());
+        }
+    }
+
+    /// The rows of a statement, if it returns any, and its CommandComplete.
+    fn complete(&mut self, statement: &str, result: &ResultSet) {
+        if result.columns().is_empty() {
+            return self.command_complete(&command_tag(statement));
+        }
+        self.rows(result);
+        self.command_complete(&format!("SELECT {}", result.rows().len()));
+    }
+
+    fn rows(&mut self, result: &ResultSet) {
+        let mut body = vec![];
+        put_i16(&mut body, result.columns().len());
+        for (index, name) in result.columns().iter().enumerate() {
+            let oid = column_oid(result.rows().iter().map(|row| &row[index]));
+            put_str(&mut body, name);
+            body.extend(0i32.to_be_bytes()); // table OID
+            body.extend(0i16.to_be_bytes()); // column number
+            body.extend(oid.to_be_bytes());
+            body.extend((-1i16).to_be_bytes()); // variable length
+            body.extend((-1i32).to_be_bytes()); // no type modifier
+            body.extend(0i16.to_be_bytes()); // text format
+        }
+        self.message(b'T', &body); // RowDescription
+
+        for row in result.rows() {
+            let mut body = vec![];
+            put_i16(&mut body, row.len());
+            for value in row {
+                match text_format(value) {
+                    Some(text) => {
+                        body.extend((text.len() as i32).to_be_bytes());
+                        body.extend(text.as_bytes());
+                    }
+                    None => body.extend((-1i32).to_be_bytes()),
+                }
+            }
+            self.message(b'D', &body); // DataRow
+        }
+    }
+
+    fn command_complete(&mut self, tag: &str) {
+        let mut body = vec![];
+        put_str(&mut body, tag);
+        self.message(b'C', &body);
+    }
+
+    fn error(&mut self, code: &str, message: &str) {
+        let mut body = vec![];
+        for (field, value) in [
+            (b'S', "ERROR"),
+            (b'V', "ERROR"),
+            (b'C', code),
+            (b'M', message),
+        ] {
+            body.push(field);
+            put_str(&mut body, value);
+        }
+        body.push(0);
+        self.message(b'E', &body); // ErrorResponse
+    }
+
+    fn ready_for_query(&mut self) {
+        self.message(b'Z', b"I"); // idle, not in a transaction
+    }
+
+    fn message(&mut self, tag: u8, body: &[u8]) {
+        self.out.push(tag);
+        self.out.extend((body.len() as i32 + 4).to_be_bytes());
+        self.out.extend(body);
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        self.stream.write_all(&self.out).await?;
+        self.out.clear();
+        self.stream.flush().await
+    }
+}
+
+fn put_str(buffer: &mut Vec<u8>, s: &str) {
+    buffer.extend(s.as_bytes());
+    buffer.push(0);
+}
+
+fn put_i16(buffer: &mut Vec<u8>, n: usize) {
+    buffer.extend((n as i16).to_be_bytes());
+}
+
+/// The type of a column from every value in it: `int8`, `float8` or `bytea` where the
+/// values that are not `NULL` agree, `float8` for integers mixed with reals, and `text`
+/// otherwise.
+fn column_oid<'v>(values: impl Iterator<Item = &'v Value>) -> i32 {
+    let mut oid = None;
+    for value in values {
+        let value_oid = match value {
+            Value::Null => continue,
+            Value::Integer(_) => INT8_OID,
+            Value::Real(_) => FLOAT8_OID,
+            Value::Blob(_) => BYTEA_OID,
+            Value::Text(_) => return TEXT_OID,
+        };
+        oid = match (oid, value_oid) {
+            (None, value_oid) => Some(value_oid),
+            (Some(oid), value_oid) if oid == value_oid => Some(oid),
+            (Some(INT8_OID | FLOAT8_OID), INT8_OID | FLOAT8_OID) => Some(FLOAT8_OID),
+            _ => return TEXT_OID,
+        };
+    }
+    oid.unwrap_or(TEXT_OID)
+}
+
+/// `None` for `NULL`; blobs use the `\x` hex format of `bytea`.
+fn text_format(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::Blob(blob) => {
+            let hex = blob.iter().map(|byte| format!("{:02x}", byte));
+            Some(format!("\\x{}", hex.collect::<String>()))
+        }
+        value => Some(value.to_string()),
+    }
+}
+
+/// `CREATE TABLE`, `DROP VIEW`, `USE` and so on, from the statement's leading keywords.
+fn command_tag(statement: &str) -> String {
+    let mut words = Tokenizer::new(statement)
+        .iter()
+        .map_while(Result::ok)
+        .filter(|t| !t.token().is_comment())
+        .map(|t| t.text().to_uppercase());
+    let first = words.next().unwrap_or_default();
+    match first.as_str() {
+        "CREATE" | "DROP" | "ALTER" => format!("{} {}", first, words.next().unwrap_or_default()),
+        _ => first,
+    }
+}
+
+fn sqlstate(err: &DatabaseError) -> &'static str {
+    match err {
+        DatabaseError::Parse(_) => "42601",
+        DatabaseError::Catalog(CatalogError::UnsupportedQuery) => "0A000",
+        DatabaseError::Catalog(_) => "42000",
+        DatabaseError::Function(_) => "22000",
+        DatabaseError::Deserialize(_) => "XX000",
+        DatabaseError::ReadOnly => "25006",
+        DatabaseError::Busy => "55P03",
+        DatabaseError::Storage(_) => "58030",
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, PoisonError, RwLock, Weak},
+};
+
+use tokio::sync::{Mutex, MutexGuard};
+
+use super::{
+    database::{Database, DatabaseError, ResultSet},
+    mvcc::{Stamp, TxnId},
+    query::{BodyKind, Query},
+    storage::Storage,
+    tokenizer::Tokenizer,
+    value::Value,
+};
+
+/// A committed version of the database, read at the transaction that wrote it.
+///
+/// Snapshots share rows with each other and with the running transaction, reading
+/// only the row versions committed when they were taken.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    db: Arc<Database>,
+}
+
+impl Snapshot {
+    pub fn txn(&self) -> TxnId {
+        self.db.catalog().txn()
+    }
+
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    /// Run a read-only statement against this version.
+    pub fn execute(&self, sql: &str, params: &[Value]) -> Result<ResultSet, DatabaseError> {
+        self.db.read(sql, params)
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    current: RwLock<Snapshot>,
+    /// Held by the one transaction allowed to write, along with the file commits are
+    /// saved to.
+    writer: Mutex<Option<Storage>>,
+    /// Every version published, oldest first, for finding the oldest one still read.
+    published: std::sync::Mutex<VecDeque<Weak<Database>>>,
+}
+
+/// A database handle that can be cloned across threads.
+///
+/// Readers work on the last committed [`Snapshot`] without waiting; writers take turns,
+/// each adding row versions tagged with its transaction ID and publishing them on commit.
+#[derive(Debug, Clone)]
+pub struct SharedDatabase {
+    inner: Arc<Inner>,
+}
+
+impl SharedDatabase {
+    /// Share `db`, saving each commit to its file if it was opened from one.
+    pub fn new(mut db: Database) -> Self {
+        let storage = db.take_storage();
+        let db = Arc::new(db);
+        Self {
+            inner: Arc::new(Inner {
+                published: std::sync::Mutex::new(VecDeque::from([Arc::downgrade(&db)])),
+                current: RwLock::new(Snapshot { db }),
+                writer: Mutex::new(storage),
+            }),
+        }
+    }
+
+    /// Make `snapshot` the latest committed version.
+    fn publish(&self, snapshot: Snapshot) {
+        self.inner
+            .published
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push_back(Arc::downgrade(&snapshot.db));
+        *self
+            .inner
+            .current
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = snapshot;
+    }
+
+    /// The stamp of the oldest version a snapshot or transaction still reads. Row
+    /// versions deleted at or before it are seen by no one.
+    pub fn horizon(&self) -> Stamp {
+        let mut published = self
+            .inner
+            .published
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        published.retain(|db| db.strong_count() > 0);
+        // the oldest may be dropped by now, leaving the one after it the oldest
+        match published.iter().find_map(Weak::upgrade) {
+            Some(db) => db.catalog().stamp(),
+            None => self.snapshot().database().catalog().stamp(),
+        }
+    }
+
+    /// The latest committed version as a database of its own, which takes over saving
+    /// to the file; other handles and snapshots go on with a copy of it in memory.
+    /// Without any, nothing is copied and this cannot fail.
+    pub async fn into_database(self) -> Result<Database, DatabaseError> {
+        let mut writer = self.inner.writer.lock().await;
+        let snapshot = self.snapshot();
+        let mut db = snapshot.database().fork();
+        // one reference is held by `current` and one by `snapshot`
+        let shared = Arc::strong_count(&self.inner) > 1 || Arc::strong_count(&snapshot.db) > 2;
+        if writer.is_some() && shared {
+            // the rows in the file are the database's alone from now on
+            let copy = snapshot.database().try_clone()?;
+            self.publish(Snapshot { db: Arc::new(copy) });
+        }
+        if let Some(storage) = writer.take() {
+            db.set_storage(storage);
+        }
+        Ok(db)
+    }
+
+    /// The latest committed version.
+    pub fn snapshot(&self) -> Snapshot {
+        self.inner
+            .current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Start a write transaction, waiting for the current writer to finish.
+    pub async fn begin(&self) -> Transaction<'_> {
+        let writer = self.inner.writer.lock().await;
+        self.transaction(writer)
+    }
+
+    /// Start a write transaction unless another one is running.
+    pub fn try_begin(&self) -> Result<Transaction<'_>, DatabaseError> {
+        match self.inner.writer.try_lock() {
+            Ok(writer) => Ok(self.transaction(writer)),
+            Err(_) => Err(DatabaseError::Busy),
+        }
+    }
+
+    fn transaction<'a>(&'a self, writer: MutexGuard<'a, Option<Storage>>) -> Transaction<'a> {
+        // no other writer can commit while we hold the lock, so this is the version we replace
+        let base = self.snapshot();
+        let mut db = base.database().fork();
+        db.catalog_mut().begin_transaction(base.txn() + 1);
+        Transaction {
+            shared: self,
+            writer,
+            base,
+            db,
+            vacuum: false,
+            committed: false,
+        }
+    }
+
+    /// Run one statement: reads use a snapshot, writes commit a transaction of their own.
+    pub async fn execute(&self, sql: &str, params: &[Value]) -> Result<ResultSet, DatabaseError> {
+        let query = Query::new(Tokenizer::new(sql).tokenize()?)?;
+        if matches!(
+            query.body(),
+            BodyKind::Select | BodyKind::SelectDistinct | BodyKind::Explain
+        ) {
+            return self.snapshot().execute(sql, params);
+        }
+        let mut transaction = self.begin().await;
+        let result = transaction.execute(sql, params)?;
+        transaction.commit()?;
+        Ok(result)
+    }
+}
+
+/// Changes visible only to this transaction until [`Transaction::commit`].
+/// Dropping it without committing rolls back.
+pub struct Transaction<'a> {
+    shared: &'a SharedDatabase,
+    writer: MutexGuard<'a, Option<Storage>>,
+    /// The version this transaction started from, which holds every table it can write.
+    base: Snapshot,
+    db: Database,
+    /// Whether a `VACUUM` ran, so the file is compacted on commit.
+    vacuum: bool,
+    committed: bool,
+}
+
+impl Transaction<'_> {
+    pub fn execute(&mut self, sql: &str, params: &[Value]) -> Result<ResultSet, DatabaseError> {
+        let query = Query::new(Tokenizer::new(sql).tokenize()?)?;
+        let result = self.db.execute(sql, params)?;
+        self.vacuum |= query.body() == BodyKind::Vacuum;
+        Ok(result)
+    }
+
+    /// Save the changes and publish them as the latest version, returning its
+    /// transaction ID. Nothing is published if they cannot be saved; they are if only
+    /// compacting the file after a `VACUUM` fails.
+    pub fn commit(mut self) -> Result<TxnId, DatabaseError> {
+        if let Some(storage) = self.writer.as_mut() {
+            storage.write(self.db.catalog())?;
+        }
+        let snapshot = Snapshot {
+            db: Arc::new(self.db.fork()),
+        };
+        let txn = snapshot.txn();
+        self.shared.publish(snapshot.clone());
+        self.committed = true;
+        // the version this transaction started from need not be kept for it any more
+        self.base = snapshot;
+        let horizon = self.shared.horizon();
+        self.db.catalog().prune_rows(horizon);
+        if let (Some(storage), true) = (self.writer.as_mut(), self.vacuum) {
+            storage.vacuum(self.db.catalog(), horizon)?;
+        }
+        Ok(txn)
+    }
+}
+
+impl Drop for Transaction<'_> {
+    /// Remove the row versions this transaction wrote to tables other snapshots share.
+    /// Tables it created are dropped along with it.
+    fn drop(&mut self) {
+        if !self.committed {
+            let txn = self.db.catalog().txn();
+            self.base.database().catalog().rollback(Stamp::begin(txn));
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::{cmp::Ordering, collections::HashSet};
+
+use serde_json::Value as Json;
+
+use super::{database::Row, executor::sort_order, expression::BinaryOperator, json, value::Value};
+
+/// The table `ANALYZE` keeps its statistics in, one row per column of each table.
+pub const STATS_TABLE: &str = "xsdb_stats";
+
+/// Columns of [`STATS_TABLE`] and their types.
+pub const STATS_COLUMNS: [(&str, &str); 6] = [
+    ("table_name", "TEXT"),
+    ("column_name", "TEXT"),
+    ("row_count", "INTEGER"),
+    ("distinct_count", "INTEGER"),
+    ("null_count", "INTEGER"),
+    ("histogram", "JSON"),
+];
+
+/// Buckets of a histogram, each holding about as many values.
+const BUCKETS: usize = 8;
+
+/// Fraction of rows a condition is taken to keep when the statistics cannot tell.
+pub const DEFAULT_SELECTIVITY: f64 = 1.0 / 3.0;
+
+/// Fraction of rows `column = value` is taken to keep without statistics.
+pub const DEFAULT_EQ_SELECTIVITY: f64 = 0.1;
+
+/// What `ANALYZE` found in one column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnStats {
+    pub rows: u64,
+    pub distinct: u64,
+    pub nulls: u64,
+    /// Bounds of equal-depth buckets over the numbers and text of the column, smallest
+    /// value first and largest last.
+    pub histogram: Vec<Value>,
+}
+
+impl ColumnStats {
+    pub fn collect<'v>(values: impl Iterator<Item = &'v Value>) -> Self {
+        let mut rows = 0;
+        let mut nulls = 0;
+        let mut distinct = HashSet::new();
+        let mut ordered = vec![];
+        for value in values {
+            rows += 1;
+            match value {
+                Value::Null => nulls += 1,
+                value => {
+                    distinct.insert(value.distinct_key());
+                    if !matches!(value, Value::Blob(_)) {
+                        ordered.push(value);
+                    }
+                }
+            }
+        }
+        ordered.sort_by(|left, right| sort_order(left, right));
+        let histogram = match ordered.len() {
+            0 => vec![],
+            len => (0..=BUCKETS)
+                .map(|bucket| ordered[(bucket * (len - 1)) / BUCKETS].clone())
+                .collect(),
+        };
+        Self {
+            rows,
+            distinct: distinct.len() as u64,
+            nulls,
+            histogram,
+        }
+    }
+
+    /// The row of [`STATS_TABLE`] holding these statistics.
+    pub fn to_row(&self, table: &str, column: &str) -> Row {
+        let histogram = self
+            .histogram
+            .iter()
+            .map(|value| match value {
+                Value::Integer(integer) => Json::from(*integer),
+                Value::Real(real) => Json::from(*real),
+                value => Json::from(value.to_string()),
+            })
+            .collect();
+        vec![
+            Value::Text(table.to_string()),
+            Value::Text(column.to_string()),
+            Value::Integer(self.rows as i64),
+            Value::Integer(self.distinct as i64),
+            Value::Integer(self.nulls as i64),
+            Value::Text(Json::Array(histogram).to_string()),
+        ]
+    }
+
+    /// The table, column and statistics of a row of [`STATS_TABLE`], if it holds them.
+    pub fn from_row(row: &[Value]) -> Option<(String, String, Self)> {
+        let [Value::Text(table), Value::Text(column), Value::Integer(rows), Value::Integer(distinct), Value::Integer(nulls), Value::Text(histogram)] =
+            row
+        else {
+            return None;
+        };
+        let Ok(Json::Array(histogram)) = serde_json::from_str(histogram) else {
+            return None;
+        };
+        let stats = Self {
+            rows: u64::try_from(*rows).ok()?,
+            distinct: u64::try_from(*distinct).ok()?,
+            nulls: u64::try_from(*nulls).ok()?,
+            histogram: histogram.iter().map(json::to_value).collect(),
+        };
+        Some((table.clone(), column.clone(), stats))
+    }
+
+    /// Fraction of the rows for which `column operator value` holds.
+    pub fn selectivity(&self, operator: BinaryOperator, value: &Value) -> f64 {
+        if self.rows == 0 || value.is_null() {
+            return 0.0;
+        }
+        let not_null = (self.rows - self.nulls) as f64 / self.rows as f64;
+        let equal = not_null / self.distinct.max(1) as f64;
+        match operator {
+            BinaryOperator::Eq => equal,
+            BinaryOperator::NotEq => not_null - equal,
+            BinaryOperator::Lt | BinaryOperator::Lte => not_null * self.below(value),
+            BinaryOperator::Gt | BinaryOperator::Gte => not_null * (1.0 - self.below(value)),
+            _ => DEFAULT_SELECTIVITY,
+        }
+    }
+
+    /// Fraction of the rows for which `column BETWEEN low AND high` holds.
+    pub fn between(&self, low: &Value, high: &Value) -> f64 {
+        if self.rows == 0 || low.is_null() || high.is_null() {
+            return 0.0;
+        }
+        if self.histogram.len() < 2 {
+            return DEFAULT_SELECTIVITY;
+        }
+        let not_null = (self.rows - self.nulls) as f64 / self.rows as f64;
+        let equal = not_null / self.distinct.max(1) as f64;
+        let within = (self.below(high) - self.below(low)).max(0.0);
+        (not_null * within + equal).min(not_null)
+    }
+
+    /// Fraction of the rows for which `column LIKE 'prefix%'` holds: the text from
+    /// `prefix` up to the last text starting with it.
+    pub fn prefixed(&self, prefix: &str) -> f64 {
+        let last = format!("{prefix}{}", char::MAX);
+        self.between(&Value::Text(prefix.to_string()), &Value::Text(last))
+    }
+
+    /// Fraction of the values in the histogram below `value`, counting half of the
+    /// bucket it falls in.
+    fn below(&self, value: &Value) -> f64 {
+        if self.histogram.len() < 2 {
+            return DEFAULT_SELECTIVITY;
+        }
+        let buckets = (self.histogram.len() - 1) as f64;
+        let bounds = self
+            .histogram
+            .iter()
+            .filter(|bound| sort_order(bound, value) == Ordering::Less)
+            .count();
+        match bounds {
+            0 => 0.0,
+            bounds if bounds == self.histogram.len() => 1.0,
+            bounds => (bounds as f64 - 0.5) / buckets,
+        }
+    }
+}
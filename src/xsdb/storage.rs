@@ -0,0 +1,634 @@
+use std::{
+    collections::BTreeSet,
+    fmt, io, mem,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use super::{
+    buffer::{BufferError, BufferPool, Metrics, PageId, PageStore, PAGE_SIZE},
+    catalog::{Catalog, RowId},
+    database::Row,
+    mvcc::{RowStore, Stamp},
+    value::Value,
+};
+
+/// The first bytes of every database file, ending in the format version.
+const MAGIC: &[u8; 8] = b"xsdb\0\0\0\x02";
+
+/// Bytes at the start of each page of a chain that hold the ID of the next one,
+/// `0` for the last.
+const NEXT: usize = 8;
+
+/// Bytes of data each page of a chain holds after [`NEXT`].
+const PAYLOAD: usize = PAGE_SIZE - NEXT;
+
+/// Kinds of the records of a [`Heap`].
+const INSERT: u8 = 1;
+const DELETE: u8 = 2;
+
+/// Bytes of an insert record before the row: kind, row id and length of the row.
+const INSERT_HEADER: usize = 17;
+
+#[derive(Debug)]
+pub enum StorageError {
+    Buffer(BufferError),
+    /// The file is not a database file, or it is damaged.
+    Corrupt,
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Buffer(err) => write!(f, "{}", err),
+            StorageError::Corrupt => write!(f, "not a database file, or a damaged one"),
+        }
+    }
+}
+
+impl From<BufferError> for StorageError {
+    fn from(err: BufferError) -> Self {
+        StorageError::Buffer(err)
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(err: io::Error) -> Self {
+        StorageError::Buffer(BufferError::Io(err))
+    }
+}
+
+/// Page 0 of the file. Free pages are not recorded: they are the ones neither the
+/// image nor a heap it names reaches.
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    /// Pages in the file, the header included.
+    pages: u64,
+    /// First page of the image, `0` before anything was written.
+    first: PageId,
+    /// Length of the image in bytes.
+    len: u64,
+}
+
+impl Header {
+    fn read(page: &[u8]) -> Result<Option<Self>, StorageError> {
+        if page.iter().all(|&b| b == 0) {
+            return Ok(None);
+        }
+        if &page[..8] != MAGIC {
+            return Err(StorageError::Corrupt);
+        }
+        let header = Self {
+            pages: read_u64(page, 8),
+            first: read_u64(page, 16),
+            len: read_u64(page, 24),
+        };
+        Ok(Some(header))
+    }
+
+    fn write(&self, page: &mut [u8]) {
+        page.fill(0);
+        page[..8].copy_from_slice(MAGIC);
+        page[8..16].copy_from_slice(&self.pages.to_le_bytes());
+        page[16..24].copy_from_slice(&self.first.to_le_bytes());
+        page[24..32].copy_from_slice(&self.len.to_le_bytes());
+    }
+}
+
+/// The pages of a database file, shared by the [`Storage`] that commits it and the
+/// [`Heap`]s of its tables.
+pub(crate) struct Pager {
+    pool: BufferPool<Box<dyn PageStore + Send>>,
+    /// Pages in the file, the header included, counting new ones not committed yet.
+    pages: u64,
+    /// Pages nothing committed uses, reused lowest first.
+    free: BTreeSet<PageId>,
+    /// Pages let go of since the last commit, which the committed file may still use.
+    released: Vec<PageId>,
+    /// Take new pages at the end of the file even while some are free.
+    grow: bool,
+}
+
+impl fmt::Debug for Pager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pager")
+            .field("pages", &self.pages)
+            .field("free", &self.free.len())
+            .field("metrics", &self.pool.metrics())
+            .finish()
+    }
+}
+
+pub(crate) type SharedPager = Arc<Mutex<Pager>>;
+
+fn lock(pager: &SharedPager) -> MutexGuard<'_, Pager> {
+    pager.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Pager {
+    /// A page to write to: the lowest free one, else a new one at the end of the file.
+    fn allocate(&mut self) -> PageId {
+        if !self.grow {
+            if let Some(id) = self.free.pop_first() {
+                return id;
+            }
+        }
+        self.pages += 1;
+        self.pages - 1
+    }
+
+    /// The `len` bytes long chain of pages starting at `first`, in order.
+    fn chain(&mut self, first: PageId, len: u64) -> Result<Vec<PageId>, StorageError> {
+        let count = len.div_ceil(PAYLOAD as u64);
+        if count >= self.pages {
+            return Err(StorageError::Corrupt);
+        }
+        let mut ids = Vec::with_capacity(count as usize);
+        let mut id = first;
+        for _ in 0..count {
+            if id == 0 || id >= self.pages {
+                return Err(StorageError::Corrupt);
+            }
+            ids.push(id);
+            let page = self.pool.pin(id)?;
+            id = read_u64(self.pool.page(&page), 0);
+            self.pool.unpin(page);
+        }
+        Ok(ids)
+    }
+
+    /// Fill `buffer` from offset `at` of the chain `pages`.
+    fn read(&mut self, pages: &[PageId], at: u64, buffer: &mut [u8]) -> Result<(), StorageError> {
+        let mut done = 0;
+        while done < buffer.len() {
+            let offset = at as usize + done;
+            let id = *pages.get(offset / PAYLOAD).ok_or(StorageError::Corrupt)?;
+            let start = NEXT + offset % PAYLOAD;
+            let take = (buffer.len() - done).min(PAGE_SIZE - start);
+            let page = self.pool.pin(id)?;
+            buffer[done..done + take].copy_from_slice(&self.pool.page(&page)[start..start + take]);
+            self.pool.unpin(page);
+            done += take;
+        }
+        Ok(())
+    }
+
+    /// Write `bytes` at offset `at` of the chain `pages`, which is at most that long,
+    /// adding pages to it as needed.
+    fn write(
+        &mut self,
+        pages: &mut Vec<PageId>,
+        at: u64,
+        bytes: &[u8],
+    ) -> Result<(), StorageError> {
+        let mut done = 0;
+        while done < bytes.len() {
+            let offset = at as usize + done;
+            if offset / PAYLOAD == pages.len() {
+                self.extend(pages)?;
+            }
+            let start = NEXT + offset % PAYLOAD;
+            let take = (bytes.len() - done).min(PAGE_SIZE - start);
+            let page = self.pool.pin(pages[offset / PAYLOAD])?;
+            self.pool.page_mut(&page)[start..start + take]
+                .copy_from_slice(&bytes[done..done + take]);
+            self.pool.unpin(page);
+            done += take;
+        }
+        Ok(())
+    }
+
+    /// Add an empty page to the end of the chain `pages`.
+    fn extend(&mut self, pages: &mut Vec<PageId>) -> Result<(), StorageError> {
+        let id = self.allocate();
+        let page = self.pool.pin(id)?;
+        self.pool.page_mut(&page).fill(0);
+        self.pool.unpin(page);
+        if let Some(&last) = pages.last() {
+            let page = self.pool.pin(last)?;
+            self.pool.page_mut(&page)[..NEXT].copy_from_slice(&id.to_le_bytes());
+            self.pool.unpin(page);
+        }
+        pages.push(id);
+        Ok(())
+    }
+
+    /// Write the header and wait until it is on disk, along with every page before it.
+    fn commit(&mut self, header: &Header) -> Result<(), StorageError> {
+        self.pool.flush()?;
+        self.pool.sync()?;
+        let page = self.pool.pin(0)?;
+        header.write(self.pool.page_mut(&page));
+        self.pool.unpin(page);
+        self.pool.flush()?;
+        Ok(self.pool.sync()?)
+    }
+}
+
+/// The rows of one table, appended to a chain of pages: a record for each version
+/// written, and one for each row deleted. Replaying the records in order gives the
+/// rows as they were last saved.
+///
+/// Records past the length last committed are not part of the file yet; rolling back
+/// cuts them off again.
+#[derive(Debug)]
+pub(crate) struct Heap {
+    pager: SharedPager,
+    pages: Vec<PageId>,
+    len: u64,
+}
+
+impl Heap {
+    pub(crate) fn new(pager: &SharedPager) -> Self {
+        Self {
+            pager: Arc::clone(pager),
+            pages: vec![],
+            len: 0,
+        }
+    }
+
+    /// The heap of `len` bytes starting at page `first`.
+    fn open(pager: &SharedPager, first: PageId, len: u64) -> Result<Self, StorageError> {
+        let pages = lock(pager).chain(first, len)?;
+        Ok(Self {
+            pager: Arc::clone(pager),
+            pages,
+            len,
+        })
+    }
+
+    pub(crate) fn pager(&self) -> &SharedPager {
+        &self.pager
+    }
+
+    pub(crate) fn first(&self) -> PageId {
+        self.pages.first().copied().unwrap_or(0)
+    }
+
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Append a version of row `id`, returning where its record starts.
+    pub(crate) fn insert(&mut self, id: RowId, row: &[Value]) -> Result<u64, StorageError> {
+        let mut values = Vec::new();
+        put_u64(&mut values, row.len() as u64);
+        for value in row {
+            put_value(&mut values, value);
+        }
+        let mut record = vec![INSERT];
+        put_u64(&mut record, id);
+        put_bytes(&mut record, &values);
+        self.append(&record)
+    }
+
+    pub(crate) fn delete(&mut self, id: RowId) -> Result<(), StorageError> {
+        let mut record = vec![DELETE];
+        put_u64(&mut record, id);
+        self.append(&record).map(|_| ())
+    }
+
+    fn append(&mut self, record: &[u8]) -> Result<u64, StorageError> {
+        let at = self.len;
+        lock(&self.pager).write(&mut self.pages, at, record)?;
+        self.len += record.len() as u64;
+        Ok(at)
+    }
+
+    /// The row of the insert record at `at`.
+    pub(crate) fn row(&self, at: u64) -> Result<Row, StorageError> {
+        let mut pager = lock(&self.pager);
+        let mut header = [0; INSERT_HEADER];
+        pager.read(&self.pages, at, &mut header)?;
+        if header[0] != INSERT {
+            return Err(StorageError::Corrupt);
+        }
+        let len = usize::try_from(read_u64(&header, 9)).map_err(|_| StorageError::Corrupt)?;
+        if at + (INSERT_HEADER + len) as u64 > self.len {
+            return Err(StorageError::Corrupt);
+        }
+        let mut values = vec![0; len];
+        pager.read(&self.pages, at + INSERT_HEADER as u64, &mut values)?;
+        drop(pager);
+
+        let mut reader = Reader {
+            image: &values,
+            at: 0,
+        };
+        let row = (0..reader.u64()?)
+            .map(|_| reader.value())
+            .collect::<Result<_, _>>()?;
+        if reader.at != values.len() {
+            return Err(StorageError::Corrupt);
+        }
+        Ok(row)
+    }
+
+    /// Every record in order: the row id, and where the row starts for an insert.
+    pub(crate) fn records(&self) -> Result<Vec<(RowId, Option<u64>)>, StorageError> {
+        let mut pager = lock(&self.pager);
+        let mut records = vec![];
+        let mut at = 0;
+        while at < self.len {
+            let mut header = [0; INSERT_HEADER];
+            let short = (self.len - at).min(INSERT_HEADER as u64) as usize;
+            pager.read(&self.pages, at, &mut header[..short])?;
+            let id = read_u64(&header, 1);
+            match header[0] {
+                INSERT if short == INSERT_HEADER => {
+                    records.push((id, Some(at)));
+                    at = read_u64(&header, 9)
+                        .checked_add(at + INSERT_HEADER as u64)
+                        .ok_or(StorageError::Corrupt)?;
+                }
+                DELETE if short >= 9 => {
+                    records.push((id, None));
+                    at += 9;
+                }
+                _ => return Err(StorageError::Corrupt),
+            }
+        }
+        if at != self.len {
+            return Err(StorageError::Corrupt);
+        }
+        Ok(records)
+    }
+
+    /// Cut the records off after the first `len` bytes. The pages no longer needed were
+    /// added since the last commit, so they are free again right away.
+    pub(crate) fn truncate(&mut self, len: u64) {
+        let keep = len.div_ceil(PAYLOAD as u64) as usize;
+        lock(&self.pager)
+            .free
+            .extend(self.pages.drain(keep.min(self.pages.len())..));
+        self.len = self.len.min(len);
+    }
+}
+
+impl Drop for Heap {
+    /// The pages are freed once a commit no longer names the heap.
+    fn drop(&mut self) {
+        let pages = mem::take(&mut self.pages);
+        lock(&self.pager).released.extend(pages);
+    }
+}
+
+/// A database file: a header page, an image of the schema that names a heap of pages
+/// for the rows of each table, and the heaps, all read and written through one
+/// [`BufferPool`].
+///
+/// Writes go to pages the committed file does not use, so a commit only has to write
+/// the pages that changed, then a new image, and then the header that points to it.
+/// The pages the old image and dropped heaps used are reused once the header is on
+/// disk. [`Storage::vacuum`] rewrites everything at the start of the file and cuts off
+/// the rest.
+#[derive(Debug)]
+pub struct Storage {
+    pager: SharedPager,
+    header: Header,
+    /// The pages of the image the header points to.
+    image: Vec<PageId>,
+}
+
+impl Storage {
+    /// Open the file in `store`, setting up an empty one if it has no pages yet,
+    /// with `memory_limit` bytes to cache pages in.
+    pub fn open(
+        store: impl PageStore + Send + 'static,
+        memory_limit: usize,
+    ) -> Result<Self, StorageError> {
+        let mut pool = BufferPool::new(Box::new(store) as Box<dyn PageStore + Send>, memory_limit);
+        let page = pool.pin(0)?;
+        let header = Header::read(pool.page(&page));
+        pool.unpin(page);
+        let header = header?;
+        let mut pager = Pager {
+            pool,
+            pages: header.map_or(1, |header| header.pages),
+            free: BTreeSet::new(),
+            released: vec![],
+            grow: false,
+        };
+        let header = match header {
+            Some(header) => header,
+            None => {
+                let header = Header {
+                    pages: 1,
+                    first: 0,
+                    len: 0,
+                };
+                pager.commit(&header)?;
+                header
+            }
+        };
+        Ok(Self {
+            pager: Arc::new(Mutex::new(pager)),
+            header,
+            image: vec![],
+        })
+    }
+
+    /// Pages in the file, the header included.
+    pub fn pages(&self) -> u64 {
+        lock(&self.pager).pages
+    }
+
+    /// Pages nothing uses, waiting to be reused or cut off by [`Storage::vacuum`].
+    pub fn free_pages(&self) -> u64 {
+        lock(&self.pager).free.len() as u64
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        lock(&self.pager).pool.metrics()
+    }
+
+    pub(crate) fn pager(&self) -> &SharedPager {
+        &self.pager
+    }
+
+    /// The schema script of the image last written, and the rows of each table, with the
+    /// database and table they belong to; nothing for a new file. Every page neither
+    /// reaches is free.
+    pub(crate) fn load(&mut self) -> Result<(String, Vec<TableRows>), StorageError> {
+        let mut pager = lock(&self.pager);
+        self.image = pager.chain(self.header.first, self.header.len)?;
+        let mut image = vec![0; self.header.len as usize];
+        pager.read(&self.image, 0, &mut image)?;
+        drop(pager);
+        if image.is_empty() {
+            return Ok((String::new(), vec![]));
+        }
+
+        let mut reader = Reader {
+            image: &image,
+            at: 0,
+        };
+        let script = reader.text()?;
+        let mut used: BTreeSet<_> = self.image.iter().copied().collect();
+        let mut tables = Vec::new();
+        for _ in 0..reader.u64()? {
+            let database = reader.text()?;
+            let name = reader.text()?;
+            let heap = Heap::open(&self.pager, reader.u64()?, reader.u64()?)?;
+            for &id in &heap.pages {
+                if !used.insert(id) {
+                    return Err(StorageError::Corrupt);
+                }
+            }
+            tables.push((database, name, RowStore::open(heap, reader.u64()?)?));
+        }
+        if reader.at != image.len() {
+            return Err(StorageError::Corrupt);
+        }
+        let mut pager = lock(&self.pager);
+        pager.free = (1..pager.pages).filter(|id| !used.contains(id)).collect();
+        Ok((script, tables))
+    }
+
+    /// Save `catalog`: write the pages its tables changed and a new image, wait until
+    /// they are on disk, then write the header that points to them.
+    pub(crate) fn write(&mut self, catalog: &Catalog) -> Result<(), StorageError> {
+        let image = encode(catalog)?;
+        let mut pager = lock(&self.pager);
+        let mut pages = vec![];
+        pager.write(&mut pages, 0, &image)?;
+        let header = Header {
+            pages: pager.pages,
+            first: pages.first().copied().unwrap_or(0),
+            len: image.len() as u64,
+        };
+        pager.commit(&header)?;
+
+        self.header = header;
+        let old = mem::replace(&mut self.image, pages);
+        let released = mem::take(&mut pager.released);
+        pager.free.extend(old.into_iter().chain(released));
+        drop(pager);
+        catalog.committed();
+        Ok(())
+    }
+
+    /// Rewrite `catalog`, which was just written, onto the pages right after the
+    /// header and cut the file off after it, leaving no free pages. Row versions deleted
+    /// at or before `horizon` are dropped.
+    ///
+    /// The rows are rewritten twice: first to new pages past the end of the file, so
+    /// that the pages at the start are all free once that is committed, then to those.
+    pub(crate) fn vacuum(&mut self, catalog: &Catalog, horizon: Stamp) -> Result<(), StorageError> {
+        for grow in [true, false] {
+            lock(&self.pager).grow = grow;
+            let rewritten = catalog
+                .rewrite_rows(horizon)
+                .and_then(|()| self.write(catalog));
+            lock(&self.pager).grow = false;
+            rewritten?;
+        }
+
+        let mut pager = lock(&self.pager);
+        let pages = (1..pager.pages)
+            .rev()
+            .find(|id| !pager.free.contains(id))
+            .map_or(1, |id| id + 1);
+        pager.free.retain(|&id| id < pages);
+        pager.pages = pages;
+        self.header.pages = pages;
+        pager.commit(&self.header)?;
+        Ok(pager.pool.truncate(pages)?)
+    }
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+/// Rows read back from a file, with the database and table they belong to.
+pub(crate) type TableRows = (String, String, RowStore);
+
+/// The image of a catalog: the script that rebuilds its schema, then for each table
+/// where its heap starts, how long it is, and the id of its next row.
+fn encode(catalog: &Catalog) -> Result<Vec<u8>, StorageError> {
+    let mut image = Vec::new();
+    put_bytes(&mut image, catalog.dump_schema()?.as_bytes());
+    let tables: Vec<_> = catalog.all_tables().collect();
+    put_u64(&mut image, tables.len() as u64);
+    for (database, name, table) in tables {
+        put_bytes(&mut image, database.as_bytes());
+        put_bytes(&mut image, name.as_bytes());
+        let (first, len, next_row_id) = table.extent();
+        put_u64(&mut image, first);
+        put_u64(&mut image, len);
+        put_u64(&mut image, next_row_id);
+    }
+    Ok(image)
+}
+
+fn put_u64(image: &mut Vec<u8>, n: u64) {
+    image.extend_from_slice(&n.to_le_bytes());
+}
+
+fn put_bytes(image: &mut Vec<u8>, bytes: &[u8]) {
+    put_u64(image, bytes.len() as u64);
+    image.extend_from_slice(bytes);
+}
+
+fn put_value(image: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => image.push(0),
+        Value::Integer(integer) => {
+            image.push(1);
+            image.extend_from_slice(&integer.to_le_bytes());
+        }
+        Value::Real(real) => {
+            image.push(2);
+            image.extend_from_slice(&real.to_bits().to_le_bytes());
+        }
+        Value::Text(text) => {
+            image.push(3);
+            put_bytes(image, text.as_bytes());
+        }
+        Value::Blob(blob) => {
+            image.push(4);
+            put_bytes(image, blob);
+        }
+    }
+}
+
+struct Reader<'a> {
+    image: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], StorageError> {
+        let bytes = self
+            .image
+            .get(self.at..self.at.saturating_add(len))
+            .ok_or(StorageError::Corrupt)?;
+        self.at += len;
+        Ok(bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64, StorageError> {
+        Ok(read_u64(self.take(8)?, 0))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], StorageError> {
+        let len = self.u64()?;
+        self.take(usize::try_from(len).map_err(|_| StorageError::Corrupt)?)
+    }
+
+    fn text(&mut self) -> Result<String, StorageError> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| StorageError::Corrupt)
+    }
+
+    fn value(&mut self) -> Result<Value, StorageError> {
+        let value = match self.take(1)?[0] {
+            0 => Value::Null,
+            1 => Value::Integer(self.u64()? as i64),
+            2 => Value::Real(f64::from_bits(self.u64()?)),
+            3 => Value::Text(self.text()?),
+            4 => Value::Blob(self.bytes()?.to_vec()),
+            _ => return Err(StorageError::Corrupt),
+        };
+        Ok(value)
+    }
+}
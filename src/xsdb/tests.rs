@@ -0,0 +1,3751 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use inquire::Autocomplete;
+
+use super::{
+    buffer::{BufferError, BufferPool, Metrics, PageId, PageStore, PAGE_SIZE},
+    catalog::{Catalog, CatalogError, TriggerEvent, TriggerTiming},
+    completion::SqlCompleter,
+    database::{Database, DatabaseError, ResultSet, Row, POOL_MEMORY},
+    editor::{Action, LineEditor},
+    expression::Expression,
+    fulltext::{Analyzer, InvertedIndex},
+    functions::{FunctionError, Functions},
+    highlight::highlight,
+    migrations::{MigrationError, Migrations},
+    query::{BodyKind, Query, WhenKind},
+    select::{ResultColumn, Select},
+    server,
+    shared::{SharedDatabase, Snapshot},
+    storage::{Storage, StorageError},
+    tokenizer::{Edit, ParseError, Token, TokenKind, TokenValue, Tokenizer},
+    value::Value,
+    window::{self, FrameBound, Window, WindowRow},
+};
+
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    Tokenizer::new(source).tokenize().unwrap()
+}
+
+fn kinds(source: &str) -> Vec<TokenKind> {
+    tokenize(source).iter().map(|t| t.token()).collect()
+}
+
+#[test]
+fn test_nested_block_comment() {
+    let tokens = tokenize("SELECT /* outer /* inner */ still outer */ 1");
+    assert_eq!(
+        tokens.iter().map(|t| t.token()).collect::<Vec<_>>(),
+        vec![
+            TokenKind::SELECT,
+            TokenKind::MultiLineComment,
+            TokenKind::Number,
+            TokenKind::EOF
+        ]
+    );
+    assert_eq!(tokens[1].text(), "/* outer /* inner */ still outer */");
+}
+
+#[test]
+fn test_unterminated_block_comment() {
+    assert!(Tokenizer::new("/* /* */").tokenize().is_err());
+}
+
+#[test]
+fn test_block_comment_after_operator() {
+    assert_eq!(
+        kinds("1 +/* c */2"),
+        vec![
+            TokenKind::Number,
+            TokenKind::Plus,
+            TokenKind::MultiLineComment,
+            TokenKind::Number,
+            TokenKind::EOF
+        ]
+    );
+}
+
+#[test]
+fn test_escaped_string() {
+    let tokens = tokenize(r"E'a\tb\n\'c\'' e'\x41\101ç'");
+    assert_eq!(tokens[0].token(), TokenKind::EscapedString);
+    assert_eq!(
+        tokens[0].value(),
+        Some(&TokenValue::String("a\tb\n'c'".to_string()))
+    );
+    assert_eq!(
+        tokens[1].value(),
+        Some(&TokenValue::String("AAç".to_string()))
+    );
+}
+
+#[test]
+fn test_quoted_string_value() {
+    let tokens = tokenize("'it''s' \"col\"");
+    assert_eq!(
+        tokens[0].value(),
+        Some(&TokenValue::String("it's".to_string()))
+    );
+    assert_eq!(
+        tokens[1].value(),
+        Some(&TokenValue::String("col".to_string()))
+    );
+}
+
+#[test]
+fn test_hex_string() {
+    let tokens = tokenize("X'ABcd01'");
+    assert_eq!(tokens[0].token(), TokenKind::HexString);
+    assert_eq!(
+        tokens[0].value(),
+        Some(&TokenValue::Blob(vec![0xab, 0xcd, 0x01]))
+    );
+    assert!(Tokenizer::new("X'ABC'").tokenize().is_err());
+    assert!(Tokenizer::new("X'ZZ'").tokenize().is_err());
+}
+
+#[test]
+fn test_hex_and_binary_numbers() {
+    let tokens = tokenize("0x1F 0b101 0.5");
+    assert_eq!(tokens[0].token(), TokenKind::HexNumber);
+    assert_eq!(tokens[0].value(), Some(&TokenValue::Integer(31)));
+    assert_eq!(tokens[1].token(), TokenKind::BinaryNumber);
+    assert_eq!(tokens[1].value(), Some(&TokenValue::Integer(5)));
+    assert_eq!(tokens[2].token(), TokenKind::Number);
+    assert!(Tokenizer::new("0b102").tokenize().is_err());
+}
+
+#[test]
+fn test_dollar_quoted_string() {
+    let tokens = tokenize("$$it's $x$ here$$ $fn$ şeker $$ $fn$");
+    assert_eq!(tokens[0].token(), TokenKind::DollarQuotedString);
+    assert_eq!(
+        tokens[0].value(),
+        Some(&TokenValue::String("it's $x$ here".to_string()))
+    );
+    assert_eq!(tokens[1].token(), TokenKind::DollarQuotedString);
+    assert_eq!(
+        tokens[1].value(),
+        Some(&TokenValue::String(" şeker $$ ".to_string()))
+    );
+    assert_eq!(tokens[1].text(), "$fn$ şeker $$ $fn$");
+    assert!(Tokenizer::new("$$never closed").tokenize().is_err());
+
+    // `$` followed by a digit is a placeholder, not the start of a `$tag$`
+    let tokens = tokenize("$1 $tag$ $2 $tag$ $$3$$");
+    assert_eq!(tokens[0].token(), TokenKind::Placeholder);
+    assert_eq!(tokens[0].value(), Some(&TokenValue::Integer(1)));
+    assert_eq!(tokens[1].token(), TokenKind::DollarQuotedString);
+    assert_eq!(
+        tokens[1].value(),
+        Some(&TokenValue::String(" $2 ".to_string()))
+    );
+    assert_eq!(tokens[2].token(), TokenKind::DollarQuotedString);
+    assert_eq!(
+        tokens[2].value(),
+        Some(&TokenValue::String("3".to_string()))
+    );
+}
+
+#[test]
+fn test_iterator_matches_tokenize() {
+    let source = "SELECT a, 'b' FROM t -- done\n";
+    let streamed = Tokenizer::new(source)
+        .iter()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(streamed.len(), tokenize(source).len());
+    assert_eq!(streamed.last().unwrap().token(), TokenKind::EOF);
+}
+
+#[test]
+fn test_iterator_stops_after_error() {
+    let mut tokens = Tokenizer::new("SELECT 'open").iter();
+    assert!(tokens.next().unwrap().is_ok());
+    assert!(tokens.next().unwrap().is_err());
+    assert!(tokens.next().is_none());
+}
+
+fn summary(tokens: &[Token]) -> Vec<(TokenKind, usize, usize, Option<TokenValue>)> {
+    tokens
+        .iter()
+        .map(|t| (t.token(), t.span().start, t.span().end, t.value().cloned()))
+        .collect()
+}
+
+fn assert_relex(old_source: &str, start: usize, old_end: usize, insert: &str) {
+    let new_source = format!(
+        "{}{}{}",
+        &old_source[..start],
+        insert,
+        &old_source[old_end..]
+    );
+    let old = tokenize(old_source);
+    let edit = Edit {
+        start,
+        old_end,
+        new_end: start + insert.len(),
+    };
+    let relexed = Tokenizer::new(&new_source).relex(&old, &edit).unwrap();
+    assert_eq!(
+        summary(&relexed),
+        summary(&tokenize(&new_source)),
+        "{}",
+        new_source
+    );
+}
+
+#[test]
+fn test_relex() {
+    let source = "SELECT name, 'x' FROM users WHERE id >= 10 -- note\nLIMIT 5";
+    assert_relex(source, 7, 11, "title");
+    assert_relex(source, 0, 3, "");
+    assert_relex(source, 3, 3, "ECT");
+    assert_relex(source, 13, 13, "'y' || ");
+    assert_relex(source, 39, 40, "-");
+    assert_relex(source, 14, 14, "/* ");
+    assert_relex(source, 14, 15, "a' 'b");
+    assert_relex(source, source.len(), source.len(), " OFFSET 2");
+    assert_relex(source, 0, 0, "  ");
+    assert_relex("", 0, 0, "SELECT 1");
+    // the lexer looks ahead past the token before the edit
+    assert_relex("SELECT 1e+ FROM t", 10, 10, "5");
+    assert_relex("SELECT 1e+5 FROM t", 10, 11, "");
+    assert_relex("SELECT a, 1.e", 13, 13, "-2");
+}
+
+fn parse(source: &str) -> Query {
+    Query::new(tokenize(source)).unwrap()
+}
+
+fn execute(catalog: &mut Catalog, source: &str) -> Result<(), CatalogError> {
+    catalog.execute(&parse(source), &Functions::new())
+}
+
+#[test]
+fn test_parse_create_table() {
+    let query = parse(
+        "CREATE TABLE IF NOT EXISTS users (id INTEGER PRIMARY KEY, /* name */ \"full name\" TEXT, PRIMARY KEY (id))",
+    );
+    assert_eq!(query.body(), BodyKind::CreateTable);
+    assert_eq!(query.when(), WhenKind::IfNotExists);
+    assert_eq!(query.value(), Some("users"));
+    let fields = query
+        .subqueries()
+        .iter()
+        .map(|field| field.value().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(fields, vec!["id", "full name"]);
+    assert!(Query::new(tokenize("CREATE TABLE users (id")).is_err());
+
+    let query = parse(
+        "CREATE TABLE \"customers\"(\"id\" SMALLSERIAL PRIMARY KEY, \
+         \"balance\" NUMERIC(10, 3) NOT NULL DEFAULT 0, \
+         \"customer_id\" INT2 REFERENCES \"customers\"(\"id\"), \
+         \"created_at\" TIMESTAMPTZ NOT NULL DEFAULT NOW(), \
+         total DOUBLE PRECISION DEFAULT -1.5, note DEFAULT ('a' || 'b'))",
+    );
+    let definitions = query
+        .subqueries()
+        .iter()
+        .map(|field| {
+            let parts = field.subqueries().iter();
+            let parts = parts.map(|part| (part.body(), part.value().unwrap()));
+            (field.value().unwrap(), parts.collect::<Vec<_>>())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        definitions,
+        vec![
+            ("id", vec![(BodyKind::Type, "SMALLSERIAL")]),
+            (
+                "balance",
+                vec![(BodyKind::Type, "NUMERIC(10, 3)"), (BodyKind::Default, "0")]
+            ),
+            ("customer_id", vec![(BodyKind::Type, "INT2")]),
+            (
+                "created_at",
+                vec![
+                    (BodyKind::Type, "TIMESTAMPTZ"),
+                    (BodyKind::Default, "NOW()")
+                ]
+            ),
+            (
+                "total",
+                vec![
+                    (BodyKind::Type, "DOUBLE PRECISION"),
+                    (BodyKind::Default, "-1.5")
+                ]
+            ),
+            ("note", vec![(BodyKind::Default, "('a' || 'b')")]),
+        ]
+    );
+    assert!(Query::new(tokenize("CREATE TABLE t (a DEFAULT)")).is_err());
+    assert!(Query::new(tokenize("CREATE TABLE t (a) garbage")).is_err());
+    assert!(Query::new(tokenize("CREATE TABLE t (a); DROP TABLE t")).is_err());
+}
+
+#[test]
+fn test_catalog_create_table() {
+    let mut catalog = Catalog::new();
+    execute(&mut catalog, "CREATE TABLE users (id INTEGER, name TEXT)").unwrap();
+    assert_eq!(
+        execute(&mut catalog, "CREATE TABLE users (id INTEGER)"),
+        Err(CatalogError::TableExists("users".to_string()))
+    );
+    execute(
+        &mut catalog,
+        "CREATE TABLE IF NOT EXISTS users (id INTEGER)",
+    )
+    .unwrap();
+    assert_eq!(catalog.table("users").unwrap().columns(), ["id", "name"]);
+}
+
+#[test]
+fn test_highlight() {
+    assert_eq!(
+        highlight("SELECT 'a' -- c"),
+        "\x1b[1;34mSELECT\x1b[0m \x1b[32m'a'\x1b[0m \x1b[90m-- c\x1b[0m"
+    );
+    assert_eq!(highlight("x 'open"), "x 'open");
+}
+
+#[test]
+fn test_completion() {
+    let mut catalog = Catalog::new();
+    execute(&mut catalog, "CREATE TABLE users (id INTEGER, name TEXT)").unwrap();
+    execute(&mut catalog, "CREATE TABLE notes (id INTEGER, note TEXT)").unwrap();
+    let mut completer = SqlCompleter::new(&catalog);
+
+    assert_eq!(completer.get_suggestions("sel").unwrap(), vec!["SELECT"]);
+    assert_eq!(
+        completer.get_suggestions("SELECT n").unwrap(),
+        vec!["note", "name", "notes", "NOT", "NULL"]
+    );
+    assert_eq!(
+        completer.get_suggestions("SELECT n FROM users").unwrap(),
+        Vec::<String>::new()
+    );
+    assert_eq!(completer.get_completion("SELECT i", None).unwrap(), None);
+    assert_eq!(
+        completer.get_completion("SELECT na", None).unwrap(),
+        Some("SELECT name".to_string())
+    );
+    assert_eq!(
+        completer
+            .get_completion("SELECT * FROM us", Some("users".to_string()))
+            .unwrap(),
+        Some("SELECT * FROM users".to_string())
+    );
+}
+
+fn press(editor: &mut LineEditor, code: KeyCode) -> Action {
+    editor.handle(KeyEvent::new(code, KeyModifiers::NONE))
+}
+
+/// Type `text` into the editor, checking after each key that the line is highlighted
+/// from re-lexed tokens just as lexing the whole line would.
+fn type_text(editor: &mut LineEditor, text: &str) {
+    for ch in text.chars() {
+        assert_eq!(press(editor, KeyCode::Char(ch)), Action::Redraw);
+        assert_lexed(editor);
+    }
+}
+
+fn assert_lexed(editor: &LineEditor) {
+    assert_eq!(
+        editor.render(),
+        highlight(editor.line()),
+        "{}",
+        editor.line()
+    );
+}
+
+#[test]
+fn test_line_editor() {
+    let mut catalog = Catalog::new();
+    execute(&mut catalog, "CREATE TABLE users (id INTEGER, name TEXT)").unwrap();
+    let mut editor = LineEditor::new(SqlCompleter::new(&catalog));
+
+    // the line is highlighted while it is typed, including an unterminated string
+    type_text(&mut editor, "SELECT 'é");
+    assert_eq!(editor.render(), highlight("SELECT 'é"));
+    type_text(&mut editor, "'");
+    assert_eq!(
+        editor.render(),
+        "\x1b[1;34mSELECT\x1b[0m \x1b[32m'é'\x1b[0m"
+    );
+
+    press(&mut editor, KeyCode::Backspace);
+    press(&mut editor, KeyCode::Backspace);
+    press(&mut editor, KeyCode::Left);
+    press(&mut editor, KeyCode::Delete);
+    assert_lexed(&editor);
+    assert_eq!(editor.line(), "SELECT ");
+    assert_eq!(editor.cursor(), 7);
+
+    // Tab completes a single candidate and lists several
+    type_text(&mut editor, "na");
+    assert_eq!(press(&mut editor, KeyCode::Tab), Action::Redraw);
+    assert_lexed(&editor);
+    assert_eq!(editor.line(), "SELECT name");
+    type_text(&mut editor, " FROM users WHERE i");
+    let Action::Suggest(candidates) = press(&mut editor, KeyCode::Tab) else {
+        panic!("expected suggestions");
+    };
+    assert_eq!(candidates[..3], ["id", "INTERSECT", "INSERT"]);
+    assert!(editor.line().ends_with("WHERE i"));
+
+    type_text(&mut editor, "d = 1e+5");
+
+    press(&mut editor, KeyCode::Home);
+    type_text(&mut editor, "-- ");
+    assert_eq!(editor.cursor(), 3);
+    assert_eq!(editor.render(), highlight(editor.line()));
+    assert_eq!(press(&mut editor, KeyCode::Enter), Action::Submit);
+    assert_eq!(
+        editor.handle(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+        Action::Cancel
+    );
+}
+
+#[test]
+fn test_parse_alter_table() {
+    let query = parse("ALTER TABLE users ADD COLUMN age INTEGER, DROP IF EXISTS nick, RENAME name TO title, RENAME TO people;");
+    assert_eq!(query.body(), BodyKind::AlterTable);
+    assert_eq!(query.value(), Some("users"));
+    let actions = query
+        .subqueries()
+        .iter()
+        .map(|action| (action.body(), action.when(), action.value().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        actions,
+        vec![
+            (BodyKind::AddColumn, WhenKind::None, "age"),
+            (BodyKind::DropColumn, WhenKind::IfExists, "nick"),
+            (BodyKind::RenameColumn, WhenKind::None, "name"),
+            (BodyKind::RenameTable, WhenKind::None, "people"),
+        ]
+    );
+    assert_eq!(
+        query.subqueries()[0].subqueries()[0].value(),
+        Some("INTEGER")
+    );
+    assert_eq!(query.subqueries()[2].subqueries()[0].value(), Some("title"));
+    let query = parse("ALTER TABLE users ADD status VARCHAR(10) NOT NULL DEFAULT 'open', DROP x");
+    assert_eq!(
+        query.subqueries()[0]
+            .subqueries()
+            .iter()
+            .map(|part| (part.body(), part.value().unwrap()))
+            .collect::<Vec<_>>(),
+        vec![
+            (BodyKind::Type, "VARCHAR(10)"),
+            (BodyKind::Default, "'open'")
+        ]
+    );
+    assert!(Query::new(tokenize("ALTER TABLE users RENAME name")).is_err());
+    assert!(Query::new(tokenize("ALTER TABLE users DROP age x")).is_err());
+}
+
+#[test]
+fn test_catalog_alter_table() {
+    let mut catalog = Catalog::new();
+    execute(&mut catalog, "CREATE TABLE users (id INTEGER, name TEXT)").unwrap();
+    execute(
+        &mut catalog,
+        "ALTER TABLE users ADD age INTEGER, RENAME name TO title, RENAME TO people",
+    )
+    .unwrap();
+    assert!(catalog.table("users").is_none());
+    assert_eq!(
+        catalog.table("people").unwrap().columns(),
+        ["id", "title", "age"]
+    );
+
+    // a failing action leaves the table untouched
+    assert_eq!(
+        execute(&mut catalog, "ALTER TABLE people DROP id, DROP missing"),
+        Err(CatalogError::NoSuchColumn("missing".to_string()))
+    );
+    assert_eq!(
+        catalog.table("people").unwrap().columns(),
+        ["id", "title", "age"]
+    );
+
+    execute(
+        &mut catalog,
+        "ALTER TABLE people DROP IF EXISTS missing, ADD IF NOT EXISTS age",
+    )
+    .unwrap();
+    assert_eq!(
+        execute(&mut catalog, "ALTER TABLE people ADD age INTEGER"),
+        Err(CatalogError::ColumnExists("age".to_string()))
+    );
+    assert_eq!(
+        execute(&mut catalog, "ALTER TABLE users ADD age INTEGER"),
+        Err(CatalogError::NoSuchTable("users".to_string()))
+    );
+    assert_eq!(
+        execute(&mut catalog, "ALTER TABLE people ADD x DEFAULT (1 +)"),
+        Err(CatalogError::InvalidDefault("x".to_string()))
+    );
+    assert_eq!(
+        execute(&mut catalog, "ALTER TABLE people ADD x DEFAULT abs('a')"),
+        Err(CatalogError::InvalidDefault("x".to_string()))
+    );
+
+    // existing rows get the default, as do rows inserted without a value for it
+    let mut db = Database::new();
+    let rows = |db: &mut Database, sql: &str| db.execute(sql, &[]).unwrap().rows().to_vec();
+    db.execute("CREATE TABLE notes (id INTEGER, body TEXT)", &[])
+        .unwrap();
+    db.execute(
+        "INSERT INTO notes VALUES (1, 'buy milk'), (2, 'pay rent')",
+        &[],
+    )
+    .unwrap();
+    db.execute("CREATE FULLTEXT INDEX words ON notes (body)", &[])
+        .unwrap();
+    db.execute(
+        "ALTER TABLE notes ADD COLUMN status VARCHAR(10) DEFAULT 'open', \
+         ADD priority INTEGER DEFAULT (1 + 2), ADD due",
+        &[],
+    )
+    .unwrap();
+    db.execute("INSERT INTO notes (id, body) VALUES (3, 'more milk')", &[])
+        .unwrap();
+    let open = |id: i64| {
+        vec![
+            Value::Integer(id),
+            Value::Text("open".to_string()),
+            Value::Integer(3),
+            Value::Null,
+        ]
+    };
+    assert_eq!(
+        rows(&mut db, "SELECT id, status, priority, due FROM notes"),
+        vec![open(1), open(2), open(3)]
+    );
+    let table = db.catalog().table("notes").unwrap();
+    assert_eq!(table.definitions()[2].type_name(), Some("VARCHAR(10)"));
+    assert_eq!(table.definitions()[3].default_text(), Some("(1 + 2)"));
+    assert!(db
+        .catalog()
+        .dump().unwrap()
+        .contains("CREATE TABLE notes (id INTEGER, body TEXT, status VARCHAR(10) DEFAULT 'open', priority INTEGER DEFAULT (1 + 2), due);\n"));
+
+    // the index is rebuilt with the table, so rows stay searchable after a column is dropped
+    db.execute("ALTER TABLE notes DROP id", &[]).unwrap();
+    assert_eq!(
+        rows(&mut db, "SELECT body FROM notes WHERE body MATCH 'milk'"),
+        vec![
+            vec![Value::Text("buy milk".to_string())],
+            vec![Value::Text("more milk".to_string())]
+        ]
+    );
+
+    // indexes follow a renamed column they cover, and go with a dropped one
+    db.execute("CREATE INDEX by_body ON notes (body)", &[])
+        .unwrap();
+    db.execute(
+        "ALTER TABLE notes RENAME body TO content, DROP priority",
+        &[],
+    )
+    .unwrap();
+    assert_eq!(
+        db.catalog().index("by_body").unwrap().columns(),
+        ["content"]
+    );
+    assert_eq!(db.catalog().index("words").unwrap().columns(), ["content"]);
+    let by_content = "SELECT content FROM notes WHERE content BETWEEN 'm' AND 'n'";
+    assert_eq!(
+        rows(&mut db, &format!("EXPLAIN {by_content}"))[0][0],
+        Value::Text("SEARCH notes USING INDEX by_body".to_string())
+    );
+    assert_eq!(
+        rows(&mut db, by_content),
+        vec![vec![Value::Text("more milk".to_string())]]
+    );
+    assert_eq!(
+        rows(
+            &mut db,
+            "SELECT status FROM notes WHERE content MATCH 'rent'"
+        ),
+        vec![vec![Value::Text("open".to_string())]]
+    );
+    db.execute("ALTER TABLE notes DROP content", &[]).unwrap();
+    assert_eq!(db.catalog().index_names().count(), 0);
+    assert_eq!(
+        rows(
+            &mut db,
+            "SELECT status FROM notes WHERE status BETWEEN 'a' AND 'z'"
+        )
+        .len(),
+        3
+    );
+}
+
+#[test]
+fn test_parse_drop_and_use() {
+    let query = parse("DROP TABLE IF EXISTS users;");
+    assert_eq!(query.body(), BodyKind::DropTable);
+    assert_eq!(query.when(), WhenKind::IfExists);
+    assert_eq!(query.value(), Some("users"));
+    assert_eq!(
+        parse("CREATE DATABASE IF NOT EXISTS shop").when(),
+        WhenKind::IfNotExists
+    );
+    assert_eq!(parse("DROP DATABASE shop").body(), BodyKind::DropDatabase);
+    assert_eq!(parse("USE shop").body(), BodyKind::UseDatabase);
+    assert_eq!(parse("DROP INDEX x").body(), BodyKind::DropIndex);
+    assert!(Query::new(tokenize("DROP COLUMN x")).is_err());
+    assert!(Query::new(tokenize("USE shop extra")).is_err());
+}
+
+#[test]
+fn test_catalog_databases() {
+    let mut catalog = Catalog::new();
+    assert_eq!(catalog.current_database(), "main");
+    execute(&mut catalog, "CREATE TABLE users (id INTEGER)").unwrap();
+
+    execute(&mut catalog, "CREATE DATABASE shop").unwrap();
+    assert_eq!(
+        execute(&mut catalog, "CREATE DATABASE shop"),
+        Err(CatalogError::DatabaseExists("shop".to_string()))
+    );
+    execute(&mut catalog, "CREATE DATABASE IF NOT EXISTS shop").unwrap();
+
+    execute(&mut catalog, "USE shop").unwrap();
+    assert!(catalog.table("users").is_none());
+    execute(&mut catalog, "CREATE TABLE users (name TEXT)").unwrap();
+    assert_eq!(catalog.table("users").unwrap().columns(), ["name"]);
+    assert_eq!(
+        execute(&mut catalog, "DROP DATABASE shop"),
+        Err(CatalogError::DatabaseInUse("shop".to_string()))
+    );
+
+    execute(&mut catalog, "USE main").unwrap();
+    assert_eq!(catalog.table("users").unwrap().columns(), ["id"]);
+    execute(&mut catalog, "DROP DATABASE shop").unwrap();
+    execute(&mut catalog, "DROP DATABASE IF EXISTS shop").unwrap();
+    assert_eq!(
+        execute(&mut catalog, "USE shop"),
+        Err(CatalogError::NoSuchDatabase("shop".to_string()))
+    );
+    assert_eq!(catalog.database_names().collect::<Vec<_>>(), vec!["main"]);
+}
+
+#[test]
+fn test_catalog_drop_table() {
+    let mut catalog = Catalog::new();
+    execute(&mut catalog, "CREATE TABLE users (id INTEGER)").unwrap();
+    execute(&mut catalog, "DROP TABLE users").unwrap();
+    assert!(catalog.table("users").is_none());
+    assert_eq!(
+        execute(&mut catalog, "DROP TABLE users"),
+        Err(CatalogError::NoSuchTable("users".to_string()))
+    );
+    execute(&mut catalog, "DROP TABLE IF EXISTS users").unwrap();
+}
+
+#[test]
+fn test_parse_view() {
+    let query =
+        parse("CREATE VIEW IF NOT EXISTS adults AS SELECT name FROM users WHERE age >= 18;");
+    assert_eq!(query.body(), BodyKind::CreateView);
+    assert_eq!(query.when(), WhenKind::IfNotExists);
+    assert_eq!(query.value(), Some("adults"));
+    let select = &query.subqueries()[0];
+    assert_eq!(select.body(), BodyKind::Select);
+    assert_eq!(
+        select.value(),
+        Some("SELECT name FROM users WHERE age >= 18")
+    );
+    assert_eq!(parse("DROP VIEW adults").body(), BodyKind::DropView);
+    assert!(Query::new(tokenize("CREATE VIEW adults SELECT 1")).is_err());
+    assert!(Query::new(tokenize("CREATE VIEW adults AS DROP TABLE users")).is_err());
+}
+
+#[test]
+fn test_catalog_views() {
+    let mut catalog = Catalog::new();
+    execute(&mut catalog, "CREATE TABLE users (name TEXT, age INTEGER)").unwrap();
+    execute(
+        &mut catalog,
+        "CREATE VIEW adults AS SELECT name FROM users WHERE age >= 18",
+    )
+    .unwrap();
+    assert_eq!(
+        catalog.view("adults").unwrap().query(),
+        "SELECT name FROM users WHERE age >= 18"
+    );
+    assert_eq!(
+        execute(&mut catalog, "CREATE VIEW users AS SELECT 1"),
+        Err(CatalogError::TableExists("users".to_string()))
+    );
+    assert_eq!(
+        execute(&mut catalog, "CREATE TABLE adults (id INTEGER)"),
+        Err(CatalogError::ViewExists("adults".to_string()))
+    );
+    execute(&mut catalog, "CREATE VIEW IF NOT EXISTS adults AS SELECT 1").unwrap();
+    assert_eq!(
+        catalog.view("adults").unwrap().query(),
+        "SELECT name FROM users WHERE age >= 18"
+    );
+
+    let mut completer = SqlCompleter::new(&catalog);
+    assert_eq!(
+        completer.get_suggestions("SELECT * FROM adu").unwrap(),
+        vec!["adults"]
+    );
+
+    execute(&mut catalog, "DROP VIEW adults").unwrap();
+    assert_eq!(
+        execute(&mut catalog, "DROP VIEW adults"),
+        Err(CatalogError::NoSuchView("adults".to_string()))
+    );
+    execute(&mut catalog, "DROP VIEW IF EXISTS adults").unwrap();
+}
+
+#[test]
+fn test_select_through_views() {
+    let mut db = Database::new();
+    for sql in [
+        "CREATE TABLE users (id INTEGER, name TEXT, age INTEGER)",
+        "CREATE TABLE pets (owner INTEGER, pet TEXT)",
+        "INSERT INTO users VALUES (1, 'ann', 34), (2, 'bob', 12), (3, 'cy', 18)",
+        "INSERT INTO pets (pet, owner) VALUES ('cat', 1), ('dog', 3), ('fish', 1)",
+        "CREATE VIEW adults AS SELECT id, name FROM users WHERE age >= 18",
+        "CREATE VIEW named AS SELECT name AS who FROM adults ORDER BY 1 DESC",
+    ] {
+        db.execute(sql, &[]).unwrap();
+    }
+
+    let result = db.execute("SELECT * FROM adults", &[]).unwrap();
+    assert_eq!(result.columns(), &["id", "name"]);
+    assert_eq!(
+        result.rows(),
+        &[
+            vec![Value::Integer(1), text("ann")],
+            vec![Value::Integer(3), text("cy")]
+        ]
+    );
+    // a view over a view, with its own ORDER BY
+    assert_eq!(
+        db.execute("SELECT who FROM named", &[]).unwrap().rows(),
+        &[vec![text("cy")], vec![text("ann")]]
+    );
+    assert_eq!(
+        db.execute(
+            "SELECT a.name, p.pet FROM adults a JOIN pets p ON p.owner = a.id ORDER BY p.pet",
+            &[]
+        )
+        .unwrap()
+        .rows(),
+        &[
+            vec![text("ann"), text("cat")],
+            vec![text("cy"), text("dog")],
+            vec![text("ann"), text("fish")]
+        ]
+    );
+    assert_eq!(
+        db.execute(
+            "SELECT u.name, pet FROM users u LEFT JOIN pets ON owner = u.id WHERE u.age < 18",
+            &[]
+        )
+        .unwrap()
+        .rows(),
+        &[vec![text("bob"), Value::Null]]
+    );
+    assert_eq!(
+        db.execute(
+            "SELECT name FROM (SELECT name, age FROM users) ORDER BY age LIMIT 1 OFFSET 1",
+            &[]
+        )
+        .unwrap()
+        .rows(),
+        &[vec![text("cy")]]
+    );
+    assert_eq!(
+        db.execute("SELECT name FROM users, adults", &[]),
+        Err(DatabaseError::Catalog(CatalogError::AmbiguousColumn(
+            "name".to_string()
+        )))
+    );
+
+    // views resolve at query time, so a view may come to refer to itself
+    db.execute("CREATE VIEW loop_a AS SELECT * FROM loop_b", &[])
+        .unwrap();
+    db.execute("CREATE VIEW loop_b AS SELECT * FROM loop_a", &[])
+        .unwrap();
+    assert_eq!(
+        db.execute("SELECT * FROM loop_a", &[]),
+        Err(DatabaseError::Catalog(CatalogError::RecursiveView(
+            "loop_a".to_string()
+        )))
+    );
+    assert_eq!(
+        db.execute("INSERT INTO users (id) VALUES (1, 2)", &[]),
+        Err(DatabaseError::Catalog(CatalogError::ColumnCountMismatch(
+            1, 2
+        )))
+    );
+}
+
+#[test]
+fn test_parse_trigger() {
+    let query = parse(
+        "CREATE TRIGGER log_insert AFTER insert ON users FOR EACH ROW BEGIN INSERT INTO log VALUES (NEW.id); END;",
+    );
+    assert_eq!(query.body(), BodyKind::CreateTrigger);
+    assert_eq!(query.value(), Some("log_insert"));
+    let parts = query
+        .subqueries()
+        .iter()
+        .map(|part| (part.body(), part.value().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        parts,
+        vec![
+            (BodyKind::After, "INSERT"),
+            (BodyKind::Table, "users"),
+            (BodyKind::Block, "INSERT INTO log VALUES (NEW.id);"),
+        ]
+    );
+    assert_eq!(
+        parse("DROP TRIGGER IF EXISTS log_insert").body(),
+        BodyKind::DropTrigger
+    );
+    assert!(Query::new(tokenize(
+        "CREATE TRIGGER t AFTER SELECT ON users FOR EACH ROW BEGIN END"
+    ))
+    .is_err());
+    assert!(Query::new(tokenize(
+        "CREATE TRIGGER t BEFORE DELETE ON users BEGIN END"
+    ))
+    .is_err());
+    assert!(Query::new(tokenize(
+        "CREATE TRIGGER t BEFORE DELETE ON users FOR EACH ROW BEGIN"
+    ))
+    .is_err());
+}
+
+#[test]
+fn test_catalog_triggers() {
+    let mut catalog = Catalog::new();
+    let create = "CREATE TRIGGER touch BEFORE UPDATE ON users FOR EACH ROW BEGIN SET NEW.updated_at = NOW(); END";
+    assert_eq!(
+        execute(&mut catalog, create),
+        Err(CatalogError::NoSuchTable("users".to_string()))
+    );
+    execute(
+        &mut catalog,
+        "CREATE TABLE users (id INTEGER, updated_at TEXT)",
+    )
+    .unwrap();
+    execute(&mut catalog, create).unwrap();
+    assert_eq!(
+        execute(&mut catalog, create),
+        Err(CatalogError::TriggerExists("touch".to_string()))
+    );
+
+    execute(&mut catalog, "ALTER TABLE users RENAME TO people").unwrap();
+    let trigger = catalog.trigger("touch").unwrap();
+    assert_eq!(trigger.table(), "people");
+    assert_eq!(trigger.statements(), "SET NEW.updated_at = NOW();");
+    assert_eq!(
+        catalog
+            .triggers_for("people", TriggerTiming::Before, TriggerEvent::Update)
+            .count(),
+        1
+    );
+    assert_eq!(
+        catalog
+            .triggers_for("people", TriggerTiming::After, TriggerEvent::Update)
+            .count(),
+        0
+    );
+
+    execute(&mut catalog, "DROP TABLE people").unwrap();
+    assert!(catalog.trigger("touch").is_none());
+    assert_eq!(
+        execute(&mut catalog, "DROP TRIGGER touch"),
+        Err(CatalogError::NoSuchTrigger("touch".to_string()))
+    );
+    execute(&mut catalog, "DROP TRIGGER IF EXISTS touch").unwrap();
+}
+
+#[test]
+fn test_firing_triggers() {
+    let mut db = Database::new();
+    for sql in [
+        "CREATE TABLE accounts (id INTEGER, balance INTEGER)",
+        "CREATE TABLE audit (event TEXT, id INTEGER, old INTEGER, new INTEGER)",
+        "CREATE TRIGGER on_insert AFTER INSERT ON accounts FOR EACH ROW BEGIN
+            INSERT INTO audit VALUES ('insert', NEW.id, NULL, NEW.balance); END",
+        "CREATE TRIGGER on_update AFTER UPDATE ON accounts FOR EACH ROW BEGIN
+            INSERT INTO audit VALUES ('update', new.id, old.balance, new.balance); END",
+        "CREATE TRIGGER on_delete BEFORE DELETE ON accounts FOR EACH ROW BEGIN
+            INSERT INTO audit (event, id, old) VALUES ('delete', OLD.id, OLD.balance);
+            DELETE FROM audit WHERE id = OLD.id AND event = 'insert'; END",
+        "INSERT INTO accounts VALUES (1, 100), (2, 50)",
+        "UPDATE accounts SET balance = balance - 30 WHERE id = 1",
+        "DELETE FROM accounts WHERE balance < 60",
+    ] {
+        db.execute(sql, &[]).unwrap();
+    }
+    let audit = |db: &mut Database| db.execute("SELECT * FROM audit", &[]).unwrap();
+    let int = |i: i64| Value::Integer(i);
+    assert_eq!(
+        audit(&mut db).rows(),
+        &[
+            vec![text("insert"), int(1), Value::Null, int(100)],
+            vec![text("update"), int(1), int(100), int(70)],
+            vec![text("delete"), int(2), int(50), Value::Null],
+        ]
+    );
+
+    // a failing trigger undoes the statement that fired it and everything it did
+    db.execute(
+        "CREATE TRIGGER fail AFTER INSERT ON audit FOR EACH ROW BEGIN
+            INSERT INTO missing VALUES (1); END",
+        &[],
+    )
+    .unwrap();
+    assert_eq!(
+        db.execute("INSERT INTO accounts VALUES (3, 10)", &[]),
+        Err(DatabaseError::Catalog(CatalogError::NoSuchTable(
+            "missing".to_string()
+        )))
+    );
+    assert_eq!(
+        db.execute("SELECT id FROM accounts", &[]).unwrap().rows(),
+        &[vec![int(1)]]
+    );
+    assert_eq!(audit(&mut db).rows().len(), 3);
+    db.execute("DROP TRIGGER fail", &[]).unwrap();
+
+    db.execute(
+        "CREATE TRIGGER again AFTER INSERT ON audit FOR EACH ROW BEGIN
+            INSERT INTO audit VALUES (NEW.event, NEW.id + 1, NULL, NULL); END",
+        &[],
+    )
+    .unwrap();
+    assert_eq!(
+        db.execute("INSERT INTO audit (event) VALUES ('loop')", &[]),
+        Err(DatabaseError::Catalog(CatalogError::TriggerDepth(32)))
+    );
+    assert_eq!(audit(&mut db).rows().len(), 3);
+}
+
+#[test]
+fn test_parse_common_tables() {
+    let source = "WITH RECURSIVE tree (id, parent) AS (SELECT id, parent FROM todos WHERE parent IS NULL UNION ALL SELECT t.id, t.parent FROM todos t JOIN tree ON (t.parent = tree.id)), top AS (SELECT 1) SELECT * FROM tree;";
+    let query = parse(source);
+    assert_eq!(query.body(), BodyKind::Select);
+    assert_eq!(query.value(), Some(&source[..source.len() - 1]));
+
+    let tree = &query.subqueries()[0];
+    assert_eq!(tree.body(), BodyKind::RecursiveCommonTable);
+    assert_eq!(tree.value(), Some("tree"));
+    let parts = tree
+        .subqueries()
+        .iter()
+        .map(|part| (part.body(), part.value().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        parts,
+        vec![
+            (BodyKind::Field, "id"),
+            (BodyKind::Field, "parent"),
+            (
+                BodyKind::Select,
+                "SELECT id, parent FROM todos WHERE parent IS NULL UNION ALL SELECT t.id, t.parent FROM todos t JOIN tree ON (t.parent = tree.id)"
+            ),
+        ]
+    );
+    assert_eq!(query.subqueries()[1].body(), BodyKind::RecursiveCommonTable);
+    assert_eq!(
+        query.subqueries()[1].subqueries()[0].value(),
+        Some("SELECT 1")
+    );
+
+    assert_eq!(
+        parse("WITH x AS (SELECT 1) SELECT * FROM x").subqueries()[0].body(),
+        BodyKind::CommonTable
+    );
+    assert!(Query::new(tokenize("WITH x AS (SELECT 1)")).is_err());
+    assert!(Query::new(tokenize("WITH x AS (SELECT 1 SELECT * FROM x")).is_err());
+    assert!(Query::new(tokenize("WITH x AS (DROP TABLE y) SELECT 1")).is_err());
+}
+
+#[test]
+fn test_view_with_common_table() {
+    let mut catalog = Catalog::new();
+    execute(
+        &mut catalog,
+        "CREATE VIEW one AS WITH x AS (SELECT 1) SELECT * FROM x",
+    )
+    .unwrap();
+    assert_eq!(
+        catalog.view("one").unwrap().query(),
+        "WITH x AS (SELECT 1) SELECT * FROM x"
+    );
+}
+
+#[test]
+fn test_common_tables() {
+    let mut db = Database::new();
+    for sql in [
+        "CREATE TABLE staff (id INTEGER, name TEXT, boss INTEGER)",
+        "INSERT INTO staff VALUES (1, 'ada', NULL), (2, 'bo', 1), (3, 'cy', 1), (4, 'di', 3), (5, 'ed', 4)",
+        "CREATE VIEW bosses AS WITH b AS (SELECT boss FROM staff WHERE boss IS NOT NULL) SELECT * FROM b",
+    ] {
+        db.execute(sql, &[]).unwrap();
+    }
+    let rows = |db: &mut Database, sql: &str| db.execute(sql, &[]).unwrap().rows().to_vec();
+    let int = |i: i64| Value::Integer(i);
+
+    // walk the hierarchy down from the top, one level per step
+    assert_eq!(
+        rows(
+            &mut db,
+            "WITH RECURSIVE chain (id, name, level) AS (
+                SELECT id, name, 0 FROM staff WHERE boss IS NULL
+                UNION ALL
+                SELECT s.id, s.name, chain.level + 1 FROM staff s JOIN chain ON s.boss = chain.id
+            ) SELECT name, level FROM chain ORDER BY level, name"
+        ),
+        vec![
+            vec![text("ada"), int(0)],
+            vec![text("bo"), int(1)],
+            vec![text("cy"), int(1)],
+            vec![text("di"), int(2)],
+            vec![text("ed"), int(3)],
+        ]
+    );
+    // and up from the bottom, through a second table that reads the first
+    assert_eq!(
+        rows(
+            &mut db,
+            "WITH RECURSIVE up AS (
+                SELECT id, boss FROM staff WHERE name = 'ed'
+                UNION ALL
+                SELECT staff.id, staff.boss FROM staff JOIN up ON staff.id = up.boss
+            ), names AS (SELECT name FROM staff JOIN up ON staff.id = up.id)
+            SELECT * FROM names"
+        ),
+        vec![
+            vec![text("ada")],
+            vec![text("cy")],
+            vec![text("di")],
+            vec![text("ed")],
+        ]
+    );
+
+    // UNION stops at rows it has already seen; UNION ALL keeps going
+    db.execute("UPDATE staff SET boss = 5 WHERE id = 1", &[])
+        .unwrap();
+    let cycle = |union: &str| {
+        format!(
+            "WITH RECURSIVE r (id) AS (SELECT 1 {} SELECT boss FROM staff JOIN r ON staff.id = r.id)
+            SELECT id FROM r",
+            union
+        )
+    };
+    assert_eq!(
+        rows(&mut db, &cycle("UNION")),
+        vec![vec![int(1)], vec![int(5)], vec![int(4)], vec![int(3)]]
+    );
+    assert_eq!(
+        db.execute(&cycle("UNION ALL"), &[]),
+        Err(DatabaseError::Catalog(CatalogError::RecursionLimit(
+            "r".to_string(),
+            1000
+        )))
+    );
+
+    assert_eq!(
+        db.execute("WITH t (a, b) AS (SELECT 1) SELECT * FROM t", &[]),
+        Err(DatabaseError::Catalog(CatalogError::ColumnCountMismatch(
+            2, 1
+        )))
+    );
+    // a view does not see the common tables of the query that reads it
+    assert_eq!(
+        rows(
+            &mut db,
+            "WITH staff AS (SELECT 1 AS boss) SELECT * FROM bosses"
+        )
+        .len(),
+        5
+    );
+}
+
+fn shape(query: &Query) -> String {
+    match query.body() {
+        BodyKind::Select | BodyKind::SelectDistinct => {
+            format!("{:?}({})", query.body(), query.value().unwrap())
+        }
+        body => format!(
+            "{:?}[{}]",
+            body,
+            query
+                .subqueries()
+                .iter()
+                .map(shape)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+#[test]
+fn test_parse_set_operations() {
+    let query = parse("SELECT DISTINCT name FROM users");
+    assert_eq!(query.body(), BodyKind::SelectDistinct);
+    assert!(query.subqueries().is_empty());
+
+    let query = parse("SELECT a FROM x UNION ALL SELECT b FROM y INTERSECT SELECT c FROM z EXCEPT SELECT DISTINCT d FROM w");
+    assert_eq!(query.body(), BodyKind::Select);
+    assert_eq!(
+        shape(&query.subqueries()[0]),
+        "Except[UnionAll[Select(SELECT a FROM x), Intersect[Select(SELECT b FROM y), Select(SELECT c FROM z)]], SelectDistinct(SELECT DISTINCT d FROM w)]"
+    );
+    assert_eq!(
+        query.subqueries()[0].subqueries()[0].value(),
+        Some("SELECT a FROM x UNION ALL SELECT b FROM y INTERSECT SELECT c FROM z")
+    );
+
+    // set operators inside parentheses belong to the subquery
+    let query = parse("SELECT a FROM x WHERE a IN (SELECT a FROM y UNION SELECT a FROM z)");
+    assert_eq!(query.body(), BodyKind::Select);
+    assert!(query.subqueries().is_empty());
+
+    let query = parse("WITH t AS (SELECT 1 UNION SELECT 2) SELECT * FROM t UNION SELECT 3");
+    assert_eq!(
+        query.subqueries()[0].subqueries()[0].subqueries()[0].body(),
+        BodyKind::Union
+    );
+    assert_eq!(query.subqueries()[1].body(), BodyKind::Union);
+}
+
+#[test]
+fn test_set_operation_checks() {
+    let error = |source: &str| Query::new(tokenize(source)).unwrap_err();
+    assert!(matches!(
+        error("SELECT 1 UNION"),
+        ParseError::UnexpectedToken
+    ));
+    assert!(matches!(
+        error("SELECT FROM x"),
+        ParseError::UnexpectedToken
+    ));
+    // whether the sides match is only known with a catalog
+    parse("SELECT a, b FROM x UNION SELECT a FROM y");
+    parse("SELECT 1, 'a' UNION SELECT 2, 3");
+
+    let mut db = Database::new();
+    db.execute("CREATE TABLE x (a INTEGER, b TEXT)", &[])
+        .unwrap();
+    let catalog_error = |db: &mut Database, sql: &str| match db.execute(sql, &[]) {
+        Err(DatabaseError::Catalog(err)) => err,
+        other => panic!("{} did not fail to plan: {:?}", sql, other),
+    };
+    assert_eq!(
+        catalog_error(&mut db, "SELECT a, b FROM x UNION SELECT a FROM x"),
+        CatalogError::ColumnCountMismatch(2, 1)
+    );
+    assert_eq!(
+        catalog_error(&mut db, "SELECT 1, 'a' UNION SELECT 2, 3"),
+        CatalogError::ColumnTypeMismatch(2)
+    );
+    // `*` is counted once the table is read
+    assert_eq!(
+        catalog_error(&mut db, "SELECT * FROM x EXCEPT SELECT 1"),
+        CatalogError::ColumnCountMismatch(2, 1)
+    );
+    db.execute(
+        "SELECT 1, a FROM x UNION SELECT 2, 'b' INTERSECT SELECT a, 'c' FROM x",
+        &[],
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_set_operations() {
+    let mut db = Database::new();
+    for sql in [
+        "CREATE TABLE a (n INTEGER)",
+        "CREATE TABLE b (n INTEGER)",
+        "INSERT INTO a VALUES (3), (1), (2), (1), (NULL)",
+        "INSERT INTO b VALUES (2), (4), (2.0), (NULL)",
+    ] {
+        db.execute(sql, &[]).unwrap();
+    }
+    let column = |db: &mut Database, sql: &str| {
+        db.execute(sql, &[])
+            .unwrap()
+            .rows()
+            .iter()
+            .map(|row| row[0].clone())
+            .collect::<Vec<_>>()
+    };
+    let ints = |ints: &[i64]| ints.iter().map(|i| Value::Integer(*i)).collect::<Vec<_>>();
+    let null = Value::Null;
+
+    assert_eq!(
+        column(&mut db, "SELECT n FROM a UNION ALL SELECT n FROM b").len(),
+        9
+    );
+    // 2 and 2.0 are the same row, and so are two NULLs
+    assert_eq!(
+        column(&mut db, "SELECT n FROM a UNION SELECT n FROM b ORDER BY 1"),
+        [vec![null.clone()], ints(&[1, 2, 3, 4])].concat()
+    );
+    assert_eq!(
+        column(
+            &mut db,
+            "SELECT n FROM a INTERSECT SELECT n FROM b ORDER BY n"
+        ),
+        [vec![null], ints(&[2])].concat()
+    );
+    assert_eq!(
+        column(
+            &mut db,
+            "SELECT n FROM a EXCEPT SELECT n FROM b ORDER BY n DESC LIMIT 1"
+        ),
+        ints(&[3])
+    );
+    // INTERSECT binds tighter, and ORDER BY and LIMIT apply to the whole result
+    assert_eq!(
+        column(
+            &mut db,
+            "SELECT 9 UNION SELECT n FROM a INTERSECT SELECT n FROM b ORDER BY 1 DESC LIMIT 2"
+        ),
+        ints(&[9, 2])
+    );
+    assert_eq!(
+        column(&mut db, "SELECT DISTINCT n FROM a WHERE n IS NOT NULL"),
+        ints(&[3, 1, 2])
+    );
+    assert_eq!(
+        column(&mut db, "SELECT DISTINCT n % 2 FROM b ORDER BY 1"),
+        [vec![Value::Null], vec![Value::Integer(0)]].concat()
+    );
+}
+
+fn text(text: &str) -> Value {
+    Value::Text(text.to_string())
+}
+
+#[test]
+fn test_builtin_functions() {
+    let functions = Functions::new();
+    let call = |name: &str, args: &[Value]| functions.call(name, args).unwrap();
+    assert_eq!(call("length", &[text("çığ")]), Value::Integer(3));
+    assert_eq!(call("UPPER", &[text("istanbul")]), text("ISTANBUL"));
+    assert_eq!(
+        call("UPPER", &[text("istanbul ılık"), text("tr")]),
+        text("İSTANBUL ILIK")
+    );
+    assert_eq!(
+        call("LOWER", &[text("IĞDIR İzmir"), text("tr_TR")]),
+        text("ığdır izmir")
+    );
+    assert_eq!(call("LOWER", &[text("İzmir")]), text("izmir"));
+    assert_eq!(
+        call("SUBSTR", &[text("şehir"), Value::Integer(2)]),
+        text("ehir")
+    );
+    assert_eq!(
+        call(
+            "SUBSTR",
+            &[text("abcdef"), Value::Integer(0), Value::Integer(3)]
+        ),
+        text("ab")
+    );
+    assert_eq!(
+        call("COALESCE", &[Value::Null, Value::Integer(2)]),
+        Value::Integer(2)
+    );
+    assert_eq!(call("ROUND", &[Value::Real(2.5)]), Value::Real(3.0));
+    assert_eq!(
+        call("ROUND", &[Value::Real(-1.25), Value::Integer(1)]),
+        Value::Real(-1.3)
+    );
+    assert_eq!(
+        call("ROUND", &[Value::Integer(1250), Value::Integer(-2)]),
+        Value::Integer(1300)
+    );
+    assert_eq!(call("ABS", &[Value::Integer(-4)]), Value::Integer(4));
+
+    // extreme arguments clamp instead of overflowing
+    for start in [i64::MIN, i64::MIN + 1, 0] {
+        assert_eq!(
+            call("SUBSTR", &[text("abc"), Value::Integer(start)]),
+            text("abc")
+        );
+    }
+    for (start, length, expected) in [
+        (i64::MIN, 0, ""),
+        (i64::MIN, i64::MAX, ""),
+        (1, i64::MAX, "abc"),
+        (i64::MAX, i64::MAX, ""),
+    ] {
+        assert_eq!(
+            call(
+                "SUBSTR",
+                &[text("abc"), Value::Integer(start), Value::Integer(length)]
+            ),
+            text(expected)
+        );
+    }
+    assert_eq!(
+        call("SUBSTR", &[text("abc"), Value::Integer(i64::MAX)]),
+        text("")
+    );
+    for digits in [i64::MAX, i64::from(i32::MAX) + 1, 400] {
+        assert_eq!(
+            call("ROUND", &[Value::Real(1.25), Value::Integer(digits)]),
+            Value::Real(1.25)
+        );
+        assert_eq!(
+            call("ROUND", &[Value::Integer(1250), Value::Integer(digits)]),
+            Value::Integer(1250)
+        );
+    }
+    for digits in [i64::MIN, -i64::from(i32::MAX) - 2, -400] {
+        assert_eq!(
+            call("ROUND", &[Value::Real(1.25), Value::Integer(digits)]),
+            Value::Real(0.0)
+        );
+        assert_eq!(
+            call("ROUND", &[Value::Integer(i64::MIN), Value::Integer(digits)]),
+            Value::Integer(0)
+        );
+    }
+    assert_eq!(
+        call("DATE", &[text("2024-02-29 13:45:00")]),
+        text("2024-02-29")
+    );
+    assert_eq!(call("ABS", &[Value::Null]), Value::Null);
+
+    assert_eq!(
+        functions.call("ABS", &[Value::Integer(i64::MIN)]),
+        Err(FunctionError::Overflow("ABS".to_string()))
+    );
+    assert_eq!(
+        functions.call("ROUND", &[Value::Integer(i64::MAX), Value::Integer(-19)]),
+        Err(FunctionError::Overflow("ROUND".to_string()))
+    );
+    assert_eq!(
+        functions.call("abs", &[]),
+        Err(FunctionError::WrongArgumentCount("ABS".to_string(), 0))
+    );
+    assert!(matches!(
+        functions.call("DATE", &[text("yesterday")]),
+        Err(FunctionError::InvalidArgument(..))
+    ));
+    assert!(matches!(
+        functions.call("nope", &[]),
+        Err(FunctionError::NoSuchFunction(_))
+    ));
+}
+
+#[test]
+fn test_database_constant_select() {
+    let mut db = Database::new();
+    assert_eq!(
+        db.execute(
+            "SELECT UPPER('ılık', 'tr'), LENGTH(X'00FF'), ROUND(-2.5), COALESCE(NULL, -1);",
+            &[]
+        )
+        .unwrap()
+        .rows(),
+        vec![vec![
+            text("ILIK"),
+            Value::Integer(2),
+            Value::Real(-3.0),
+            Value::Integer(-1)
+        ]]
+    );
+    assert_eq!(
+        db.execute("CREATE TABLE t (a INT)", &[]).unwrap(),
+        ResultSet::default()
+    );
+    assert_eq!(
+        db.execute("SELECT a FROM t", &[]).unwrap().rows(),
+        &[] as &[Row]
+    );
+    assert_eq!(
+        db.execute("SELECT UPPER(1)", &[]),
+        Err(DatabaseError::Function(FunctionError::InvalidArgument(
+            "UPPER".to_string(),
+            Value::Integer(1)
+        )))
+    );
+    assert!(matches!(
+        db.execute("SELECT UPPER('a'", &[]),
+        Err(DatabaseError::Parse(_))
+    ));
+}
+
+#[test]
+fn test_register_function() {
+    let mut db = Database::new();
+    db.register_function("vat", 1, |args| match &args[0] {
+        Value::Integer(amount) => Ok(Value::Integer(amount * 118 / 100)),
+        value => Err(FunctionError::InvalidArgument(
+            "VAT".to_string(),
+            value.clone(),
+        )),
+    });
+    assert_eq!(
+        db.execute("SELECT VAT(ABS(-100)) AS total", &[])
+            .unwrap()
+            .rows(),
+        vec![vec![Value::Integer(118)]]
+    );
+    assert_eq!(
+        db.execute("SELECT vat(1, 2)", &[]),
+        Err(DatabaseError::Function(FunctionError::WrongArgumentCount(
+            "VAT".to_string(),
+            2
+        )))
+    );
+    // registering over a built-in replaces it
+    db.register_function("length", 1, |_| Ok(Value::Integer(0)));
+    assert_eq!(
+        db.execute("SELECT LENGTH('abc')", &[]).unwrap().rows(),
+        vec![vec![Value::Integer(0)]]
+    );
+}
+
+#[test]
+fn test_predicates() {
+    let mut db = Database::new();
+    let mut row = |sql: &str| db.execute(sql, &[]).unwrap().rows()[0].clone();
+    let (t, f, null) = (Value::Integer(1), Value::Integer(0), Value::Null);
+
+    assert_eq!(
+        row("SELECT 'abc' LIKE 'a%', 'abc' LIKE 'A%', 'abc' ILIKE 'A_C', 'abc' NOT LIKE '%b'"),
+        vec![t.clone(), f.clone(), t.clone(), t.clone()]
+    );
+    assert_eq!(
+        row("SELECT 'İSTANBUL' ILIKE 'i%', 12 LIKE '1_', NULL LIKE '%', 'a' NOT LIKE NULL"),
+        vec![t.clone(), t.clone(), null.clone(), null.clone()]
+    );
+    assert_eq!(
+        row("SELECT 'abc' GLOB 'a*', 'abc' GLOB 'A*', 'a1' GLOB '?[0-9]', 'ab' GLOB '[^a]*'"),
+        vec![t.clone(), f.clone(), t.clone(), f.clone()]
+    );
+    assert_eq!(
+        row("SELECT 2 BETWEEN 1 AND 3, 2.5 NOT BETWEEN 1 AND 2, 5 BETWEEN NULL AND 3"),
+        vec![t.clone(), t.clone(), f.clone()]
+    );
+    assert_eq!(
+        row("SELECT 2 BETWEEN NULL AND 3, 'b' BETWEEN 'a' AND 'c'"),
+        vec![null.clone(), t.clone()]
+    );
+    assert_eq!(
+        row("SELECT 1 IN (1, NULL), 2 IN (1, NULL), 2 NOT IN (1, 3), NULL IN (1)"),
+        vec![t.clone(), null.clone(), t.clone(), null.clone()]
+    );
+    assert_eq!(
+        row("SELECT NULL IS NULL, 1 IS NULL, NULL IS NOT NULL, NOT NULL IS NULL, NOT NULL"),
+        vec![t.clone(), f.clone(), f.clone(), f.clone(), null.clone()]
+    );
+
+    assert!(matches!(
+        db.execute("SELECT 1 BETWEEN 'a' AND 2", &[]),
+        Err(DatabaseError::Function(FunctionError::InvalidArgument(..)))
+    ));
+    assert!(matches!(
+        db.execute("SELECT 1 NOT 2", &[]),
+        Err(DatabaseError::Parse(ParseError::UnexpectedToken))
+    ));
+    assert!(matches!(
+        db.execute("SELECT 1 IS 2", &[]),
+        Err(DatabaseError::Parse(ParseError::UnexpectedToken))
+    ));
+}
+
+#[test]
+fn test_operators() {
+    let mut db = Database::new();
+    let mut row = |sql: &str| db.execute(sql, &[]).unwrap().rows()[0].clone();
+    let (t, f, null) = (Value::Integer(1), Value::Integer(0), Value::Null);
+
+    assert_eq!(
+        row("SELECT 1 + 2 * 3, (1 + 2) * 3, 7 / 2, 7 % 3, 7.0 / 2, -(2 - 5), 1 / 0"),
+        vec![
+            Value::Integer(7),
+            Value::Integer(9),
+            Value::Integer(3),
+            Value::Integer(1),
+            Value::Real(3.5),
+            Value::Integer(3),
+            null.clone()
+        ]
+    );
+    assert_eq!(
+        row("SELECT 'a' || 1 || 'b', 'a' || NULL, 1 + NULL, 2 > 1, 2 <= 1, 'a' <> 'b', 1 == 1.0"),
+        vec![
+            text("a1b"),
+            null.clone(),
+            null.clone(),
+            t.clone(),
+            f.clone(),
+            t.clone(),
+            t.clone()
+        ]
+    );
+    // three-valued logic: FALSE AND NULL is FALSE, TRUE OR NULL is TRUE, the rest is NULL
+    assert_eq!(
+        row("SELECT 0 AND NULL, 1 AND NULL, 1 OR NULL, 0 OR NULL, NULL = NULL, NOT (1 = NULL)"),
+        vec![
+            f.clone(),
+            null.clone(),
+            t.clone(),
+            null.clone(),
+            null.clone(),
+            null.clone()
+        ]
+    );
+    assert_eq!(
+        row("SELECT 1 < 2 AND 'b' LIKE 'a' || '%' OR 3 BETWEEN 1 + 1 AND 4, NOT 1 = 2 AND 2 = 2"),
+        vec![t.clone(), t.clone()]
+    );
+    assert_eq!(
+        row("SELECT 5 IN (2 + 3, 1), 'x' IS NULL OR 1 IN (NULL, 2), 2 * -3"),
+        vec![t.clone(), null.clone(), Value::Integer(-6)]
+    );
+
+    assert_eq!(
+        db.execute("SELECT 9223372036854775807 + 1", &[]),
+        Err(DatabaseError::Function(FunctionError::Overflow(
+            "+".to_string()
+        )))
+    );
+    assert!(matches!(
+        db.execute("SELECT 'a' + 1", &[]),
+        Err(DatabaseError::Function(FunctionError::InvalidArgument(..)))
+    ));
+}
+
+#[test]
+fn test_pattern_backtracking() {
+    // a recursive matcher takes exponential time on these; they must finish at once
+    let text = "a".repeat(200);
+    let like = format!("{}b", "%a".repeat(30));
+    let glob = format!("{}b", "*a".repeat(30));
+    let mut db = Database::new();
+    let start = std::time::Instant::now();
+    let rows = db
+        .execute(
+            "SELECT $1 LIKE $2, $1 GLOB $3, $1 || 'b' LIKE $2",
+            &[text.as_str().into(), like.into(), glob.into()],
+        )
+        .unwrap();
+    assert_eq!(
+        rows.rows()[0],
+        vec![Value::Integer(0), Value::Integer(0), Value::Integer(1)]
+    );
+    assert!(start.elapsed() < std::time::Duration::from_secs(1));
+}
+
+#[test]
+fn test_ordered_index() {
+    let query = parse("CREATE INDEX IF NOT EXISTS people_name ON people (name, age)");
+    assert_eq!(
+        (query.body(), query.when(), query.value()),
+        (
+            BodyKind::CreateIndex,
+            WhenKind::IfNotExists,
+            Some("people_name")
+        )
+    );
+    assert!(Query::new(tokenize("CREATE INDEX i ON people (name) WITH english")).is_err());
+    assert!(Query::new(tokenize("CREATE INDEX i ON people ()")).is_err());
+
+    let mut db = Database::new();
+    let rows = |db: &mut Database, sql: &str| db.execute(sql, &[]).unwrap().rows().to_vec();
+    db.execute(
+        "CREATE TABLE people (id INTEGER, name TEXT, age INTEGER)",
+        &[],
+    )
+    .unwrap();
+    for id in 0..100 {
+        db.execute(
+            "INSERT INTO people VALUES ($1, $2, $3)",
+            &[
+                Value::Integer(id),
+                Value::Text(format!(
+                    "{}{:02}",
+                    if id % 2 == 0 { "ann" } else { "bob" },
+                    id
+                )),
+                Value::Integer(id % 50),
+            ],
+        )
+        .unwrap();
+    }
+    db.execute(
+        "INSERT INTO people VALUES (100, NULL, NULL), (101, 7, NULL)",
+        &[],
+    )
+    .unwrap();
+    let queries = [
+        "SELECT id FROM people WHERE age BETWEEN 10 AND 12",
+        "SELECT id FROM people WHERE name LIKE 'bob1%'",
+        "SELECT id FROM people WHERE name LIKE 'ann_8' AND age > 40",
+    ];
+    let scanned: Vec<_> = queries.iter().map(|sql| rows(&mut db, sql)).collect();
+    let step = |step: &str, estimate: i64| vec![text(step), Value::Integer(estimate)];
+    assert_eq!(
+        rows(&mut db, &format!("EXPLAIN {}", queries[0])),
+        [step("SCAN people", 34)]
+    );
+
+    // BETWEEN and a prefix LIKE read the rows in range, in the order of the table
+    db.execute("CREATE INDEX people_age ON people (age)", &[])
+        .unwrap();
+    db.execute("CREATE INDEX people_name ON people (name, age)", &[])
+        .unwrap();
+    assert_eq!(
+        rows(&mut db, &format!("EXPLAIN {}", queries[0])),
+        [step("SEARCH people USING INDEX people_age", 6)]
+    );
+    assert_eq!(
+        rows(&mut db, &format!("EXPLAIN {}", queries[1])),
+        [step("SEARCH people USING INDEX people_name", 6)]
+    );
+    let indexed: Vec<_> = queries.iter().map(|sql| rows(&mut db, sql)).collect();
+    assert_eq!(indexed, scanned);
+    assert_eq!(
+        indexed[0],
+        [10, 11, 12, 60, 61, 62]
+            .map(|id| vec![Value::Integer(id)])
+            .to_vec()
+    );
+    assert_eq!(
+        indexed[1],
+        [11, 13, 15, 17, 19]
+            .map(|id| vec![Value::Integer(id)])
+            .to_vec()
+    );
+    assert_eq!(
+        indexed[2],
+        [vec![Value::Integer(48)], vec![Value::Integer(98)]]
+    );
+
+    // the index follows writes, and a condition it cannot narrow down is a scan
+    db.execute("UPDATE people SET age = 11 WHERE id = 0", &[])
+        .unwrap();
+    db.execute("DELETE FROM people WHERE id = 61", &[]).unwrap();
+    assert_eq!(
+        rows(&mut db, queries[0]),
+        [0, 10, 11, 12, 60, 62]
+            .map(|id| vec![Value::Integer(id)])
+            .to_vec()
+    );
+    for sql in [
+        "EXPLAIN SELECT id FROM people WHERE age NOT BETWEEN 10 AND 12",
+        "EXPLAIN SELECT id FROM people WHERE name LIKE '%1'",
+        "EXPLAIN SELECT id FROM people WHERE name ILIKE 'bob1%'",
+    ] {
+        assert_eq!(rows(&mut db, sql)[0][0], text("SCAN people"), "{sql}");
+    }
+    // values the bounds do not compare with are still read, so the condition rejects them
+    assert!(matches!(
+        db.execute(
+            "SELECT id FROM people WHERE name BETWEEN 'bob10' AND 'bob12'",
+            &[]
+        ),
+        Err(DatabaseError::Function(FunctionError::InvalidArgument(..)))
+    ));
+
+    assert!(db
+        .catalog()
+        .dump()
+        .unwrap()
+        .contains("CREATE INDEX people_name ON people (name, age);\n"));
+    let mut restored = Database::new();
+    restored.restore(&db.catalog().dump().unwrap()).unwrap();
+    assert_eq!(
+        rows(&mut restored, &format!("EXPLAIN {}", queries[0])),
+        [step("SEARCH people USING INDEX people_age", 6)]
+    );
+}
+
+/// The window of `SELECT f() OVER (<over>)`.
+fn parse_window(over: &str) -> Window {
+    let sql = format!("SELECT f() OVER ({})", over);
+    match Select::parse(&tokenize(&sql)) {
+        Ok(mut select) => match select.columns.remove(0) {
+            ResultColumn::Expression(_, Expression::Window { window, .. }) => window,
+            other => panic!("not a window: {:?}", other),
+        },
+        other => panic!("{} did not parse: {:?}", sql, other),
+    }
+}
+
+/// Run `function` over `(partition, order, arg)` rows.
+fn run_window(function: &str, over: &str, rows: &[(&str, i64, Value)]) -> Vec<Value> {
+    let window = parse_window(over);
+    let rows = rows
+        .iter()
+        .map(|(partition, order, arg)| WindowRow {
+            partition: if window.partition_by.is_empty() {
+                vec![]
+            } else {
+                vec![text(partition)]
+            },
+            order: if window.order_by.is_empty() {
+                vec![]
+            } else {
+                vec![Value::Integer(*order)]
+            },
+            args: match function {
+                "ROW_NUMBER" | "RANK" | "DENSE_RANK" => vec![],
+                _ => vec![arg.clone()],
+            },
+        })
+        .collect::<Vec<_>>();
+    window::evaluate(function, &window, &rows).unwrap()
+}
+
+#[test]
+fn test_parse_window() {
+    let window = parse_window(
+        "PARTITION BY 1, UPPER('b') ORDER BY 2 DESC, 3 ROWS BETWEEN 2 PRECEDING AND CURRENT ROW",
+    );
+    assert_eq!(window.partition_by.len(), 2);
+    assert_eq!(
+        window
+            .order_by
+            .iter()
+            .map(|(_, desc)| *desc)
+            .collect::<Vec<_>>(),
+        vec![true, false]
+    );
+    assert_eq!(
+        window.frame,
+        Some((FrameBound::Preceding(2), FrameBound::CurrentRow))
+    );
+    assert_eq!(
+        parse_window("ROWS UNBOUNDED PRECEDING").frame,
+        Some((FrameBound::UnboundedPreceding, FrameBound::CurrentRow))
+    );
+    assert_eq!(parse_window("").frame, None);
+
+    for over in [
+        "ROWS BETWEEN UNBOUNDED FOLLOWING AND CURRENT ROW",
+        "ROWS BETWEEN CURRENT ROW AND UNBOUNDED PRECEDING",
+        "ORDER 1",
+        "PARTITION BY 1 ORDER BY 2 ROWS",
+    ] {
+        let sql = format!("SELECT f() OVER ({})", over);
+        assert!(Select::parse(&tokenize(&sql)).is_err(), "{}", over);
+    }
+}
+
+#[test]
+fn test_window_functions() {
+    let amount = |amount: i64| Value::Integer(amount);
+    // account, date, amount
+    let rows = [
+        ("cash", 3, amount(-20)),
+        ("bank", 1, amount(500)),
+        ("cash", 1, amount(100)),
+        ("cash", 2, amount(50)),
+        ("bank", 2, amount(-100)),
+        ("cash", 2, amount(30)),
+    ];
+    // the expressions only shape the window; `run_window` supplies the values
+    let over = "PARTITION BY 1 ORDER BY 2";
+
+    assert_eq!(
+        run_window("ROW_NUMBER", over, &rows),
+        [4, 1, 1, 2, 2, 3].map(amount)
+    );
+    assert_eq!(
+        run_window("RANK", over, &rows),
+        [4, 1, 1, 2, 2, 2].map(amount)
+    );
+    assert_eq!(
+        run_window("DENSE_RANK", over, &rows),
+        [3, 1, 1, 2, 2, 2].map(amount)
+    );
+    // running balances; without ROWS the peers dated 2 are summed together
+    assert_eq!(
+        run_window("SUM", over, &rows),
+        [160, 500, 100, 180, 400, 180].map(amount)
+    );
+    assert_eq!(
+        run_window("SUM", &format!("{} ROWS UNBOUNDED PRECEDING", over), &rows),
+        [160, 500, 100, 150, 400, 180].map(amount)
+    );
+    assert_eq!(
+        run_window(
+            "SUM",
+            &format!("{} ROWS BETWEEN 1 PRECEDING AND 1 FOLLOWING", over),
+            &rows
+        ),
+        [10, 400, 150, 180, 400, 60].map(amount)
+    );
+    assert_eq!(
+        run_window("LAG", over, &rows),
+        vec![
+            amount(30),
+            Value::Null,
+            Value::Null,
+            amount(100),
+            amount(500),
+            amount(50)
+        ]
+    );
+    assert_eq!(
+        run_window("LEAD", over, &rows),
+        vec![
+            Value::Null,
+            amount(-100),
+            amount(50),
+            amount(30),
+            Value::Null,
+            amount(-20)
+        ]
+    );
+    assert_eq!(
+        run_window("COUNT", "PARTITION BY 1", &rows),
+        [4, 2, 4, 4, 2, 4].map(amount)
+    );
+    assert_eq!(run_window("MAX", "", &rows), [500; 6].map(amount));
+    assert_eq!(
+        run_window("MIN", "ORDER BY 2 DESC", &rows),
+        [-20, -100, -100, -100, -100, -100].map(amount)
+    );
+    assert_eq!(
+        run_window("AVG", "PARTITION BY 1", &rows)[1],
+        Value::Real(200.0)
+    );
+}
+
+#[test]
+fn test_window_in_select() {
+    let mut db = Database::new();
+    assert_eq!(
+        db.execute(
+            "SELECT ROW_NUMBER() OVER (ORDER BY 1), SUM(5) OVER (PARTITION BY 1), \
+             LAG(1, 1, 'none') OVER (), COUNT(*) OVER ()",
+            &[]
+        )
+        .unwrap()
+        .rows(),
+        vec![vec![
+            Value::Integer(1),
+            Value::Integer(5),
+            text("none"),
+            Value::Integer(1)
+        ]]
+    );
+    assert_eq!(
+        db.execute("SELECT RANK(1) OVER ()", &[]),
+        Err(DatabaseError::Function(FunctionError::WrongArgumentCount(
+            "RANK".to_string(),
+            1
+        )))
+    );
+    assert_eq!(
+        db.execute("SELECT UPPER('a') OVER ()", &[]),
+        Err(DatabaseError::Function(FunctionError::NoSuchFunction(
+            "UPPER".to_string()
+        )))
+    );
+
+    // over a table, windows see every row that passes WHERE, before ORDER BY and LIMIT
+    for sql in [
+        "CREATE TABLE ledger (account TEXT, day INTEGER, amount INTEGER)",
+        "INSERT INTO ledger VALUES ('cash', 3, -20), ('bank', 1, 500), ('cash', 1, 100), \
+         ('cash', 2, 50), ('bank', 2, -100), ('void', 1, 0)",
+    ] {
+        db.execute(sql, &[]).unwrap();
+    }
+    let amount = |amount: i64| Value::Integer(amount);
+    assert_eq!(
+        db.execute(
+            "SELECT account, day, SUM(amount) OVER (PARTITION BY account ORDER BY day) AS balance, \
+             RANK() OVER (ORDER BY amount DESC) FROM ledger WHERE account <> 'void' \
+             ORDER BY account, day",
+            &[]
+        )
+        .unwrap()
+        .rows(),
+        vec![
+            vec![text("bank"), amount(1), amount(500), amount(1)],
+            vec![text("bank"), amount(2), amount(400), amount(5)],
+            vec![text("cash"), amount(1), amount(100), amount(2)],
+            vec![text("cash"), amount(2), amount(150), amount(3)],
+            vec![text("cash"), amount(3), amount(130), amount(4)],
+        ]
+    );
+    assert_eq!(
+        db.execute(
+            "SELECT day FROM ledger ORDER BY ROW_NUMBER() OVER (ORDER BY amount) DESC LIMIT 2",
+            &[]
+        )
+        .unwrap()
+        .rows(),
+        vec![vec![amount(1)], vec![amount(1)]]
+    );
+    assert_eq!(
+        db.execute("SELECT day FROM ledger WHERE ROW_NUMBER() OVER () = 1", &[]),
+        Err(DatabaseError::Function(FunctionError::MisusedWindow(
+            "ROW_NUMBER".to_string()
+        )))
+    );
+}
+
+#[test]
+fn test_parameters_and_column_names() {
+    let mut db = Database::new();
+    let result = db
+        .execute(
+            "SELECT UPPER($1) AS \"name\", $2 IS NULL, LENGTH( $1 ) , $2 AS nothing",
+            &["ayşe".into(), None::<i64>.into()],
+        )
+        .unwrap();
+    assert_eq!(
+        result.columns(),
+        ["name", "$2 IS NULL", "LENGTH( $1 )", "nothing"]
+    );
+    assert_eq!(
+        result.rows(),
+        vec![vec![
+            text("AYŞE"),
+            Value::Integer(1),
+            Value::Integer(4),
+            Value::Null
+        ]]
+    );
+
+    assert_eq!(
+        db.execute("SELECT $1, $3", &[1.into(), 2.into()]),
+        Err(DatabaseError::Function(FunctionError::UnboundParameter(3)))
+    );
+    assert!(matches!(
+        db.execute("SELECT $0", &[]),
+        Err(DatabaseError::Parse(ParseError::InvalidLiteral))
+    ));
+    // placeholders do not clash with dollar quoting
+    assert_eq!(
+        db.execute("SELECT $$a$1$$", &[]).unwrap().rows(),
+        vec![vec![text("a$1")]]
+    );
+}
+
+#[test]
+fn test_typed_query() {
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Todo {
+        id: i64,
+        title: String,
+        done: bool,
+        due: Option<String>,
+    }
+
+    let mut db = Database::new();
+    let todos: Vec<Todo> = db
+        .query(
+            "SELECT $1 AS id, UPPER($2) AS title, $3 IS NULL AS done, DATE($3) AS due",
+            &[7.into(), "süt al".into(), "2024-05-01 10:00:00".into()],
+        )
+        .unwrap();
+    assert_eq!(
+        todos,
+        vec![Todo {
+            id: 7,
+            title: "SÜT AL".to_string(),
+            done: false,
+            due: Some("2024-05-01".to_string()),
+        }]
+    );
+
+    assert!(matches!(
+        db.query::<Todo>("SELECT 1 AS id", &[]),
+        Err(DatabaseError::Deserialize(_))
+    ));
+    assert_eq!(
+        db.query::<Todo>("CREATE TABLE todos (id INT)", &[])
+            .unwrap(),
+        vec![]
+    );
+}
+
+async fn read_message(stream: &mut tokio::net::TcpStream) -> (u8, Vec<u8>) {
+    use tokio::io::AsyncReadExt;
+
+    let tag = stream.read_u8().await.unwrap();
+    let len = stream.read_i32().await.unwrap() as usize;
+    let mut body = vec![0; len - 4];
+    stream.read_exact(&mut body).await.unwrap();
+    (tag, body)
+}
+
+/// Send a simple query and collect the replies up to ReadyForQuery.
+async fn pg_query(stream: &mut tokio::net::TcpStream, sql: &str) -> Vec<(u8, Vec<u8>)> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut message = vec![b'Q'];
+    message.extend((sql.len() as i32 + 5).to_be_bytes());
+    message.extend(sql.as_bytes());
+    message.push(0);
+    stream.write_all(&message).await.unwrap();
+
+    let mut replies = vec![];
+    loop {
+        let reply = read_message(stream).await;
+        if reply.0 == b'Z' {
+            return replies;
+        }
+        replies.push(reply);
+    }
+}
+
+#[tokio::test]
+async fn test_pg_wire_server() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let db = SharedDatabase::new(Database::new());
+    tokio::spawn(server::serve(listener, db.clone()));
+    let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+
+    // encryption is declined
+    stream.write_all(&8i32.to_be_bytes()).await.unwrap();
+    stream.write_all(&80877103i32.to_be_bytes()).await.unwrap();
+    assert_eq!(stream.read_u8().await.unwrap(), b'N');
+
+    let mut startup = 196608i32.to_be_bytes().to_vec();
+    startup.extend(b"user\0xsdb\0\0");
+    stream
+        .write_all(&(startup.len() as i32 + 4).to_be_bytes())
+        .await
+        .unwrap();
+    stream.write_all(&startup).await.unwrap();
+    assert_eq!(read_message(&mut stream).await, (b'R', vec![0, 0, 0, 0]));
+    loop {
+        match read_message(&mut stream).await {
+            (b'S', _) => continue,
+            reply => break assert_eq!(reply, (b'Z', b"I".to_vec())),
+        }
+    }
+
+    let replies = pg_query(
+        &mut stream,
+        "CREATE TABLE t (a INT); SELECT 1 AS one, NULL, 'ş';",
+    )
+    .await;
+    let tags = replies.iter().map(|(tag, _)| *tag).collect::<Vec<_>>();
+    assert_eq!(tags, b"CTDC");
+    assert_eq!(replies[0].1, b"CREATE TABLE\0");
+    let description = &replies[1].1;
+    assert_eq!(&description[..2], &[0, 3]);
+    assert_eq!(&description[2..6], b"one\0");
+    assert_eq!(&description[12..16], &20i32.to_be_bytes());
+    let mut row = vec![0, 3, 0, 0, 0, 1, b'1', 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 2];
+    row.extend("ş".as_bytes());
+    assert_eq!(replies[2].1, row);
+    assert_eq!(replies[3].1, b"SELECT 1\0");
+    assert!(db.snapshot().database().catalog().table("t").is_some());
+
+    // the first failing statement ends the query
+    let replies = pg_query(&mut stream, "CREATE TABLE t (a INT); DROP TABLE t").await;
+    assert_eq!(replies.len(), 1);
+    assert_eq!(replies[0].0, b'E');
+    let error = String::from_utf8_lossy(&replies[0].1);
+    assert!(error.contains("C42000\0"), "{}", error);
+    assert!(error.contains("table 't' already exists"), "{}", error);
+
+    assert_eq!(
+        pg_query(&mut stream, " -- nothing\n;").await,
+        vec![(b'I', vec![])]
+    );
+
+    // the statements of a query run as one transaction
+    let replies = pg_query(
+        &mut stream,
+        "CREATE TABLE u (a INT); INSERT INTO u VALUES (1); DROP TABLE missing",
+    )
+    .await;
+    let tags = replies.iter().map(|(tag, _)| *tag).collect::<Vec<_>>();
+    assert_eq!(tags, b"CCE");
+    assert!(db.snapshot().database().catalog().table("u").is_none());
+
+    // column types come from every row, not just the first
+    let replies = pg_query(
+        &mut stream,
+        "SELECT NULL AS a, 1 AS b, 1 AS c UNION ALL SELECT 2, 2.5, X'00'",
+    )
+    .await;
+    let description = &replies[0].1;
+    assert_eq!(&description[10..14], &20i32.to_be_bytes());
+    assert_eq!(&description[30..34], &701i32.to_be_bytes());
+    assert_eq!(&description[50..54], &25i32.to_be_bytes());
+
+    // after an extended-protocol error everything up to Sync is ignored, queries included
+    let mut messages = vec![b'P', 0, 0, 0, 4];
+    let sql = b"CREATE TABLE skipped (a)\0";
+    messages.push(b'Q');
+    messages.extend((sql.len() as i32 + 4).to_be_bytes());
+    messages.extend(sql);
+    messages.extend([b'S', 0, 0, 0, 4]);
+    stream.write_all(&messages).await.unwrap();
+    assert_eq!(read_message(&mut stream).await.0, b'E');
+    assert_eq!(read_message(&mut stream).await, (b'Z', b"I".to_vec()));
+    assert!(db
+        .snapshot()
+        .database()
+        .catalog()
+        .table("skipped")
+        .is_none());
+
+    stream.write_all(&[b'X', 0, 0, 0, 4]).await.unwrap();
+    assert_eq!(stream.read_u8().await.ok(), None);
+}
+
+#[tokio::test]
+async fn test_snapshot_isolation() {
+    let db = SharedDatabase::new(Database::new());
+    let before = db.snapshot();
+
+    let mut transaction = db.begin().await;
+    transaction.execute("CREATE TABLE a (x INT)", &[]).unwrap();
+    transaction.execute("CREATE TABLE b (x INT)", &[]).unwrap();
+    assert_eq!(db.try_begin().err(), Some(DatabaseError::Busy));
+    // readers neither wait for nor see the open transaction
+    assert_eq!(db.snapshot().database().catalog().table_names().count(), 0);
+    assert_eq!(transaction.commit(), Ok(1));
+
+    assert_eq!(before.txn(), 0);
+    assert_eq!(before.database().catalog().table_names().count(), 0);
+    let after = db.snapshot();
+    assert_eq!(after.txn(), 1);
+    assert_eq!(
+        after.database().catalog().table_names().collect::<Vec<_>>(),
+        ["a", "b"]
+    );
+    assert_eq!(
+        after.execute("DROP TABLE a", &[]),
+        Err(DatabaseError::ReadOnly)
+    );
+
+    // dropping a transaction rolls it back
+    let mut transaction = db.try_begin().unwrap();
+    transaction.execute("DROP TABLE a", &[]).unwrap();
+    drop(transaction);
+    assert_eq!(db.snapshot().txn(), 1);
+    assert!(db.snapshot().database().catalog().table("a").is_some());
+
+    db.execute("DROP TABLE a", &[]).await.unwrap();
+    assert_eq!(db.snapshot().txn(), 2);
+    assert_eq!(
+        db.execute("SELECT 1 AS one", &[]).await.unwrap().rows(),
+        vec![vec![Value::Integer(1)]]
+    );
+    assert_eq!(db.snapshot().txn(), 2);
+}
+
+#[tokio::test]
+async fn test_row_versions() {
+    let db = SharedDatabase::new(Database::new());
+    db.execute("CREATE TABLE t (id INT, name TEXT)", &[])
+        .await
+        .unwrap();
+    db.execute("INSERT INTO t VALUES (1, 'a'), (2, 'b'), (3, 'c')", &[])
+        .await
+        .unwrap();
+    let rows = |snapshot: &Snapshot| {
+        let result = snapshot.execute("SELECT id, name FROM t", &[]).unwrap();
+        let rows = result.rows().iter();
+        rows.map(|row| format!("{}{}", row[0], row[1]))
+            .collect::<Vec<_>>()
+    };
+    let before = db.snapshot();
+
+    // snapshots keep reading the versions committed when they were taken
+    let mut transaction = db.begin().await;
+    transaction
+        .execute("UPDATE t SET name = 'B' WHERE id = 2", &[])
+        .unwrap();
+    transaction
+        .execute("DELETE FROM t WHERE id = 1", &[])
+        .unwrap();
+    transaction
+        .execute("INSERT INTO t VALUES (4, 'd')", &[])
+        .unwrap();
+    // a failing statement only undoes itself
+    assert!(transaction
+        .execute("UPDATE t SET id = abs('x') WHERE id = 3", &[])
+        .is_err());
+    assert_eq!(
+        transaction
+            .execute("SELECT id FROM t", &[])
+            .unwrap()
+            .rows()
+            .len(),
+        3
+    );
+    assert_eq!(rows(&db.snapshot()), ["1a", "2b", "3c"]);
+    transaction.commit().unwrap();
+    assert_eq!(rows(&before), ["1a", "2b", "3c"]);
+    assert_eq!(rows(&db.snapshot()), ["2B", "3c", "4d"]);
+
+    // a rolled back transaction leaves nothing behind for the next one to see
+    let committed = db.snapshot();
+    let mut transaction = db.begin().await;
+    transaction
+        .execute("INSERT INTO t VALUES (5, 'e')", &[])
+        .unwrap();
+    transaction.execute("DELETE FROM t", &[]).unwrap();
+    transaction.execute("DROP TABLE t", &[]).unwrap();
+    drop(transaction);
+    let mut transaction = db.begin().await;
+    assert_eq!(
+        transaction
+            .execute("SELECT id, name FROM t", &[])
+            .unwrap()
+            .rows()
+            .len(),
+        3
+    );
+    transaction
+        .execute("ALTER TABLE t ADD flag DEFAULT 1", &[])
+        .unwrap();
+    transaction.commit().unwrap();
+    assert_eq!(rows(&committed), ["2B", "3c", "4d"]);
+    assert_eq!(
+        committed.database().catalog().table("t").unwrap().columns(),
+        ["id", "name"]
+    );
+    assert_eq!(
+        db.snapshot()
+            .execute("SELECT flag FROM t WHERE id = 4", &[])
+            .unwrap()
+            .rows(),
+        [vec![Value::Integer(1)]]
+    );
+
+    // a cloned database has rows of its own
+    let mut copy = db.snapshot().database().try_clone().unwrap();
+    copy.execute("DELETE FROM t", &[]).unwrap();
+    assert_eq!(rows(&db.snapshot()), ["2B", "3c", "4d"]);
+}
+
+#[tokio::test]
+async fn test_version_horizon() {
+    let db = SharedDatabase::new(Database::new());
+    db.execute("CREATE TABLE t (id INT)", &[]).await.unwrap();
+    db.execute("INSERT INTO t VALUES (1), (2), (3)", &[])
+        .await
+        .unwrap();
+    let versions = || {
+        let snapshot = db.snapshot();
+        snapshot
+            .database()
+            .catalog()
+            .table("t")
+            .unwrap()
+            .version_count()
+    };
+    let oldest = db.snapshot();
+    assert_eq!(db.horizon(), oldest.database().catalog().stamp());
+
+    // an open snapshot keeps the versions it reads
+    db.execute("DELETE FROM t WHERE id = 1", &[]).await.unwrap();
+    db.execute("UPDATE t SET id = 20 WHERE id = 2", &[])
+        .await
+        .unwrap();
+    assert_eq!(versions(), 4);
+    assert_eq!(db.horizon(), oldest.database().catalog().stamp());
+    assert_eq!(
+        oldest
+            .execute("SELECT id FROM t", &[])
+            .unwrap()
+            .rows()
+            .len(),
+        3
+    );
+
+    // once it is gone, the next commit drops them
+    drop(oldest);
+    db.execute("INSERT INTO t VALUES (4)", &[]).await.unwrap();
+    assert_eq!(db.horizon(), db.snapshot().database().catalog().stamp());
+    assert_eq!(versions(), 3);
+
+    // and so does VACUUM
+    let oldest = db.snapshot();
+    db.execute("DELETE FROM t WHERE id = 4", &[]).await.unwrap();
+    assert_eq!(versions(), 3);
+    drop(oldest);
+    db.execute("VACUUM", &[]).await.unwrap();
+    assert_eq!(versions(), 2);
+    assert_eq!(
+        db.snapshot()
+            .execute("SELECT id FROM t", &[])
+            .unwrap()
+            .rows(),
+        [vec![Value::Integer(20)], vec![Value::Integer(3)]]
+    );
+}
+
+#[test]
+fn test_concurrent_readers_and_writers() {
+    const WRITERS: usize = 8;
+    const TRANSACTIONS: usize = 25;
+
+    let db = SharedDatabase::new(Database::new());
+    futures::executor::block_on(async {
+        let mut transaction = db.begin().await;
+        transaction
+            .execute("CREATE TABLE entries (writer INT, n INT)", &[])
+            .unwrap();
+        transaction
+            .execute("CREATE TABLE totals (total INT)", &[])
+            .unwrap();
+        transaction
+            .execute("INSERT INTO totals VALUES (0)", &[])
+            .unwrap();
+        transaction.commit().unwrap();
+    });
+    std::thread::scope(|scope| {
+        for writer in 0..WRITERS {
+            let db = db.clone();
+            scope.spawn(move || {
+                for n in 0..TRANSACTIONS {
+                    let mut transaction = futures::executor::block_on(db.begin());
+                    for table in ["a", "b"] {
+                        let sql = format!("CREATE TABLE {}_{}_{} (x INT)", table, writer, n);
+                        transaction.execute(&sql, &[]).unwrap();
+                        let sql = format!("INSERT INTO entries VALUES ({}, {})", writer, n);
+                        transaction.execute(&sql, &[]).unwrap();
+                    }
+                    transaction
+                        .execute("UPDATE totals SET total = total + 2", &[])
+                        .unwrap();
+                    if n % 5 == 4 {
+                        // rolled back: none of it may show
+                        transaction.execute("DELETE FROM entries", &[]).unwrap();
+                        continue;
+                    }
+                    transaction.commit().unwrap();
+                }
+            });
+        }
+
+        for _ in 0..4 {
+            let db = db.clone();
+            scope.spawn(move || {
+                let mut last_txn = 0;
+                loop {
+                    let snapshot = db.snapshot();
+                    assert!(snapshot.txn() >= last_txn);
+                    last_txn = snapshot.txn();
+
+                    // every transaction is seen whole or not at all
+                    let catalog = snapshot.database().catalog();
+                    let tables = catalog.table_names().collect::<Vec<_>>();
+                    let committed = 2 * (last_txn as usize - 1);
+                    assert_eq!(tables.len(), committed + 2);
+                    for table in tables.iter().filter(|table| table.starts_with("a_")) {
+                        assert!(catalog.table(&format!("b{}", &table[1..])).is_some());
+                    }
+                    let rows = snapshot.execute("SELECT * FROM entries", &[]).unwrap();
+                    let total = snapshot.execute("SELECT total FROM totals", &[]).unwrap();
+                    assert_eq!(rows.rows().len(), committed);
+                    assert_eq!(total.rows(), [vec![Value::Integer(committed as i64)]]);
+                    if last_txn == (WRITERS * TRANSACTIONS * 4 / 5) as u64 + 1 {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    let snapshot = db.snapshot();
+    let committed = WRITERS * TRANSACTIONS * 4 / 5;
+    assert_eq!(snapshot.txn(), committed as u64 + 1);
+    assert_eq!(
+        snapshot.database().catalog().table_names().count(),
+        2 * committed + 2
+    );
+}
+
+#[test]
+fn test_vacuum() {
+    let query = parse("VACUUM");
+    assert_eq!((query.body(), query.value()), (BodyKind::Vacuum, None));
+    let query = parse("vacuum archive;");
+    assert_eq!(
+        (query.body(), query.value()),
+        (BodyKind::Vacuum, Some("archive"))
+    );
+    assert!(Query::new(tokenize("VACUUM a b")).is_err());
+
+    let mut catalog = Catalog::new();
+    execute(&mut catalog, "CREATE TABLE t (a INT)").unwrap();
+    execute(&mut catalog, "VACUUM").unwrap();
+    execute(&mut catalog, "VACUUM main").unwrap();
+    assert_eq!(
+        execute(&mut catalog, "VACUUM archive"),
+        Err(CatalogError::NoSuchDatabase("archive".to_string()))
+    );
+    assert!(catalog.table("t").is_some());
+}
+
+#[test]
+fn test_analyze() {
+    let query = parse("ANALYZE");
+    assert_eq!((query.body(), query.value()), (BodyKind::Analyze, None));
+    let query = parse("analyze notes;");
+    assert_eq!(
+        (query.body(), query.value()),
+        (BodyKind::Analyze, Some("notes"))
+    );
+
+    let mut catalog = Catalog::new();
+    execute(&mut catalog, "CREATE TABLE notes (a INT)").unwrap();
+    execute(&mut catalog, "ANALYZE").unwrap();
+    execute(&mut catalog, "ANALYZE notes").unwrap();
+    assert_eq!(
+        execute(&mut catalog, "ANALYZE missing"),
+        Err(CatalogError::NoSuchTable("missing".to_string()))
+    );
+
+    // statistics land in a table of their own, replaced by each ANALYZE
+    let mut db = Database::new();
+    let rows = |db: &mut Database, sql: &str| db.execute(sql, &[]).unwrap().rows().to_vec();
+    db.execute("CREATE TABLE big (id INTEGER, k INTEGER)", &[])
+        .unwrap();
+    db.execute("CREATE TABLE small (id INTEGER, k INTEGER, note TEXT)", &[])
+        .unwrap();
+    for id in 0..200 {
+        db.execute(
+            "INSERT INTO big VALUES ($1, $2)",
+            &[Value::Integer(id), Value::Integer(id % 20)],
+        )
+        .unwrap();
+    }
+    for id in 0..20 {
+        let note = if id < 5 { Value::Null } else { text("n") };
+        db.execute(
+            "INSERT INTO small VALUES ($1, $1, $2)",
+            &[Value::Integer(id), note],
+        )
+        .unwrap();
+    }
+    let join = "SELECT big.id, small.id FROM big JOIN small ON big.k = small.k WHERE big.id < 5";
+    let explain = format!("EXPLAIN {join}");
+    let step = |step: &str, estimate: i64| vec![text(step), Value::Integer(estimate)];
+    let before = rows(&mut db, join);
+    assert_eq!(
+        rows(&mut db, &explain),
+        vec![step("SCAN small", 20), step("JOIN SCAN big", 7)]
+    );
+
+    db.execute("ANALYZE", &[]).unwrap();
+    db.execute("ANALYZE small", &[]).unwrap();
+    assert_eq!(
+        rows(
+            &mut db,
+            "SELECT column_name, row_count, distinct_count, null_count, histogram \
+             FROM xsdb_stats WHERE table_name = 'small'"
+        ),
+        vec![
+            vec![
+                text("id"),
+                Value::Integer(20),
+                Value::Integer(20),
+                Value::Integer(0),
+                text("[0,2,4,7,9,11,14,16,19]")
+            ],
+            vec![
+                text("k"),
+                Value::Integer(20),
+                Value::Integer(20),
+                Value::Integer(0),
+                text("[0,2,4,7,9,11,14,16,19]")
+            ],
+            vec![
+                text("note"),
+                Value::Integer(20),
+                Value::Integer(1),
+                Value::Integer(5),
+                text(r#"["n","n","n","n","n","n","n","n","n"]"#)
+            ],
+        ]
+    );
+    assert_eq!(rows(&mut db, "SELECT histogram FROM xsdb_stats").len(), 5);
+
+    // with statistics the filtered side is known to be small and is read first, and
+    // the rows still come in the order the tables are written in
+    assert_eq!(
+        rows(&mut db, &explain),
+        vec![step("SCAN big", 13), step("JOIN SCAN small", 13)]
+    );
+    let after = rows(&mut db, join);
+    assert_eq!(after, before);
+    assert_eq!(
+        after,
+        (0..5)
+            .map(|id| vec![Value::Integer(id), Value::Integer(id)])
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        rows(
+            &mut db,
+            "EXPLAIN SELECT * FROM small s LEFT JOIN big ON big.k = s.k WHERE s.note IS NULL"
+        ),
+        vec![
+            step("SCAN small AS s", 20),
+            step("LEFT JOIN SCAN big", 200),
+            step("FILTER", 50)
+        ]
+    );
+
+    // a fulltext index is used where it finds fewer rows than a scan would read
+    db.execute("CREATE FULLTEXT INDEX notes ON small (note)", &[])
+        .unwrap();
+    db.execute("UPDATE small SET note = 'rare' WHERE id = 7", &[])
+        .unwrap();
+    assert_eq!(
+        rows(
+            &mut db,
+            "EXPLAIN SELECT * FROM big JOIN small ON big.k = small.k WHERE small.note MATCH 'rare'"
+        ),
+        vec![
+            step("SEARCH small USING FULLTEXT INDEX", 1),
+            step("JOIN SCAN big", 10)
+        ]
+    );
+    assert_eq!(
+        rows(
+            &mut db,
+            "SELECT big.id FROM big JOIN small ON big.k = small.k WHERE small.note MATCH 'rare'"
+        ),
+        (0..10)
+            .map(|n| vec![Value::Integer(n * 20 + 7)])
+            .collect::<Vec<_>>()
+    );
+    db.execute("UPDATE small SET note = 'every row'", &[])
+        .unwrap();
+    assert_eq!(
+        rows(
+            &mut db,
+            "EXPLAIN SELECT * FROM big JOIN small ON big.k = small.k WHERE small.note MATCH 'every'"
+        )[0],
+        step("SCAN small", 7)
+    );
+
+    // without statistics a range is taken to be a third of the rows, worth reading
+    // through the index; the histogram shows most rows in it, cheaper to scan
+    db.execute("CREATE TABLE ages (id INTEGER)", &[]).unwrap();
+    for id in 0..100 {
+        db.execute("INSERT INTO ages VALUES ($1)", &[Value::Integer(id)])
+            .unwrap();
+    }
+    db.execute("CREATE INDEX ages_id ON ages (id)", &[])
+        .unwrap();
+    let wide = "SELECT id FROM ages WHERE id BETWEEN 0 AND 89";
+    let narrow = "EXPLAIN SELECT id FROM ages WHERE id BETWEEN 40 AND 44";
+    let searched = rows(&mut db, wide);
+    assert_eq!(
+        rows(&mut db, &format!("EXPLAIN {wide}")),
+        vec![step("SEARCH ages USING INDEX ages_id", 90)]
+    );
+    db.execute("ANALYZE ages", &[]).unwrap();
+    assert_eq!(
+        rows(&mut db, &format!("EXPLAIN {wide}")),
+        vec![step("SCAN ages", 95)]
+    );
+    assert_eq!(rows(&mut db, wide), searched);
+    assert_eq!(searched.len(), 90);
+    assert_eq!(
+        rows(&mut db, narrow),
+        vec![step("SEARCH ages USING INDEX ages_id", 5)]
+    );
+
+    db.execute("DROP TABLE small", &[]).unwrap();
+    assert_eq!(
+        rows(
+            &mut db,
+            "SELECT table_name FROM xsdb_stats WHERE table_name = 'small'"
+        ),
+        Vec::<Row>::new()
+    );
+}
+
+#[test]
+fn test_statements() {
+    let statements = Tokenizer::new(
+        "CREATE TABLE t (a); ; -- note\nCREATE TRIGGER g AFTER INSERT ON t FOR EACH ROW BEGIN DELETE FROM t; END; USE main",
+    )
+    .statements()
+    .unwrap();
+    assert_eq!(
+        statements,
+        vec![
+            "CREATE TABLE t (a)",
+            "CREATE TRIGGER g AFTER INSERT ON t FOR EACH ROW BEGIN DELETE FROM t; END",
+            "USE main",
+        ]
+    );
+    assert_eq!(
+        Tokenizer::new(" ; -- only a comment").statements(),
+        Ok(vec![])
+    );
+}
+
+#[test]
+fn test_dump_and_restore() {
+    let mut db = Database::new();
+    for sql in [
+        "CREATE TABLE users (id INT PRIMARY KEY, \"full name\" TEXT)",
+        "CREATE VIEW a_names AS SELECT \"full name\" FROM b_users",
+        "CREATE VIEW b_users AS SELECT * FROM users",
+        "CREATE TRIGGER log AFTER DELETE ON users FOR EACH ROW BEGIN DELETE FROM users; END",
+        "CREATE DATABASE archive",
+        "USE archive",
+        "CREATE TABLE \"old users\" (id)",
+        "USE main",
+        "INSERT INTO users VALUES (1, 'Ann'), (2, 'it''s'), (3, NULL)",
+        "USE archive",
+    ] {
+        db.execute(sql, &[]).unwrap();
+    }
+    for value in [
+        Value::Blob(vec![0, 255]),
+        Value::Real(3.0),
+        Value::Integer(i64::MIN),
+        Value::Real(f64::INFINITY),
+    ] {
+        db.execute("INSERT INTO \"old users\" VALUES ($1)", &[value])
+            .unwrap();
+    }
+
+    let script = db.catalog().dump().unwrap();
+    assert_eq!(
+        script,
+        "CREATE DATABASE archive;\n\
+         USE archive;\n\
+         CREATE TABLE \"old users\" (id);\n\
+         INSERT INTO \"old users\" (id) VALUES\n\
+         (X'00FF'),\n\
+         (3.0),\n\
+         ((-9223372036854775807 - 1)),\n\
+         (9e999);\n\
+         USE main;\n\
+         CREATE TABLE users (id INT, \"full name\" TEXT);\n\
+         INSERT INTO users (id, \"full name\") VALUES\n\
+         (1, 'Ann'),\n\
+         (2, 'it''s'),\n\
+         (3, NULL);\n\
+         CREATE VIEW b_users AS SELECT * FROM users;\n\
+         CREATE VIEW a_names AS SELECT \"full name\" FROM b_users;\n\
+         CREATE TRIGGER log AFTER DELETE ON users FOR EACH ROW BEGIN DELETE FROM users; END;\n\
+         USE archive;\n"
+    );
+
+    let mut restored = Database::new();
+    assert_eq!(restored.restore(&script), Ok(11));
+    assert_eq!(restored.catalog().dump().unwrap(), script);
+    assert_eq!(restored.catalog().current_database(), "archive");
+    assert_eq!(
+        restored
+            .execute("SELECT * FROM \"old users\"", &[])
+            .unwrap()
+            .rows(),
+        db.execute("SELECT * FROM \"old users\"", &[])
+            .unwrap()
+            .rows()
+    );
+
+    // rows are inserted a batch at a time
+    let mut numbers = Database::new();
+    numbers.execute("CREATE TABLE n (i INT)", &[]).unwrap();
+    for i in 0..250 {
+        numbers
+            .execute("INSERT INTO n VALUES ($1)", &[Value::Integer(i)])
+            .unwrap();
+    }
+    let script = numbers.catalog().dump().unwrap();
+    assert_eq!(script.matches("INSERT INTO n (i) VALUES").count(), 3);
+    let mut restored = Database::new();
+    assert_eq!(restored.restore(&script), Ok(6));
+    assert_eq!(
+        restored.execute("SELECT i FROM n", &[]).unwrap().rows(),
+        numbers.execute("SELECT i FROM n", &[]).unwrap().rows()
+    );
+    let script = db.catalog().dump().unwrap();
+
+    // a failing statement leaves the database as it was
+    let broken = format!("{}CREATE TABLE \"old users\" (id);", script);
+    assert_eq!(
+        db.restore(&broken),
+        Err(DatabaseError::Catalog(CatalogError::TableExists(
+            "old users".to_string()
+        )))
+    );
+    assert_eq!(db.catalog().dump().unwrap(), script);
+}
+
+#[test]
+fn test_json() {
+    assert_eq!(
+        kinds("doc->'a' ->> '$.b' - 1"),
+        vec![
+            TokenKind::Identifier,
+            TokenKind::JsonArrow,
+            TokenKind::SingleQuotedString,
+            TokenKind::JsonLongArrow,
+            TokenKind::SingleQuotedString,
+            TokenKind::Minus,
+            TokenKind::Number,
+            TokenKind::EOF
+        ]
+    );
+
+    let mut db = Database::new();
+    let doc = r#"{"name": "ada", "tags": ["x", "y"], "age": 36, "admin": true, "pet": null}"#;
+    let row = |sql: &str| {
+        db.try_clone()
+            .unwrap()
+            .execute(sql, &[doc.into()])
+            .unwrap()
+            .rows()[0]
+            .clone()
+    };
+    assert_eq!(
+        row("SELECT $1 -> 'name', $1 ->> 'name', $1 -> 'tags' -> 1, $1 ->> '$.tags[#-1]'"),
+        vec![text("\"ada\""), text("ada"), text("\"y\""), text("y")]
+    );
+    assert_eq!(
+        row("SELECT $1 ->> 'admin', $1 -> 'pet', $1 -> 'missing', $1 -> 'tags' ->> 5"),
+        vec![Value::Integer(1), text("null"), Value::Null, Value::Null]
+    );
+    assert_eq!(
+        row("SELECT json_extract($1, '$.age'), json_extract($1, '$.tags'), json_extract($1, '$.age', '$.nope')"),
+        vec![Value::Integer(36), text("[\"x\",\"y\"]"), text("[36,null]")]
+    );
+    assert_eq!(
+        row("SELECT json_type($1), json_type($1, '$.age'), json_valid($1), json_valid('{'), json(' [1, 2] ')"),
+        vec![
+            text("object"),
+            text("integer"),
+            Value::Integer(1),
+            Value::Integer(0),
+            text("[1,2]")
+        ]
+    );
+    assert_eq!(
+        row("SELECT $1 ->> 'age' BETWEEN 30 AND 40, NULL -> 'a'"),
+        vec![Value::Integer(1), Value::Null]
+    );
+    assert_eq!(
+        db.execute("SELECT '{' -> 'a'", &[]),
+        Err(DatabaseError::Function(FunctionError::InvalidArgument(
+            "->".to_string(),
+            text("{")
+        )))
+    );
+    assert!(db.execute("SELECT json_extract('{}', 'a')", &[]).is_err());
+
+    let result = db
+        .execute("SELECT * FROM json_each($1, '$.tags')", &[doc.into()])
+        .unwrap();
+    assert_eq!(result.columns(), ["key", "value", "type", "fullkey"]);
+    assert_eq!(
+        result.rows(),
+        [
+            vec![
+                Value::Integer(0),
+                text("x"),
+                text("text"),
+                text("$.tags[0]")
+            ],
+            vec![
+                Value::Integer(1),
+                text("y"),
+                text("text"),
+                text("$.tags[1]")
+            ],
+        ]
+    );
+    let result = db
+        .execute(
+            "SELECT value, key FROM json_each('{\"b\": 2, \"a\": [1]}');",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(
+        result.rows(),
+        [
+            vec![text("[1]"), text("a")],
+            vec![Value::Integer(2), text("b")]
+        ]
+    );
+    assert_eq!(
+        db.execute("SELECT nope FROM json_each('[]')", &[]),
+        Err(DatabaseError::Catalog(CatalogError::NoSuchColumn(
+            "nope".to_string()
+        )))
+    );
+    assert_eq!(
+        db.execute("SELECT * FROM users", &[]),
+        Err(DatabaseError::Catalog(CatalogError::NoSuchTable(
+            "users".to_string()
+        )))
+    );
+
+    // a JSON column only takes valid JSON, however the row is written
+    db.execute("CREATE TABLE docs (id INTEGER, doc JSON)", &[])
+        .unwrap();
+    db.execute(
+        "INSERT INTO docs VALUES (1, '{\"tags\": [\"a\", \"b\"]}'), (2, NULL), (3, 42)",
+        &[],
+    )
+    .unwrap();
+    let invalid = Err(DatabaseError::Catalog(CatalogError::InvalidJson(
+        "doc".to_string(),
+    )));
+    assert_eq!(
+        db.execute("INSERT INTO docs VALUES (4, '{oops')", &[]),
+        invalid
+    );
+    assert_eq!(
+        db.execute("INSERT INTO docs VALUES (4, X'00')", &[]),
+        invalid
+    );
+    assert_eq!(
+        db.execute("UPDATE docs SET doc = 'not json' WHERE id = 2", &[]),
+        invalid
+    );
+    assert_eq!(
+        db.execute("ALTER TABLE docs ADD meta jsonb DEFAULT 'nope'", &[]),
+        Err(DatabaseError::Catalog(CatalogError::InvalidJson(
+            "meta".to_string()
+        )))
+    );
+    assert_eq!(
+        db.execute("SELECT id, doc ->> '$.tags[1]' FROM docs", &[])
+            .unwrap()
+            .rows(),
+        [
+            vec![Value::Integer(1), text("b")],
+            vec![Value::Integer(2), Value::Null],
+            vec![Value::Integer(3), Value::Null]
+        ]
+    );
+}
+
+#[test]
+fn test_fulltext_index() {
+    let query =
+        parse("CREATE FULLTEXT INDEX IF NOT EXISTS todo_text ON todos (title, description);");
+    assert_eq!(
+        (query.body(), query.when(), query.value()),
+        (
+            BodyKind::CreateFulltextIndex,
+            WhenKind::IfNotExists,
+            Some("todo_text")
+        )
+    );
+    assert_eq!(
+        query
+            .subqueries()
+            .iter()
+            .map(|q| (q.body(), q.value().unwrap()))
+            .collect::<Vec<_>>(),
+        vec![
+            (BodyKind::Table, "todos"),
+            (BodyKind::Field, "title"),
+            (BodyKind::Field, "description")
+        ]
+    );
+    assert!(Query::new(tokenize("CREATE FULLTEXT INDEX i ON todos ()")).is_err());
+    assert!(Query::new(tokenize("CREATE FULLTEXT i ON todos (a)")).is_err());
+    assert!(Query::new(tokenize("CREATE FULLTEXT INDEX i ON todos (a) WITH")).is_err());
+    let query = parse("CREATE FULLTEXT INDEX i ON todos (title) WITH turkish");
+    assert_eq!(
+        query
+            .subqueries()
+            .last()
+            .map(|q| (q.body(), q.value().unwrap())),
+        Some((BodyKind::Language, "turkish"))
+    );
+
+    let mut catalog = Catalog::new();
+    execute(&mut catalog, "CREATE TABLE todos (id, title, description)").unwrap();
+    execute(
+        &mut catalog,
+        "CREATE FULLTEXT INDEX words ON todos (title, description)",
+    )
+    .unwrap();
+    assert_eq!(
+        execute(&mut catalog, "CREATE FULLTEXT INDEX words ON todos (title)"),
+        Err(CatalogError::IndexExists("words".to_string()))
+    );
+    assert_eq!(
+        execute(&mut catalog, "CREATE FULLTEXT INDEX w ON todos (notes)"),
+        Err(CatalogError::NoSuchColumn("notes".to_string()))
+    );
+    assert_eq!(
+        execute(&mut catalog, "CREATE FULLTEXT INDEX w ON missing (a)"),
+        Err(CatalogError::NoSuchTable("missing".to_string()))
+    );
+    assert_eq!(
+        execute(
+            &mut catalog,
+            "CREATE FULLTEXT INDEX w ON todos (title) WITH klingon"
+        ),
+        Err(CatalogError::NoSuchLanguage("klingon".to_string()))
+    );
+    execute(
+        &mut catalog,
+        "CREATE FULLTEXT INDEX stems ON todos (title) WITH English",
+    )
+    .unwrap();
+    assert!(catalog
+        .dump()
+        .unwrap()
+        .contains("CREATE FULLTEXT INDEX stems ON todos (title) WITH English;\n"));
+    execute(&mut catalog, "DROP INDEX stems").unwrap();
+
+    execute(
+        &mut catalog,
+        "ALTER TABLE todos RENAME COLUMN title TO name, RENAME TO tasks",
+    )
+    .unwrap();
+    let index = catalog.index("words").unwrap();
+    assert_eq!(
+        (index.table(), index.columns()),
+        (
+            "tasks",
+            &["name".to_string(), "description".to_string()][..]
+        )
+    );
+    execute(&mut catalog, "CREATE FULLTEXT INDEX names ON tasks (name)").unwrap();
+    execute(&mut catalog, "ALTER TABLE tasks DROP COLUMN description").unwrap();
+    assert_eq!(catalog.index_names().collect::<Vec<_>>(), vec!["names"]);
+    assert!(catalog
+        .dump()
+        .unwrap()
+        .contains("CREATE FULLTEXT INDEX names ON tasks (name);\n"));
+
+    execute(&mut catalog, "DROP INDEX names").unwrap();
+    execute(&mut catalog, "DROP INDEX IF EXISTS names").unwrap();
+    assert_eq!(
+        execute(&mut catalog, "DROP INDEX names"),
+        Err(CatalogError::NoSuchIndex("names".to_string()))
+    );
+    execute(&mut catalog, "CREATE FULLTEXT INDEX names ON tasks (name)").unwrap();
+    execute(&mut catalog, "DROP TABLE tasks").unwrap();
+    assert_eq!(catalog.index_names().count(), 0);
+}
+
+#[test]
+fn test_fulltext_search() {
+    let analyzer = Analyzer::new();
+    assert_eq!(
+        analyzer.terms("Pay the İzmir rent—twice, 2x!"),
+        vec!["pay", "the", "izmir", "rent", "twice", "2x"]
+    );
+    assert_eq!(
+        Analyzer::turkish().terms("IRMAK İstanbul'da"),
+        vec!["ırmak", "istanbul", "da"]
+    );
+    let stemmed = Analyzer::new().with_stemmer(|term| term.trim_end_matches('s').to_string());
+    assert_eq!(stemmed.terms("Groceries lists s"), vec!["grocerie", "list"]);
+    assert_eq!(
+        Analyzer::for_language("english")
+            .unwrap()
+            .terms("Groceries lists glass is"),
+        vec!["grocery", "list", "glass", "is"]
+    );
+    assert!(Analyzer::for_language("klingon").is_none());
+
+    let mut index = InvertedIndex::new(Analyzer::new());
+    index.insert(1, "buy milk and bread");
+    index.insert(2, "pay rent");
+    index.insert(3, "milk milk milk for the cat");
+    index.insert(4, "call mom about the milk and the rent and the car");
+    assert_eq!(index.len(), 4);
+
+    let ids = |results: Vec<(u64, f64)>| results.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+    // more occurrences in a shorter document rank higher
+    assert_eq!(ids(index.search("MILK")), vec![3, 1, 4]);
+    assert_eq!(ids(index.search("milk rent")), vec![4]);
+    assert_eq!(ids(index.search("milk dog")), Vec::<u64>::new());
+    assert_eq!(ids(index.search("")), Vec::<u64>::new());
+    let results = index.search("rent");
+    assert!(results[0].1 > results[1].1);
+
+    index.insert(3, "feed the cat");
+    index.remove(2);
+    index.remove(7);
+    assert_eq!(index.len(), 3);
+    assert_eq!(ids(index.search("milk")), vec![1, 4]);
+    assert_eq!(ids(index.search("rent")), vec![4]);
+
+    let mut db = Database::new();
+    let row = |db: &mut Database, sql: &str| db.execute(sql, &[]).unwrap().rows()[0].clone();
+    assert_eq!(
+        row(
+            &mut db,
+            "SELECT 'Buy MILK, bread' MATCH 'milk buy', 'Buy milk' MATCH 'milk cat', \
+             'Buy milk' NOT MATCH 'cat', NULL MATCH 'milk'"
+        ),
+        vec![
+            Value::Integer(1),
+            Value::Integer(0),
+            Value::Integer(1),
+            Value::Null
+        ]
+    );
+
+    // an indexed column is searched through the index, best match first
+    db.execute("CREATE TABLE notes (id, title, body)", &[])
+        .unwrap();
+    db.execute(
+        "INSERT INTO notes VALUES (1, 'Groceries', 'buy milk and bread'), \
+         (2, 'Bills', 'pay rent'), (3, 'Cat', 'milk milk milk for the cat')",
+        &[],
+    )
+    .unwrap();
+    db.execute("CREATE FULLTEXT INDEX words ON notes (title, body)", &[])
+        .unwrap();
+    db.execute(
+        "INSERT INTO notes VALUES (4, 'Calls', 'call mom about the milk and the rent and the car')",
+        &[],
+    )
+    .unwrap();
+    let ids = |db: &mut Database, sql: &str| {
+        db.execute(sql, &[])
+            .unwrap()
+            .rows()
+            .iter()
+            .map(|row| row[0].clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        ids(&mut db, "SELECT id FROM notes WHERE body MATCH 'MILK'"),
+        vec![Value::Integer(3), Value::Integer(1), Value::Integer(4)]
+    );
+    assert_eq!(
+        ids(
+            &mut db,
+            "SELECT id FROM notes n WHERE id > 1 AND n.body MATCH 'milk' AND id < 4"
+        ),
+        vec![Value::Integer(3)]
+    );
+    // the words have to be in the column matched, not just somewhere in the index
+    assert_eq!(
+        ids(
+            &mut db,
+            "SELECT id FROM notes WHERE body MATCH 'cat groceries'"
+        ),
+        Vec::<Value>::new()
+    );
+    assert_eq!(
+        ids(&mut db, "SELECT id FROM notes WHERE body MATCH NULL"),
+        Vec::<Value>::new()
+    );
+    assert_eq!(
+        ids(
+            &mut db,
+            "SELECT id FROM notes WHERE body MATCH 'milk' ORDER BY id DESC LIMIT 2"
+        ),
+        vec![Value::Integer(4), Value::Integer(3)]
+    );
+
+    db.execute("UPDATE notes SET body = 'feed the cat' WHERE id = 3", &[])
+        .unwrap();
+    db.execute("DELETE FROM notes WHERE id = 1", &[]).unwrap();
+    assert_eq!(
+        ids(&mut db, "SELECT id FROM notes WHERE body MATCH 'milk'"),
+        vec![Value::Integer(4)]
+    );
+    assert_eq!(
+        ids(&mut db, "SELECT id FROM notes WHERE body MATCH 'cat'"),
+        vec![Value::Integer(3)]
+    );
+
+    // the index's language decides what matches
+    db.execute("CREATE TABLE places (name)", &[]).unwrap();
+    db.execute(
+        "INSERT INTO places VALUES ('IRMAK'), ('Irmak'), ('Gardens')",
+        &[],
+    )
+    .unwrap();
+    assert_eq!(
+        ids(&mut db, "SELECT name FROM places WHERE name MATCH 'ırmak'"),
+        Vec::<Value>::new()
+    );
+    db.execute(
+        "CREATE FULLTEXT INDEX turkish_names ON places (name) WITH turkish",
+        &[],
+    )
+    .unwrap();
+    assert_eq!(
+        ids(&mut db, "SELECT name FROM places WHERE name MATCH 'ırmak'"),
+        vec![
+            Value::Text("IRMAK".to_string()),
+            Value::Text("Irmak".to_string())
+        ]
+    );
+    db.execute("DROP INDEX turkish_names", &[]).unwrap();
+    db.execute(
+        "CREATE FULLTEXT INDEX english_names ON places (name) WITH english",
+        &[],
+    )
+    .unwrap();
+    assert_eq!(
+        ids(&mut db, "SELECT name FROM places WHERE name MATCH 'garden'"),
+        vec![Value::Text("Gardens".to_string())]
+    );
+}
+
+#[test]
+fn test_migrations() {
+    let dir = std::env::temp_dir().join(format!("xsdb-migrations-{}", std::process::id()));
+    let write = |migration: &str, file: &str, sql: &str| {
+        std::fs::create_dir_all(dir.join(migration)).unwrap();
+        std::fs::write(dir.join(migration).join(file), sql).unwrap();
+    };
+    write(
+        "2025-05-25-075935_create_customers",
+        "up.sql",
+        "CREATE TABLE customers (id, name);",
+    );
+    write(
+        "2025-05-25-075935_create_customers",
+        "down.sql",
+        "DROP TABLE customers;",
+    );
+    write(
+        "2025-05-25-124719_transactions",
+        "up.sql",
+        "-- Your SQL goes here\nCREATE TABLE transactions (id, amount);\n\
+         CREATE TRIGGER t AFTER INSERT ON transactions FOR EACH ROW BEGIN SELECT 1; END;",
+    );
+    write(
+        "2025-05-25-124719_transactions",
+        "down.sql",
+        "DROP TABLE transactions",
+    );
+    std::fs::write(dir.join("README"), "not a migration").unwrap();
+
+    let migrations = Migrations::load(&dir).unwrap();
+    assert_eq!(
+        migrations
+            .iter()
+            .map(|m| (m.version(), m.name()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("2025-05-25-075935", "create_customers"),
+            ("2025-05-25-124719", "transactions")
+        ]
+    );
+
+    let mut db = Database::new();
+    assert_eq!(
+        migrations.run_pending(&mut db).unwrap(),
+        vec!["2025-05-25-075935", "2025-05-25-124719"]
+    );
+    assert_eq!(
+        db.catalog().table_names().collect::<Vec<_>>(),
+        vec!["customers", "transactions", "xsdb_migrations"]
+    );
+    assert_eq!(db.catalog().trigger_names().collect::<Vec<_>>(), vec!["t"]);
+    assert_eq!(
+        migrations.run_pending(&mut db).unwrap(),
+        Vec::<String>::new()
+    );
+
+    // the applied versions are rows of a table, so a dump carries them along
+    let script = db.catalog().dump().unwrap();
+    assert!(script.contains(
+        "INSERT INTO xsdb_migrations (version) VALUES\n\
+         ('2025-05-25-075935'),\n\
+         ('2025-05-25-124719');\n"
+    ));
+    let mut restored = Database::new();
+    restored.restore(&script).unwrap();
+    assert_eq!(
+        restored.catalog().applied_migrations().unwrap(),
+        vec!["2025-05-25-075935", "2025-05-25-124719"]
+    );
+    assert_eq!(migrations.pending(&restored).unwrap().count(), 0);
+
+    assert_eq!(migrations.redo(&mut db).unwrap(), "2025-05-25-124719");
+    assert_eq!(db.catalog().applied_migrations().unwrap().len(), 2);
+    assert_eq!(migrations.revert(&mut db).unwrap(), "2025-05-25-124719");
+    assert!(db.catalog().table("transactions").is_none());
+    assert_eq!(
+        migrations
+            .pending(&db)
+            .unwrap()
+            .map(|m| m.version())
+            .collect::<Vec<_>>(),
+        vec!["2025-05-25-124719"]
+    );
+
+    // a failing migration is rolled back as a whole and not recorded
+    write(
+        "2025-06-01-000000_broken",
+        "up.sql",
+        "CREATE TABLE orders (id); CREATE TABLE customers (id);",
+    );
+    let migrations = Migrations::load(&dir).unwrap();
+    let err = migrations.run_pending(&mut db).unwrap_err();
+    assert!(matches!(
+        err,
+        MigrationError::Failed(ref version, DatabaseError::Catalog(CatalogError::TableExists(_)))
+            if version == "2025-06-01-000000"
+    ));
+    assert!(db.catalog().table("orders").is_none());
+    assert!(db.catalog().table("transactions").is_some());
+    assert_eq!(db.catalog().applied_migrations().unwrap().len(), 2);
+
+    std::fs::remove_dir_all(dir.join("2025-06-01-000000_broken")).unwrap();
+    let migrations = Migrations::load(&dir).unwrap();
+    migrations.revert(&mut db).unwrap();
+    migrations.revert(&mut db).unwrap();
+    assert_eq!(
+        db.catalog().table_names().collect::<Vec<_>>(),
+        vec!["xsdb_migrations"]
+    );
+    assert!(matches!(
+        migrations.revert(&mut db),
+        Err(MigrationError::NothingToRevert)
+    ));
+
+    // migrations of a database kept in a file are saved to it once they succeed
+    let path = dir.join("migrated.xsdb");
+    let mut file_db = Database::open(&path).unwrap();
+    migrations.run_pending(&mut file_db).unwrap();
+    drop(file_db);
+    let file_db = Database::open(&path).unwrap();
+    assert_eq!(file_db.catalog().applied_migrations().unwrap().len(), 2);
+    assert!(file_db.catalog().table("transactions").is_some());
+    drop(file_db);
+    std::fs::remove_file(&path).unwrap();
+
+    std::fs::remove_file(dir.join("2025-05-25-075935_create_customers/down.sql")).unwrap();
+    let migrations = Migrations::load(&dir).unwrap();
+    migrations.run_pending(&mut db).unwrap();
+    migrations.revert(&mut db).unwrap();
+    assert!(matches!(
+        migrations.revert(&mut db),
+        Err(MigrationError::Irreversible(version)) if version == "2025-05-25-075935"
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_project_migrations() {
+    let migrations = Migrations::load("migrations").unwrap();
+    assert_eq!(
+        migrations
+            .iter()
+            .map(|m| (m.version(), m.name()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("00000000000000", "diesel_initial_setup"),
+            ("2025-05-25-075935", "create_customers"),
+            ("2025-05-25-124719", "transactions")
+        ]
+    );
+
+    // the plpgsql bodies stay whole, semicolons and all
+    let setup =
+        std::fs::read_to_string("migrations/00000000000000_diesel_initial_setup/up.sql").unwrap();
+    let statements = Tokenizer::new(&setup).statements().unwrap();
+    assert_eq!(statements.len(), 2);
+    assert!(statements[0].starts_with("CREATE OR REPLACE FUNCTION diesel_manage_updated_at"));
+    assert!(statements[1].ends_with("END;\n$$ LANGUAGE plpgsql"));
+    let query = parse(statements[1]);
+    assert_eq!(
+        (query.body(), query.value()),
+        (BodyKind::CreateFunction, Some("diesel_set_updated_at"))
+    );
+
+    // xsdb cannot run plpgsql, so the setup migration fails as a whole and nothing after
+    // it is applied
+    let mut db = Database::new();
+    let err = migrations.run_pending(&mut db).unwrap_err();
+    assert!(matches!(
+        err,
+        MigrationError::Failed(
+            ref version,
+            DatabaseError::Catalog(CatalogError::UnsupportedLanguage(ref language))
+        ) if version == "00000000000000" && language == "plpgsql"
+    ));
+    assert_eq!(
+        err.to_string(),
+        "migration 00000000000000 failed: functions in language 'plpgsql' are not supported"
+    );
+    assert!(db.catalog().applied_migrations().unwrap().is_empty());
+    assert_eq!(db.catalog().table_names().count(), 0);
+    assert_eq!(migrations.pending(&db).unwrap().count(), 3);
+}
+
+/// Pages kept in memory, counting the reads that reach it.
+#[derive(Default)]
+struct MemoryStore {
+    pages: HashMap<PageId, Vec<u8>>,
+    reads: usize,
+}
+
+impl PageStore for MemoryStore {
+    fn read_page(&mut self, id: PageId, buffer: &mut [u8]) -> std::io::Result<()> {
+        self.reads += 1;
+        match self.pages.get(&id) {
+            Some(page) => buffer.copy_from_slice(page),
+            None => buffer.fill(0),
+        }
+        Ok(())
+    }
+
+    fn write_page(&mut self, id: PageId, buffer: &[u8]) -> std::io::Result<()> {
+        self.pages.insert(id, buffer.to_vec());
+        Ok(())
+    }
+}
+
+#[test]
+fn test_buffer_pool() {
+    let mut pool = BufferPool::new(MemoryStore::default(), 2 * PAGE_SIZE + 100);
+    assert_eq!(pool.capacity(), 2);
+    assert_eq!(BufferPool::new(MemoryStore::default(), 0).capacity(), 1);
+
+    let page = pool.pin(7).unwrap();
+    assert_eq!(page.id(), 7);
+    assert!(pool.page(&page).iter().all(|&b| b == 0));
+    pool.page_mut(&page)[..5].copy_from_slice(b"hello");
+    pool.unpin(page);
+
+    let page = pool.pin(8).unwrap();
+    pool.unpin(page);
+    let page = pool.pin(7).unwrap();
+    assert_eq!(&pool.page(&page)[..5], b"hello");
+    pool.unpin(page);
+    assert_eq!(
+        pool.metrics(),
+        Metrics {
+            hits: 1,
+            misses: 2,
+            evictions: 0,
+            writes: 0
+        }
+    );
+
+    // 8 is the least recently used, and clean, so it is dropped without a write
+    let nine = pool.pin(9).unwrap();
+    assert_eq!(pool.metrics().evictions, 1);
+    assert_eq!(pool.metrics().writes, 0);
+
+    // pinned pages stay; the dirty page 7 is written back when it makes room
+    let ten = pool.pin(10).unwrap();
+    assert_eq!(pool.metrics().writes, 1);
+    assert!(matches!(pool.pin(11), Err(BufferError::AllPinned)));
+    pool.unpin(nine);
+    let page = pool.pin(7).unwrap();
+    assert_eq!(&pool.page(&page)[..5], b"hello");
+    pool.unpin(page);
+    pool.unpin(ten);
+    assert_eq!(
+        pool.metrics(),
+        Metrics {
+            hits: 1,
+            misses: 6,
+            evictions: 3,
+            writes: 1
+        }
+    );
+
+    let page = pool.pin(10).unwrap();
+    pool.page_mut(&page)[0] = 1;
+    pool.unpin(page);
+    pool.flush().unwrap();
+    pool.flush().unwrap();
+    assert_eq!(pool.metrics().writes, 2);
+}
+
+#[test]
+fn test_buffer_pool_file() {
+    let path = std::env::temp_dir().join(format!("xsdb-pages-{}", std::process::id()));
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+    let mut pool = BufferPool::new(file, PAGE_SIZE);
+    for id in [3, 0] {
+        let page = pool.pin(id).unwrap();
+        pool.page_mut(&page).fill(id as u8 + 1);
+        pool.unpin(page);
+    }
+    pool.flush().unwrap();
+    assert_eq!(
+        std::fs::metadata(&path).unwrap().len(),
+        4 * PAGE_SIZE as u64
+    );
+
+    // a larger-than-memory scan reads every page back through the single frame
+    for id in 0..5 {
+        let page = pool.pin(id).unwrap();
+        let expected = match id {
+            0 => 1,
+            3 => 4,
+            _ => 0,
+        };
+        assert!(pool.page(&page).iter().all(|&b| b == expected));
+        pool.unpin(page);
+    }
+    assert_eq!(pool.metrics().evictions, 5);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_storage_pages() {
+    let path = std::env::temp_dir().join(format!("xsdb-storage-{}", std::process::id()));
+    let open = |memory_limit| Database::open_with_memory_limit(&path, memory_limit).unwrap();
+    let ids = |db: &mut Database| {
+        let result = db.execute("SELECT id FROM t", &[]).unwrap();
+        result
+            .rows()
+            .iter()
+            .map(|row| row[0].clone())
+            .collect::<Vec<_>>()
+    };
+    let mut db = open(PAGE_SIZE);
+    assert_eq!(db.storage().unwrap().pages(), 1);
+    db.execute("CREATE TABLE t (id INTEGER, body TEXT)", &[])
+        .unwrap();
+    let body = "x".repeat(1000);
+    for id in 0..20 {
+        db.execute(
+            "INSERT INTO t VALUES ($1, $2)",
+            &[Value::Integer(id), Value::Text(body.clone())],
+        )
+        .unwrap();
+    }
+    drop(db);
+
+    // rows are read from their pages through the pool, which holds a single page here
+    let mut db = open(PAGE_SIZE);
+    assert_eq!(ids(&mut db).len(), 20);
+    assert!(db.storage().unwrap().metrics().evictions > 0);
+    let mut roomy = open(POOL_MEMORY);
+    assert_eq!(ids(&mut roomy).len(), 20);
+    assert_eq!(roomy.storage().unwrap().metrics().evictions, 0);
+    drop(roomy);
+
+    // a commit writes back the end of the heap, a new image and the header
+    let writes = db.storage().unwrap().metrics().writes;
+    db.execute("INSERT INTO t VALUES (20, 'short')", &[])
+        .unwrap();
+    assert_eq!(db.storage().unwrap().metrics().writes - writes, 3);
+
+    // deleted rows keep their pages until VACUUM rewrites the heap
+    let pages = db.storage().unwrap().pages();
+    db.execute("DELETE FROM t WHERE id < 18", &[]).unwrap();
+    assert_eq!(db.storage().unwrap().pages(), pages);
+    db.execute("VACUUM", &[]).unwrap();
+    let storage = db.storage().unwrap();
+    assert_eq!(storage.free_pages(), 0);
+    assert_eq!(storage.pages(), 3);
+    assert_eq!(
+        std::fs::metadata(&path).unwrap().len(),
+        3 * PAGE_SIZE as u64
+    );
+    drop(db);
+    assert_eq!(ids(&mut open(PAGE_SIZE)), [18, 19, 20].map(Value::Integer));
+
+    std::fs::write(&path, b"not a database").unwrap();
+    let file = std::fs::File::options()
+        .read(true)
+        .write(true)
+        .open(&path)
+        .unwrap();
+    assert!(matches!(
+        Storage::open(file, PAGE_SIZE),
+        Err(StorageError::Corrupt)
+    ));
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// A page written, or `None` for a sync.
+type Logged = Option<(PageId, Vec<u8>)>;
+
+/// Pages shared by every store opened on them, logging each write and sync, so the
+/// file can be looked at as it would be after a crash.
+#[derive(Clone, Default)]
+struct LoggedStore(Arc<Mutex<Vec<Logged>>>);
+
+impl LoggedStore {
+    fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    /// The pages as they are after the first `logged` writes and syncs.
+    fn crashed(&self, logged: usize) -> MemoryStore {
+        let log = self.0.lock().unwrap();
+        MemoryStore {
+            pages: log[..logged].iter().flatten().cloned().collect(),
+            reads: 0,
+        }
+    }
+}
+
+impl PageStore for LoggedStore {
+    fn read_page(&mut self, id: PageId, buffer: &mut [u8]) -> std::io::Result<()> {
+        let log = self.0.lock().unwrap();
+        match log.iter().flatten().rev().find(|(page, _)| *page == id) {
+            Some((_, page)) => buffer.copy_from_slice(page),
+            None => buffer.fill(0),
+        }
+        Ok(())
+    }
+
+    fn write_page(&mut self, id: PageId, buffer: &[u8]) -> std::io::Result<()> {
+        self.0.lock().unwrap().push(Some((id, buffer.to_vec())));
+        Ok(())
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.0.lock().unwrap().push(None);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_storage_crash() {
+    let store = LoggedStore::default();
+    let rows = |store: MemoryStore| {
+        let mut db = Database::open_store(store, PAGE_SIZE).unwrap();
+        db.execute("SELECT * FROM t", &[]).unwrap().rows().to_vec()
+    };
+    let mut db = Database::open_store(store.clone(), PAGE_SIZE).unwrap();
+    db.execute("CREATE TABLE t (id INTEGER, body TEXT)", &[])
+        .unwrap();
+    db.execute("INSERT INTO t VALUES (1, 'one'), (2, 'two')", &[])
+        .unwrap();
+
+    // whenever a statement is cut off, the file holds the rows before or after it
+    let body = Value::Text("x".repeat(3 * PAGE_SIZE));
+    for (sql, params) in [
+        ("INSERT INTO t VALUES (3, $1)", &[body][..]),
+        ("UPDATE t SET body = 'uno' WHERE id = 1", &[]),
+        ("DELETE FROM t WHERE id = 2", &[]),
+        ("VACUUM", &[]),
+    ] {
+        let before = store.len();
+        db.execute(sql, params).unwrap();
+        let after = store.len();
+        let (old, new) = (rows(store.crashed(before)), rows(store.crashed(after)));
+        for logged in before..after {
+            let found = rows(store.crashed(logged));
+            assert!(
+                found == old || found == new,
+                "{} after {} writes",
+                sql,
+                logged
+            );
+        }
+    }
+
+    // the header is written on its own, after the pages it points to are on disk
+    let log = store.0.lock().unwrap();
+    for (at, entry) in log.iter().enumerate().skip(1) {
+        if matches!(entry, Some((0, _))) {
+            assert!(log[at - 1].is_none() && log.get(at + 1).is_none_or(Option::is_none));
+        }
+    }
+}
+
+#[test]
+fn test_database_file() {
+    let path = std::env::temp_dir().join(format!("xsdb-database-{}", std::process::id()));
+    let rows = |db: &mut Database, sql: &str| db.execute(sql, &[]).unwrap().rows().to_vec();
+    let mut db = Database::open(&path).unwrap();
+    for sql in [
+        "CREATE TABLE notes (id INTEGER, body TEXT, status TEXT DEFAULT 'open', data BLOB)",
+        "CREATE FULLTEXT INDEX notes_body ON notes (body)",
+        "INSERT INTO notes (id, body, data) VALUES (1, 'buy milk', x'00ff'), (2, 'call mom', NULL)",
+        "INSERT INTO notes (id, body) VALUES (3, 'more milk')",
+        "UPDATE notes SET status = 'done' WHERE id = 2",
+        "DELETE FROM notes WHERE id = 3",
+        "CREATE DATABASE other",
+        "USE other",
+        "CREATE TABLE prices (price REAL)",
+        "INSERT INTO prices VALUES (1.5)",
+    ] {
+        db.execute(sql, &[]).unwrap();
+    }
+    // a statement that fails is neither applied nor saved
+    assert!(db.execute("INSERT INTO missing VALUES (1)", &[]).is_err());
+    drop(db);
+
+    let mut db = Database::open(&path).unwrap();
+    assert_eq!(db.catalog().current_database(), "other");
+    assert_eq!(
+        rows(&mut db, "SELECT price FROM prices"),
+        [vec![Value::Real(1.5)]]
+    );
+    db.execute("USE main", &[]).unwrap();
+    assert_eq!(
+        rows(&mut db, "SELECT * FROM notes"),
+        [
+            vec![
+                Value::Integer(1),
+                Value::Text("buy milk".to_string()),
+                Value::Text("open".to_string()),
+                Value::Blob(vec![0, 255])
+            ],
+            vec![
+                Value::Integer(2),
+                Value::Text("call mom".to_string()),
+                Value::Text("done".to_string()),
+                Value::Null
+            ]
+        ]
+    );
+    assert_eq!(
+        rows(&mut db, "SELECT id FROM notes WHERE body MATCH 'milk'"),
+        [vec![Value::Integer(1)]]
+    );
+    db.execute("INSERT INTO notes (id) VALUES (4)", &[])
+        .unwrap();
+    assert_eq!(
+        rows(&mut db, "SELECT status FROM notes WHERE id = 4"),
+        [vec![Value::Text("open".to_string())]]
+    );
+
+    // VACUUM gives back the pages of deleted rows
+    let body = "x".repeat(PAGE_SIZE);
+    for id in 10..30 {
+        db.execute(
+            "INSERT INTO notes (id, body) VALUES ($1, $2)",
+            &[Value::Integer(id), Value::Text(body.clone())],
+        )
+        .unwrap();
+    }
+    let pages = db.storage().unwrap().pages();
+    db.execute("DELETE FROM notes WHERE id >= 10", &[]).unwrap();
+    db.execute("VACUUM", &[]).unwrap();
+    let storage = db.storage().unwrap();
+    assert_eq!(storage.free_pages(), 0);
+    assert!(storage.pages() < pages / 5);
+    assert_eq!(
+        std::fs::metadata(&path).unwrap().len(),
+        storage.pages() * PAGE_SIZE as u64
+    );
+
+    // shared, the database is saved on each commit and handed back with its file
+    let shared = SharedDatabase::new(db);
+    futures::executor::block_on(shared.execute("DELETE FROM notes WHERE id = 4", &[])).unwrap();
+    let mut transaction = futures::executor::block_on(shared.begin());
+    transaction
+        .execute("DELETE FROM notes WHERE id = 1", &[])
+        .unwrap();
+    drop(transaction);
+    let count = |db: &mut Database| rows(db, "SELECT id FROM notes").len();
+    assert_eq!(count(&mut Database::open(&path).unwrap()), 2);
+    let other = shared.clone();
+    let mut db = futures::executor::block_on(shared.into_database()).unwrap();
+    assert!(db.storage().is_some());
+    db.execute("DELETE FROM notes", &[]).unwrap();
+    db.execute("VACUUM", &[]).unwrap();
+    drop(db);
+    assert_eq!(count(&mut Database::open(&path).unwrap()), 0);
+    // the other handle kept a copy in memory
+    let rows = other
+        .snapshot()
+        .execute("SELECT id FROM notes", &[])
+        .unwrap();
+    assert_eq!(rows.rows().len(), 2);
+
+    // the last handle gives its file back as it is
+    let shared = SharedDatabase::new(Database::open(&path).unwrap());
+    futures::executor::block_on(shared.execute("INSERT INTO notes (id) VALUES (1)", &[])).unwrap();
+    let db = futures::executor::block_on(shared.into_database()).unwrap();
+    assert!(db.storage().is_some());
+    assert_eq!(count(&mut Database::open(&path).unwrap()), 1);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_session_variables() {
+    assert_eq!(
+        kinds("SET @Total := @x"),
+        vec![
+            TokenKind::SET,
+            TokenKind::Variable,
+            TokenKind::DuckAssignment,
+            TokenKind::Variable,
+            TokenKind::EOF
+        ]
+    );
+    let query = parse("SET @Total = (SELECT 1) + 2;");
+    assert_eq!(query.body(), BodyKind::SetVariable);
+    assert_eq!(query.value(), Some("total"));
+    assert_eq!(query.subqueries()[0].value(), Some("(SELECT 1) + 2"));
+    assert_eq!(parse("n := 3").value(), Some("n"));
+    assert!(Query::new(tokenize("SET @x :=")).is_err());
+
+    let mut db = Database::new();
+    db.execute("SET @x := 5", &[]).unwrap();
+    db.execute("SET @Name = UPPER($1)", &[text("ada")]).unwrap();
+    db.execute("y := (SELECT LENGTH(@name), 'ignored')", &[])
+        .unwrap();
+    db.execute("@none := (SELECT value FROM json_each('[]'))", &[])
+        .unwrap();
+    db.execute("both := COALESCE(@none, @y)", &[]).unwrap();
+    let row = |db: &Database, sql: &str| db.read(sql, &[]).unwrap().rows()[0].clone();
+    assert_eq!(
+        row(&db, "SELECT @x, @name, @Y, @none, @both, @unset"),
+        vec![
+            Value::Integer(5),
+            text("ADA"),
+            Value::Integer(3),
+            Value::Null,
+            Value::Integer(3),
+            Value::Null
+        ]
+    );
+    assert_eq!(
+        db.variables().keys().collect::<Vec<_>>(),
+        vec!["both", "name", "none", "x", "y"]
+    );
+    assert_eq!(db.read("SET @x := 1", &[]), Err(DatabaseError::ReadOnly));
+
+    // a script's variables are seen by its later statements
+    db.restore("SET @x := 'a'; CREATE TABLE t (a); SET @x := UPPER(@x);")
+        .unwrap();
+    assert_eq!(
+        row(&db, "SELECT @x, @y"),
+        vec![text("A"), Value::Integer(3)]
+    );
+}
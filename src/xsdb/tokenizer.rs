@@ -1,12 +1,36 @@
 use core::fmt;
 use std::{cmp::min, iter::Peekable, str::Chars};
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum ParseError {
-    UnexpectedToken
+    UnexpectedToken,
+    UnterminatedLiteral,
+    UnterminatedComment,
+    InvalidEscapeSequence,
+    InvalidLiteral,
 }
 
-#[derive(Debug, Clone, Copy)]
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken => write!(f, "unexpected token"),
+            ParseError::UnterminatedLiteral => write!(f, "unterminated literal"),
+            ParseError::UnterminatedComment => write!(f, "unterminated comment"),
+            ParseError::InvalidEscapeSequence => write!(f, "invalid escape sequence"),
+            ParseError::InvalidLiteral => write!(f, "invalid literal"),
+        }
+    }
+}
+
+/// Decoded value of a literal token, with quotes and escapes already resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenValue {
+    String(String),
+    Blob(Vec<u8>),
+    Integer(i64),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct Span {
     pub start: usize,
     pub end: usize,
@@ -17,6 +41,14 @@ impl fmt::Display for Span {
         write!(f, "{}..{}", self.start, self.end)
     }
 }
+impl Span {
+    fn shifted(&self, delta: isize) -> Span {
+        Span {
+            start: self.start.wrapping_add_signed(delta),
+            end: self.end.wrapping_add_signed(delta),
+        }
+    }
+}
 impl From<std::ops::Range<usize>> for Span {
     fn from(range: std::ops::Range<usize>) -> Self {
         Span {
@@ -27,6 +59,7 @@ impl From<std::ops::Range<usize>> for Span {
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum TokenKind {
     EOF,
     // Whitespace
@@ -34,12 +67,20 @@ pub enum TokenKind {
     Space,
     Tab,
     SingleLineComment,
+    MultiLineComment, // /* ... */, may be nested
 
     // Generic
     Identifier,
     Number,
+    HexNumber,    // 0x1F
+    BinaryNumber, // 0b101
     SingleQuotedString,
     DoubleQuotedString,
+    EscapedString,      // E'...' with backslash escapes
+    HexString,          // X'ABCD' blob literal
+    DollarQuotedString, // $$...$$ or $tag$...$tag$
+    Placeholder,        // $1, bound to a parameter when the query runs
+    Variable,           // @name, a session variable
 
     /// SPECIAL CHARACTERS
     LeftParen,
@@ -47,6 +88,7 @@ pub enum TokenKind {
     LeftBracket,
     RightBracket,
     Comma,
+    Period,
     SemiColon,
     Colon,
 
@@ -60,7 +102,9 @@ pub enum TokenKind {
     Gt,
     Lte,
     Gte,
-    Arrow, // =>
+    Arrow,         // =>
+    JsonArrow,     // ->
+    JsonLongArrow, // ->>
     Plus,
     Minus,
     Multiply,
@@ -72,18 +116,34 @@ pub enum TokenKind {
     ShiftRight,
     DoubleColon,
     DuckAssignment,
+    Concat, // ||
 
     // Keywords
     SELECT,
+    WITH,
+    RECURSIVE,
+    DISTINCT,
+    ALL,
+    UNION,
+    INTERSECT,
+    EXCEPT,
     CREATE,
     DELETE,
     UPDATE,
     INSERT,
     DROP,
+    ALTER,
+    USE,
+    VACUUM,
+    ANALYZE,
+    EXPLAIN,
 
     TABLE,
     DATABASE,
     INDEX,
+    FULLTEXT,
+    VIEW,
+    TRIGGER,
 
     IF,
     WHERE,
@@ -92,69 +152,210 @@ pub enum TokenKind {
 
     NOT,
     EXISTS,
+    LIKE,
+    ILIKE,
+    GLOB,
+    BETWEEN,
+    IN,
+    IS,
+    MATCH,
 
     ON,
+    BEFORE,
+    AFTER,
+    FOR,
+    EACH,
+    ROW,
+    BEGIN,
+    END,
     FROM,
+    JOIN,
+    INNER,
+    LEFT,
+    OUTER,
+    CROSS,
     INTO,
     VALUES,
     SET,
     AS,
 
+    ADD,
+    COLUMN,
+    RENAME,
+    TO,
+
     ORDER,
     BY,
     ASC,
     DESC,
 
+    OVER,
+    PARTITION,
+    ROWS,
+    PRECEDING,
+    FOLLOWING,
+    UNBOUNDED,
+    CURRENT,
+
     LIMIT,
     OFFSET,
 
     PRIMARY,
     KEY,
     AUTOINCREMENT,
+    DEFAULT,
 
     INTEGER,
     TEXT,
     NULL,
 }
 
+/// Keywords recognised by the tokenizer, also used for highlighting and completion.
+pub(crate) const KEYWORDS: &[(&str, TokenKind)] = &[
+    ("SELECT", TokenKind::SELECT),
+    ("WITH", TokenKind::WITH),
+    ("RECURSIVE", TokenKind::RECURSIVE),
+    ("DISTINCT", TokenKind::DISTINCT),
+    ("ALL", TokenKind::ALL),
+    ("UNION", TokenKind::UNION),
+    ("INTERSECT", TokenKind::INTERSECT),
+    ("EXCEPT", TokenKind::EXCEPT),
+    ("CREATE", TokenKind::CREATE),
+    ("DELETE", TokenKind::DELETE),
+    ("UPDATE", TokenKind::UPDATE),
+    ("INSERT", TokenKind::INSERT),
+    ("DROP", TokenKind::DROP),
+    ("ALTER", TokenKind::ALTER),
+    ("USE", TokenKind::USE),
+    ("VACUUM", TokenKind::VACUUM),
+    ("ANALYZE", TokenKind::ANALYZE),
+    ("EXPLAIN", TokenKind::EXPLAIN),
+    ("TABLE", TokenKind::TABLE),
+    ("DATABASE", TokenKind::DATABASE),
+    ("INDEX", TokenKind::INDEX),
+    ("FULLTEXT", TokenKind::FULLTEXT),
+    ("VIEW", TokenKind::VIEW),
+    ("TRIGGER", TokenKind::TRIGGER),
+    ("IF", TokenKind::IF),
+    ("WHERE", TokenKind::WHERE),
+    ("AND", TokenKind::AND),
+    ("OR", TokenKind::OR),
+    ("NOT", TokenKind::NOT),
+    ("EXISTS", TokenKind::EXISTS),
+    ("LIKE", TokenKind::LIKE),
+    ("ILIKE", TokenKind::ILIKE),
+    ("GLOB", TokenKind::GLOB),
+    ("BETWEEN", TokenKind::BETWEEN),
+    ("IN", TokenKind::IN),
+    ("IS", TokenKind::IS),
+    ("MATCH", TokenKind::MATCH),
+    ("ON", TokenKind::ON),
+    ("BEFORE", TokenKind::BEFORE),
+    ("AFTER", TokenKind::AFTER),
+    ("FOR", TokenKind::FOR),
+    ("EACH", TokenKind::EACH),
+    ("ROW", TokenKind::ROW),
+    ("BEGIN", TokenKind::BEGIN),
+    ("END", TokenKind::END),
+    ("FROM", TokenKind::FROM),
+    ("JOIN", TokenKind::JOIN),
+    ("INNER", TokenKind::INNER),
+    ("LEFT", TokenKind::LEFT),
+    ("OUTER", TokenKind::OUTER),
+    ("CROSS", TokenKind::CROSS),
+    ("INTO", TokenKind::INTO),
+    ("VALUES", TokenKind::VALUES),
+    ("SET", TokenKind::SET),
+    ("AS", TokenKind::AS),
+    ("ADD", TokenKind::ADD),
+    ("COLUMN", TokenKind::COLUMN),
+    ("RENAME", TokenKind::RENAME),
+    ("TO", TokenKind::TO),
+    ("ORDER", TokenKind::ORDER),
+    ("BY", TokenKind::BY),
+    ("ASC", TokenKind::ASC),
+    ("DESC", TokenKind::DESC),
+    ("OVER", TokenKind::OVER),
+    ("PARTITION", TokenKind::PARTITION),
+    ("ROWS", TokenKind::ROWS),
+    ("PRECEDING", TokenKind::PRECEDING),
+    ("FOLLOWING", TokenKind::FOLLOWING),
+    ("UNBOUNDED", TokenKind::UNBOUNDED),
+    ("CURRENT", TokenKind::CURRENT),
+    ("LIMIT", TokenKind::LIMIT),
+    ("OFFSET", TokenKind::OFFSET),
+    ("PRIMARY", TokenKind::PRIMARY),
+    ("KEY", TokenKind::KEY),
+    ("AUTOINCREMENT", TokenKind::AUTOINCREMENT),
+    ("DEFAULT", TokenKind::DEFAULT),
+    ("INTEGER", TokenKind::INTEGER),
+    ("TEXT", TokenKind::TEXT),
+    ("NULL", TokenKind::NULL),
+];
+
 impl TokenKind {
     fn make_keyword_or_identifier(word: &str) -> TokenKind {
-        match word.to_ascii_uppercase().as_str() {
-            "SELECT" => TokenKind::SELECT,
-            "CREATE" => TokenKind::CREATE,
-            "DELETE" => TokenKind::DELETE,
-            "UPDATE" => TokenKind::UPDATE,
-            "INSERT" => TokenKind::INSERT,
-            "DROP" => TokenKind::DROP,
-            "TABLE" => TokenKind::TABLE,
-            "DATABASE" => TokenKind::DATABASE,
-            "INDEX" => TokenKind::INDEX,
-            "IF" => TokenKind::IF,
-            "WHERE" => TokenKind::WHERE,
-            "AND" => TokenKind::AND,
-            "OR" => TokenKind::OR,
-            "NOT" => TokenKind::NOT,
-            "EXISTS" => TokenKind::EXISTS,
-            "ON" => TokenKind::ON,
-            "FROM" => TokenKind::FROM,
-            "INTO" => TokenKind::INTO,
-            "VALUES" => TokenKind::VALUES,
-            "SET" => TokenKind::SET,
-            "AS" => TokenKind::AS,
-            "ORDER" => TokenKind::ORDER,
-            "BY" => TokenKind::BY,
-            "ASC" => TokenKind::ASC,
-            "DESC" => TokenKind::DESC,
-            "LIMIT" => TokenKind::LIMIT,
-            "OFFSET" => TokenKind::OFFSET,
-            "PRIMARY" => TokenKind::PRIMARY,
-            "KEY" => TokenKind::KEY,
-            "AUTOINCREMENT" => TokenKind::AUTOINCREMENT,
-            "INTEGER" => TokenKind::INTEGER,
-            "TEXT" => TokenKind::TEXT,
-            "NULL" => TokenKind::NULL,
-            _ => TokenKind::Identifier,
-        }
+        let word = word.to_ascii_uppercase();
+        KEYWORDS
+            .iter()
+            .find(|(keyword, _)| *keyword == word)
+            .map_or(TokenKind::Identifier, |(_, kind)| *kind)
+    }
+
+    pub(crate) fn is_keyword(&self) -> bool {
+        KEYWORDS.iter().any(|(_, kind)| kind == self)
+    }
+
+    pub(crate) fn is_comment(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::SingleLineComment | TokenKind::MultiLineComment
+        )
+    }
+
+    pub(crate) fn is_string(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::SingleQuotedString
+                | TokenKind::EscapedString
+                | TokenKind::HexString
+                | TokenKind::DollarQuotedString
+        )
+    }
+
+    pub(crate) fn is_number(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Number | TokenKind::HexNumber | TokenKind::BinaryNumber
+        )
+    }
+
+    pub(crate) fn is_operator(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Operator
+                | TokenKind::Eq
+                | TokenKind::NotEq
+                | TokenKind::Lt
+                | TokenKind::Gt
+                | TokenKind::Lte
+                | TokenKind::Gte
+                | TokenKind::Arrow
+                | TokenKind::JsonArrow
+                | TokenKind::JsonLongArrow
+                | TokenKind::Plus
+                | TokenKind::Minus
+                | TokenKind::Multiply
+                | TokenKind::Divide
+                | TokenKind::Modulo
+                | TokenKind::Ampersand
+                | TokenKind::Caret
+                | TokenKind::ShiftLeft
+                | TokenKind::ShiftRight
+                | TokenKind::DoubleColon
+                | TokenKind::DuckAssignment
+                | TokenKind::Concat
+        )
     }
 
     fn is_skipped(&self) -> bool {
@@ -167,6 +368,7 @@ pub struct Token<'s> {
     token: TokenKind,
     source: &'s str,
     span: Span,
+    value: Option<TokenValue>,
 }
 
 impl<'s> fmt::Display for Token<'s> {
@@ -176,13 +378,13 @@ impl<'s> fmt::Display for Token<'s> {
     }
 }
 
-#[allow(dead_code)]
 impl<'s> Token<'s> {
     pub(crate) fn eof(source: &'s str) -> Self {
         Token {
             source,
             token: TokenKind::EOF,
             span: (source.len()..source.len()).into(),
+            value: None,
         }
     }
 
@@ -197,6 +399,32 @@ impl<'s> Token<'s> {
     pub(crate) fn text(&self) -> &str {
         &self.source[self.span.start..self.span.end]
     }
+
+    pub(crate) fn source(&self) -> &'s str {
+        self.source
+    }
+
+    /// Decoded value for literal tokens (strings, blobs, hex/binary numbers).
+    pub(crate) fn value(&self) -> Option<&TokenValue> {
+        self.value.as_ref()
+    }
+
+    /// The token without its source, to keep it across edits of an owned line.
+    /// Its span still refers to the source it was lexed from, so only its kind, span and
+    /// value can be read; `text` and `Display` need the source.
+    pub(crate) fn detach(&self) -> Token<'static> {
+        self.rebased("", 0)
+    }
+
+    /// Rebind the token to another source, moving its span by `delta` bytes.
+    fn rebased<'n>(&self, source: &'n str, delta: isize) -> Token<'n> {
+        Token {
+            token: self.token,
+            source,
+            span: self.span.shifted(delta),
+            value: self.value.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -204,14 +432,17 @@ struct State<'s> {
     peekable: Peekable<Chars<'s>>,
     start: usize,
     cursor: usize,
+    value: Option<TokenValue>,
 }
 
 impl<'s> State<'s> {
-    fn new(source: &'s str) -> Self {
+    /// Start lexing from a byte offset, which must be a token boundary.
+    fn at(source: &'s str, offset: usize) -> Self {
         Self {
-            peekable: source.chars().peekable(),
-            start: 0,
-            cursor: 0,
+            peekable: source[offset..].chars().peekable(),
+            start: offset,
+            cursor: offset,
+            value: None,
         }
     }
 
@@ -219,7 +450,8 @@ impl<'s> State<'s> {
         match self.peekable.next() {
             None => None,
             Some(c) => {
-                self.cursor += 1;
+                // spans index into the source, so track the byte offset
+                self.cursor += c.len_utf8();
                 Some(c)
             }
         }
@@ -239,8 +471,87 @@ impl<'s> State<'s> {
     fn advance(&mut self) {
         self.start = self.cursor;
     }
+
+    /// Peek the char after the next one without consuming anything.
+    fn peek_second(&self) -> Option<char> {
+        let mut lookahead = self.peekable.clone();
+        lookahead.next();
+        lookahead.next()
+    }
+
+    fn set_value(&mut self, value: TokenValue) {
+        self.value = Some(value);
+    }
+
+    fn take_value(&mut self) -> Option<TokenValue> {
+        self.value.take()
+    }
+}
+
+/// A text edit, in byte offsets: `start..old_end` of the old source was replaced by
+/// `start..new_end` of the new source.
+#[derive(Debug, Clone, Copy)]
+pub struct Edit {
+    pub start: usize,
+    pub old_end: usize,
+    pub new_end: usize,
+}
+
+impl Edit {
+    fn delta(&self) -> isize {
+        self.new_end as isize - self.old_end as isize
+    }
+}
+
+/// Lazily lexes tokens, skipping whitespace and ending with `EOF`. Stops after the first error.
+pub struct Tokens<'s> {
+    tokenizer: Tokenizer<'s>,
+    state: State<'s>,
+    finished: bool,
+}
+
+impl<'s> Iterator for Tokens<'s> {
+    type Item = Result<Token<'s>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        loop {
+            match self.tokenizer.next_token(&mut self.state) {
+                Ok(Some(token)) => {
+                    let value = self.state.take_value();
+                    let span = self.state.span();
+                    self.state.advance();
+                    if !token.is_skipped() {
+                        return Some(Ok(Token {
+                            token,
+                            source: self.tokenizer.source,
+                            span,
+                            value,
+                        }));
+                    }
+                }
+                Ok(None) => {
+                    self.finished = true;
+                    return Some(Ok(Token {
+                        token: TokenKind::EOF,
+                        source: self.tokenizer.source,
+                        span: self.state.span(),
+                        value: None,
+                    }));
+                }
+                Err(err) => {
+                    self.finished = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct Tokenizer<'s> {
     source: &'s str,
 }
@@ -249,26 +560,102 @@ impl<'s> Tokenizer<'s> {
         Self { source }
     }
 
-    pub fn tokenize(&mut self) -> Result<Vec<Token>, ParseError> {
-        let mut state = State::new(self.source);
-        let mut tokens: Vec<Token> = vec![];
+    pub fn tokenize(&mut self) -> Result<Vec<Token<'s>>, ParseError> {
+        self.iter().collect()
+    }
 
-        while let Some(token) = self.next_token(&mut state)? {
-            if !token.is_skipped() {
-                tokens.push(Token {
-                    token,
-                    source: self.source,
-                    span: state.span(),
-                });
+    pub fn iter(&self) -> Tokens<'s> {
+        self.iter_at(0)
+    }
+
+    /// The `;`-separated statements of the source, skipping empty ones.
+    /// Semicolons between `BEGIN` and `END` of a `CREATE TRIGGER` belong to its body.
+    pub fn statements(&self) -> Result<Vec<&'s str>, ParseError> {
+        let mut statements = vec![];
+        let mut start = None;
+        let mut trigger = false;
+        let mut in_body = false;
+        for token in self.iter() {
+            let token = token?;
+            match token.token() {
+                TokenKind::SemiColon if in_body => (),
+                TokenKind::SemiColon | TokenKind::EOF => {
+                    if let Some(start) = start.take() {
+                        statements.push(&self.source[start..token.span().start]);
+                    }
+                    trigger = false;
+                }
+                kind if kind.is_comment() => (),
+                kind => {
+                    match kind {
+                        TokenKind::TRIGGER => trigger = true,
+                        TokenKind::BEGIN if trigger => in_body = true,
+                        TokenKind::END => in_body = false,
+                        _ => (),
+                    }
+                    start.get_or_insert(token.span().start);
+                }
             }
-            state.advance();
         }
+        Ok(statements)
+    }
 
-        tokens.push(Token {
-            token: TokenKind::EOF,
-            source: self.source,
-            span: state.span(),
-        });
+    fn iter_at(&self, offset: usize) -> Tokens<'s> {
+        Tokens {
+            tokenizer: *self,
+            state: State::at(self.source, offset),
+            finished: false,
+        }
+    }
+
+    /// Re-lex `self.source` after `edit` was applied to the source of `old`.
+    ///
+    /// Lexing restarts at the first of the tokens that run up to the edit with no space
+    /// between them, since the lexer looks ahead across token boundaries (`1e+` and `5`
+    /// make one number). It stops as soon as a new token past the edit has the kind and
+    /// start of a shifted old one; from there on the old tokens are reused.
+    pub fn relex(&self, old: &[Token<'_>], edit: &Edit) -> Result<Vec<Token<'s>>, ParseError> {
+        let delta = edit.delta();
+
+        // tokens right before the edit can change too, e.g. `SEL` + `ECT` or `-` + `-`
+        let touched = old
+            .iter()
+            .position(|t| t.span.end >= edit.start)
+            .unwrap_or(old.len());
+        let mut restart = touched.saturating_sub(1);
+        while restart > 0 && old[restart - 1].span.end == old[restart].span.start {
+            restart -= 1;
+        }
+        let offset = old
+            .get(restart)
+            .map_or(0, |t| min(t.span.start, edit.start));
+
+        let mut tokens: Vec<Token<'s>> = old[..restart]
+            .iter()
+            .map(|t| t.rebased(self.source, 0))
+            .collect();
+        let mut reusable = old
+            .iter()
+            .skip(restart)
+            .filter(|t| t.span.start >= edit.old_end)
+            .peekable();
+
+        for token in self.iter_at(offset) {
+            let token = token?;
+            if token.span.start >= edit.new_end {
+                while reusable
+                    .next_if(|t| t.span.shifted(delta).start < token.span.start)
+                    .is_some()
+                {}
+                if reusable.peek().is_some_and(|t| {
+                    t.token == token.token && t.span.shifted(delta).start == token.span.start
+                }) {
+                    tokens.extend(reusable.map(|t| t.rebased(self.source, delta)));
+                    return Ok(tokens);
+                }
+            }
+            tokens.push(token);
+        }
 
         Ok(tokens)
     }
@@ -288,6 +675,20 @@ impl<'s> Tokenizer<'s> {
                     }
                     Ok(Some(TokenKind::NewLine))
                 }
+                // E'...' escape string
+                'E' | 'e' if chars.peek_second() == Some('\'') => {
+                    chars.next(); // consume the prefix
+                    let s = self.tokenize_escaped_string(chars)?;
+                    chars.set_value(TokenValue::String(s));
+                    Ok(Some(TokenKind::EscapedString))
+                }
+                // X'ABCD' blob literal
+                'X' | 'x' if chars.peek_second() == Some('\'') => {
+                    chars.next(); // consume the prefix
+                    let digits = self.tokenize_quoted_string(chars, '\'')?;
+                    chars.set_value(TokenValue::Blob(decode_hex_blob(&digits)?));
+                    Ok(Some(TokenKind::HexString))
+                }
                 // identifier or keyword
                 ch if is_identifier_start(ch) => {
                     chars.next(); // consume the first char
@@ -296,14 +697,55 @@ impl<'s> Tokenizer<'s> {
                 }
                 // single quoted string
                 '\'' => {
-                    self.tokenize_quoted_string(chars, '\'')?;
+                    let s = self.tokenize_quoted_string(chars, '\'')?;
+                    chars.set_value(TokenValue::String(s));
                     Ok(Some(TokenKind::SingleQuotedString))
                 }
                 // double quoted string
                 '\"' => {
-                    self.tokenize_quoted_string(chars, '"')?;
+                    let s = self.tokenize_quoted_string(chars, '"')?;
+                    chars.set_value(TokenValue::String(s));
                     Ok(Some(TokenKind::DoubleQuotedString))
                 }
+                // $1 parameter placeholder
+                '$' if chars.peek_second().is_some_and(|ch| ch.is_ascii_digit()) => {
+                    chars.next(); // consume the '$'
+                    let digits = peeking_take_while(chars, |ch| ch.is_ascii_digit());
+                    let index = digits.parse().map_err(|_| ParseError::InvalidLiteral)?;
+                    chars.set_value(TokenValue::Integer(index));
+                    Ok(Some(TokenKind::Placeholder))
+                }
+                // @name session variable
+                '@' if chars.peek_second().is_some_and(is_identifier_start) => {
+                    chars.next(); // consume the '@'
+                    let first = chars.next().unwrap();
+                    let name = self.tokenize_word(first, chars);
+                    chars.set_value(TokenValue::String(name));
+                    Ok(Some(TokenKind::Variable))
+                }
+                // dollar quoted string
+                '$' => {
+                    let s = self.tokenize_dollar_quoted_string(chars)?;
+                    chars.set_value(TokenValue::String(s));
+                    Ok(Some(TokenKind::DollarQuotedString))
+                }
+                // 0x / 0b prefixed numbers
+                '0' if matches!(chars.peek_second(), Some('x' | 'X' | 'b' | 'B')) => {
+                    chars.next(); // consume the '0'
+                    let radix = match chars.next() {
+                        Some('x' | 'X') => 16,
+                        _ => 2,
+                    };
+                    let digits = peeking_take_while(chars, |ch| ch.is_ascii_alphanumeric());
+                    let value = i64::from_str_radix(&digits, radix)
+                        .map_err(|_| ParseError::InvalidLiteral)?;
+                    chars.set_value(TokenValue::Integer(value));
+                    if radix == 16 {
+                        Ok(Some(TokenKind::HexNumber))
+                    } else {
+                        Ok(Some(TokenKind::BinaryNumber))
+                    }
+                }
                 // numbers and period
                 '0'..='9' => {
                     let mut s = peeking_take_while(chars, |ch| ch.is_ascii_digit());
@@ -358,6 +800,13 @@ impl<'s> Tokenizer<'s> {
                 ch if is_operator_part(ch) => {
                     chars.next(); // consume the first op char
 
+                    // maybe multi-line comment
+                    if ch == '/' && chars.peek() == Some(&'*') {
+                        chars.next(); // consume the '*'
+                        self.tokenize_multi_line_comment(chars)?;
+                        return Ok(Some(TokenKind::MultiLineComment));
+                    }
+
                     // maybe single-line comment
                     if ch == '-' {
                         match chars.peek() {
@@ -430,6 +879,9 @@ impl<'s> Tokenizer<'s> {
                     if len == 2 {
                         match op.as_str() {
                             "=>" => return Ok(Some(TokenKind::Arrow)),
+                            "==" => return Ok(Some(TokenKind::Eq)),
+                            "||" => return Ok(Some(TokenKind::Concat)),
+                            "->" => return Ok(Some(TokenKind::JsonArrow)),
                             ">=" => return Ok(Some(TokenKind::Gte)),
                             "<=" => return Ok(Some(TokenKind::Lte)),
                             "<>" => return Ok(Some(TokenKind::NotEq)),
@@ -440,6 +892,10 @@ impl<'s> Tokenizer<'s> {
                         }
                     }
 
+                    if op == "->>" {
+                        return Ok(Some(TokenKind::JsonLongArrow));
+                    }
+
                     // 6. Qualified operators
                     Ok(Some(TokenKind::Operator))
                 }
@@ -449,6 +905,7 @@ impl<'s> Tokenizer<'s> {
                 '[' => self.consume_and_return(chars, TokenKind::LeftBracket),
                 ']' => self.consume_and_return(chars, TokenKind::RightBracket),
                 ',' => self.consume_and_return(chars, TokenKind::Comma),
+                '.' => self.consume_and_return(chars, TokenKind::Period),
                 ';' => self.consume_and_return(chars, TokenKind::SemiColon),
                 ':' => {
                     chars.next();
@@ -495,6 +952,113 @@ impl<'s> Tokenizer<'s> {
         comment
     }
 
+    /// Consume a (possibly nested) block comment, after the opening `/*` is consumed.
+    fn tokenize_multi_line_comment(&self, chars: &mut State) -> Result<String, ParseError> {
+        let mut comment = String::new();
+        let mut depth = 1;
+
+        while let Some(ch) = chars.next() {
+            match ch {
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    depth += 1;
+                    comment.push_str("/*");
+                }
+                '*' if chars.peek() == Some(&'/') => {
+                    chars.next();
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(comment);
+                    }
+                    comment.push_str("*/");
+                }
+                _ => comment.push(ch),
+            }
+        }
+
+        Err(ParseError::UnterminatedComment)
+    }
+
+    /// Read an `E'...'` string, starting with the opening quote, resolving backslash escapes.
+    fn tokenize_escaped_string(&self, chars: &mut State) -> Result<String, ParseError> {
+        let mut s = String::new();
+        chars.next(); // consume the opening quote
+
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\'' => {
+                    if chars.peek() == Some(&'\'') {
+                        chars.next();
+                        s.push('\'');
+                    } else {
+                        return Ok(s);
+                    }
+                }
+                '\\' => {
+                    let escaped = chars.next().ok_or(ParseError::UnterminatedLiteral)?;
+                    match escaped {
+                        'b' => s.push('\u{8}'),
+                        'f' => s.push('\u{c}'),
+                        'n' => s.push('\n'),
+                        'r' => s.push('\r'),
+                        't' => s.push('\t'),
+                        '0'..='7' => {
+                            let mut digits = escaped.to_string();
+                            while digits.len() < 3 && matches!(chars.peek(), Some('0'..='7')) {
+                                digits.push(chars.next().unwrap());
+                            }
+                            s.push(decode_code_point(&digits, 8)?);
+                        }
+                        'x' => {
+                            let digits = take_hex_digits(chars, 2);
+                            s.push(decode_code_point(&digits, 16)?);
+                        }
+                        'u' => {
+                            let digits = take_hex_digits(chars, 4);
+                            if digits.len() != 4 {
+                                return Err(ParseError::InvalidEscapeSequence);
+                            }
+                            s.push(decode_code_point(&digits, 16)?);
+                        }
+                        'U' => {
+                            let digits = take_hex_digits(chars, 8);
+                            if digits.len() != 8 {
+                                return Err(ParseError::InvalidEscapeSequence);
+                            }
+                            s.push(decode_code_point(&digits, 16)?);
+                        }
+                        // any other escaped char stands for itself: \\ \' \"
+                        other => s.push(other),
+                    }
+                }
+                _ => s.push(ch),
+            }
+        }
+
+        Err(ParseError::UnterminatedLiteral)
+    }
+
+    /// Read a `$tag$...$tag$` string, starting with the opening `$`. The tag may be empty.
+    fn tokenize_dollar_quoted_string(&self, chars: &mut State) -> Result<String, ParseError> {
+        chars.next(); // consume the opening '$'
+        let tag = peeking_take_while(chars, |ch| ch.is_alphanumeric() || ch == '_');
+        if chars.next() != Some('$') {
+            return Err(ParseError::UnexpectedToken);
+        }
+
+        let delimiter = format!("${}$", tag);
+        let mut s = String::new();
+        while let Some(ch) = chars.next() {
+            s.push(ch);
+            if s.ends_with(&delimiter) {
+                s.truncate(s.len() - delimiter.len());
+                return Ok(s);
+            }
+        }
+
+        Err(ParseError::UnterminatedLiteral)
+    }
+
     /// Read a single quoted string, starting with the opening quote.
     fn tokenize_quoted_string(
         &self,
@@ -522,7 +1086,7 @@ impl<'s> Tokenizer<'s> {
             }
         }
 
-        Err(ParseError::UnexpectedToken)
+        Err(ParseError::UnterminatedLiteral)
     }
 }
 
@@ -554,3 +1118,28 @@ fn peeking_take_while(chars: &mut State, mut predicate: impl FnMut(char) -> bool
     }
     s
 }
+
+fn take_hex_digits(chars: &mut State, max: usize) -> String {
+    let mut digits = String::new();
+    while digits.len() < max && chars.peek().is_some_and(|ch| ch.is_ascii_hexdigit()) {
+        digits.push(chars.next().unwrap());
+    }
+    digits
+}
+
+fn decode_code_point(digits: &str, radix: u32) -> Result<char, ParseError> {
+    u32::from_str_radix(digits, radix)
+        .ok()
+        .and_then(char::from_u32)
+        .ok_or(ParseError::InvalidEscapeSequence)
+}
+
+fn decode_hex_blob(digits: &str) -> Result<Vec<u8>, ParseError> {
+    if !digits.len().is_multiple_of(2) || !digits.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        return Err(ParseError::InvalidLiteral);
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| ParseError::InvalidLiteral))
+        .collect()
+}
@@ -0,0 +1,151 @@
+use std::fmt;
+
+use super::tokenizer::{ParseError, Token, TokenKind, TokenValue};
+
+/// A single SQL value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    /// Value of a literal token, or `None` if the token is not a literal.
+    pub(crate) fn from_literal(token: &Token) -> Result<Option<Value>, ParseError> {
+        let value = match (token.token(), token.value()) {
+            (TokenKind::NULL, _) => Value::Null,
+            (TokenKind::Placeholder, _) => return Ok(None),
+            (TokenKind::Number, _) => {
+                let text = token.text().trim_end_matches('L');
+                match text.parse::<i64>() {
+                    Ok(integer) => Value::Integer(integer),
+                    Err(_) => Value::Real(text.parse().map_err(|_| ParseError::InvalidLiteral)?),
+                }
+            }
+            (_, Some(TokenValue::Integer(integer))) => Value::Integer(*integer),
+            (_, Some(TokenValue::Blob(blob))) => Value::Blob(blob.clone()),
+            (kind, Some(TokenValue::String(text))) if kind.is_string() => Value::Text(text.clone()),
+            _ => return Ok(None),
+        };
+        Ok(Some(value))
+    }
+
+    /// The value written as a SQL literal that reads back as the same value.
+    pub fn to_literal(&self) -> String {
+        match self {
+            Value::Null => "NULL".to_string(),
+            // `9223372036854775808` reads as a real before it is negated
+            Value::Integer(i64::MIN) => format!("({} - 1)", i64::MIN + 1),
+            Value::Integer(integer) => integer.to_string(),
+            // no literal reads as NaN
+            Value::Real(real) if real.is_nan() => "NULL".to_string(),
+            Value::Real(real) if real.is_infinite() => {
+                if *real > 0.0 { "9e999" } else { "-9e999" }.to_string()
+            }
+            // `{:?}` keeps the `.0` that tells `1.0` from `1`
+            Value::Real(real) => format!("{:?}", real),
+            Value::Text(text) => format!("'{}'", text.replace('\'', "''")),
+            Value::Blob(_) => self.to_string(),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "NULL",
+            Value::Integer(_) => "INTEGER",
+            Value::Real(_) => "REAL",
+            Value::Text(_) => "TEXT",
+            Value::Blob(_) => "BLOB",
+        }
+    }
+}
+
+/// A value as `DISTINCT` and set operations compare it: integers equal to the same whole
+/// reals, and `NULL` equal to itself.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum DistinctKey {
+    Null,
+    Integer(i64),
+    Real(u64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    pub(crate) fn distinct_key(&self) -> DistinctKey {
+        match self {
+            Value::Null => DistinctKey::Null,
+            Value::Integer(integer) => DistinctKey::Integer(*integer),
+            // `i64::MAX as f64` is 2^63, one past the largest integer
+            Value::Real(real)
+                if real.fract() == 0.0 && *real >= i64::MIN as f64 && *real < i64::MAX as f64 =>
+            {
+                DistinctKey::Integer(*real as i64)
+            }
+            Value::Real(real) => DistinctKey::Real(real.to_bits()),
+            Value::Text(text) => DistinctKey::Text(text.clone()),
+            Value::Blob(blob) => DistinctKey::Blob(blob.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Integer(integer) => write!(f, "{}", integer),
+            Value::Real(real) => write!(f, "{}", real),
+            Value::Text(text) => write!(f, "{}", text),
+            Value::Blob(blob) => {
+                write!(f, "X'")?;
+                for byte in blob {
+                    write!(f, "{:02X}", byte)?;
+                }
+                write!(f, "'")
+            }
+        }
+    }
+}
+
+impl From<i64> for Value {
+    fn from(integer: i64) -> Self {
+        Value::Integer(integer)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(real: f64) -> Self {
+        Value::Real(real)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::Text(text.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::Text(text)
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(blob: Vec<u8>) -> Self {
+        Value::Blob(blob)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
@@ -0,0 +1,274 @@
+use std::{cmp::Ordering, ops::Range};
+
+use super::{
+    expression::{compare, Expression},
+    functions::{Arity, FunctionError},
+    value::Value,
+};
+
+/// One end of a `ROWS` frame, relative to the current row.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameBound {
+    UnboundedPreceding,
+    Preceding(usize),
+    CurrentRow,
+    Following(usize),
+    UnboundedFollowing,
+}
+
+/// `OVER (PARTITION BY ... ORDER BY ... ROWS BETWEEN ... AND ...)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Window {
+    pub(crate) partition_by: Vec<Expression>,
+    /// Sort keys, `true` for `DESC`.
+    pub(crate) order_by: Vec<(Expression, bool)>,
+    pub(crate) frame: Option<(FrameBound, FrameBound)>,
+}
+
+/// The values a window function needs from one input row.
+#[derive(Debug, Clone)]
+pub(crate) struct WindowRow {
+    pub(crate) partition: Vec<Value>,
+    pub(crate) order: Vec<Value>,
+    pub(crate) args: Vec<Value>,
+}
+
+fn arity(name: &str) -> Option<Arity> {
+    match name {
+        "ROW_NUMBER" | "RANK" | "DENSE_RANK" => Some(0.into()),
+        "LAG" | "LEAD" => Some(Arity::between(1, 3)),
+        "SUM" | "AVG" | "MIN" | "MAX" => Some(1.into()),
+        // `COUNT(*)` has no arguments
+        "COUNT" => Some(Arity::between(0, 1)),
+        _ => None,
+    }
+}
+
+/// Evaluate the window function `name` for every row, returning results in input order.
+pub(crate) fn evaluate(
+    name: &str,
+    window: &Window,
+    rows: &[WindowRow],
+) -> Result<Vec<Value>, FunctionError> {
+    let name = name.to_uppercase();
+    let arity = arity(&name).ok_or_else(|| FunctionError::NoSuchFunction(name.clone()))?;
+    if let Some(row) = rows.iter().find(|row| !arity.accepts(row.args.len())) {
+        return Err(FunctionError::WrongArgumentCount(name, row.args.len()));
+    }
+
+    let mut partitions: Vec<(&[Value], Vec<usize>)> = vec![];
+    for (index, row) in rows.iter().enumerate() {
+        match partitions
+            .iter_mut()
+            .find(|(key, _)| *key == row.partition.as_slice())
+        {
+            Some((_, members)) => members.push(index),
+            None => partitions.push((&row.partition, vec![index])),
+        }
+    }
+
+    let descending = window
+        .order_by
+        .iter()
+        .map(|(_, descending)| *descending)
+        .collect::<Vec<_>>();
+    let mut results = vec![Value::Null; rows.len()];
+    for (_, mut members) in partitions {
+        let mut error = None;
+        members.sort_by(|a, b| {
+            order(&rows[*a], &rows[*b], &descending).unwrap_or_else(|err| {
+                error.get_or_insert(err);
+                Ordering::Equal
+            })
+        });
+        if let Some(err) = error {
+            return Err(err);
+        }
+
+        // rows that sort equal are peers and share a group
+        let mut groups = vec![0];
+        for pair in members.windows(2) {
+            let peer = order(&rows[pair[0]], &rows[pair[1]], &descending)? == Ordering::Equal;
+            groups.push(groups.last().unwrap() + !peer as usize);
+        }
+
+        let partition = Partition {
+            name: &name,
+            window,
+            rows,
+            members: &members,
+            groups: &groups,
+        };
+        for (position, index) in members.iter().enumerate() {
+            results[*index] = partition.evaluate_at(position)?;
+        }
+    }
+    Ok(results)
+}
+
+/// Compare two rows by their sort keys, with `NULL`s first.
+fn order(a: &WindowRow, b: &WindowRow, descending: &[bool]) -> Result<Ordering, FunctionError> {
+    for ((a, b), descending) in a.order.iter().zip(&b.order).zip(descending) {
+        let ordering = compare("ORDER BY", a, b)?.unwrap_or_else(|| b.is_null().cmp(&a.is_null()));
+        let ordering = if *descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        if ordering != Ordering::Equal {
+            return Ok(ordering);
+        }
+    }
+    Ok(Ordering::Equal)
+}
+
+struct Partition<'a> {
+    name: &'a str,
+    window: &'a Window,
+    rows: &'a [WindowRow],
+    /// Row indices in window order.
+    members: &'a [usize],
+    /// Peer group of each member.
+    groups: &'a [usize],
+}
+
+impl Partition<'_> {
+    fn row(&self, position: usize) -> &WindowRow {
+        &self.rows[self.members[position]]
+    }
+
+    fn evaluate_at(&self, position: usize) -> Result<Value, FunctionError> {
+        let group = self.groups[position];
+        match self.name {
+            "ROW_NUMBER" => Ok(Value::Integer(position as i64 + 1)),
+            "RANK" => {
+                let first_peer = self.groups.iter().position(|g| *g == group).unwrap();
+                Ok(Value::Integer(first_peer as i64 + 1))
+            }
+            "DENSE_RANK" => Ok(Value::Integer(group as i64 + 1)),
+            "LAG" | "LEAD" => {
+                let args = &self.row(position).args;
+                let offset = match args.get(1) {
+                    None => 1,
+                    Some(Value::Integer(offset)) if *offset >= 0 => *offset as usize,
+                    Some(value) => {
+                        return Err(FunctionError::InvalidArgument(
+                            self.name.to_string(),
+                            value.clone(),
+                        ))
+                    }
+                };
+                let target = if self.name == "LAG" {
+                    position.checked_sub(offset)
+                } else {
+                    position
+                        .checked_add(offset)
+                        .filter(|target| *target < self.members.len())
+                };
+                Ok(match target {
+                    Some(target) => self.row(target).args[0].clone(),
+                    None => args.get(2).cloned().unwrap_or(Value::Null),
+                })
+            }
+            _ => {
+                let values = self
+                    .frame(position)
+                    .map(|position| self.row(position).args.first())
+                    .collect::<Vec<_>>();
+                aggregate(self.name, &values)
+            }
+        }
+    }
+
+    /// Rows the aggregate sees; without `ROWS` it runs up to the last peer of the current row.
+    fn frame(&self, position: usize) -> Range<usize> {
+        let len = self.members.len();
+        let (start, end) = match self.window.frame {
+            Some(frame) => frame,
+            None if self.window.order_by.is_empty() => return 0..len,
+            None => {
+                let group = self.groups[position];
+                let end = self.groups.iter().rposition(|g| *g == group).unwrap();
+                return 0..end + 1;
+            }
+        };
+        let start = match start {
+            FrameBound::UnboundedPreceding => 0,
+            FrameBound::Preceding(n) => position.saturating_sub(n),
+            FrameBound::CurrentRow => position,
+            FrameBound::Following(n) => position.saturating_add(n),
+            FrameBound::UnboundedFollowing => len,
+        };
+        let end = match end {
+            FrameBound::UnboundedPreceding => 0,
+            FrameBound::Preceding(n) => (position + 1).saturating_sub(n),
+            FrameBound::CurrentRow => position + 1,
+            FrameBound::Following(n) => position.saturating_add(n).saturating_add(1),
+            FrameBound::UnboundedFollowing => len,
+        };
+        let end = end.min(len);
+        start.min(end)..end
+    }
+}
+
+/// `None` stands for a row of `COUNT(*)`, which has no argument.
+fn aggregate(name: &str, values: &[Option<&Value>]) -> Result<Value, FunctionError> {
+    if name == "COUNT" {
+        let count = values
+            .iter()
+            .filter(|value| value.is_none_or(|value| !value.is_null()))
+            .count();
+        return Ok(Value::Integer(count as i64));
+    }
+
+    let values = values
+        .iter()
+        .flatten()
+        .copied()
+        .filter(|value| !value.is_null())
+        .collect::<Vec<_>>();
+    if values.is_empty() {
+        return Ok(Value::Null);
+    }
+    match name {
+        "SUM" | "AVG" => {
+            let mut sum = Value::Integer(0);
+            for value in &values {
+                sum = match (sum, value) {
+                    (Value::Integer(sum), Value::Integer(value)) => Value::Integer(
+                        sum.checked_add(*value)
+                            .ok_or_else(|| FunctionError::Overflow(name.to_string()))?,
+                    ),
+                    (Value::Integer(sum), Value::Real(value)) => Value::Real(sum as f64 + value),
+                    (Value::Real(sum), Value::Integer(value)) => Value::Real(sum + *value as f64),
+                    (Value::Real(sum), Value::Real(value)) => Value::Real(sum + value),
+                    (_, value) => {
+                        return Err(FunctionError::InvalidArgument(
+                            name.to_string(),
+                            (*value).clone(),
+                        ))
+                    }
+                };
+            }
+            match (name, sum) {
+                ("AVG", Value::Integer(sum)) => Ok(Value::Real(sum as f64 / values.len() as f64)),
+                ("AVG", Value::Real(sum)) => Ok(Value::Real(sum / values.len() as f64)),
+                (_, sum) => Ok(sum),
+            }
+        }
+        _ => {
+            let wanted = if name == "MIN" {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            };
+            let mut best = values[0];
+            for value in &values[1..] {
+                if compare(name, value, best)? == Some(wanted) {
+                    best = value;
+                }
+            }
+            Ok(best.clone())
+        }
+    }
+}